@@ -1,37 +1,169 @@
 use anyhow::Result;
 use crossterm::event::EventStream;
-use crossterm::event::{Event, KeyCode, KeyModifiers, MouseEventKind};
-use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use crossterm::event::{Event, KeyCode, KeyEventKind, KeyModifiers, MouseEventKind};
+use ratatui::layout::Rect;
+use ratatui::style::{Color, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Paragraph};
 use ratatui::{DefaultTerminal, Frame};
+use ratatui_code_editor::utils::get_lang;
 use std::collections::HashSet;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use tokio_stream::StreamExt;
 
-use crate::editor::{EditorAction, EditorPanel, Fallback};
+use crate::bookmarks::{Bookmark, BookmarkAction, BookmarksPanel};
+use crate::cli::{Args, Switches};
+use crate::coder::missing_tokens;
+use crate::config::{Config, ConfigEnv};
+use crate::diffview::DiffView;
+use crate::editor::{AutocompleteResult, EditorAction, EditorPanel, Fallback, FileStats, PathForm};
+use crate::encoding::{self, Encoding};
+use crate::gotofile::resolve_path;
+use crate::history::{HistoryAction, HistoryMenu};
+use crate::jumps::{Jump, JumpList};
 use crate::llm::LlmClient;
+use crate::outline::{OutlineAction, OutlinePanel};
+use crate::permalink::permalink;
+use crate::recent::{RecentAction, RecentPanel};
+use crate::scrollbar;
 use crate::search::{SearchAction, SearchMode, SearchPanel};
+use crate::searcher::SearchKind;
+use crate::snippets::{SnippetAction, SnippetMenu};
+use crate::status::StatusBar;
+use crate::tags::find_definitions;
 use crate::tree::{TreeAction, TreePanel, should_refresh_tree};
-use crate::utils::{abs_file, is_focused};
+use crate::utils::{abs_file, display_path, find_color, is_focused};
 use crate::watcher::FsWatcher;
 
 pub type Theme = Vec<(&'static str, &'static str)>;
 
+/// Percent of the width `Ctrl+Shift+Left/Right` move the divider by
+const SPLIT_STEP: usize = 5;
+/// Bounds of the left panel width, in percent, when resized by keyboard
+const SPLIT_MIN: usize = 10;
+const SPLIT_MAX: usize = 80;
+
+/// Below this width the left panel and the editor take turns: only the
+/// focused one is shown, full width
+const MIN_SPLIT_WIDTH: u16 = 60;
+
+/// Redraw interval while an AI request runs, so the status bar spinner
+/// moves even though nothing arrives for a long time
+const TICK: Duration = Duration::from_millis(250);
+
+/// Splits `area` into the left panel, the editor and the status row; a
+/// hidden or squeezed out pane gets an empty area at the left edge
+fn screen_layout(area: Rect, left_ratio: Option<usize>, left_focused: bool) -> [Rect; 3] {
+    let status_height = area.height.min(1);
+    let main = Rect {
+        height: area.height - status_height,
+        ..area
+    };
+    let status = Rect {
+        y: main.bottom(),
+        height: status_height,
+        ..area
+    };
+    let left_width = match left_ratio {
+        Some(_) if area.width < MIN_SPLIT_WIDTH && left_focused => main.width,
+        Some(ratio) if area.width >= MIN_SPLIT_WIDTH => {
+            (main.width as usize * ratio.min(100) / 100) as u16
+        }
+        _ => 0,
+    };
+    let left = Rect {
+        width: left_width,
+        ..main
+    };
+    let editor = Rect {
+        x: main.x + left_width,
+        width: main.width - left_width,
+        ..main
+    };
+    [left, editor, status]
+}
+
 #[derive(PartialEq)]
 pub enum LeftPanelMode {
     Tree,
     Search,
+    Outline,
+    Recent,
+    Bookmarks,
 }
 
 pub enum Message {
     Quit,
     ToggleLeftPanel,
+    ResizeLeftPanel {
+        wider: bool,
+    },
+    FocusTree,
+    FocusEditor,
+    NextBuffer,
+    ShowLlmDebug,
+    CycleLeftPanel,
+    RevealInTree,
+    GotoDefinition,
+    OpenPathUnderCursor,
+    /// Reads the current file again into a fresh editor
+    ReloadFile {
+        confirmed: bool,
+    },
+    ClearMarks,
+    ReloadConfig,
+    JumpToChange {
+        backwards: bool,
+    },
+    JumpBack,
+    JumpForward,
+    InsertFilePath(PathForm),
+    CopyPermalink,
+    ToggleHighlight,
+    NormalizeLineEndings,
+    ToggleWhitespace,
+    ToggleReindentPaste,
+    PinSelection,
+    ClearPinned,
+    Instruct(String),
+    FormatSelection,
+    /// Inserts a commit message the model writes for the staged or session
+    /// changes
+    WriteCommitMsg,
+    /// Swaps the marked completion for the next candidate of its request
+    NextCandidate,
+    /// Shows the current file in a second pane, or closes it
+    ToggleSplit,
+    SwitchPane,
+    SetLanguage(String),
+    InsertTemplate,
+    OpenSnippets,
+    SnippetAction(SnippetAction),
+    OpenHistory,
+    HistoryAction(HistoryAction),
     ActivateSearch(SearchMode),
     SearchAction(SearchAction),
+    OutlineAction(OutlineAction),
+    RecentAction(RecentAction),
+    BookmarkAction(BookmarkAction),
+    AddBookmark(Bookmark),
     SearchUpdate(crate::search::SearchUpdate),
+    /// Replaces every match of the local search with the text
+    ReplaceAll(String),
     OpenFile(String),
-    SaveCurrentFile,
+    OpenFileBackground(String),
+    /// Saves the current file, over changes made to it on disk when
+    /// `overwrite` is set
+    SaveCurrentFile {
+        overwrite: bool,
+    },
+    /// Shows the file on disk against the buffer about to be saved over it
+    ShowSaveConflict,
     FileChangedExternally(notify::Event),
-    AutocompleteResult(Result<Vec<ratatui_code_editor::code::Edit>>),
+    AutocompleteResult(AutocompleteResult),
+    /// Redraw while waiting on an AI request
+    Tick,
     None,
 }
 
@@ -44,6 +176,38 @@ pub struct LeftPanel {
     is_resizing: bool,
     pub tree: TreePanel,
     pub search: SearchPanel,
+    pub outline: OutlinePanel,
+    pub recent: RecentPanel,
+    pub bookmarks: BookmarksPanel,
+}
+
+/// What the text typed in the status row is for
+enum PromptKind {
+    Instruction,
+    Language,
+    /// `y` reloads the current file although the buffer differs from it
+    ConfirmReload,
+    /// `o` saves over a file changed on disk, `r` reloads it, `d` shows
+    /// the difference
+    SaveConflict,
+    /// The note of a bookmark about to be added, which may stay empty
+    BookmarkNote(Bookmark),
+    /// Text every match of the local search is replaced with, may be empty
+    Replace,
+}
+
+struct Prompt {
+    kind: PromptKind,
+    text: String,
+}
+
+impl Prompt {
+    fn new(kind: PromptKind) -> Self {
+        Self {
+            kind,
+            text: String::new(),
+        }
+    }
 }
 
 pub struct App {
@@ -51,23 +215,57 @@ pub struct App {
     theme: Theme,
     left_panel: LeftPanel,
     editor_panel: EditorPanel,
+    status: StatusBar,
     watcher: FsWatcher,
+    /// Workspace roots given on the command line
+    roots: Vec<PathBuf>,
+    /// `--no-*` switches, which outlive config reloads
+    switches: Switches,
+    /// Why autocomplete is unavailable, shown when its key is pressed
+    ai_hint: Option<&'static str>,
+    /// Change marks are cleared after this long without interaction
+    marks_timeout: Option<Duration>,
+    last_interaction: Instant,
+    jumps: JumpList,
+    /// The recent edits panel needs refreshing before it is shown again
+    recent_stale: bool,
+    /// Snippet menu, while open it takes all key and mouse input
+    snippets: Option<SnippetMenu>,
+    history: Option<HistoryMenu>,
+    snippets_file: PathBuf,
+    templates_file: PathBuf,
+    bookmarks_file: PathBuf,
+    /// Text being typed, shown instead of the status bar
+    prompt: Option<Prompt>,
+    /// Full-screen diff, while open it takes all key and mouse input
+    diff: Option<DiffView>,
+    /// Env files re-read when the config is reloaded, under the process
+    /// environment
+    env: ConfigEnv,
 }
 
 impl App {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         language: &str,
         content: &str,
+        encoding: Encoding,
         filename: &str,
         llm_client: Option<LlmClient>,
+        config: &Config,
+        args: &Args,
+        env: ConfigEnv,
     ) -> Result<Self> {
-        let root_path = std::env::current_dir().unwrap();
+        let roots = &args.roots;
         let theme = ratatui_code_editor::theme::vesper();
         let left_panel_visible = filename.is_empty();
 
-        let tree = TreePanel::new(&root_path, &theme);
-        let search = SearchPanel::new();
-        let center = EditorPanel::new(language, content, filename, llm_client)?;
+        let tree = TreePanel::new(roots, &theme);
+        let mut search = SearchPanel::new();
+        search.roots = roots.to_vec();
+        let highlight = !config.no_highlight;
+        let mut center = EditorPanel::new(language, content, filename, llm_client, highlight)?;
+        center.set_encoding(filename, encoding);
 
         let left = LeftPanel {
             visible: left_panel_visible,
@@ -78,6 +276,9 @@ impl App {
             is_resizing: false,
             tree,
             search,
+            outline: OutlinePanel::new(),
+            recent: RecentPanel::new(),
+            bookmarks: BookmarksPanel::new(),
         };
 
         let mut app = Self {
@@ -85,22 +286,98 @@ impl App {
             theme: theme.clone(),
             left_panel: left,
             editor_panel: center,
-            watcher: FsWatcher::new(),
+            status: StatusBar::new(),
+            watcher: if config.no_watch {
+                FsWatcher::disabled()
+            } else {
+                FsWatcher::new()
+            },
+            roots: roots.to_vec(),
+            switches: args.switches,
+            ai_hint: None,
+            marks_timeout: None,
+            last_interaction: Instant::now(),
+            jumps: JumpList::new(),
+            recent_stale: false,
+            snippets: None,
+            history: None,
+            snippets_file: PathBuf::new(),
+            templates_file: PathBuf::new(),
+            bookmarks_file: PathBuf::new(),
+            prompt: None,
+            diff: None,
+            env,
         };
+        app.apply_config(config);
+
+        let mut skipped = Vec::new();
+        for extra in args.files.iter().skip(1) {
+            if !Path::new(extra).is_file() {
+                skipped.push(format!("{extra} (not a file)"));
+            } else if let Err(err) = app.editor_panel.preload(extra) {
+                skipped.push(format!("{extra} ({err})"));
+            }
+        }
+        if !skipped.is_empty() {
+            app.status.warn(format!("skipped {}", skipped.join(", ")));
+        }
 
         if !filename.is_empty() {
             app.left_panel.tree.open_file_path(filename, &app.theme);
+            let stats = FileStats::of(content);
+            if stats.is_large() {
+                app.status.warn(format!(
+                    "large file ({stats}), syntax highlighting disabled"
+                ));
+            }
+            if !Path::new(filename).exists() {
+                app.status
+                    .info(format!("new file {filename}, created on save"));
+            }
+            app.warn_mixed_line_endings();
+            app.offer_template();
+        }
+        if let Some(query) = &args.search {
+            app.activate_search(SearchMode::GlobalSearch)?;
+            app.left_panel.search.query = query.clone();
+            app.run_search();
         }
         app.sync_watch_paths()?;
+        for warning in &args.warnings {
+            app.status.warn(warning.clone());
+        }
 
         Ok(app)
     }
 
+    /// Tells which of several candidates the marked completion is
+    fn show_candidate(&mut self) {
+        if let Some(label) = self.editor_panel.candidate_label() {
+            self.status.toast(format!("{label}, Alt+j shows the next"));
+        }
+    }
+
+    /// Applies edits given as JSON with `--apply-edits` to the current
+    /// buffer, marked like AI edits for review before saving
+    pub fn apply_edits_json(&mut self, json: &str) {
+        match self.editor_panel.apply_edits_json(json) {
+            Ok(0) => self.status.info("no edits to apply"),
+            Ok(count) => self
+                .status
+                .toast(format!("{count} edits applied, Ctrl+Z undoes them")),
+            Err(err) => self.status.error(format!("cannot apply edits: {err}")),
+        }
+    }
+
     pub async fn run(mut self, mut terminal: DefaultTerminal) -> Result<()> {
         let mut events = EventStream::new();
         terminal.draw(|frame| self.render(frame))?;
+        let mut tick = tokio::time::interval(TICK);
+        tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
 
         while !self.quit {
+            let marks_deadline = self.marks_deadline();
+            let flash_deadline = self.editor_panel.flash_deadline();
             let msg = tokio::select! {
                 maybe_event = events.next() => {
                     match maybe_event {
@@ -126,50 +403,297 @@ impl App {
                         _ => Message::None,
                     }
                 }
+                _ = sleep_until(marks_deadline) => Message::ClearMarks,
+                // redraw once the change highlight is over
+                _ = sleep_until(flash_deadline.filter(|d| *d > Instant::now())) => Message::None,
+                // only ticks while a request runs, idle the loop just waits
+                _ = tick.tick(), if self.editor_panel.autocomplete_busy() => Message::Tick,
             };
 
+            // a tick that finds the request done has nothing new to show
+            let redraw = !matches!(msg, Message::Tick) || self.editor_panel.autocomplete_busy();
             self.update(msg).await?;
-            terminal.draw(|frame| self.render(frame))?;
+            self.refresh_recent().await;
+            if redraw {
+                terminal.draw(|frame| self.render(frame))?;
+            }
         }
 
         Ok(())
     }
 
     fn render(&mut self, frame: &mut Frame) {
-        let left_ratio = if self.left_panel.visible {
-            self.left_panel.split_ratio as u16
-        } else {
-            0
-        };
-        let chunks = Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints([
-                Constraint::Percentage(left_ratio),
-                Constraint::Percentage(100 - left_ratio),
-            ])
-            .split(frame.area());
+        if let Some(view) = &mut self.diff {
+            view.render(frame);
+            return;
+        }
+        let left_ratio = self
+            .left_panel
+            .visible
+            .then_some(self.left_panel.split_ratio);
+        let [left, editor, status] =
+            screen_layout(frame.area(), left_ratio, self.left_panel.focused);
+        let rows = [editor, status];
+        let chunks = [left, editor];
 
         self.left_panel.area = chunks[0];
-        self.editor_panel.area = chunks[1];
+        let (editor_area, editor_bar) = scrollbar::split(chunks[1]);
+        self.editor_panel.set_area(editor_area);
+
+        // the divider between the panes lights up while the left one has focus
+        if self.left_panel.visible && chunks[0].width > 0 && chunks[1].width > 0 {
+            self.left_panel.area.width -= 1;
+            let key = if self.left_panel.focused {
+                "function"
+            } else {
+                "comment"
+            };
+            let color = find_color(&self.theme, key).unwrap_or(Color::DarkGray);
+            let divider = Block::default()
+                .borders(Borders::RIGHT)
+                .border_style(Style::default().fg(color));
+            frame.render_widget(divider, chunks[0]);
+        }
 
         if self.left_panel.visible {
             match self.left_panel.mode {
                 LeftPanelMode::Search => self.left_panel.search.render(frame, self.left_panel.area),
                 LeftPanelMode::Tree => self.left_panel.tree.render(frame, self.left_panel.area),
+                LeftPanelMode::Outline => {
+                    self.left_panel.outline.render(frame, self.left_panel.area)
+                }
+                LeftPanelMode::Recent => self.left_panel.recent.render(frame, self.left_panel.area),
+                LeftPanelMode::Bookmarks => self
+                    .left_panel
+                    .bookmarks
+                    .render(frame, self.left_panel.area),
             }
         }
 
         self.editor_panel.render(frame);
+        self.editor_panel.render_scrollbar(frame, editor_bar);
+        if let Some(menu) = &mut self.snippets {
+            menu.render(frame, self.editor_panel.area);
+        }
+        if let Some(menu) = &mut self.history {
+            menu.render(frame, self.editor_panel.area);
+        }
+        match &self.prompt {
+            Some(Prompt { kind, text }) => {
+                let label = match kind {
+                    PromptKind::Instruction => " Instruction: ",
+                    PromptKind::Language => " Language: ",
+                    PromptKind::ConfirmReload => {
+                        " Reload from disk, discarding changes in the buffer? (y/n) "
+                    }
+                    PromptKind::SaveConflict => {
+                        " Changed on disk: (o)verwrite with the buffer, (r)eload it, (d)iff? "
+                    }
+                    PromptKind::BookmarkNote(_) => " Bookmark note (optional): ",
+                    PromptKind::Replace => " Replace all matches with: ",
+                };
+                let prompt = Line::from(vec![
+                    Span::styled(label, Style::default().fg(Color::Yellow)),
+                    Span::raw(format!("{text}▏")),
+                ]);
+                frame.render_widget(Paragraph::new(prompt), rows[1]);
+            }
+            None => self
+                .status
+                .render(frame, rows[1], &self.editor_panel.status_info()),
+        }
     }
 
     fn handle_event(&mut self, event: &Event) -> Message {
+        self.last_interaction = Instant::now();
+        if let Some(view) = &mut self.diff {
+            view.handle_event(event);
+            if view.closed() {
+                // back to the choice the diff was opened from
+                self.diff = None;
+                self.prompt = Some(Prompt::new(PromptKind::SaveConflict));
+            }
+            return Message::None;
+        }
+        if let Some(menu) = &mut self.snippets
+            && matches!(event, Event::Key(_) | Event::Mouse(_))
+        {
+            return Message::SnippetAction(menu.handle_event(event));
+        }
+        if let Some(menu) = &mut self.history
+            && matches!(event, Event::Key(_) | Event::Mouse(_))
+        {
+            return Message::HistoryAction(menu.handle_event(event));
+        }
+        if self.prompt.is_some() {
+            return self.handle_prompt_event(event);
+        }
+        if let Event::Key(key) = event
+            && key.kind == KeyEventKind::Press
+        {
+            self.status.dismiss_toast();
+        }
         match event {
             Event::Key(key) => {
                 if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('t') {
                     return Message::ToggleLeftPanel;
                 }
-                if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char(' ') {
-                    self.editor_panel.spawn_autocomplete();
+                if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('b') {
+                    return Message::CycleLeftPanel;
+                }
+                // Ctrl+Tab needs keyboard enhancement as well, Alt+. does not
+                if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Tab
+                    || key.modifiers == KeyModifiers::ALT && key.code == KeyCode::Char('.')
+                {
+                    return Message::NextBuffer;
+                }
+                // Ctrl+digit needs keyboard enhancement, Alt+digit works everywhere
+                if key
+                    .modifiers
+                    .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+                {
+                    match key.code {
+                        KeyCode::Char('1') => return Message::FocusTree,
+                        KeyCode::Char('2') => return Message::FocusEditor,
+                        _ => {}
+                    }
+                }
+                // Ctrl+] arrives as Ctrl+5 in terminals without keyboard enhancement
+                if key.modifiers.contains(KeyModifiers::CONTROL)
+                    && matches!(key.code, KeyCode::Char(']') | KeyCode::Char('5'))
+                {
+                    return Message::GotoDefinition;
+                }
+                // Ctrl+Shift+E arrives as Ctrl+E in most terminals
+                if key.modifiers.contains(KeyModifiers::CONTROL)
+                    && matches!(key.code, KeyCode::Char('e') | KeyCode::Char('E'))
+                {
+                    return Message::RevealInTree;
+                }
+                if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('l') {
+                    return Message::ClearMarks;
+                }
+                // Ctrl+Shift+O needs keyboard enhancement, else it is Ctrl+O
+                if key
+                    .modifiers
+                    .contains(KeyModifiers::CONTROL | KeyModifiers::SHIFT)
+                    && matches!(key.code, KeyCode::Char('o') | KeyCode::Char('O'))
+                    || key.modifiers.contains(KeyModifiers::CONTROL)
+                        && key.code == KeyCode::Char('O')
+                {
+                    return Message::OpenPathUnderCursor;
+                }
+                // Ctrl+I only arrives as itself with keyboard enhancement,
+                // otherwise it is Tab, so Alt+Left/Right work everywhere
+                if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('o')
+                    || key.modifiers == KeyModifiers::ALT && key.code == KeyCode::Left
+                {
+                    return Message::JumpBack;
+                }
+                if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('i')
+                    || key.modifiers == KeyModifiers::ALT && key.code == KeyCode::Right
+                {
+                    return Message::JumpForward;
+                }
+                // the mouse-free way to move the divider
+                if self.left_panel.focused
+                    && key
+                        .modifiers
+                        .contains(KeyModifiers::CONTROL | KeyModifiers::SHIFT)
+                    && matches!(key.code, KeyCode::Left | KeyCode::Right)
+                {
+                    return Message::ResizeLeftPanel {
+                        wider: key.code == KeyCode::Right,
+                    };
+                }
+                if key.code == KeyCode::F(5) {
+                    return Message::ReloadConfig;
+                }
+                if key.code == KeyCode::F(7) {
+                    let backwards = key.modifiers.contains(KeyModifiers::SHIFT);
+                    return Message::JumpToChange { backwards };
+                }
+                // in search it bookmarks the selected result
+                if key.modifiers == KeyModifiers::ALT && key.code == KeyCode::Char('b') {
+                    match self.bookmark_target() {
+                        Some(bookmark) => {
+                            self.prompt = Some(Prompt::new(PromptKind::BookmarkNote(bookmark)))
+                        }
+                        None => self.status.info("nothing to bookmark, open a file first"),
+                    }
+                    return Message::None;
+                }
+                if key.modifiers.contains(KeyModifiers::ALT) && !self.left_panel.focused {
+                    let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+                    match key.code {
+                        KeyCode::Char('P') => return Message::InsertFilePath(PathForm::Absolute),
+                        KeyCode::Char('p') if shift => {
+                            return Message::InsertFilePath(PathForm::Absolute);
+                        }
+                        KeyCode::Char('p') => return Message::InsertFilePath(PathForm::Relative),
+                        KeyCode::Char('n') => return Message::InsertFilePath(PathForm::Stem),
+                        KeyCode::Char('c') => return Message::CopyPermalink,
+                        KeyCode::Char('h') => return Message::ToggleHighlight,
+                        KeyCode::Char('d') => return Message::ShowLlmDebug,
+                        KeyCode::Char('l') => return Message::NormalizeLineEndings,
+                        KeyCode::Char('w') => return Message::ToggleWhitespace,
+                        KeyCode::Char('v') => return Message::ToggleReindentPaste,
+                        KeyCode::Char('K') => return Message::ClearPinned,
+                        KeyCode::Char('k') if shift => return Message::ClearPinned,
+                        KeyCode::Char('k') => return Message::PinSelection,
+                        KeyCode::Char('s') => return Message::OpenSnippets,
+                        KeyCode::Char('t') => return Message::InsertTemplate,
+                        KeyCode::Char('u') => return Message::OpenHistory,
+                        KeyCode::Char('o') => return Message::OpenPathUnderCursor,
+                        KeyCode::Char('f') => return Message::FormatSelection,
+                        KeyCode::Char('m') => return Message::WriteCommitMsg,
+                        KeyCode::Char('j') => return Message::NextCandidate,
+                        KeyCode::Char('x') => return Message::ToggleSplit,
+                        KeyCode::Up | KeyCode::Down if self.editor_panel.is_split() => {
+                            return Message::SwitchPane;
+                        }
+                        KeyCode::Char('r') => return Message::ReloadFile { confirmed: false },
+                        KeyCode::Char('g') => {
+                            self.prompt = Some(Prompt::new(PromptKind::Language));
+                            return Message::None;
+                        }
+                        _ => {}
+                    }
+                }
+                // a second Esc after hiding the search panel drops the search
+                if key.code == KeyCode::Esc
+                    && !self.left_panel.focused
+                    && self.left_panel.search.is_hidden()
+                    && !self.editor_panel.editor.has_marks()
+                {
+                    self.left_panel.search.deactivate();
+                    self.status.info("search cleared");
+                    return Message::None;
+                }
+                if self.editor_panel.instruct_key.matches(key) {
+                    match self.ai_hint {
+                        Some(hint) => self.status.info(hint),
+                        None if !self.editor_panel.has_selection() => {
+                            self.status.info("select the code to change first")
+                        }
+                        None => self.prompt = Some(Prompt::new(PromptKind::Instruction)),
+                    }
+                    return Message::None;
+                }
+                if self.editor_panel.autocomplete_key.matches(key) {
+                    match self.ai_hint {
+                        Some(hint) => self.status.info(hint),
+                        None if !self.editor_panel.autocomplete_enabled() => {
+                            let what = match get_lang(&self.editor_panel.filename).as_str() {
+                                "unknown" => "this file".to_string(),
+                                lang => format!("{lang} files"),
+                            };
+                            self.status.info(format!(
+                                "autocomplete is off for {what} (REDAI_AUTOCOMPLETE_LANGUAGES)"
+                            ));
+                        }
+                        None => self.editor_panel.spawn_autocomplete(),
+                    }
                     return Message::None;
                 }
             }
@@ -190,13 +714,10 @@ impl App {
                             self.left_panel.focused = is_focused(mouse, self.left_panel.area);
                         }
                     }
-                    MouseEventKind::Drag(_) => {
-                        if self.left_panel.is_resizing {
-                            let total =
-                                self.left_panel.area.width + self.editor_panel.area.width + 2;
-                            let ratio = (mouse.column as f32 / total as f32 * 100.0) as u16;
-                            self.left_panel.split_ratio = ratio.clamp(0, 100) as usize;
-                        }
+                    MouseEventKind::Drag(_) if self.left_panel.is_resizing => {
+                        let total = self.left_panel.area.width + self.editor_panel.area.width + 2;
+                        let ratio = (mouse.column as f32 / total as f32 * 100.0) as u16;
+                        self.left_panel.split_ratio = ratio.clamp(0, 100) as usize;
                     }
                     MouseEventKind::Up(_) => self.left_panel.is_resizing = false,
                     _ => {}
@@ -218,6 +739,27 @@ impl App {
                         .handle_event(event, self.left_panel.area);
                     Message::SearchAction(action)
                 }
+                LeftPanelMode::Outline => {
+                    let action = self
+                        .left_panel
+                        .outline
+                        .handle_event(event, self.left_panel.area);
+                    Message::OutlineAction(action)
+                }
+                LeftPanelMode::Recent => {
+                    let action = self
+                        .left_panel
+                        .recent
+                        .handle_event(event, self.left_panel.area);
+                    Message::RecentAction(action)
+                }
+                LeftPanelMode::Bookmarks => {
+                    let action = self
+                        .left_panel
+                        .bookmarks
+                        .handle_event(event, self.left_panel.area);
+                    Message::BookmarkAction(action)
+                }
                 LeftPanelMode::Tree => {
                     let action =
                         self.left_panel
@@ -225,6 +767,7 @@ impl App {
                             .handle_event(event, self.left_panel.area, &self.theme);
                     match action {
                         TreeAction::OpenFile(path) => Message::OpenFile(path),
+                        TreeAction::OpenFileBackground(path) => Message::OpenFileBackground(path),
                         TreeAction::Quit => Message::Quit,
                         TreeAction::None => Message::None,
                     }
@@ -235,31 +778,340 @@ impl App {
         }
     }
 
+    /// Typing in the prompt: `Enter` submits it, `Esc` cancels
+    fn handle_prompt_event(&mut self, event: &Event) -> Message {
+        let Some(Prompt { kind, text }) = &mut self.prompt else {
+            return Message::None;
+        };
+        if let PromptKind::ConfirmReload = kind {
+            if let Event::Key(key) = event
+                && key.kind == KeyEventKind::Press
+            {
+                self.prompt = None;
+                if matches!(key.code, KeyCode::Char('y') | KeyCode::Char('Y')) {
+                    return Message::ReloadFile { confirmed: true };
+                }
+                self.status.info("reload cancelled");
+            }
+            return Message::None;
+        }
+        if let PromptKind::SaveConflict = kind {
+            if let Event::Key(key) = event
+                && key.kind == KeyEventKind::Press
+            {
+                self.prompt = None;
+                match key.code {
+                    KeyCode::Char('o') | KeyCode::Char('O') => {
+                        return Message::SaveCurrentFile { overwrite: true };
+                    }
+                    KeyCode::Char('r') | KeyCode::Char('R') => {
+                        return Message::ReloadFile { confirmed: true };
+                    }
+                    KeyCode::Char('d') | KeyCode::Char('D') => return Message::ShowSaveConflict,
+                    _ => self.status.info("save cancelled"),
+                }
+            }
+            return Message::None;
+        }
+        match event {
+            Event::Key(key) if key.kind == KeyEventKind::Press => match key.code {
+                KeyCode::Esc => self.prompt = None,
+                KeyCode::Enter
+                    if text.trim().is_empty()
+                        && !matches!(kind, PromptKind::BookmarkNote(_) | PromptKind::Replace) =>
+                {
+                    self.prompt = None
+                }
+                KeyCode::Enter => {
+                    let text = std::mem::take(text);
+                    return match kind {
+                        PromptKind::Instruction => Message::Instruct(text),
+                        PromptKind::Language => Message::SetLanguage(text),
+                        PromptKind::ConfirmReload | PromptKind::SaveConflict => Message::None,
+                        PromptKind::BookmarkNote(bookmark) => Message::AddBookmark(Bookmark {
+                            note: text.trim().to_string(),
+                            ..bookmark.clone()
+                        }),
+                        PromptKind::Replace => Message::ReplaceAll(text),
+                    };
+                }
+                KeyCode::Backspace => {
+                    text.pop();
+                }
+                KeyCode::Char(c)
+                    if !key
+                        .modifiers
+                        .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+                {
+                    text.push(c)
+                }
+                _ => {}
+            },
+            Event::Paste(pasted) => text.push_str(&pasted.replace('\n', " ")),
+            _ => {}
+        }
+        Message::None
+    }
+
     fn handle_editor_event(&mut self, event: &Event) -> Message {
+        self.recent_stale = true;
         match self.editor_panel.handle_event(event) {
             EditorAction::Quit => Message::Quit,
             EditorAction::ActivateSearch(mode) => Message::ActivateSearch(mode),
-            EditorAction::Save => Message::SaveCurrentFile,
+            EditorAction::Save => Message::SaveCurrentFile { overwrite: false },
+            EditorAction::AcceptedAiEdit => {
+                self.status.toast("AI edit applied, Ctrl+Z undoes it");
+                Message::None
+            }
             EditorAction::None => Message::None,
         }
     }
 
     async fn update(&mut self, msg: Message) -> Result<()> {
         match msg {
-            Message::Quit => self.quit = true,
+            Message::Quit => {
+                self.editor_panel.shutdown().await;
+                self.quit = true;
+            }
             Message::ToggleLeftPanel => self.toggle_left_panel(),
+            Message::ResizeLeftPanel { wider } => {
+                let ratio = self.left_panel.split_ratio;
+                self.left_panel.split_ratio = if wider {
+                    (ratio + SPLIT_STEP).min(SPLIT_MAX)
+                } else {
+                    ratio.saturating_sub(SPLIT_STEP).max(SPLIT_MIN)
+                };
+            }
+            Message::FocusTree => self.focus_tree(),
+            Message::FocusEditor => self.left_panel.focused = false,
+            Message::ShowLlmDebug => self.show_llm_debug().await?,
+            Message::NextBuffer => match self.editor_panel.next_buffer() {
+                Some(name) => {
+                    self.open_file(&name).await?;
+                }
+                None => self.status.info("no other open files"),
+            },
+            Message::CycleLeftPanel => self.cycle_left_panel(),
+            Message::RevealInTree => self.reveal_in_tree()?,
+            Message::GotoDefinition => self.goto_definition().await?,
+            Message::OpenPathUnderCursor => self.open_path_under_cursor().await?,
+            Message::ReloadFile { confirmed } => self.reload_file(confirmed).await?,
+            Message::ClearMarks => self.editor_panel.editor.remove_marks(),
+            Message::ReloadConfig => self.reload_config().await,
+            Message::JumpToChange { backwards } => {
+                let origin = self.current_jump();
+                match self.editor_panel.jump_to_change(backwards) {
+                    Some((index, total)) => {
+                        self.record_jump(origin);
+                        self.status.info(format!("change {index}/{total}"))
+                    }
+                    None => self.status.info("no changes since the file was opened"),
+                }
+            }
+            Message::JumpBack => self.walk_jumps(false).await?,
+            Message::JumpForward => self.walk_jumps(true).await?,
+            Message::InsertFilePath(form) => {
+                if let Some(text) = self.editor_panel.insert_file_path(form, &self.roots) {
+                    self.status.info(format!("inserted {text}"));
+                }
+            }
+            Message::CopyPermalink => self.copy_permalink(),
+            Message::ToggleHighlight => {
+                let highlight = !self.editor_panel.highlight();
+                self.editor_panel.set_highlight(highlight)?;
+                let state = if highlight { "on" } else { "off" };
+                self.status.info(format!("syntax highlighting {state}"));
+            }
+            Message::ToggleWhitespace => {
+                let show = !self.editor_panel.show_whitespace;
+                self.editor_panel.show_whitespace = show;
+                let state = if show { "shown" } else { "hidden" };
+                self.status.info(format!("whitespace {state}"));
+            }
+            Message::ToggleReindentPaste => {
+                let reindent = !self.editor_panel.reindent_paste;
+                self.editor_panel.reindent_paste = reindent;
+                let state = if reindent { "on" } else { "off" };
+                self.status.info(format!("paste reindent {state}"));
+            }
+            Message::NormalizeLineEndings => match self.editor_panel.normalize_line_endings() {
+                Some(style) => self
+                    .status
+                    .info(format!("line endings converted to {style}")),
+                None => self.status.info("line endings are consistent"),
+            },
+            Message::PinSelection => match self.editor_panel.pin_selection().await {
+                Some((start, end)) => self.status.info(format!(
+                    "lines {start}-{end} pinned as AI context, Alt+Shift+K clears"
+                )),
+                None => self.status.info("select the code to pin as AI context"),
+            },
+            Message::ClearPinned => {
+                let count = self.editor_panel.clear_pinned().await;
+                self.status.info(format!("{count} pinned regions cleared"));
+            }
+            Message::Instruct(instruction) => {
+                self.prompt = None;
+                self.editor_panel.spawn_instruct(instruction);
+            }
+            Message::ToggleSplit => {
+                if self.editor_panel.toggle_split() {
+                    self.status
+                        .info("split: Alt+Up / Alt+Down switches panes, Alt+x closes");
+                } else if self.editor_panel.filename.is_empty() {
+                    self.status.info("no file to split");
+                }
+            }
+            Message::SwitchPane => self.editor_panel.switch_pane(),
+            Message::NextCandidate => match self.editor_panel.next_candidate() {
+                Ok(true) => self.show_candidate(),
+                Ok(false) => self.status.info("no other candidates for this completion"),
+                Err(err) => self.status.error(format!("candidate: {err}")),
+            },
+            Message::FormatSelection => match self.ai_hint {
+                Some(hint) => self.status.info(hint),
+                None if !self.editor_panel.has_selection() => {
+                    self.status.info("select the code to format first")
+                }
+                None => self.editor_panel.spawn_format(),
+            },
+            Message::WriteCommitMsg => match self.ai_hint {
+                Some(hint) => self.status.info(hint),
+                None => self.editor_panel.spawn_commit_message(),
+            },
+            Message::SetLanguage(lang) => {
+                self.prompt = None;
+                match self.editor_panel.set_language(&lang) {
+                    Ok(()) => self.status.info(format!(
+                        "highlighting {} as {}",
+                        display_path(Path::new(&self.editor_panel.filename), &self.roots),
+                        self.editor_panel.language()
+                    )),
+                    Err(err) => self.status.error(format!("{err}")),
+                }
+            }
+            Message::InsertTemplate => self.insert_template(),
+            Message::OpenSnippets => self.open_snippets(),
+            Message::SnippetAction(action) => self.process_snippet_action(action),
+            Message::OpenHistory => {
+                let entries = self.editor_panel.undo_history();
+                if entries.len() > 1 {
+                    self.history = Some(HistoryMenu::new(entries));
+                } else {
+                    self.status.info("nothing to undo or redo");
+                }
+            }
+            Message::HistoryAction(action) => self.process_history_action(action),
             Message::ActivateSearch(mode) => self.activate_search(mode)?,
             Message::SearchAction(action) => self.process_search_action(action).await?,
+            Message::OutlineAction(action) => self.process_outline_action(action),
+            Message::RecentAction(action) => self.process_recent_action(action).await?,
+            Message::BookmarkAction(action) => self.process_bookmark_action(action).await?,
+            Message::AddBookmark(bookmark) => {
+                self.prompt = None;
+                self.add_bookmark(bookmark);
+            }
             Message::SearchUpdate(update) => self.left_panel.search.apply_update(update),
-            Message::OpenFile(path) => self.open_file(&path).await?,
-            Message::SaveCurrentFile => self.editor_panel.save().await?,
+            Message::ReplaceAll(text) => self.replace_all(&text),
+            Message::OpenFile(path) => {
+                let origin = self.current_jump();
+                if self.open_file(&path).await? {
+                    self.record_jump(origin);
+                }
+            }
+            Message::OpenFileBackground(path) => {
+                let name = display_path(Path::new(&path), &self.roots);
+                match self.editor_panel.preload(&path) {
+                    Ok(()) => {
+                        self.sync_watch_paths()?;
+                        self.status.info(format!(
+                            "{name} opened in the background, Ctrl+Tab switches"
+                        ));
+                    }
+                    Err(err) => self.status.error(format!("cannot open {name}: {err}")),
+                }
+            }
+            Message::SaveCurrentFile { overwrite } => self.save_file(overwrite).await?,
+            Message::ShowSaveConflict => self.show_save_conflict(),
             Message::FileChangedExternally(e) => self.handle_file_change(e).await?,
-            Message::AutocompleteResult(r) => self.editor_panel.handle_autocomplete(r).await?,
-            Message::None => {}
+            Message::AutocompleteResult(r) => {
+                self.last_interaction = Instant::now();
+                self.recent_stale = true;
+                self.handle_autocomplete(r).await?
+            }
+            Message::Tick | Message::None => {}
+        }
+        Ok(())
+    }
+
+    async fn handle_autocomplete(&mut self, (version, result): AutocompleteResult) -> Result<()> {
+        // a result for an older request or for a buffer edited since is dropped
+        if !self.editor_panel.is_current_autocomplete(version) {
+            log::debug!("dropping stale autocomplete result {version}");
+            return Ok(());
+        }
+        match result {
+            Ok(edits) if edits.is_empty() => self.status.info("autocomplete: no changes"),
+            Ok(edits) => {
+                match self.editor_panel.handle_autocomplete(edits).await {
+                    Ok(Some(lines)) => self.status.warn(format!(
+                        "AI edit deletes {lines} lines: Tab or Enter applies it, any other key discards it"
+                    )),
+                    Ok(None) => self.show_candidate(),
+                    Err(err) => self.status.error(format!("autocomplete: {err}")),
+                }
+            }
+            Err(err) => self
+                .status
+                .error(format!("autocomplete: {err} ({})", err.advice())),
         }
         Ok(())
     }
 
+    /// Opens the menu of snippets for the current file's language
+    fn open_snippets(&mut self) {
+        let lang = self.editor_panel.language();
+        match crate::snippets::load(&self.snippets_file, &lang) {
+            Ok(items) if items.is_empty() => self.status.info(format!(
+                "no snippets for {lang} files, add them to {}",
+                self.snippets_file.display()
+            )),
+            Ok(items) => self.snippets = Some(SnippetMenu::new(items)),
+            Err(err) => self.status.error(format!("snippets: {err:#}")),
+        }
+    }
+
+    fn process_snippet_action(&mut self, action: SnippetAction) {
+        match action {
+            SnippetAction::None => {}
+            SnippetAction::Close => self.snippets = None,
+            SnippetAction::Apply(snippet) => {
+                self.snippets = None;
+                self.editor_panel.wrap_selection(&snippet.body);
+                self.recent_stale = true;
+                self.status
+                    .info(format!("inserted snippet {}", snippet.name));
+            }
+        }
+    }
+
+    fn process_history_action(&mut self, action: HistoryAction) {
+        match action {
+            HistoryAction::None => {}
+            HistoryAction::Close => self.history = None,
+            HistoryAction::Travel(steps) => {
+                self.history = None;
+                self.editor_panel.travel_history(steps);
+                self.recent_stale = true;
+                match steps {
+                    0 => {}
+                    steps if steps < 0 => self.status.info(format!("undid {} edits", -steps)),
+                    steps => self.status.info(format!("redid {steps} edits")),
+                }
+            }
+        }
+    }
+
     fn toggle_left_panel(&mut self) {
         if !self.left_panel.visible {
             self.left_panel.visible = true;
@@ -271,6 +1123,453 @@ impl App {
         }
     }
 
+    /// Opens the dump of the last LLM exchange; it is rewritten on every
+    /// request and the open buffer follows it
+    async fn show_llm_debug(&mut self) -> Result<()> {
+        let Some(path) = self.editor_panel.llm_debug_file.clone() else {
+            self.status
+                .info("set REDAI_DEBUG_LLM to record LLM requests");
+            return Ok(());
+        };
+        if !path.exists() {
+            self.status.info("no LLM request yet");
+            return Ok(());
+        }
+        self.open_file(&abs_file(&path.to_string_lossy())).await?;
+        Ok(())
+    }
+
+    /// Shows the file tree and focuses it, whatever was shown or focused
+    fn focus_tree(&mut self) {
+        self.left_panel.visible = true;
+        self.left_panel.mode = LeftPanelMode::Tree;
+        self.left_panel.focused = true;
+    }
+
+    /// Switches the left panel Tree -> Search -> Outline -> Recent ->
+    /// Bookmarks, keeping the search query and results around for the next
+    /// visit
+    fn cycle_left_panel(&mut self) {
+        if self.left_panel.visible {
+            self.left_panel.mode = match self.left_panel.mode {
+                LeftPanelMode::Tree => LeftPanelMode::Search,
+                LeftPanelMode::Search => LeftPanelMode::Outline,
+                LeftPanelMode::Outline => LeftPanelMode::Recent,
+                LeftPanelMode::Recent => LeftPanelMode::Bookmarks,
+                LeftPanelMode::Bookmarks => LeftPanelMode::Tree,
+            };
+        }
+        self.left_panel.visible = true;
+        self.left_panel.focused = true;
+
+        match self.left_panel.mode {
+            LeftPanelMode::Search => self.left_panel.search.active = true,
+            LeftPanelMode::Outline => {
+                let content = self.editor_panel.editor.get_content();
+                self.left_panel.outline.refresh(&content);
+            }
+            LeftPanelMode::Recent => self.recent_stale = true,
+            LeftPanelMode::Tree | LeftPanelMode::Bookmarks => {}
+        }
+    }
+
+    /// Jumps to the definition of the word under the cursor using the
+    /// project's ctags file; several candidates are listed in the search panel
+    async fn goto_definition(&mut self) -> Result<()> {
+        let Some(word) = self.editor_panel.word_under_cursor() else {
+            return Ok(());
+        };
+        let mut results = Vec::new();
+        let mut errors = Vec::new();
+        for root in &self.roots {
+            match find_definitions(root, &word) {
+                Ok(found) => results.extend(found),
+                Err(err) => errors.push(err),
+            }
+        }
+        if errors.len() == self.roots.len()
+            && let Some(err) = errors.first()
+        {
+            self.status.error(format!("go to definition: {err}"));
+            return Ok(());
+        }
+
+        match results.len() {
+            0 => self
+                .status
+                .info(format!("no definition found for '{word}'")),
+            1 => {
+                // opened here rather than through the search panel, whose
+                // fallback may still hold the origin of an earlier search
+                let result = results.remove(0);
+                let origin = self.current_jump();
+                if let Some(file_path) = &result.file_path
+                    && !self.open_file(file_path).await?
+                {
+                    return Ok(());
+                }
+                self.record_jump(origin);
+                self.editor_panel.editor.set_cursor(result.match_start);
+                self.editor_panel
+                    .editor
+                    .focus(&self.editor_panel.focus_area());
+                self.left_panel.focused = false;
+            }
+            n => {
+                self.activate_search(SearchMode::GlobalSearch)?;
+                self.left_panel
+                    .search
+                    .show_results(SearchMode::GlobalSearch, &word, results);
+                self.status.info(format!("{n} definitions for '{word}'"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Opens the file whose path is under the cursor, like Vim's `gf`,
+    /// going to the line when the path is followed by one
+    async fn open_path_under_cursor(&mut self) -> Result<()> {
+        let Some((token, line)) = self.editor_panel.path_under_cursor() else {
+            self.status.info("no file path under the cursor");
+            return Ok(());
+        };
+        let current = Path::new(&self.editor_panel.filename);
+        let Some(path) = resolve_path(&token, current, &self.roots) else {
+            self.status.info(format!("no file found for '{token}'"));
+            return Ok(());
+        };
+        let origin = self.current_jump();
+        if !self.open_file(&path.to_string_lossy()).await? {
+            return Ok(());
+        }
+        self.record_jump(origin);
+        if let Some(line) = line {
+            self.editor_panel.goto_line(line);
+        }
+        Ok(())
+    }
+
+    /// Replaces the current buffer with the file as it is on disk, asking
+    /// first when that loses text of the buffer
+    async fn reload_file(&mut self, confirmed: bool) -> Result<()> {
+        if self.editor_panel.filename.is_empty() {
+            return Ok(());
+        }
+        if !confirmed && self.editor_panel.differs_from_disk() {
+            self.prompt = Some(Prompt::new(PromptKind::ConfirmReload));
+            return Ok(());
+        }
+        let name = display_path(Path::new(&self.editor_panel.filename), &self.roots);
+        if let Err(err) = self.editor_panel.reload().await {
+            self.status.error(format!("cannot reload {name}: {err}"));
+            return Ok(());
+        }
+        let path = PathBuf::from(abs_file(&self.editor_panel.filename));
+        if let Err(err) = self.watcher.rewatch(&path) {
+            log::warn!("rewatch {}: {err}", path.display());
+        }
+        if self.left_panel.mode == LeftPanelMode::Outline {
+            let content = self.editor_panel.editor.get_content();
+            self.left_panel.outline.refresh(&content);
+        }
+        self.status.info(format!("reloaded {name} from disk"));
+        Ok(())
+    }
+
+    /// Saves the current file, asking first when it changed on disk since
+    /// it was read, unless `overwrite` already says to save over it
+    async fn save_file(&mut self, overwrite: bool) -> Result<()> {
+        if self.editor_panel.no_write {
+            self.status
+                .warn("not saved: writes are off (--no-write), copy the changes out instead");
+            return Ok(());
+        }
+        let created = !Path::new(&self.editor_panel.filename).exists();
+        let saved = if overwrite {
+            self.editor_panel.overwrite().await.map(|()| true)
+        } else {
+            self.editor_panel.save().await
+        };
+        match saved {
+            Ok(true) => {}
+            Ok(false) => {
+                self.prompt = Some(Prompt::new(PromptKind::SaveConflict));
+                return Ok(());
+            }
+            // the buffer stays as it is, e.g. to remove what the encoding lacks
+            Err(err) => {
+                let name = display_path(Path::new(&self.editor_panel.filename), &self.roots);
+                self.status.error(format!("cannot save {name}: {err:#}"));
+                return Ok(());
+            }
+        }
+        if created {
+            self.sync_watch_paths()?;
+        }
+        let content = self.editor_panel.editor.get_content();
+        let path = Path::new(&self.editor_panel.filename);
+        self.left_panel.search.update_symbols(path, &content);
+        self.recent_stale = true;
+        Ok(())
+    }
+
+    /// Opens the diff of the current file from disk to the buffer
+    fn show_save_conflict(&mut self) {
+        let filename = &self.editor_panel.filename;
+        let name = display_path(Path::new(filename), &self.roots);
+        match encoding::read_file(filename) {
+            Ok((disk, _)) => {
+                let buffer = self.editor_panel.editor.get_content();
+                let disk_name = format!("{name} (disk)");
+                let buffer_name = format!("{name} (buffer)");
+                self.diff = Some(DiffView::new(&disk_name, &disk, &buffer_name, &buffer));
+            }
+            Err(err) => self.status.error(format!("cannot read {name}: {err}")),
+        }
+    }
+
+    /// Copies a web link to the selected lines at the current commit
+    fn copy_permalink(&mut self) {
+        if self.editor_panel.filename.is_empty() {
+            return;
+        }
+        let (start, end) = self.editor_panel.selected_lines();
+        let path = Path::new(&self.editor_panel.filename);
+        match permalink(path, start, end) {
+            Ok(url) => {
+                let _ = self.editor_panel.editor.set_clipboard(&url);
+                self.status.info(format!("copied {url}"));
+            }
+            Err(err) => self.status.warn(format!("permalink: {err}")),
+        }
+    }
+
+    fn current_jump(&self) -> Option<Jump> {
+        (!self.editor_panel.filename.is_empty()).then(|| Jump {
+            filename: self.editor_panel.filename.clone(),
+            offset: self.editor_panel.editor.get_cursor(),
+        })
+    }
+
+    fn record_jump(&mut self, origin: Option<Jump>) {
+        if let Some(origin) = origin {
+            self.jumps.push(origin);
+        }
+    }
+
+    /// Moves through the jump list, reopening files as needed
+    async fn walk_jumps(&mut self, forward: bool) -> Result<()> {
+        let Some(current) = self.current_jump() else {
+            return Ok(());
+        };
+        let target = if forward {
+            self.jumps.forward()
+        } else {
+            self.jumps.back(current)
+        };
+        let Some(target) = target else {
+            self.status.info(if forward {
+                "no newer jump"
+            } else {
+                "no older jump"
+            });
+            return Ok(());
+        };
+
+        if target.filename != self.editor_panel.filename
+            && !self.open_file(&target.filename).await?
+        {
+            return Ok(());
+        }
+        // the file may have shrunk since the jump was recorded
+        let len = self.editor_panel.editor.code_ref().len_chars();
+        self.editor_panel.editor.set_cursor(target.offset.min(len));
+        self.editor_panel.editor.set_selection(None);
+        self.editor_panel
+            .editor
+            .focus(&self.editor_panel.focus_area());
+        Ok(())
+    }
+
+    /// Focuses the tree with the current file expanded and selected
+    fn reveal_in_tree(&mut self) -> Result<()> {
+        if self.editor_panel.filename.is_empty() {
+            return Ok(());
+        }
+        self.left_panel.visible = true;
+        self.left_panel.focused = true;
+        self.left_panel.mode = LeftPanelMode::Tree;
+        self.left_panel
+            .tree
+            .open_file_path(&self.editor_panel.filename, &self.theme);
+        self.sync_watch_paths()
+    }
+
+    fn process_outline_action(&mut self, action: OutlineAction) {
+        let (line, exit) = match action {
+            OutlineAction::JumpTo(line) => (line, false),
+            OutlineAction::JumpToAndExit(line) => (line, true),
+            OutlineAction::Close => {
+                self.left_panel.mode = LeftPanelMode::Tree;
+                self.left_panel.focused = false;
+                return;
+            }
+            OutlineAction::None => return,
+        };
+
+        let code = self.editor_panel.editor.code_ref();
+        let offset = code.line_to_char(line.min(code.len_lines().saturating_sub(1)));
+        self.editor_panel.editor.set_cursor(offset);
+        self.editor_panel
+            .editor
+            .focus(&self.editor_panel.focus_area());
+        if exit {
+            self.left_panel.focused = false;
+        }
+    }
+
+    async fn process_recent_action(&mut self, action: RecentAction) -> Result<()> {
+        match action {
+            RecentAction::Open(path) => {
+                let origin = self.current_jump();
+                if self.open_file(&path).await? {
+                    self.record_jump(origin);
+                }
+                self.left_panel.focused = false;
+            }
+            RecentAction::Close => {
+                self.left_panel.mode = LeftPanelMode::Tree;
+                self.left_panel.focused = false;
+            }
+            RecentAction::None => {}
+        }
+        Ok(())
+    }
+
+    /// Where `Alt+b` puts a bookmark: the selected search result while the
+    /// search panel is focused, else the cursor
+    fn bookmark_target(&self) -> Option<Bookmark> {
+        let search = &self.left_panel.search;
+        if self.left_panel.focused
+            && self.left_panel.mode == LeftPanelMode::Search
+            && let Some(result) = search.selected.and_then(|i| search.result(i))
+            && result.kind == SearchKind::Content
+        {
+            let path = result
+                .file_path
+                .unwrap_or(self.editor_panel.filename.clone());
+            return Some(Bookmark {
+                path: abs_file(&path).into(),
+                line: result.line,
+                column: result.column,
+                line_content: result.line_content,
+                note: String::new(),
+            });
+        }
+        if self.editor_panel.filename.is_empty() {
+            return None;
+        }
+        let code = self.editor_panel.editor.code_ref();
+        let (line, column) = code.point(self.editor_panel.editor.get_cursor());
+        let line_content = code.line(line).to_string();
+        Some(Bookmark {
+            path: abs_file(&self.editor_panel.filename).into(),
+            line,
+            column,
+            line_content: line_content.trim_end_matches(['\n', '\r']).to_string(),
+            note: String::new(),
+        })
+    }
+
+    /// Adds a bookmark, replacing one on the same line, and saves them all
+    fn add_bookmark(&mut self, bookmark: Bookmark) {
+        let mut items = std::mem::take(&mut self.left_panel.bookmarks.items);
+        items.retain(|b| (&b.path, b.line) != (&bookmark.path, bookmark.line));
+        let label = format!(
+            "{}:{}",
+            display_path(&bookmark.path, &self.roots),
+            bookmark.line + 1
+        );
+        items.push(bookmark);
+        let selected = items.len() - 1;
+        self.left_panel.bookmarks.list.selected = Some(selected);
+        self.set_bookmarks(items);
+        self.status.info(format!("bookmarked {label}"));
+    }
+
+    /// Lists `items` in the bookmarks panel and writes them to the
+    /// bookmarks file
+    fn set_bookmarks(&mut self, items: Vec<Bookmark>) {
+        if let Err(err) = crate::bookmarks::save(&self.bookmarks_file, &items) {
+            self.status.error(format!("bookmarks: {err:#}"));
+        }
+        self.left_panel.bookmarks.refresh(items, &self.roots);
+    }
+
+    async fn process_bookmark_action(&mut self, action: BookmarkAction) -> Result<()> {
+        match action {
+            BookmarkAction::Open(index) => {
+                let Some(bookmark) = self.left_panel.bookmarks.items.get(index).cloned() else {
+                    return Ok(());
+                };
+                let origin = self.current_jump();
+                if !self.open_file(&bookmark.path.to_string_lossy()).await? {
+                    return Ok(());
+                }
+                self.record_jump(origin);
+
+                // lines edited above it since are followed by its text
+                let line = bookmark.anchor(&self.editor_panel.editor.get_content());
+                let code = self.editor_panel.editor.code_ref();
+                let line_start = code.line_to_char(line);
+                let line_length = code
+                    .line(line)
+                    .to_string()
+                    .trim_end_matches(['\n', '\r'])
+                    .chars()
+                    .count();
+                let offset = line_start + bookmark.column.min(line_length);
+                self.editor_panel.editor.set_cursor(offset);
+                self.editor_panel
+                    .editor
+                    .focus(&self.editor_panel.focus_area());
+                self.left_panel.focused = false;
+                if line != bookmark.line {
+                    let mut items = self.left_panel.bookmarks.items.clone();
+                    items[index].line = line;
+                    self.set_bookmarks(items);
+                }
+            }
+            BookmarkAction::Delete(index) => {
+                let mut items = self.left_panel.bookmarks.items.clone();
+                if index < items.len() {
+                    items.remove(index);
+                    self.set_bookmarks(items);
+                }
+            }
+            BookmarkAction::Close => {
+                self.left_panel.mode = LeftPanelMode::Tree;
+                self.left_panel.focused = false;
+            }
+            BookmarkAction::None => {}
+        }
+        Ok(())
+    }
+
+    /// Relists the recent edits panel after edits, saves and opened files,
+    /// only while it is shown
+    async fn refresh_recent(&mut self) {
+        if !self.recent_stale
+            || !self.left_panel.visible
+            || self.left_panel.mode != LeftPanelMode::Recent
+        {
+            return;
+        }
+        self.recent_stale = false;
+        let edits = self.editor_panel.recent_edits().await;
+        self.left_panel.recent.refresh(edits, &self.roots);
+    }
+
     fn activate_search(&mut self, mode: SearchMode) -> Result<()> {
         self.editor_panel.fallback = Some(Fallback {
             filename: self.editor_panel.filename.clone(),
@@ -289,48 +1588,74 @@ impl App {
 
         if let Some(q) = self.editor_panel.editor.get_selection_text() {
             self.left_panel.search.query = q;
-            if self.left_panel.search.mode == SearchMode::GlobalSearch {
-                self.left_panel
-                    .search
-                    .start_global_search(std::env::current_dir()?);
-            } else {
+            self.run_search();
+        } else if self.left_panel.search.result_count() == 0
+            && !self.left_panel.search.query.is_empty()
+            && !self.left_panel.search.search_in_progress
+        {
+            // the query survives a mode switch, re-run it in the new mode
+            self.run_search();
+        }
+        Ok(())
+    }
+
+    /// Runs the search query in the panel's current mode
+    fn run_search(&mut self) {
+        match self.left_panel.search.mode {
+            SearchMode::Search => {
                 let content = self.editor_panel.editor.get_content();
                 self.left_panel.search.search(&content);
             }
+            SearchMode::GlobalSearch => self.left_panel.search.start_global_search(),
+            SearchMode::Symbols => self.left_panel.search.find_symbols(),
         }
-        Ok(())
     }
 
     async fn process_search_action(&mut self, action: SearchAction) -> Result<()> {
         match action {
-            SearchAction::UpdateSearch => {
-                if self.left_panel.search.mode == SearchMode::GlobalSearch {
-                    self.left_panel
-                        .search
-                        .start_global_search(std::env::current_dir()?);
-                } else {
-                    let content = self.editor_panel.editor.get_content();
-                    self.left_panel.search.search(&content);
-                }
-            }
+            SearchAction::UpdateSearch => self.run_search(),
             SearchAction::Clear => {
                 self.left_panel.search.results.clear();
                 self.left_panel.search.selected = None;
                 self.left_panel.search.scroll_offset = 0;
             }
             SearchAction::JumpTo(result) => {
-                if let Some(file_path) = &result.file_path {
-                    self.open_file(file_path).await?;
+                if let Some(file_path) = &result.file_path
+                    && !self.open_file(file_path).await?
+                {
+                    return Ok(());
+                }
+                if result.kind == SearchKind::FileName {
+                    self.editor_panel.editor.set_cursor(0);
+                    self.editor_panel
+                        .editor
+                        .focus(&self.editor_panel.focus_area());
+                    self.editor_panel.editor.remove_marks();
+                } else {
+                    self.editor_panel.editor.set_cursor(result.match_start);
+                    self.editor_panel
+                        .editor
+                        .focus(&self.editor_panel.focus_area());
+                    let marks = vec![(result.match_start, result.match_end, "#585858")];
+                    self.editor_panel.editor.set_marks(marks);
                 }
-                self.editor_panel.editor.set_cursor(result.match_start);
-                self.editor_panel.editor.focus(&self.editor_panel.area);
-                let marks = vec![(result.match_start, result.match_end, "#585858")];
-                self.editor_panel.editor.set_marks(marks);
                 self.left_panel.focused = true;
             }
             SearchAction::JumpToAndExit(result) => {
-                if let Some(file_path) = &result.file_path {
-                    self.open_file(file_path).await?;
+                // results are previewed while browsing, the jump starts where
+                // the search was opened
+                let origin = match &self.editor_panel.fallback {
+                    Some(fallback) => Some(Jump {
+                        filename: fallback.filename.clone(),
+                        offset: fallback.cursor,
+                    }),
+                    None => self.current_jump(),
+                };
+                self.record_jump(origin);
+                if let Some(file_path) = &result.file_path
+                    && !self.open_file(file_path).await?
+                {
+                    return Ok(());
                 }
                 let left_visible = self
                     .editor_panel
@@ -340,13 +1665,19 @@ impl App {
                     .unwrap_or(self.left_panel.visible);
                 self.left_panel.mode = LeftPanelMode::Tree;
                 self.left_panel.visible = left_visible;
-                self.editor_panel.editor.set_cursor(result.match_start);
-                self.editor_panel.editor.focus(&self.editor_panel.area);
+                let cursor = match result.kind {
+                    SearchKind::FileName => 0,
+                    SearchKind::Content => result.match_start,
+                };
+                self.editor_panel.editor.set_cursor(cursor);
+                self.editor_panel
+                    .editor
+                    .focus(&self.editor_panel.focus_area());
                 self.left_panel.focused = false;
                 self.editor_panel.editor.remove_marks();
             }
             SearchAction::Close => {
-                self.left_panel.search.cancel();
+                self.editor_panel.editor.remove_marks();
                 if let Some(fallback) = self.editor_panel.fallback.take() {
                     let _ = self.editor_panel.open_file(&fallback.filename).await;
                     self.editor_panel.editor.set_cursor(fallback.cursor);
@@ -355,23 +1686,117 @@ impl App {
                     }
                     self.editor_panel.editor.set_offset_y(fallback.offsets.0);
                     self.editor_panel.editor.set_offset_x(fallback.offsets.1);
-                    self.editor_panel.editor.focus(&self.editor_panel.area);
+                    self.editor_panel
+                        .editor
+                        .focus(&self.editor_panel.focus_area());
                     self.left_panel.visible = fallback.left_panel_visible;
                 }
                 self.left_panel.mode = LeftPanelMode::Tree;
                 self.left_panel.focused = false;
             }
+            SearchAction::Export => {
+                let path = self.left_panel.search.export_path.display().to_string();
+                match self.left_panel.search.export(&self.editor_panel.filename) {
+                    Ok(count) => self
+                        .status
+                        .info(format!("{count} results written to {path}")),
+                    Err(err) => self.status.error(format!("cannot write {path}: {err}")),
+                }
+            }
+            SearchAction::Replace => self.prompt = Some(Prompt::new(PromptKind::Replace)),
             SearchAction::None => {}
         }
         Ok(())
     }
 
-    async fn open_file(&mut self, path: &str) -> Result<()> {
-        self.editor_panel.open_file(path).await?;
+    /// Replaces every match of the local search in the current file as one
+    /// undoable batch, then searches again
+    fn replace_all(&mut self, replacement: &str) {
+        self.prompt = None;
+        // the matches are found again in case the buffer changed since
+        self.run_search();
+        let content = self.editor_panel.editor.get_content();
+        let (replaced, count) = self.left_panel.search.replace_all(&content, replacement);
+        if count == 0 {
+            self.status.info("nothing to replace");
+            return;
+        }
+        self.editor_panel.replace_keeping_point(&content, &replaced);
+        self.run_search();
+        let s = if count == 1 { "" } else { "es" };
+        self.status.info(format!("replaced {count} match{s}"));
+    }
+
+    /// Opens `path` in the editor; a file that cannot be read is reported
+    /// and the current buffer stays, returning false
+    async fn open_file(&mut self, path: &str) -> Result<bool> {
+        let stats = match self.editor_panel.open_file(path).await {
+            Ok(stats) => stats,
+            Err(err) => {
+                let name = display_path(Path::new(path), &self.roots);
+                self.status.error(format!("cannot open {name}: {err}"));
+                return Ok(false);
+            }
+        };
+        if let Some(stats) = stats {
+            self.status.warn(format!(
+                "large file ({stats}), syntax highlighting disabled"
+            ));
+        }
+        self.warn_mixed_line_endings();
+        self.offer_template();
         self.left_panel.tree.open_file_path(path, &self.theme);
+        if self.left_panel.mode == LeftPanelMode::Outline {
+            let content = self.editor_panel.editor.get_content();
+            self.left_panel.outline.refresh(&content);
+        }
         self.sync_watch_paths()?;
         self.left_panel.focused = false;
-        Ok(())
+        Ok(true)
+    }
+
+    /// Warns about a current file mixing `\n` and `\r\n` line endings
+    fn warn_mixed_line_endings(&mut self) {
+        if let Some((lf, crlf)) = self.editor_panel.mixed_line_endings() {
+            let style = if crlf > lf { "CRLF" } else { "LF" };
+            self.status.warn(format!(
+                "mixed line endings ({lf} LF, {crlf} CRLF), Alt+L converts them to {style}"
+            ));
+        }
+    }
+
+    /// Points out the template for an empty current file, if there is one
+    fn offer_template(&mut self) {
+        if !self.editor_panel.editor.get_content().is_empty() {
+            return;
+        }
+        let filename = &self.editor_panel.filename;
+        if let Ok(Some(template)) = crate::snippets::find_template(&self.templates_file, filename) {
+            let pattern = template.pattern;
+            self.status
+                .info(format!("empty file, Alt+T inserts the {pattern} template"));
+        }
+    }
+
+    /// Fills the empty current file with the template matching its name
+    fn insert_template(&mut self) {
+        let filename = &self.editor_panel.filename;
+        match crate::snippets::find_template(&self.templates_file, filename) {
+            Ok(Some(template)) => {
+                if self.editor_panel.insert_template(&template.body) {
+                    self.recent_stale = true;
+                    let pattern = template.pattern;
+                    self.status.info(format!("inserted the {pattern} template"));
+                } else {
+                    self.status.info("templates only go into empty files");
+                }
+            }
+            Ok(None) => self.status.info(format!(
+                "no template for this file, add one to {}",
+                self.templates_file.display()
+            )),
+            Err(err) => self.status.error(format!("templates: {err:#}")),
+        }
     }
 
     async fn handle_file_change(&mut self, event: notify::Event) -> Result<()> {
@@ -383,6 +1808,113 @@ impl App {
         Ok(())
     }
 
+    /// Settings that can change while running
+    fn apply_config(&mut self, config: &Config) {
+        self.left_panel.search.wrap_around = config.search_wrap;
+        self.left_panel.search.export_path = config.search_export.clone();
+        self.left_panel.search.max_line_length = config.search_max_line_length;
+        self.left_panel.search.order = config.search_order;
+        self.left_panel.search.compact = config.search_compact;
+        self.left_panel.search.defaults = config.search_defaults;
+        self.left_panel.search.word_boundary = config.word_boundary;
+        self.editor_panel.word_boundary = config.word_boundary;
+        self.editor_panel.autocomplete_key = config.autocomplete_key;
+        self.editor_panel.instruct_key = config.instruct_key;
+        self.editor_panel.autocomplete_languages = config.autocomplete_languages.clone();
+        self.editor_panel.tab_width = config.tab_width;
+        self.editor_panel.show_whitespace = config.show_whitespace;
+        self.editor_panel.reindent_paste = config.reindent_paste;
+        self.editor_panel.confirm_overwrite = config.confirm_overwrite;
+        self.editor_panel.no_write = config.no_write;
+        self.snippets_file = config.snippets_file.clone();
+        self.templates_file = config.templates_file.clone();
+        if self.bookmarks_file != config.bookmarks_file {
+            self.bookmarks_file = config.bookmarks_file.clone();
+            match crate::bookmarks::load(&self.bookmarks_file) {
+                Ok(items) => self.left_panel.bookmarks.refresh(items, &self.roots),
+                Err(err) => self.status.error(format!("bookmarks: {err:#}")),
+            }
+        }
+        self.editor_panel.auto_indent = config.auto_indent;
+        self.editor_panel.completion_newline = config.completion_newline;
+        self.editor_panel.patch_retry = config.patch_retry;
+        self.editor_panel.completion_candidates = config.completion_candidates;
+        self.editor_panel.llm_debug_file = config.llm_debug_file.clone();
+        self.editor_panel.system_prompt = config.system_prompt.clone();
+        if let Some(prompt) = &config.system_prompt {
+            let missing = missing_tokens(prompt);
+            if !missing.is_empty() {
+                let warning = format!(
+                    "REDAI_SYSTEM_PROMPT_FILE never mentions {}, completions may not parse",
+                    missing.join(" ")
+                );
+                log::warn!("{warning}");
+                self.status.warn(warning);
+            }
+        }
+        self.editor_panel.trim_trailing_whitespace = config.trim_trailing_whitespace;
+        self.editor_panel.ignore_path_case = config.ignore_path_case;
+        self.editor_panel.final_newline = config.final_newline;
+        self.editor_panel.confirm_delete_lines = config.confirm_delete_lines;
+        self.editor_panel.context = config.context;
+        if let Err(err) = self.editor_panel.set_highlight(!config.no_highlight) {
+            self.status.error(format!("syntax highlighting: {err}"));
+        }
+        self.marks_timeout = config.marks_timeout;
+        if self.left_panel.tree.max_entries != config.tree_max_entries {
+            self.left_panel.tree.max_entries = config.tree_max_entries;
+            if let Err(err) = self.left_panel.tree.refresh(&self.theme) {
+                log::warn!("tree refresh: {err}");
+            }
+        }
+        self.ai_hint = if config.disable_ai {
+            Some("AI is disabled (--no-ai or REDAI_DISABLE_AI)")
+        } else if LlmClient::from_config(config).is_none() {
+            Some("AI is disabled, set OPENROUTER_API_KEY to enable it")
+        } else {
+            None
+        };
+        self.editor_panel.model = self.ai_hint.is_none().then(|| config.model.clone());
+    }
+
+    /// Re-reads the config and rebuilds the LLM client; an invalid config
+    /// is reported and the current settings stay
+    async fn reload_config(&mut self) {
+        let mut config = match Config::load(&self.env) {
+            Ok(config) => config,
+            Err(err) => {
+                self.status
+                    .error(format!("reload config: {err:#}, keeping the current one"));
+                return;
+            }
+        };
+
+        self.switches.apply(&mut config);
+        let key_warning = config.resolve_api_key();
+        let llm_client = LlmClient::from_config(&config);
+        let ai = if llm_client.is_some() {
+            config.model.as_str()
+        } else {
+            "AI disabled"
+        };
+        // problems found applying the config replace this note
+        self.status.info(format!("config reloaded ({ai})"));
+        if let Some(warning) = key_warning {
+            self.status.warn(warning);
+        }
+        self.apply_config(&config);
+        self.editor_panel.set_llm_client(llm_client).await;
+    }
+
+    /// When the current marks expire, if auto-clearing is enabled
+    fn marks_deadline(&self) -> Option<Instant> {
+        let timeout = self.marks_timeout?;
+        self.editor_panel
+            .editor
+            .has_marks()
+            .then(|| self.last_interaction + timeout)
+    }
+
     fn sync_watch_paths(&mut self) -> Result<()> {
         let mut watch_paths = self
             .left_panel
@@ -395,10 +1927,137 @@ impl App {
             .filter(|p| p.is_dir())
             .collect::<HashSet<_>>();
 
-        if !self.editor_panel.filename.is_empty() {
-            watch_paths.insert(PathBuf::from(abs_file(&self.editor_panel.filename)));
+        // a new file is watched once it is saved
+        for filename in self.editor_panel.open_files() {
+            if Path::new(filename).exists() {
+                watch_paths.insert(PathBuf::from(abs_file(filename)));
+            }
         }
         self.watcher.sync(watch_paths)?;
         Ok(())
     }
 }
+
+/// Sleeps until `deadline`, or forever when there is none
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_screen_layout() {
+        let [left, editor, status] = screen_layout(Rect::new(0, 0, 100, 30), Some(20), false);
+        assert_eq!((left.width, editor.x, editor.width), (20, 20, 80));
+        assert_eq!((editor.height, status.y, status.height), (29, 29, 1));
+        let [left, editor, _] = screen_layout(Rect::new(0, 0, 100, 30), None, false);
+        assert_eq!((left.width, editor.width), (0, 100));
+
+        // too narrow for two panes, the focused one takes the whole width
+        let [left, editor, _] = screen_layout(Rect::new(0, 0, 40, 10), Some(20), false);
+        assert_eq!((left.width, editor.width), (0, 40));
+        let [left, editor, _] = screen_layout(Rect::new(0, 0, 40, 10), Some(20), true);
+        assert_eq!((left.width, editor.width), (40, 0));
+
+        for width in 0..4 {
+            for height in 0..4 {
+                let area = Rect::new(0, 0, width, height);
+                for (ratio, focused) in [(None, false), (Some(30), false), (Some(30), true)] {
+                    let [left, editor, status] = screen_layout(area, ratio, focused);
+                    assert_eq!(left.width + editor.width, width);
+                    assert_eq!(editor.height + status.height, height);
+                    assert!(area.contains(editor.as_position()) || editor.is_empty());
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_save_error_keeps_running() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        std::fs::write(&path, b"caf\xe9\n").unwrap();
+        let filename = path.to_string_lossy().to_string();
+        let mut config = Config::from_lookup(|_| None).unwrap();
+        config.no_watch = true;
+        let args = Args::default();
+        let mut app = App::new(
+            "text",
+            "café\n",
+            Encoding::Latin1,
+            &filename,
+            None,
+            &config,
+            &args,
+            ConfigEnv::default(),
+        )
+        .unwrap();
+
+        // Latin-1 has no arrow
+        let insert = ratatui_code_editor::actions::InsertText {
+            text: "→".to_string(),
+        };
+        app.editor_panel.editor.apply(insert);
+        for overwrite in [false, true] {
+            app.update(Message::SaveCurrentFile { overwrite })
+                .await
+                .unwrap();
+            let message = app.status.message().unwrap();
+            assert!(message.text.starts_with("cannot save"), "{}", message.text);
+            assert!(!app.quit);
+        }
+        assert_eq!(std::fs::read(&path).unwrap(), b"caf\xe9\n");
+        assert_eq!(app.editor_panel.editor.get_content(), "→café\n");
+    }
+
+    #[tokio::test]
+    async fn test_goto_definition_records_one_jump() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = dir.path().join("main.rs");
+        let lib = dir.path().join("lib.rs");
+        std::fs::write(&main, "fn main() { run(); }\n").unwrap();
+        std::fs::write(&lib, "pub fn run() {}\n").unwrap();
+        std::fs::write(
+            dir.path().join("tags"),
+            "run\tlib.rs\t/^pub fn run() {}$/;\"\tf\n",
+        )
+        .unwrap();
+        let filename = main.to_string_lossy().to_string();
+        let mut config = Config::from_lookup(|_| None).unwrap();
+        config.no_watch = true;
+        let mut args = Args::default();
+        args.roots = vec![dir.path().to_path_buf()];
+        let mut app = App::new(
+            "rust",
+            "fn main() { run(); }\n",
+            Encoding::Utf8,
+            &filename,
+            None,
+            &config,
+            &args,
+            ConfigEnv::default(),
+        )
+        .unwrap();
+
+        // a search left open holds the position it started from
+        app.editor_panel.editor.set_cursor(3);
+        app.activate_search(SearchMode::GlobalSearch).unwrap();
+        app.editor_panel.editor.set_cursor(13);
+        app.goto_definition().await.unwrap();
+
+        assert_eq!(app.editor_panel.filename, lib.to_string_lossy());
+        assert_eq!(app.editor_panel.editor.get_cursor(), 7);
+        let origin = Jump {
+            filename,
+            offset: 13,
+        };
+        let current = app.current_jump().unwrap();
+        assert_eq!(app.jumps.back(current), Some(origin));
+        assert_eq!(app.jumps.back(app.current_jump().unwrap()), None);
+    }
+}
@@ -0,0 +1,212 @@
+use anyhow::{Context, Result};
+use crossterm::event::{Event, KeyCode, KeyEventKind, MouseEventKind};
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Style},
+    text::{Line, Span},
+};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::listpanel::ListPanel;
+use crate::utils::display_path;
+
+/// A position in a workspace file kept across sessions
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub path: PathBuf,
+    /// 0-based line and char column when bookmarked or last opened
+    pub line: usize,
+    pub column: usize,
+    /// Text of the line, to find it again once lines above it change
+    pub line_content: String,
+    #[serde(default)]
+    pub note: String,
+}
+
+impl Bookmark {
+    /// The line of the bookmark in `content`: its own line while that still
+    /// holds the bookmarked text, else the nearest line that does, else its
+    /// own line as far as the file reaches
+    pub fn anchor(&self, content: &str) -> usize {
+        let lines: Vec<&str> = content.lines().collect();
+        let wanted = self.line_content.trim();
+        let holds = |i: usize| lines.get(i).is_some_and(|l| l.trim() == wanted);
+        if wanted.is_empty() || holds(self.line) {
+            return self.line.min(lines.len().saturating_sub(1));
+        }
+        (1..lines.len().max(self.line + 1))
+            .flat_map(|distance| [self.line.checked_sub(distance), Some(self.line + distance)])
+            .flatten()
+            .find(|&i| holds(i))
+            .unwrap_or_else(|| self.line.min(lines.len().saturating_sub(1)))
+    }
+}
+
+/// Bookmarks saved in `path`; none while it does not exist
+pub fn load(path: &Path) -> Result<Vec<Bookmark>> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => {
+            serde_json::from_str(&text).with_context(|| format!("invalid {}", path.display()))
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err).with_context(|| format!("reading {}", path.display())),
+    }
+}
+
+pub fn save(path: &Path, bookmarks: &[Bookmark]) -> Result<()> {
+    if let Some(dir) = path.parent()
+        && !dir.as_os_str().is_empty()
+    {
+        std::fs::create_dir_all(dir)?;
+    }
+    let text = serde_json::to_string_pretty(bookmarks)?;
+    std::fs::write(path, text + "\n").with_context(|| format!("writing {}", path.display()))
+}
+
+pub enum BookmarkAction {
+    None,
+    Close,
+    Open(usize),
+    Delete(usize),
+}
+
+/// Saved bookmarks, to get back to places found while exploring
+pub struct BookmarksPanel {
+    pub items: Vec<Bookmark>,
+    pub list: ListPanel,
+    /// `path:line` of `items` as shown, relative to the roots
+    labels: Vec<String>,
+}
+
+impl BookmarksPanel {
+    pub fn new() -> Self {
+        Self {
+            items: Vec::new(),
+            list: ListPanel::default(),
+            labels: Vec::new(),
+        }
+    }
+
+    /// Replaces the listed bookmarks, keeping the selection in range
+    pub fn refresh(&mut self, items: Vec<Bookmark>, roots: &[PathBuf]) {
+        self.labels = items
+            .iter()
+            .map(|item| format!("{}:{}", display_path(&item.path, roots), item.line + 1))
+            .collect();
+        self.list.selected = match items.len() {
+            0 => None,
+            len => Some(self.list.selected.unwrap_or(0).min(len - 1)),
+        };
+        self.items = items;
+        self.list.scroll_offset = self.list.scroll_offset.min(self.list.selected.unwrap_or(0));
+    }
+
+    pub fn handle_event(&mut self, event: &Event, area: Rect) -> BookmarkAction {
+        match event {
+            Event::Key(key) if !matches!(key.kind, KeyEventKind::Press) => BookmarkAction::None,
+            Event::Key(key) => match key.code {
+                KeyCode::Esc => BookmarkAction::Close,
+                KeyCode::Up => {
+                    self.list.select_prev(self.items.len(), area);
+                    BookmarkAction::None
+                }
+                KeyCode::Down => {
+                    self.list.select_next(self.items.len(), area);
+                    BookmarkAction::None
+                }
+                KeyCode::Enter => self
+                    .list
+                    .selected
+                    .map_or(BookmarkAction::None, BookmarkAction::Open),
+                KeyCode::Delete => self
+                    .list
+                    .selected
+                    .map_or(BookmarkAction::None, BookmarkAction::Delete),
+                _ => BookmarkAction::None,
+            },
+            Event::Mouse(mouse) => match mouse.kind {
+                MouseEventKind::Down(_) => {
+                    let index = self.list.row_at(mouse.row, area);
+                    if self.list.select(index, self.items.len(), area) {
+                        BookmarkAction::Open(index)
+                    } else {
+                        BookmarkAction::None
+                    }
+                }
+                kind => {
+                    self.list.scroll(kind, self.items.len(), area);
+                    BookmarkAction::None
+                }
+            },
+            _ => BookmarkAction::None,
+        }
+    }
+
+    pub fn render(&self, frame: &mut Frame, area: Rect) {
+        let titles = ("Bookmarks", "No bookmarks, Alt+b adds one");
+        self.list
+            .render_rows(frame, area, titles, self.items.len(), |i| {
+                let item = &self.items[i];
+                // without a note the bookmarked line says what is there
+                let (note, color) = match item.note.trim() {
+                    "" => (item.line_content.trim(), Color::Gray),
+                    note => (note, Color::Yellow),
+                };
+                Line::from(vec![
+                    Span::styled(self.labels[i].clone(), Style::default().fg(Color::Cyan)),
+                    Span::raw(" "),
+                    Span::styled(note.to_string(), Style::default().fg(color)),
+                ])
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bookmark(line: usize, line_content: &str) -> Bookmark {
+        Bookmark {
+            path: PathBuf::from("src/main.rs"),
+            line,
+            column: 4,
+            line_content: line_content.to_string(),
+            note: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_anchor() {
+        let content = "fn a() {}\n\nfn main() {\n    run();\n}\n";
+        assert_eq!(bookmark(3, "    run();").anchor(content), 3);
+        // lines added above or below move it to the nearest copy
+        let shifted = format!("// one\n// two\n{content}");
+        assert_eq!(bookmark(3, "run();").anchor(&shifted), 5);
+        let removed = "fn main() {\n    run();\n}\n";
+        assert_eq!(bookmark(3, "    run();").anchor(removed), 1);
+        // a line that is gone keeps its place within the file
+        assert_eq!(bookmark(3, "gone();").anchor(content), 3);
+        assert_eq!(bookmark(9, "gone();").anchor(removed), 2);
+    }
+
+    #[test]
+    fn test_load_and_save() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".redai/bookmarks.json");
+        assert!(load(&path).unwrap().is_empty());
+
+        let mut saved = bookmark(3, "    run();");
+        saved.note = "entry point".to_string();
+        save(&path, std::slice::from_ref(&saved)).unwrap();
+        assert_eq!(load(&path).unwrap(), vec![saved]);
+
+        // a note is optional in the file
+        let text = r#"[{"path": "a.rs", "line": 0, "column": 0, "line_content": "x"}]"#;
+        std::fs::write(&path, text).unwrap();
+        assert_eq!(load(&path).unwrap()[0].note, "");
+        std::fs::write(&path, "not json").unwrap();
+        assert!(load(&path).is_err());
+    }
+}
@@ -0,0 +1,328 @@
+use anyhow::{Context, Result, bail};
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+
+/// Command line arguments: `redai [OPTIONS] [FILE...] [DIR...]`,
+/// `redai --search QUERY [DIR...]` or `redai --diff OLD NEW`
+#[derive(Debug, Default, PartialEq)]
+pub struct Args {
+    /// Workspace roots shown as top-level tree nodes and searched globally
+    pub roots: Vec<PathBuf>,
+    /// Files to open on start, the first one focused
+    pub files: Vec<String>,
+    /// `--no-*` switches that override the config
+    pub switches: Switches,
+    /// `--diff OLD NEW`: review the changes between two files, either of
+    /// which may be `-` for stdin
+    pub diff: Option<(String, String)>,
+    /// `--search QUERY`: global search run on start
+    pub search: Option<String>,
+    /// `--apply-edits PATH`: JSON edits applied to the first file on start,
+    /// read from stdin for `-`
+    pub apply_edits: Option<String>,
+    /// `--env PATH`: env file taking precedence over the project and user ones
+    pub env_file: Option<PathBuf>,
+    /// Problems worked around while parsing, shown once the UI is up
+    pub warnings: Vec<String>,
+    /// The root is the working directory because no directory was given
+    root_from_cwd: bool,
+}
+
+/// Command line switches, which win over the config across reloads
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Switches {
+    /// `--no-ai`: run as a plain editor without an LLM client
+    pub no_ai: bool,
+    /// `--no-highlight`: open files without syntax highlighting
+    pub no_highlight: bool,
+    /// `--no-watch`: never reload open files changed by other programs
+    pub no_watch: bool,
+    /// `--no-write`: never save files, AI edits stay in the buffers
+    pub no_write: bool,
+}
+
+impl Switches {
+    pub fn apply(self, config: &mut Config) {
+        config.disable_ai |= self.no_ai;
+        config.no_highlight |= self.no_highlight;
+        config.no_watch |= self.no_watch;
+        config.no_write |= self.no_write;
+    }
+}
+
+impl Args {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut parsed = Args::default();
+        let mut diff = false;
+        let mut positional = Vec::new();
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--no-ai" => parsed.switches.no_ai = true,
+                "--no-highlight" => parsed.switches.no_highlight = true,
+                "--no-watch" => parsed.switches.no_watch = true,
+                "--no-write" => parsed.switches.no_write = true,
+                "--diff" => diff = true,
+                "--search" => {
+                    let query = args.next().context("--search expects a query")?;
+                    parsed.search = Some(query);
+                }
+                option if option.starts_with("--search=") => {
+                    parsed.search = Some(option["--search=".len()..].to_string());
+                }
+                "--apply-edits" => {
+                    let path = args.next().context("--apply-edits expects a file or -")?;
+                    parsed.apply_edits = Some(path);
+                }
+                "--env" => {
+                    let path = args.next().context("--env expects a file")?;
+                    parsed.env_file = Some(PathBuf::from(path));
+                }
+                option if option.starts_with("--env=") => {
+                    parsed.env_file = Some(PathBuf::from(&option["--env=".len()..]));
+                }
+                option if option.starts_with("--") => bail!("unknown option {option}"),
+                _ => positional.push(arg),
+            }
+        }
+
+        if diff {
+            let [old, new] = <[String; 2]>::try_from(positional)
+                .ok()
+                .context("--diff expects two files, e.g. --diff old.rs new.rs")?;
+            if old == "-" && new == "-" {
+                bail!("--diff can read only one side from stdin");
+            }
+            parsed.diff = Some((old, new));
+            positional = Vec::new();
+        }
+
+        for arg in positional {
+            let path = PathBuf::from(&arg);
+            if path.is_dir() {
+                let root = std::fs::canonicalize(&path)
+                    .with_context(|| format!("cannot open directory {arg}"))?;
+                parsed.roots.push(root);
+            } else {
+                parsed.files.push(arg);
+            }
+        }
+        if parsed
+            .search
+            .as_deref()
+            .is_some_and(|query| query.is_empty())
+        {
+            bail!("--search expects a query");
+        }
+        if parsed.apply_edits.is_some() && parsed.files.is_empty() {
+            bail!("--apply-edits needs a file to apply the edits to");
+        }
+
+        if parsed.roots.is_empty() {
+            let cwd = std::env::current_dir();
+            parsed.root_from_cwd = cwd.is_ok();
+            let (root, warning) = default_root(cwd, &parsed.files);
+            parsed.roots.push(root);
+            parsed.warnings.extend(warning);
+        }
+        parsed.roots = dedup_roots(parsed.roots);
+
+        Ok(parsed)
+    }
+
+    /// Replaces a root taken from the working directory with the project
+    /// it is in, see [`project_root`]; directories given explicitly stay
+    pub fn detect_project_root(&mut self, markers: &[String]) {
+        if !self.root_from_cwd {
+            return;
+        }
+        if let Some(root) = project_root(&self.roots[0], markers) {
+            self.roots[0] = root;
+        }
+    }
+}
+
+/// The nearest of `dir` and its ancestors containing one of `markers`,
+/// e.g. `.git` or `Cargo.toml`, so that starting in a subdirectory still
+/// shows and searches the whole project
+pub fn project_root(dir: &Path, markers: &[String]) -> Option<PathBuf> {
+    dir.ancestors()
+        .find(|ancestor| markers.iter().any(|marker| ancestor.join(marker).exists()))
+        .map(Path::to_path_buf)
+}
+
+/// The working directory, or when it is gone (e.g. deleted during a long
+/// session) the directory of the first file given by absolute path, else `/`
+fn default_root(cwd: std::io::Result<PathBuf>, files: &[String]) -> (PathBuf, Option<String>) {
+    let err = match cwd {
+        Ok(cwd) => return (cwd, None),
+        Err(err) => err,
+    };
+    let root = files
+        .iter()
+        .map(Path::new)
+        .filter(|file| file.is_absolute())
+        .filter_map(Path::parent)
+        .find(|dir| dir.is_dir())
+        .map_or_else(|| PathBuf::from("/"), Path::to_path_buf);
+    let warning = format!(
+        "working directory unavailable ({err}), using {} as the root",
+        root.display()
+    );
+    (root, Some(warning))
+}
+
+/// Drops repeated roots and roots nested inside another root, keeping order
+fn dedup_roots(roots: Vec<PathBuf>) -> Vec<PathBuf> {
+    let mut kept: Vec<PathBuf> = Vec::new();
+    for root in &roots {
+        let nested = roots
+            .iter()
+            .any(|other| other != root && root.starts_with(other));
+        if !nested && !kept.contains(root) {
+            kept.push(root.clone());
+        }
+    }
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_roots_and_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a");
+        let b = dir.path().join("b");
+        std::fs::create_dir_all(a.join("nested")).unwrap();
+        std::fs::create_dir(&b).unwrap();
+
+        let args = Args::parse([
+            a.to_string_lossy().to_string(),
+            "main.rs".to_string(),
+            a.join("nested").to_string_lossy().to_string(),
+            b.to_string_lossy().to_string(),
+            "lib.rs".to_string(),
+        ])
+        .unwrap();
+
+        let a = std::fs::canonicalize(a).unwrap();
+        let b = std::fs::canonicalize(b).unwrap();
+        assert_eq!(args.roots, vec![a, b]);
+        assert_eq!(args.files, vec!["main.rs", "lib.rs"]);
+    }
+
+    #[test]
+    fn test_parse_defaults_to_current_dir() {
+        let args = Args::parse(Vec::new()).unwrap();
+        assert_eq!(args.roots, vec![std::env::current_dir().unwrap()]);
+        assert!(args.files.is_empty());
+        assert_eq!(args.switches, Switches::default());
+    }
+
+    #[test]
+    fn test_project_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("crates/core/src");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::create_dir(dir.path().join(".git")).unwrap();
+        std::fs::write(dir.path().join("crates/core/Cargo.toml"), "").unwrap();
+        let markers = |list: &[&str]| list.iter().map(|m| m.to_string()).collect::<Vec<_>>();
+
+        let root = project_root(&nested, &markers(&[".git", "Cargo.toml"]));
+        assert_eq!(root, Some(dir.path().join("crates/core")));
+        let root = project_root(&nested, &markers(&[".git"]));
+        assert_eq!(root.as_deref(), Some(dir.path()));
+        assert_eq!(project_root(&nested, &[]), None);
+
+        let mut args = Args {
+            roots: vec![nested.clone()],
+            root_from_cwd: true,
+            ..Args::default()
+        };
+        args.detect_project_root(&markers(&[".git"]));
+        assert_eq!(args.roots, vec![dir.path().to_path_buf()]);
+
+        // a directory given on the command line is used as it is
+        let mut args = Args::parse([nested.to_string_lossy().to_string()]).unwrap();
+        args.detect_project_root(&markers(&[".git"]));
+        assert_eq!(args.roots, vec![std::fs::canonicalize(&nested).unwrap()]);
+    }
+
+    #[test]
+    fn test_default_root_without_cwd() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("main.rs").to_string_lossy().to_string();
+        let gone = || Err(std::io::Error::from(std::io::ErrorKind::NotFound));
+
+        let (root, warning) = default_root(gone(), &["lib.rs".to_string(), file]);
+        assert_eq!(root, dir.path());
+        assert!(
+            warning
+                .unwrap()
+                .starts_with("working directory unavailable")
+        );
+
+        let (root, warning) = default_root(gone(), &["lib.rs".to_string()]);
+        assert_eq!(root, PathBuf::from("/"));
+        assert!(warning.is_some());
+
+        let (root, warning) = default_root(Ok(dir.path().to_path_buf()), &[]);
+        assert_eq!((root.as_path(), warning), (dir.path(), None));
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let args = Args::parse(["--no-ai", "--no-highlight", "main.rs"].map(String::from)).unwrap();
+        assert!(args.switches.no_ai);
+        assert!(args.switches.no_highlight);
+        assert!(!args.switches.no_watch);
+        assert!(!args.switches.no_write);
+        let switches = Args::parse(["--no-watch", "--no-write"].map(String::from))
+            .unwrap()
+            .switches;
+        assert!(switches.no_watch && switches.no_write);
+        assert_eq!(args.files, vec!["main.rs"]);
+
+        assert!(Args::parse(["--nope".to_string()]).is_err());
+
+        let args = Args::parse(["--env", "dev.env", "--env=prod.env"].map(String::from)).unwrap();
+        assert_eq!(args.env_file, Some(PathBuf::from("prod.env")));
+        assert!(Args::parse(["--env".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_parse_search() {
+        let args = Args::parse(["--search", "TODO", "main.rs"].map(String::from)).unwrap();
+        assert_eq!(args.search, Some("TODO".to_string()));
+        assert_eq!(args.files, vec!["main.rs"]);
+        let args = Args::parse(["--search=fn main".to_string()]).unwrap();
+        assert_eq!(args.search, Some("fn main".to_string()));
+
+        assert!(Args::parse(["--search".to_string()]).is_err());
+        assert!(Args::parse(["--search=".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_parse_apply_edits() {
+        let args = Args::parse(["--apply-edits", "-", "main.rs"].map(String::from)).unwrap();
+        assert_eq!(args.apply_edits, Some("-".to_string()));
+        assert_eq!(args.files, vec!["main.rs"]);
+
+        assert!(Args::parse(["--apply-edits".to_string()]).is_err());
+        assert!(Args::parse(["--apply-edits", "edits.json"].map(String::from)).is_err());
+    }
+
+    #[test]
+    fn test_parse_diff() {
+        let args = Args::parse(["--diff", "a.rs", "-"].map(String::from)).unwrap();
+        assert_eq!(args.diff, Some(("a.rs".to_string(), "-".to_string())));
+        assert!(args.files.is_empty());
+
+        assert!(Args::parse(["--diff", "a.rs"].map(String::from)).is_err());
+        assert!(Args::parse(["--diff", "-", "-"].map(String::from)).is_err());
+    }
+}
@@ -1,34 +1,120 @@
-use crate::diff::compute_text_edits;
-use crate::llm::LlmClient;
+use crate::diff::{compute_text_edits, diff_without_unchanged};
+use crate::error::{CoderError, CoderResult};
+use crate::llm::{ChatBackend, Choice};
+use crate::outline::outline;
 use crate::prompts::*;
-use crate::tracker::Tracker;
+use crate::tracker::{RecentEdit, Tracker};
 use crate::utils::{byte_to_point, offset_to_byte};
-use anyhow::{Result, anyhow};
 use log::debug;
 use ratatui_code_editor::code::Edit;
 use serde_json::json;
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+/// How much of the other tracked files goes into a request; including
+/// them helps cross-file completions but costs tokens, so it is off by default
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ContextStrategy {
+    /// Most recently edited other files to include declarations from
+    pub other_files: usize,
+    /// Rough token budget for that extra context
+    pub budget_tokens: usize,
+}
+
+/// Code the user pinned to be sent with every request, e.g. a trait
+/// definition far from where they are completing
+#[derive(Clone, Debug, PartialEq)]
+pub struct PinnedRegion {
+    pub path: PathBuf,
+    /// 1-based line range
+    pub lines: (usize, usize),
+    pub text: String,
+}
+
+/// A completion the model offered, best first among those of its request
+#[derive(Clone, Debug, PartialEq)]
+pub struct Candidate {
+    pub edits: Vec<Edit>,
+    /// Mean log probability of its tokens, when the provider reports them
+    pub logprob: Option<f64>,
+}
+
+impl Candidate {
+    /// The answer of a request yielding a single one, none for no change
+    pub fn only(edits: Vec<Edit>) -> Vec<Self> {
+        if edits.is_empty() {
+            return Vec::new();
+        }
+        vec![Self {
+            edits,
+            logprob: None,
+        }]
+    }
+
+    /// How likely the model found it, from 0 to 1, when it has a logprob
+    pub fn confidence(&self) -> Option<f64> {
+        self.logprob.map(f64::exp)
+    }
+}
+
+/// What a request about the selected code asks the model for
+#[derive(Clone, Copy)]
+enum SelectionTask<'a> {
+    /// The selection improved as the model sees fit
+    Rewrite,
+    /// The selection changed as the instruction says
+    Instruct(&'a str),
+    /// The selection reformatted, its meaning unchanged
+    Format,
+}
 
 pub struct Coder {
-    llm: Option<LlmClient>,
+    llm: Option<Box<dyn ChatBackend>>,
     file_trackers: HashMap<PathBuf, Tracker>,
+    pinned: Vec<PinnedRegion>,
+    pub context: ContextStrategy,
+    /// Multi-line completions that end a line start a new line below
+    pub completion_newline: bool,
+    /// Asks the model once more when its patch cannot be parsed
+    pub patch_retry: bool,
+    /// Where the last request, raw response and parsed patch are written
+    /// for prompt debugging, when enabled
+    pub debug_file: Option<PathBuf>,
+    /// Used instead of [`SYSTEM_PROMPT`] for completions, when set
+    pub system_prompt: Option<String>,
+    /// Completions asked for per request, to pick from
+    pub candidates: usize,
 }
 
 impl Coder {
-    pub fn new(llm: Option<LlmClient>) -> Self {
+    pub fn new(llm: Option<Box<dyn ChatBackend>>) -> Self {
         Self {
             llm,
             file_trackers: HashMap::new(),
+            pinned: Vec::new(),
+            context: ContextStrategy::default(),
+            completion_newline: false,
+            patch_retry: true,
+            debug_file: None,
+            system_prompt: None,
+            candidates: 1,
         }
     }
 
+    /// Swaps the model backend, e.g. after the config was reloaded
+    pub fn set_llm(&mut self, llm: Option<Box<dyn ChatBackend>>) {
+        self.llm = llm;
+    }
+
+    /// Completions at `cursor`, ranked: those the model finished before
+    /// those cut off, then by logprob when the provider reports them, else
+    /// in the order returned; none when the model changed nothing
     pub async fn autocomplete(
         &self,
         original: &str,
-        _path: &str,
+        path: &str,
         cursor: usize,
-    ) -> Result<Vec<Edit>> {
+    ) -> CoderResult<Vec<Candidate>> {
         let Some(llm) = &self.llm else {
             return Ok(Vec::new());
         };
@@ -43,36 +129,215 @@ impl Coder {
         let recent_edits_summary = self.summarize_recent_edits_for_last_files(3);
         debug!("recent_edits_context {:?}", recent_edits_summary);
 
-        let messages = vec![
-            json!({ "role": "system", "content": SYSTEM_PROMPT }),
+        let system_prompt = self.system_prompt.as_deref().unwrap_or(SYSTEM_PROMPT);
+        let mut messages = vec![
+            json!({ "role": "system", "content": system_prompt }),
             json!({ "role": "user", "content": format!("Big context:\n{}", big_context.0) }),
             json!({ "role": "user", "content": format!("Small context:\n{}", context.0) }),
             json!({ "role": "user", "content": format!("Recent user activity:\n{}", recent_edits_summary) }),
             json!({ "role": "user", "content": REMINDER }),
         ];
+        if let Some(other_files) = self.other_files_message(Path::new(path)) {
+            messages.insert(2, other_files);
+        }
+        if let Some(pinned) = self.pinned_message() {
+            messages.insert(2, pinned);
+        }
 
-        let response = llm.chat(messages).await?;
+        let choices = self.chat_choices(llm.as_ref(), &messages).await?;
+        let mut first = choices[0].clone();
+        let mut response = first.content.clone();
         debug!("response {}", response);
 
-        let patch = self.parse_patch(&response, cursor)?;
+        let mut patch = self.parse_patch(&response, cursor);
         debug!("patch {:?}", patch);
+        let others: Vec<_> = choices[1..]
+            .iter()
+            .filter_map(|choice| Some((choice, self.parse_patch(&choice.content, cursor).ok()?)))
+            .collect();
+        // chatty models often get the format right when shown their mistake;
+        // one retry bounds the extra latency and cost
+        if self.patch_retry
+            && others.is_empty()
+            && let Err(err) = &patch
+            && err.is_retryable()
+        {
+            messages.push(json!({ "role": "assistant", "content": response }));
+            messages.push(json!({
+                "role": "user",
+                "content": format!("Your last response was rejected: {err}.\n{PATCH_RETRY}"),
+            }));
+            response = self.chat(llm.as_ref(), &messages).await?;
+            debug!("retry response {}", response);
+            patch = self.parse_patch(&response, cursor);
+            debug!("retry patch {:?}", patch);
+            first = Choice {
+                content: response.clone(),
+                ..Choice::default()
+            };
+        }
+        let parsed = match &patch {
+            Ok((start, search, replace)) => {
+                format!("start: {start}\nsearch:\n{search}\nreplace:\n{replace}")
+            }
+            Err(err) => format!("error: {err}"),
+        };
+        self.dump_exchange(&messages, &response, &parsed);
 
-        let (start, search, replace) = patch;
+        let patches = match patch {
+            Ok(patch) => std::iter::once((&first, patch)).chain(others).collect(),
+            Err(err) if others.is_empty() => return Err(err),
+            Err(_) => others,
+        };
+        let mut ranked: Vec<(bool, Candidate)> = Vec::new();
+        for (choice, (start, search, replace)) in patches {
+            let after = original.chars().nth(start + search.chars().count());
+            let replace = normalize_line_end(&search, &replace, after, self.completion_newline);
+            let edits = edits_at(start, &search, &replace);
+            if edits.is_empty() || ranked.iter().any(|(_, known)| known.edits == edits) {
+                continue;
+            }
+            let cut_off = choice.finish_reason.as_deref() == Some("length");
+            let logprob = choice.logprob;
+            ranked.push((cut_off, Candidate { edits, logprob }));
+        }
+        // a stable sort keeps the returned order among unscored candidates
+        ranked.sort_by(|(a_cut, a), (b_cut, b)| {
+            let score = |c: &Candidate| c.logprob.unwrap_or(f64::NEG_INFINITY);
+            a_cut.cmp(b_cut).then(score(b).total_cmp(&score(a)))
+        });
+        Ok(ranked.into_iter().map(|(_, candidate)| candidate).collect())
+    }
 
-        let edits = compute_text_edits(&search, &replace);
-        debug!("edits {:?}", edits);
+    /// Asks the model to rewrite the `[start, end)` chars of `original`,
+    /// returning edits scoped to that range
+    pub async fn rewrite(
+        &self,
+        original: &str,
+        path: &str,
+        start: usize,
+        end: usize,
+    ) -> CoderResult<Vec<Edit>> {
+        self.edit_selection(original, path, start, end, SelectionTask::Rewrite)
+            .await
+    }
 
-        let mut edits = edits
-            .into_iter()
-            .map(|mut edit| {
-                edit.start += start;
-                edit
-            })
-            .collect::<Vec<_>>();
+    /// Asks the model to change the `[start, end)` chars of `original` as
+    /// `instruction` says, taking its answer as freeform chat rather than
+    /// in the token format, for models better at instructions than edits
+    pub async fn instruct(
+        &self,
+        original: &str,
+        path: &str,
+        start: usize,
+        end: usize,
+        instruction: &str,
+    ) -> CoderResult<Vec<Edit>> {
+        let task = SelectionTask::Instruct(instruction);
+        self.edit_selection(original, path, start, end, task).await
+    }
+
+    /// Asks the model to reformat the `[start, end)` chars of `original`
+    /// without changing what the code does, for files with no formatter
+    /// at hand
+    pub async fn format_selection(
+        &self,
+        original: &str,
+        path: &str,
+        start: usize,
+        end: usize,
+    ) -> CoderResult<Vec<Edit>> {
+        self.edit_selection(original, path, start, end, SelectionTask::Format)
+            .await
+    }
+
+    /// Asks the model for a commit message describing `changes`, given as
+    /// `-`/`+` lines, returned as edits inserting it at `cursor`
+    pub async fn commit_message(&self, changes: &str, cursor: usize) -> CoderResult<Vec<Edit>> {
+        let Some(llm) = &self.llm else {
+            return Ok(Vec::new());
+        };
+        if changes.trim().is_empty() {
+            return Ok(Vec::new());
+        }
 
-        edits.sort_by(|a, b| b.start.cmp(&a.start));
+        let messages = vec![
+            json!({ "role": "system", "content": COMMIT_PROMPT }),
+            json!({ "role": "user", "content": format!("Changes:\n{changes}") }),
+        ];
+        let response = self.chat(llm.as_ref(), &messages).await?;
+        debug!("commit message response {}", response);
 
-        Ok(edits)
+        let message = parse_instruct(&response);
+        let parsed = match &message {
+            Ok(message) => format!("insert:\n{message}"),
+            Err(err) => format!("error: {err}"),
+        };
+        self.dump_exchange(&messages, &response, &parsed);
+        Ok(edits_at(cursor, "", &message?))
+    }
+
+    async fn edit_selection(
+        &self,
+        original: &str,
+        path: &str,
+        start: usize,
+        end: usize,
+        task: SelectionTask<'_>,
+    ) -> CoderResult<Vec<Edit>> {
+        let Some(llm) = &self.llm else {
+            return Ok(Vec::new());
+        };
+
+        let start_byte = offset_to_byte(start, original);
+        let end_byte = offset_to_byte(end, original);
+        let selected = &original[start_byte..end_byte];
+        let big_context = format!(
+            "{}{SELECTION_START}{selected}{SELECTION_END}{}",
+            &original[..start_byte],
+            &original[end_byte..]
+        );
+        let recent_edits_summary = self.summarize_recent_edits_for_last_files(3);
+
+        let (prompt, reminder) = match task {
+            SelectionTask::Rewrite => (REWRITE_PROMPT, REWRITE_REMINDER),
+            SelectionTask::Instruct(_) => (INSTRUCT_PROMPT, INSTRUCT_REMINDER),
+            SelectionTask::Format => (FORMAT_PROMPT, FORMAT_REMINDER),
+        };
+        let mut messages = vec![
+            json!({ "role": "system", "content": prompt }),
+            json!({ "role": "user", "content": format!("Big context:\n{}", big_context) }),
+            json!({ "role": "user", "content": format!("Selected code:\n{}", selected) }),
+            json!({ "role": "user", "content": format!("Recent user activity:\n{}", recent_edits_summary) }),
+            json!({ "role": "user", "content": reminder }),
+        ];
+        if let SelectionTask::Instruct(instruction) = task {
+            let content = format!("Instruction:\n{instruction}");
+            messages.insert(4, json!({ "role": "user", "content": content }));
+        }
+        if let Some(other_files) = self.other_files_message(Path::new(path)) {
+            messages.insert(2, other_files);
+        }
+        if let Some(pinned) = self.pinned_message() {
+            messages.insert(2, pinned);
+        }
+
+        let response = self.chat(llm.as_ref(), &messages).await?;
+        debug!("rewrite response {}", response);
+
+        let replacement = match task {
+            SelectionTask::Rewrite => self.parse_rewrite(&response),
+            SelectionTask::Instruct(_) | SelectionTask::Format => parse_instruct(&response),
+        };
+        let parsed = match &replacement {
+            Ok(replacement) => format!("replace:\n{replacement}"),
+            Err(err) => format!("error: {err}"),
+        };
+        self.dump_exchange(&messages, &response, &parsed);
+        let replacement = replacement?;
+        let after = original[end_byte..].chars().next();
+        let replacement = normalize_line_end(selected, &replacement, after, false);
+        Ok(edits_at(start, selected, &replacement))
     }
 
     fn build_context(
@@ -80,7 +345,7 @@ impl Coder {
         original: &str,
         cursor_byte: usize,
         context_lines: usize,
-    ) -> Result<(String, usize)> {
+    ) -> CoderResult<(String, usize)> {
         let mut original = original.to_string();
         original.insert_str(cursor_byte, CTOKEN);
 
@@ -106,35 +371,39 @@ impl Coder {
 
         let cursor_relative = context
             .find(CTOKEN)
-            .ok_or_else(|| anyhow!("CTOKEN not found in context"))?;
+            .ok_or_else(|| CoderError::PatchParse(format!("{} not found in context", CTOKEN)))?;
 
         let start = cursor_byte - cursor_relative;
 
         Ok((context, start))
     }
 
-    fn parse_patch(&self, patch: &str, cursor: usize) -> Result<(usize, String, String)> {
+    fn parse_patch(&self, patch: &str, cursor: usize) -> CoderResult<(usize, String, String)> {
         let search_start = patch
             .find(STOKEN)
-            .ok_or_else(|| anyhow!("Invalid patch format: missing {}", STOKEN))?;
+            .ok_or_else(|| CoderError::PatchParse(format!("missing {}", STOKEN)))?;
         let replace_divider = patch
             .find(DTOKEN)
-            .ok_or_else(|| anyhow!("Invalid patch format: missing {}", DTOKEN))?;
-        let _replace_end = patch
-            .find(RTOKEN)
-            .ok_or_else(|| anyhow!("Invalid patch format: missing {}", RTOKEN))?;
+            .ok_or_else(|| CoderError::PatchParse(format!("missing {}", DTOKEN)))?;
+        if replace_divider < search_start {
+            return Err(CoderError::PatchParse(format!(
+                "{} before {}",
+                DTOKEN, STOKEN
+            )));
+        }
+        let replace_end = replace_end(patch, replace_divider)?;
 
         let search = &patch[search_start + STOKEN.len()..replace_divider];
 
         let cursor_pos = search
             .find(CTOKEN)
-            .ok_or_else(|| anyhow::anyhow!("Invalid patch format: missing {}", CTOKEN))?;
+            .ok_or_else(|| CoderError::PatchParse(format!("missing {}", CTOKEN)))?;
         let before = &search[..cursor_pos];
 
         let search = search.replace(CTOKEN, "");
 
-        let replace = &patch[replace_divider + DTOKEN.len()..];
-        let replace = replace.replace(RTOKEN, "").replace(CTOKEN, "");
+        let replace = &patch[replace_divider + DTOKEN.len()..replace_end];
+        let replace = replace.replace(CTOKEN, "");
 
         let before_chars_len = before.chars().count();
         let start = cursor.saturating_sub(before_chars_len);
@@ -142,15 +411,80 @@ impl Coder {
         Ok((start, search, replace))
     }
 
-    pub fn update(&mut self, path: &PathBuf, content: &str) {
+    fn parse_rewrite(&self, response: &str) -> CoderResult<String> {
+        let start = response
+            .find(DTOKEN)
+            .ok_or_else(|| CoderError::PatchParse(format!("missing {}", DTOKEN)))?;
+        let end = replace_end(response, start)?;
+        Ok(response[start + DTOKEN.len()..end].to_string())
+    }
+
+    /// Sends `messages`, recording a failed request in the debug file
+    async fn chat(
+        &self,
+        llm: &dyn ChatBackend,
+        messages: &[serde_json::Value],
+    ) -> CoderResult<String> {
+        let response = llm.chat(messages.to_vec()).await;
+        if let Err(err) = &response {
+            self.dump_exchange(messages, &format!("error: {err}"), "");
+        }
+        response
+    }
+
+    /// Asks for [`Self::candidates`] replies to `messages`, recording a
+    /// failed request in the debug file
+    async fn chat_choices(
+        &self,
+        llm: &dyn ChatBackend,
+        messages: &[serde_json::Value],
+    ) -> CoderResult<Vec<Choice>> {
+        let choices = llm
+            .chat_choices(messages.to_vec(), self.candidates.max(1))
+            .await;
+        match choices {
+            Ok(choices) if choices.is_empty() => {
+                Err(CoderError::BadResponse("no choices".to_string()))
+            }
+            Err(err) => {
+                self.dump_exchange(messages, &format!("error: {err}"), "");
+                Err(err)
+            }
+            ok => ok,
+        }
+    }
+
+    /// Writes one exchange with the model to the debug file, if enabled
+    fn dump_exchange(&self, messages: &[serde_json::Value], response: &str, parsed: &str) {
+        let Some(path) = &self.debug_file else {
+            return;
+        };
+        let dump = format_exchange(messages, response, parsed);
+        let written = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(path, dump));
+        if let Err(err) = written {
+            log::warn!("cannot write {}: {err}", path.display());
+        }
+    }
+
+    pub fn update(&mut self, path: &Path, content: &str) {
         let tracker = self
             .file_trackers
-            .entry(path.clone())
+            .entry(path.to_path_buf())
             .or_insert_with(|| Tracker::new(content.to_string()));
 
         tracker.update(content.to_string());
     }
 
+    /// Content of `path` when it was first opened in this session
+    pub fn opened_content(&self, path: &Path) -> Option<String> {
+        self.file_trackers
+            .get(path)
+            .map(|tracker| tracker.opened().to_string())
+    }
+
     pub fn last_modified_files(&self, n: usize) -> Vec<PathBuf> {
         let mut files_with_latest: Vec<_> = self
             .file_trackers
@@ -172,6 +506,121 @@ impl Coder {
             .collect()
     }
 
+    /// Files with recent edits, most recently updated first; `current` is
+    /// counted with its live content and listed first when edited
+    pub fn recent_edits(&self, current: Option<(&Path, &str)>) -> Vec<RecentEdit> {
+        let mut files = self.last_modified_files(self.file_trackers.len());
+        if let Some((path, _)) = current
+            && let Some(i) = files.iter().position(|p| p == path)
+        {
+            let path = files.remove(i);
+            files.insert(0, path);
+        }
+
+        files
+            .into_iter()
+            .filter_map(|path| {
+                let tracker = self.file_trackers.get(&path)?;
+                let content = match current {
+                    Some((current, content)) if current == path => content,
+                    _ => tracker.content(),
+                };
+                let (added, removed) = tracker.edit_counts(content);
+                (added + removed > 0).then_some(RecentEdit {
+                    path,
+                    added,
+                    removed,
+                })
+            })
+            .collect()
+    }
+
+    /// Declarations of the most recently edited files other than `current`,
+    /// cut off at the token budget
+    fn other_files_context(&self, current: &Path) -> String {
+        // roughly 4 chars per token
+        let mut budget = self.context.budget_tokens * 4;
+        let mut context = String::new();
+
+        let files = self.last_modified_files(self.context.other_files + 1);
+        let others = files.iter().filter(|path| path.as_path() != current);
+        for path in others.take(self.context.other_files) {
+            let Some(tracker) = self.file_trackers.get(path) else {
+                continue;
+            };
+            let content = tracker.content();
+            let lines: Vec<&str> = content.lines().collect();
+
+            let header = format!("{}:\n", path.to_string_lossy());
+            if header.len() > budget {
+                break;
+            }
+            budget -= header.len();
+            context.push_str(&header);
+
+            for item in outline(content) {
+                let line = format!("{}\n", lines[item.line].trim_end());
+                if line.len() > budget {
+                    return context;
+                }
+                budget -= line.len();
+                context.push_str(&line);
+            }
+            context.push('\n');
+            budget = budget.saturating_sub(1);
+        }
+        context
+    }
+
+    pub fn pin(&mut self, region: PinnedRegion) {
+        self.pinned.retain(|pinned| *pinned != region);
+        self.pinned.push(region);
+    }
+
+    /// Drops all pinned regions, returning how many there were
+    pub fn clear_pinned(&mut self) -> usize {
+        std::mem::take(&mut self.pinned).len()
+    }
+
+    fn pinned_message(&self) -> Option<serde_json::Value> {
+        if self.pinned.is_empty() {
+            return None;
+        }
+        let mut context = String::new();
+        for region in &self.pinned {
+            let (start, end) = region.lines;
+            let path = region.path.to_string_lossy();
+            context.push_str(&format!("{path} lines {start}-{end}:\n{}\n", region.text));
+        }
+        Some(json!({ "role": "user", "content": format!("Pinned context:\n{}", context) }))
+    }
+
+    fn other_files_message(&self, current: &Path) -> Option<serde_json::Value> {
+        let context = self.other_files_context(current);
+        debug!("other_files_context {:?}", context);
+        (!context.trim().is_empty()).then(
+            || json!({ "role": "user", "content": format!("Other open files:\n{}", context) }),
+        )
+    }
+
+    /// Changes of every file edited this session since it was opened, as
+    /// `-`/`+` lines under its path; `current` counts with its live content
+    pub fn session_changes(&self, current: Option<(&Path, &str)>) -> String {
+        let mut changes = String::new();
+        for edit in self.recent_edits(current) {
+            let Some(tracker) = self.file_trackers.get(&edit.path) else {
+                continue;
+            };
+            let content = match current {
+                Some((path, content)) if path == edit.path => content,
+                _ => tracker.content(),
+            };
+            let diff = diff_without_unchanged(tracker.opened(), content);
+            changes.push_str(&format!("{}:\n{diff}\n", edit.path.to_string_lossy()));
+        }
+        changes
+    }
+
     pub fn summarize_recent_edits_for_last_files(&self, n: usize) -> String {
         let last_files = self.last_modified_files(n);
 
@@ -192,10 +641,124 @@ impl Coder {
     }
 }
 
+/// Format tokens a completion prompt never mentions; answers are parsed by
+/// them, so a model not told about them rarely gives usable edits
+pub fn missing_tokens(prompt: &str) -> Vec<&'static str> {
+    [STOKEN, DTOKEN, RTOKEN, CTOKEN]
+        .into_iter()
+        .filter(|token| !prompt.contains(token))
+        .collect()
+}
+
+/// The code in a freeform answer: its first fenced block if it has one,
+/// else the whole answer without surrounding blank lines
+fn parse_instruct(response: &str) -> CoderResult<String> {
+    let code = match response.split_once("```") {
+        Some((_, rest)) => {
+            // the opening fence may name the language
+            let body = rest.split_once('\n').map_or(rest, |(_, body)| body);
+            body.split_once("```").map_or(body, |(code, _)| code)
+        }
+        None => response,
+    };
+    let code = code.trim_start_matches(['\r', '\n']).trim_end();
+    if code.trim().is_empty() {
+        return Err(CoderError::PatchParse("no code in the answer".to_string()));
+    }
+    Ok(code.to_string())
+}
+
+/// Readable dump of the messages sent, the raw response and what was
+/// parsed from it
+fn format_exchange(messages: &[serde_json::Value], response: &str, parsed: &str) -> String {
+    let mut dump = String::from("# request\n");
+    for message in messages {
+        let role = message["role"].as_str().unwrap_or("?");
+        let content = message["content"].as_str().unwrap_or_default();
+        dump.push_str(&format!("\n## {role}\n{content}\n"));
+    }
+    dump.push_str(&format!("\n# response\n{response}\n"));
+    if !parsed.is_empty() {
+        dump.push_str(&format!("\n# parsed\n{parsed}\n"));
+    }
+    dump
+}
+
+/// Where the replace block that starts at `divider` ends: the first
+/// `<|REPLACE|>` after it, anything following is dropped. A response with
+/// no `<|REPLACE|>` at all ends at its end, as a stop sequence strips it
+fn replace_end(patch: &str, divider: usize) -> CoderResult<usize> {
+    match patch.find(RTOKEN) {
+        Some(end) if end < divider => Err(CoderError::PatchParse(format!(
+            "{} before {}",
+            RTOKEN, DTOKEN
+        ))),
+        Some(end) => Ok(end),
+        None => Ok(patch.trim_end().len()),
+    }
+}
+
+/// `replace` ending its last line the way `search` does in the buffer,
+/// where `after` is the char following `search`:
+/// - a dropped final newline is restored, else the next line is glued on
+/// - a newline added where the buffer already breaks the line is removed,
+///   else an empty line appears
+/// - with `completion_newline`, a multi-line replacement that ends a line
+///   gets a newline more, leaving the cursor on a fresh line below it
+fn normalize_line_end(
+    search: &str,
+    replace: &str,
+    after: Option<char>,
+    completion_newline: bool,
+) -> String {
+    let mut replace = replace.to_string();
+    if replace.is_empty() {
+        return replace;
+    }
+    let line_break_follows = matches!(after, None | Some('\n' | '\r'));
+    if search.ends_with('\n') && !replace.ends_with('\n') {
+        let eol = if search.ends_with("\r\n") {
+            "\r\n"
+        } else {
+            "\n"
+        };
+        replace.push_str(eol);
+    } else if !search.ends_with('\n') && replace.ends_with('\n') && line_break_follows {
+        replace.pop();
+        if replace.ends_with('\r') {
+            replace.pop();
+        }
+    }
+
+    let ends_line = replace.ends_with('\n') || line_break_follows;
+    if completion_newline && replace.trim_end().contains('\n') && ends_line {
+        replace.push('\n');
+    }
+    replace
+}
+
+/// Edits turning `search` into `replace`, placed at char offset `start`.
+/// Each edit's offset accounts for the ones before it, so they must be
+/// applied in order; reordering them breaks multi-edit patches
+fn edits_at(start: usize, search: &str, replace: &str) -> Vec<Edit> {
+    let edits = compute_text_edits(search, replace);
+    debug!("edits {:?}", edits);
+
+    edits
+        .into_iter()
+        .map(|mut edit| {
+            edit.start += start;
+            edit
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::llm::MockBackend;
     use indoc::indoc;
+    use ratatui_code_editor::code::Operation;
 
     #[test]
     fn test_build_context_basic() {
@@ -209,9 +772,9 @@ fn main() {
 
         let cursor = 70;
 
-        let coder = Coder::new(Some(LlmClient::new("", "", "")));
+        let coder = Coder::new(None);
 
-        let context = coder.build_context(&code, cursor, 1).unwrap();
+        let context = coder.build_context(code, cursor, 1).unwrap();
 
         println!("context:\n {:?}", context);
 
@@ -221,7 +784,7 @@ fn main() {
 
     #[test]
     fn test_parse_patch() -> anyhow::Result<()> {
-        let coder = Coder::new(Some(LlmClient::new("", "", "")));
+        let coder = Coder::new(None);
 
         let patch = "<|SEARCH|>let <|cursor|> = 10;<|DIVIDE|>let x = 10;<|REPLACE|>";
         let start_pos = 0;
@@ -235,9 +798,38 @@ fn main() {
         Ok(())
     }
 
+    #[test]
+    fn test_parse_patch_drops_trailing_text() -> anyhow::Result<()> {
+        let coder = Coder::new(None);
+
+        let patch = "<|SEARCH|>let <|cursor|> = 10;<|DIVIDE|>let x = 10;<|REPLACE|>\nThis sets x.<|REPLACE|>";
+        let (_, _, replace) = coder.parse_patch(patch, 0)?;
+        assert_eq!(replace, "let x = 10;");
+
+        // stripped by a `<|REPLACE|>` stop sequence
+        let patch = "<|SEARCH|>let <|cursor|> = 10;<|DIVIDE|>let x = 10;\n";
+        let (_, _, replace) = coder.parse_patch(patch, 0)?;
+        assert_eq!(replace, "let x = 10;");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_patch_rejects_misordered_tokens() {
+        let coder = Coder::new(None);
+
+        for patch in [
+            "<|SEARCH|>let <|cursor|> = 10;<|REPLACE|>let x = 10;<|DIVIDE|>",
+            "<|DIVIDE|>let x = 10;<|SEARCH|>let <|cursor|> = 10;<|REPLACE|>",
+        ] {
+            let err = coder.parse_patch(patch, 0).unwrap_err();
+            assert!(matches!(err, CoderError::PatchParse(_)), "{patch}");
+        }
+    }
+
     #[test]
     fn test_parse_patch_unicode() -> anyhow::Result<()> {
-        let coder = Coder::new(Some(LlmClient::new("", "", "")));
+        let coder = Coder::new(None);
 
         let patch = r#"<|SEARCH|>let <|cursor|> = "йцук";<|DIVIDE|>let x = "йцук";<|REPLACE|>"#;
         let start_pos = 0;
@@ -250,4 +842,428 @@ fn main() {
 
         Ok(())
     }
+
+    #[test]
+    fn test_parse_patch_error_kind() {
+        let coder = Coder::new(None);
+
+        let patch = "<|SEARCH|>let <|cursor|> = 10;<|REPLACE|>";
+        let err = coder.parse_patch(patch, 0).unwrap_err();
+
+        assert!(matches!(err, CoderError::PatchParse(_)));
+    }
+
+    /// Applies edits in order, as the editor does
+    fn apply(content: &str, edits: &[Edit]) -> String {
+        let mut chars: Vec<char> = content.chars().collect();
+        for edit in edits {
+            let len = edit.text.chars().count();
+            match edit.operation {
+                Operation::Insert => {
+                    chars.splice(edit.start..edit.start, edit.text.chars());
+                }
+                Operation::Remove => {
+                    chars.drain(edit.start..edit.start + len);
+                }
+            }
+        }
+        chars.into_iter().collect()
+    }
+
+    #[tokio::test]
+    async fn test_autocomplete_with_mock_backend() -> anyhow::Result<()> {
+        let original = "fn main() {\n    for i in 0..5 {\n        println!(\"{}\", );\n    }\n}\n";
+        let cursor = original.find(", )").unwrap() + 2;
+
+        let reply = "<|SEARCH|>        println!(\"{}\", <|cursor|>);<|DIVIDE|>        println!(\"{}\", i);<|REPLACE|>";
+        let backend = MockBackend::new(reply);
+        let requests = backend.requests.clone();
+        let coder = Coder::new(Some(Box::new(backend)));
+
+        let edits = coder
+            .autocomplete(original, "main.rs", cursor)
+            .await?
+            .remove(0)
+            .edits;
+
+        assert_eq!(
+            apply(original, &edits),
+            "fn main() {\n    for i in 0..5 {\n        println!(\"{}\", i);\n    }\n}\n"
+        );
+        let sent = requests.lock().unwrap();
+        assert_eq!(sent.len(), 1);
+        assert!(sent[0][2]["content"].as_str().unwrap().contains(CTOKEN));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_custom_system_prompt() -> anyhow::Result<()> {
+        let backend = MockBackend::new("<|SEARCH|>x<|cursor|><|DIVIDE|>x1<|REPLACE|>");
+        let requests = backend.requests.clone();
+        let mut coder = Coder::new(Some(Box::new(backend)));
+        coder.system_prompt = Some("Complete at <|cursor|>.".to_string());
+
+        coder.autocomplete("x\n", "main.rs", 1).await?;
+        let sent = requests.lock().unwrap();
+        assert_eq!(sent[0][0]["content"], "Complete at <|cursor|>.");
+
+        assert!(missing_tokens(SYSTEM_PROMPT).is_empty());
+        assert_eq!(
+            missing_tokens("Complete at <|cursor|>."),
+            vec![STOKEN, DTOKEN, RTOKEN]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_normalize_line_end() {
+        // the model dropped the newline the search block ended with
+        let fixed = normalize_line_end("let x = ;\n", "let x = 1;", Some('f'), false);
+        assert_eq!(fixed, "let x = 1;\n");
+        let fixed = normalize_line_end("let x = ;\r\n", "let x = 1;", Some('f'), false);
+        assert_eq!(fixed, "let x = 1;\r\n");
+
+        // the buffer already breaks the line after the search block
+        let fixed = normalize_line_end("let x = ", "let x = 1;\n", Some('\n'), false);
+        assert_eq!(fixed, "let x = 1;");
+        let fixed = normalize_line_end("let x = ", "let x = 1;\n", None, false);
+        assert_eq!(fixed, "let x = 1;");
+        // mid-line, the newline splits the line on purpose
+        let fixed = normalize_line_end("{", "{\n", Some('}'), false);
+        assert_eq!(fixed, "{\n");
+
+        // multi-line completions can leave a fresh line below
+        let block = "if x {\n    y();\n}";
+        assert_eq!(normalize_line_end("if x", block, Some('\n'), false), block);
+        assert_eq!(
+            normalize_line_end("if x", block, Some('\n'), true),
+            "if x {\n    y();\n}\n"
+        );
+        assert_eq!(
+            normalize_line_end("x", "x = 1;", Some('\n'), true),
+            "x = 1;"
+        );
+        assert_eq!(normalize_line_end("let x;\n", "", Some('a'), true), "");
+    }
+
+    #[tokio::test]
+    async fn test_autocomplete_glued_line() -> anyhow::Result<()> {
+        let original = "let x = ;\nlet y = 2;\n";
+        let reply = "<|SEARCH|>let x = <|cursor|>;\n<|DIVIDE|>let x = 1;<|REPLACE|>";
+        let coder = Coder::new(Some(Box::new(MockBackend::new(reply))));
+
+        let edits = coder
+            .autocomplete(original, "main.rs", 8)
+            .await?
+            .remove(0)
+            .edits;
+        assert_eq!(apply(original, &edits), "let x = 1;\nlet y = 2;\n");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_debug_file() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let debug_file = dir.path().join(".redai/llm-debug.txt");
+        let mut coder = Coder::new(Some(Box::new(MockBackend::new("no patch here"))));
+        coder.debug_file = Some(debug_file.clone());
+
+        assert!(coder.autocomplete("let x = ;", "a.rs", 8).await.is_err());
+        let dump = std::fs::read_to_string(&debug_file)?;
+        assert!(dump.starts_with("# request\n\n## system\n"));
+        assert!(dump.contains("let x = <|cursor|>;"));
+        assert!(dump.contains("\n# response\nno patch here\n"));
+        assert!(dump.contains("\n# parsed\nerror: "));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_autocomplete_retries_invalid_patch() -> anyhow::Result<()> {
+        let original = "let x = ;\n";
+        let backend = MockBackend::with_replies(&[
+            "Sure! Here is the fix: let x = 1;",
+            "<|SEARCH|>let x = <|cursor|>;<|DIVIDE|>let x = 1;<|REPLACE|>",
+        ]);
+        let requests = backend.requests.clone();
+        let coder = Coder::new(Some(Box::new(backend)));
+
+        let edits = coder
+            .autocomplete(original, "a.rs", 8)
+            .await?
+            .remove(0)
+            .edits;
+        assert_eq!(apply(original, &edits), "let x = 1;\n");
+
+        let sent = requests.lock().unwrap();
+        assert_eq!(sent.len(), 2);
+        let retry = &sent[1];
+        assert_eq!(retry.len(), sent[0].len() + 2);
+        assert_eq!(retry[retry.len() - 2]["role"], "assistant");
+        assert!(
+            retry.last().unwrap()["content"]
+                .as_str()
+                .unwrap()
+                .contains(STOKEN)
+        );
+        Ok(())
+    }
+
+    /// Offers the same choices, with logprobs and finish reasons, to every
+    /// request
+    struct ChoicesBackend(Vec<Choice>);
+
+    impl ChatBackend for ChoicesBackend {
+        fn chat(&self, _messages: Vec<serde_json::Value>) -> crate::llm::ChatFuture<'_> {
+            let reply = self.0[0].content.clone();
+            Box::pin(async move { Ok(reply) })
+        }
+
+        fn chat_choices(
+            &self,
+            _messages: Vec<serde_json::Value>,
+            n: usize,
+        ) -> crate::llm::ChoicesFuture<'_> {
+            let choices = self.0.iter().take(n).cloned().collect();
+            Box::pin(async move { Ok(choices) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_autocomplete_candidates() -> anyhow::Result<()> {
+        let reply = |value: &str| {
+            format!("<|SEARCH|>let x = <|cursor|>;<|DIVIDE|>let x = {value};<|REPLACE|>")
+        };
+        let choice = |value: &str, logprob, finish_reason: &str| Choice {
+            content: reply(value),
+            logprob,
+            finish_reason: Some(finish_reason.to_string()),
+        };
+        async fn results(coder: &Coder) -> CoderResult<Vec<(String, Option<f64>)>> {
+            let original = "let x = ;\n";
+            let candidates = coder.autocomplete(original, "a.rs", 8).await?;
+            Ok(candidates
+                .iter()
+                .map(|c| (apply(original, &c.edits), c.logprob))
+                .collect())
+        }
+
+        // cut off last, then the most likely first; invalid and repeated ones dropped
+        let mut coder = Coder::new(Some(Box::new(ChoicesBackend(vec![
+            choice("1", Some(-0.9), "stop"),
+            choice("2", Some(-0.1), "length"),
+            Choice {
+                content: "no patch".to_string(),
+                ..Choice::default()
+            },
+            choice("3", Some(-0.2), "stop"),
+            choice("1", Some(-0.9), "stop"),
+        ]))));
+        coder.candidates = 5;
+        assert_eq!(
+            results(&coder).await?,
+            vec![
+                ("let x = 3;\n".to_string(), Some(-0.2)),
+                ("let x = 1;\n".to_string(), Some(-0.9)),
+                ("let x = 2;\n".to_string(), Some(-0.1)),
+            ]
+        );
+        coder.candidates = 1;
+        assert_eq!(results(&coder).await?.len(), 1);
+
+        // without scores the order returned stands
+        let backend = MockBackend::with_replies(&[&reply("2"), "no patch", &reply("1")]);
+        let mut coder = Coder::new(Some(Box::new(backend)));
+        coder.candidates = 3;
+        let texts: Vec<_> = results(&coder).await?.into_iter().map(|(t, _)| t).collect();
+        assert_eq!(texts, ["let x = 2;\n", "let x = 1;\n"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_autocomplete_bad_reply() {
+        let mut coder = Coder::new(Some(Box::new(MockBackend::new("no patch here"))));
+        coder.patch_retry = false;
+        let err = coder
+            .autocomplete("let x = ;", "a.rs", 8)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, CoderError::PatchParse(_)));
+    }
+
+    #[test]
+    fn test_pinned_context() {
+        let mut coder = Coder::new(None);
+        assert_eq!(coder.pinned_message(), None);
+
+        let region = PinnedRegion {
+            path: PathBuf::from("shape.rs"),
+            lines: (3, 5),
+            text: "trait Shape {\n    fn area(&self) -> f64;\n}".to_string(),
+        };
+        coder.pin(region.clone());
+        coder.pin(region);
+        let message = coder.pinned_message().unwrap();
+        assert_eq!(
+            message["content"],
+            "Pinned context:\nshape.rs lines 3-5:\ntrait Shape {\n    fn area(&self) -> f64;\n}\n"
+        );
+
+        assert_eq!(coder.clear_pinned(), 1);
+        assert_eq!(coder.pinned_message(), None);
+    }
+
+    #[test]
+    fn test_other_files_context() {
+        let mut coder = Coder::new(None);
+        coder.update(
+            Path::new("lib.rs"),
+            "pub fn parse(input: &str) -> Ast {\n    todo!()\n}\n",
+        );
+        coder.update(Path::new("main.rs"), "fn main() {}\n");
+        assert_eq!(coder.other_files_context(Path::new("main.rs")), "");
+
+        coder.context = ContextStrategy {
+            other_files: 2,
+            budget_tokens: 100,
+        };
+        let context = coder.other_files_context(Path::new("main.rs"));
+        assert_eq!(context, "lib.rs:\npub fn parse(input: &str) -> Ast {\n\n");
+
+        coder.context.budget_tokens = 3;
+        let context = coder.other_files_context(Path::new("main.rs"));
+        assert_eq!(context, "lib.rs:\n");
+    }
+
+    #[test]
+    fn test_recent_edits() {
+        let mut coder = Coder::new(None);
+        coder.update(Path::new("lib.rs"), "a\nb\n");
+        coder.update(Path::new("lib.rs"), "a\nc\nd\n");
+        coder.update(Path::new("main.rs"), "fn main() {}\n");
+        coder.update(Path::new("notes.md"), "todo\n");
+
+        let edit = |path: &str, added, removed| RecentEdit {
+            path: PathBuf::from(path),
+            added,
+            removed,
+        };
+        assert_eq!(coder.recent_edits(None), vec![edit("lib.rs", 2, 1)]);
+
+        // the live content of the current file counts, and it comes first
+        let current = (Path::new("main.rs"), "fn main() {\n}\n");
+        assert_eq!(
+            coder.recent_edits(Some(current)),
+            vec![edit("main.rs", 2, 1), edit("lib.rs", 2, 1)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rewrite_selection() -> anyhow::Result<()> {
+        let original = "fn main() {\n    let x=1;\n    let y=2;\n}\n";
+        let start = original.find("let x").unwrap();
+        let end = original.find("\n    let y").unwrap();
+
+        let backend = MockBackend::new("<|DIVIDE|>let x = 1;<|REPLACE|>");
+        let requests = backend.requests.clone();
+        let coder = Coder::new(Some(Box::new(backend)));
+
+        let edits = coder.rewrite(original, "main.rs", start, end).await?;
+        assert_eq!(
+            apply(original, &edits),
+            "fn main() {\n    let x = 1;\n    let y=2;\n}\n"
+        );
+        assert!(edits.iter().all(|e| e.start >= start));
+
+        let sent = requests.lock().unwrap();
+        let big = sent[0][1]["content"].as_str().unwrap();
+        assert!(big.contains("<|selection|>let x=1;<|/selection|>"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_instruct_selection() -> anyhow::Result<()> {
+        let original = "fn main() {\n    let x = 1;\n}\n";
+        let start = original.find("let x").unwrap();
+        let end = original.find("\n}").unwrap();
+
+        let reply = "Here you go:\n```rust\nlet x: u64 = 1;\n```\nThe type is explicit now.";
+        let backend = MockBackend::new(reply);
+        let requests = backend.requests.clone();
+        let coder = Coder::new(Some(Box::new(backend)));
+
+        let edits = coder
+            .instruct(original, "main.rs", start, end, "make the type explicit")
+            .await?;
+        assert_eq!(
+            apply(original, &edits),
+            "fn main() {\n    let x: u64 = 1;\n}\n"
+        );
+
+        let sent = requests.lock().unwrap();
+        assert_eq!(sent[0][0]["content"], INSTRUCT_PROMPT);
+        let instruction = sent[0][4]["content"].as_str().unwrap();
+        assert_eq!(instruction, "Instruction:\nmake the type explicit");
+
+        assert_eq!(parse_instruct("  let y = 2;\n\n").unwrap(), "  let y = 2;");
+        assert!(parse_instruct("```\n```").is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_format_selection() -> anyhow::Result<()> {
+        let original = "fn main() {\n    let v = vec![1,2,\n  3];\n}\n";
+        let start = original.find("let v").unwrap();
+        let end = original.find("\n}").unwrap();
+
+        let backend = MockBackend::new("```rust\nlet v = vec![1, 2, 3];\n```");
+        let requests = backend.requests.clone();
+        let coder = Coder::new(Some(Box::new(backend)));
+
+        let edits = coder
+            .format_selection(original, "main.rs", start, end)
+            .await?;
+        assert_eq!(
+            apply(original, &edits),
+            "fn main() {\n    let v = vec![1, 2, 3];\n}\n"
+        );
+        // the edits stay inside the selection
+        assert!(edits.iter().all(|e| e.start >= start && e.start <= end));
+
+        let sent = requests.lock().unwrap();
+        assert_eq!(sent[0][0]["content"], FORMAT_PROMPT);
+        assert_eq!(sent[0].len(), 5);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_commit_message() -> anyhow::Result<()> {
+        let backend = MockBackend::new("```\nRename b to c\n\nAnd add d.\n```");
+        let requests = backend.requests.clone();
+        let mut coder = Coder::new(Some(Box::new(backend)));
+        coder.update(Path::new("lib.rs"), "a\nb\n");
+        coder.update(Path::new("lib.rs"), "a\nc\n");
+        coder.update(Path::new("notes.md"), "todo\n");
+
+        let current = (Path::new("lib.rs"), "a\nc\nd\n");
+        let changes = coder.session_changes(Some(current));
+        assert_eq!(changes, "lib.rs:\n-b\n+c\n+d\n\n");
+
+        let original = "# notes\n";
+        let edits = coder.commit_message(&changes, original.len()).await?;
+        assert_eq!(
+            apply(original, &edits),
+            "# notes\nRename b to c\n\nAnd add d."
+        );
+
+        {
+            let sent = requests.lock().unwrap();
+            assert_eq!(sent[0][0]["content"], COMMIT_PROMPT);
+            assert_eq!(sent[0][1]["content"], format!("Changes:\n{changes}"));
+        }
+
+        // nothing to describe asks nothing
+        assert!(coder.commit_message(" \n", 0).await?.is_empty());
+        assert_eq!(requests.lock().unwrap().len(), 1);
+        Ok(())
+    }
 }
@@ -1,34 +1,855 @@
-use anyhow::Result;
+use crate::coder::ContextStrategy;
+use crate::keymap::KeyBinding;
+use crate::llm::LlmParams;
+use crate::search::SearchOptions;
+use crate::searcher::SearchOrder;
+use crate::words::WordBoundary;
+use anyhow::{Context, Result};
+use crossterm::event::{KeyCode, KeyModifiers};
+use ratatui_code_editor::utils::get_lang;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Log file used when logging is enabled, relative to the working directory
+const LOG_FILE: &str = ".redai/redai.log";
+/// The last LLM exchange is written here with `REDAI_DEBUG_LLM`
+const LLM_DEBUG_FILE: &str = ".redai/llm-debug.txt";
+/// Search results are exported here unless `REDAI_SEARCH_EXPORT` is set
+const SEARCH_EXPORT_FILE: &str = ".redai/search-results.txt";
+/// Snippets are read from here unless `REDAI_SNIPPETS` is set
+const SNIPPETS_FILE: &str = ".redai/snippets";
+/// Templates for new files are read from here unless `REDAI_TEMPLATES` is set
+const TEMPLATES_FILE: &str = ".redai/templates";
+/// Bookmarks are kept here unless `REDAI_BOOKMARKS` is set
+const BOOKMARKS_FILE: &str = ".redai/bookmarks.json";
+/// The nearest directory with one of these is the project root
+const ROOT_MARKERS: &str = ".git,Cargo.toml,package.json,.redai";
+/// Default filesystems of macOS and Windows ignore the case of file names
+const IGNORE_PATH_CASE: bool = cfg!(any(target_os = "macos", target_os = "windows"));
+/// The log is rotated to `redai.log.1` on start once it grows past this
+const LOG_MAX_BYTES: u64 = 1024 * 1024;
 
 /// Application configuration
 pub struct Config {
+    /// `OPENROUTER_API_KEY`, or the key of `api_key_source` once resolved
     pub api_key: Option<String>,
+    /// Where the key comes from when not from the environment, resolved
+    /// only with AI enabled
+    api_key_source: Option<ApiKeySource>,
     pub base_url: String,
     pub model: String,
+    /// Runs without an LLM client even when an API key is set
+    pub disable_ai: bool,
+    /// Sampling controls sent with every completion request
+    pub llm_params: LlmParams,
+    /// Extra HTTP headers sent with every LLM request
+    pub headers: Vec<(String, String)>,
+    /// An LLM request not answered within this fails as timed out
+    pub request_timeout: Duration,
+    /// Extra context from other files sent with completions
+    pub context: ContextStrategy,
+    pub search_wrap: bool,
+    /// File the search panel exports its results to
+    pub search_export: PathBuf,
+    /// Search skips files with a line this long as minified, 0 never
+    pub search_max_line_length: usize,
+    /// Order of global search results
+    pub search_order: SearchOrder,
+    /// Starts the search panel without its options and timing rows
+    pub search_compact: bool,
+    /// Matching options every new search starts with
+    pub search_defaults: SearchOptions,
+    /// What counts as a word for word movement and whole-word search
+    pub word_boundary: WordBoundary,
+    pub autocomplete_key: KeyBinding,
+    /// Key that asks for an instruction to change the selection with
+    pub instruct_key: KeyBinding,
+    /// Files autocomplete runs in, by language or file name
+    pub autocomplete_languages: LanguageRules,
+    pub tab_width: usize,
+    /// AI edits deleting more lines than this need confirmation, 0 never
+    pub confirm_delete_lines: usize,
+    /// Change marks are cleared after this long without interaction
+    pub marks_timeout: Option<Duration>,
+    /// Entries listed per directory in the tree, 0 for no limit
+    pub tree_max_entries: usize,
+    /// Whether Enter keeps the current line's indentation
+    pub auto_indent: bool,
+    /// Multi-line completions that end a line leave the cursor on a new line
+    pub completion_newline: bool,
+    /// Asks the model once more when its patch cannot be parsed
+    pub patch_retry: bool,
+    /// Completions asked for per request, 1 to 5
+    pub completion_candidates: usize,
+    /// Strips trailing spaces and tabs from every line on save
+    pub trim_trailing_whitespace: bool,
+    /// Ends the file with exactly one newline on save
+    pub final_newline: bool,
+    /// Asks before saving over a file changed on disk since it was read
+    pub confirm_overwrite: bool,
+    /// Opens files without syntax highlighting
+    pub no_highlight: bool,
+    /// Runs without a file watcher, so open files never reload by themselves
+    pub no_watch: bool,
+    /// Never saves files, for trying the AI on code that must stay as is
+    pub no_write: bool,
+    /// File names differing only in case name the same file
+    pub ignore_path_case: bool,
+    /// Marks spaces and tabs in the editor
+    pub show_whitespace: bool,
+    /// Shifts multi-line pastes to the indentation of the cursor's line
+    pub reindent_paste: bool,
+    /// Snippets wrapped around the selection, besides the built-in ones
+    pub snippets_file: PathBuf,
+    /// Templates for new files, tried before the built-in ones
+    pub templates_file: PathBuf,
+    /// Bookmarked positions of the project
+    pub bookmarks_file: PathBuf,
+    /// Files or directories marking the project root, empty to use the
+    /// working directory as it is
+    pub root_markers: Vec<String>,
+    /// Debug log destination, when logging is enabled
+    pub log_file: Option<PathBuf>,
+    /// Dump of the last LLM request and response, when enabled
+    pub llm_debug_file: Option<PathBuf>,
+    /// Replaces the built-in system prompt of completions
+    pub system_prompt: Option<String>,
 }
 
 impl Config {
     /// Load configuration from environment variables
     pub fn from_env() -> Result<Self> {
-        let api_key = std::env::var("OPENROUTER_API_KEY").ok();
+        Self::from_lookup(|name| std::env::var(name).ok())
+    }
+
+    /// Reloads configuration with the current env files under the process
+    /// environment as it was on start, as the first load layered them
+    pub fn load(env: &ConfigEnv) -> Result<Self> {
+        let mut dotenv_vars = HashMap::new();
+        // lowest precedence first so that earlier files overwrite later ones
+        for path in env.files.iter().rev() {
+            // the suggested replacement writes to the environment instead
+            #[allow(deprecated)]
+            let Ok(iter) = dotenv::from_path_iter(path) else {
+                continue;
+            };
+            for item in iter {
+                let (name, value) = item.with_context(|| format!("invalid {}", path.display()))?;
+                dotenv_vars.insert(name, value);
+            }
+        }
+        Self::from_lookup(|name| {
+            env.process
+                .get(name)
+                .or_else(|| dotenv_vars.get(name))
+                .cloned()
+        })
+    }
+
+    pub fn from_lookup(var: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let env_flag = |name: &str, default: bool| parse_flag(var(name), default);
+
+        let api_key_source = match (var("REDAI_API_KEY_COMMAND"), var("REDAI_API_KEY_FILE")) {
+            (Some(command), _) => Some(ApiKeySource::Command(command)),
+            (None, Some(path)) => Some(ApiKeySource::File(path)),
+            (None, None) => None,
+        };
+        let api_key = var("OPENROUTER_API_KEY").filter(|_| api_key_source.is_none());
+
+        let base_url = var("OPENROUTER_BASE_URL")
+            .unwrap_or_else(|| "https://openrouter.ai/api/v1".to_string());
+
+        let model =
+            var("OPENROUTER_MODEL").unwrap_or_else(|| "mistralai/codestral-2508".to_string());
+
+        let disable_ai = env_flag("REDAI_DISABLE_AI", false);
+
+        let llm_params = LlmParams {
+            temperature: parse_number(var("REDAI_TEMPERATURE"), "REDAI_TEMPERATURE")?,
+            top_p: parse_number(var("REDAI_TOP_P"), "REDAI_TOP_P")?,
+            max_tokens: parse_number(var("REDAI_MAX_TOKENS"), "REDAI_MAX_TOKENS")?,
+            stop: var("REDAI_STOP")
+                .map(|stop| {
+                    stop.split(',')
+                        .map(str::trim)
+                        .filter(|s| !s.is_empty())
+                        .map(String::from)
+                        .collect()
+                })
+                .unwrap_or_default(),
+        };
+
+        let headers = match var("REDAI_HEADERS") {
+            Some(spec) => parse_headers(&spec).context("invalid REDAI_HEADERS")?,
+            None => Vec::new(),
+        };
+
+        let request_timeout = var("REDAI_REQUEST_TIMEOUT")
+            .and_then(|secs| secs.trim().parse::<f64>().ok())
+            .filter(|secs| *secs > 0.0)
+            .map_or(crate::llm::REQUEST_TIMEOUT, Duration::from_secs_f64);
 
-        let base_url = std::env::var("OPENROUTER_BASE_URL")
-            .unwrap_or_else(|_| "https://openrouter.ai/api/v1".to_string());
+        let context = ContextStrategy {
+            other_files: parse_number(var("REDAI_CONTEXT_FILES"), "REDAI_CONTEXT_FILES")?
+                .unwrap_or(0),
+            budget_tokens: parse_number(var("REDAI_CONTEXT_BUDGET"), "REDAI_CONTEXT_BUDGET")?
+                .unwrap_or(1000),
+        };
 
-        let model = std::env::var("OPENROUTER_MODEL")
-            .unwrap_or_else(|_| "mistralai/codestral-2508".to_string());
+        let search_wrap = env_flag("REDAI_SEARCH_WRAP", true);
+        let search_compact = env_flag("REDAI_SEARCH_COMPACT", false);
+        let search_defaults = SearchOptions {
+            case_sensitive: env_flag("REDAI_SEARCH_CASE_SENSITIVE", false),
+            regex_mode: env_flag("REDAI_SEARCH_REGEX", false),
+            whole_word: env_flag("REDAI_SEARCH_WHOLE_WORD", false),
+        };
+        let search_export = var("REDAI_SEARCH_EXPORT")
+            .filter(|path| !path.trim().is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(SEARCH_EXPORT_FILE));
+        let search_max_line_length = parse_number(
+            var("REDAI_SEARCH_MAX_LINE_LENGTH"),
+            "REDAI_SEARCH_MAX_LINE_LENGTH",
+        )?
+        .unwrap_or(crate::utils::MAX_LINE_LENGTH);
+        let search_order = match var("REDAI_SEARCH_ORDER") {
+            Some(spec) => SearchOrder::parse(&spec).with_context(|| {
+                format!("invalid REDAI_SEARCH_ORDER {spec:?}, expected relevance or path")
+            })?,
+            None => SearchOrder::default(),
+        };
+        let word_boundary = match var("REDAI_WORD_BOUNDARY") {
+            Some(spec) => WordBoundary::parse(&spec).with_context(|| {
+                format!(
+                    "invalid REDAI_WORD_BOUNDARY {spec:?}, expected whitespace, punctuation or subword"
+                )
+            })?,
+            None => WordBoundary::default(),
+        };
+
+        // Ctrl+Space is swallowed by some ssh clients and tmux setups
+        let autocomplete_key = match var("REDAI_AUTOCOMPLETE_KEY") {
+            Some(spec) => KeyBinding::parse(&spec).context("invalid REDAI_AUTOCOMPLETE_KEY")?,
+            None => KeyBinding::new(KeyCode::Char(' '), KeyModifiers::CONTROL),
+        };
+
+        let instruct_key = match var("REDAI_INSTRUCT_KEY") {
+            Some(spec) => KeyBinding::parse(&spec).context("invalid REDAI_INSTRUCT_KEY")?,
+            None => KeyBinding::new(KeyCode::Char('i'), KeyModifiers::ALT),
+        };
+
+        let autocomplete_languages = var("REDAI_AUTOCOMPLETE_LANGUAGES")
+            .map(|spec| LanguageRules::parse(&spec))
+            .unwrap_or_default();
+
+        let tab_width = var("REDAI_TAB_WIDTH")
+            .and_then(|w| w.trim().parse::<usize>().ok())
+            .filter(|w| (1..=16).contains(w))
+            .unwrap_or(4);
+
+        let confirm_delete_lines = parse_number(
+            var("REDAI_CONFIRM_DELETE_LINES"),
+            "REDAI_CONFIRM_DELETE_LINES",
+        )?
+        .unwrap_or(20);
+
+        // unset or 0 keeps marks until a key clears them
+        let marks_timeout = var("REDAI_MARKS_TIMEOUT")
+            .and_then(|secs| secs.trim().parse::<f64>().ok())
+            .filter(|secs| *secs > 0.0)
+            .map(Duration::from_secs_f64);
+
+        let tree_max_entries =
+            parse_number(var("REDAI_TREE_MAX_ENTRIES"), "REDAI_TREE_MAX_ENTRIES")?
+                .unwrap_or(crate::tree::MAX_ENTRIES);
+
+        let auto_indent = env_flag("REDAI_AUTO_INDENT", true);
+        let completion_newline = env_flag("REDAI_COMPLETION_NEWLINE", false);
+        let patch_retry = env_flag("REDAI_PATCH_RETRY", true);
+        let completion_candidates = var("REDAI_COMPLETION_CANDIDATES")
+            .and_then(|n| n.trim().parse::<usize>().ok())
+            .filter(|n| (1..=5).contains(n))
+            .unwrap_or(1);
+
+        // off by default: whitespace can be significant, e.g. in Markdown
+        let trim_trailing_whitespace = env_flag("REDAI_TRIM_TRAILING_WHITESPACE", false);
+        let final_newline = env_flag("REDAI_FINAL_NEWLINE", false);
+        let confirm_overwrite = env_flag("REDAI_CONFIRM_OVERWRITE", true);
+        let no_highlight = env_flag("REDAI_NO_HIGHLIGHT", false);
+        let no_watch = env_flag("REDAI_NO_WATCH", false);
+        let no_write = env_flag("REDAI_NO_WRITE", false);
+        let ignore_path_case = env_flag("REDAI_IGNORE_PATH_CASE", IGNORE_PATH_CASE);
+        let show_whitespace = env_flag("REDAI_SHOW_WHITESPACE", false);
+        let reindent_paste = env_flag("REDAI_REINDENT_PASTE", false);
+        let root_markers = var("REDAI_ROOT_MARKERS")
+            .unwrap_or_else(|| ROOT_MARKERS.to_string())
+            .split(',')
+            .map(str::trim)
+            .filter(|marker| !marker.is_empty())
+            .map(String::from)
+            .collect();
+        let snippets_file = var("REDAI_SNIPPETS")
+            .filter(|path| !path.trim().is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(SNIPPETS_FILE));
+        let templates_file = var("REDAI_TEMPLATES")
+            .filter(|path| !path.trim().is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(TEMPLATES_FILE));
+        let bookmarks_file = var("REDAI_BOOKMARKS")
+            .filter(|path| !path.trim().is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(BOOKMARKS_FILE));
+
+        // the TUI owns stderr, so logs only go to a file
+        let log_enabled = env_flag("REDAI_LOG", var("RUST_LOG").is_some());
+        let log_file = log_enabled.then(|| PathBuf::from(LOG_FILE));
+        let llm_debug_file =
+            env_flag("REDAI_DEBUG_LLM", false).then(|| PathBuf::from(LLM_DEBUG_FILE));
+        let system_prompt = match var("REDAI_SYSTEM_PROMPT_FILE").filter(|p| !p.trim().is_empty()) {
+            Some(path) => Some(
+                std::fs::read_to_string(&path)
+                    .with_context(|| format!("cannot read REDAI_SYSTEM_PROMPT_FILE {path}"))?,
+            ),
+            None => None,
+        };
 
         Ok(Self {
             api_key,
+            api_key_source,
             base_url,
             model,
+            disable_ai,
+            llm_params,
+            headers,
+            request_timeout,
+            context,
+            search_wrap,
+            search_export,
+            search_max_line_length,
+            search_order,
+            search_compact,
+            search_defaults,
+            word_boundary,
+            autocomplete_key,
+            instruct_key,
+            autocomplete_languages,
+            tab_width,
+            confirm_delete_lines,
+            marks_timeout,
+            tree_max_entries,
+            auto_indent,
+            completion_newline,
+            patch_retry,
+            completion_candidates,
+            trim_trailing_whitespace,
+            final_newline,
+            confirm_overwrite,
+            no_highlight,
+            no_watch,
+            no_write,
+            ignore_path_case,
+            show_whitespace,
+            reindent_paste,
+            snippets_file,
+            templates_file,
+            bookmarks_file,
+            root_markers,
+            log_file,
+            llm_debug_file,
+            system_prompt,
         })
     }
+
+    /// Runs `REDAI_API_KEY_COMMAND` or reads `REDAI_API_KEY_FILE` unless AI
+    /// is disabled; a failure leaves AI off and is returned as a warning
+    pub fn resolve_api_key(&mut self) -> Option<String> {
+        if self.disable_ai {
+            return None;
+        }
+        let key = match self.api_key_source.as_ref()? {
+            ApiKeySource::Command(command) => api_key_from_command(command),
+            ApiKeySource::File(path) => std::fs::read_to_string(path)
+                .with_context(|| format!("cannot read REDAI_API_KEY_FILE {path}"))
+                .map(|key| key.trim().to_string()),
+        };
+        match key {
+            Ok(key) => {
+                self.api_key = Some(key);
+                None
+            }
+            Err(err) => {
+                self.api_key = None;
+                Some(format!("{err:#}, AI is off"))
+            }
+        }
+    }
+}
+
+/// `REDAI_API_KEY_COMMAND` or `REDAI_API_KEY_FILE`
+enum ApiKeySource {
+    Command(String),
+    File(String),
+}
+
+/// Runs `command` with the shell and takes its output as the API key, so
+/// that it can come from a password manager, e.g. `pass show openrouter`
+fn api_key_from_command(command: &str) -> Result<String> {
+    let output = std::process::Command::new("sh")
+        .args(["-c", command])
+        .stdin(std::process::Stdio::null())
+        .output()
+        .context("cannot run REDAI_API_KEY_COMMAND")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!(
+            "REDAI_API_KEY_COMMAND failed ({}): {}",
+            output.status,
+            stderr.trim()
+        );
+    }
+    let key = String::from_utf8(output.stdout).context("REDAI_API_KEY_COMMAND output")?;
+    Ok(key.trim().to_string())
+}
+
+/// Reads a boolean setting, falling back to `default` when it is unset
+/// or not a recognizable value
+fn parse_flag(value: Option<String>, default: bool) -> bool {
+    match value {
+        Some(value) => match value.trim().to_lowercase().as_str() {
+            "1" | "true" | "yes" | "on" => true,
+            "0" | "false" | "no" | "off" => false,
+            _ => default,
+        },
+        None => default,
+    }
 }
 
-/// Initialize the logger
-pub fn _init_logger() {
-    env_logger::Builder::from_default_env()
-        .filter_level(log::LevelFilter::Debug)
+/// Parses an optional numeric setting, failing on values that are set
+/// but malformed rather than silently dropping them
+fn parse_number<T: std::str::FromStr>(value: Option<String>, name: &str) -> Result<Option<T>> {
+    value
+        .map(|v| {
+            v.trim()
+                .parse::<T>()
+                .ok()
+                .with_context(|| format!("invalid {name}: {v}"))
+        })
+        .transpose()
+}
+
+/// Initialize the logger, writing to `path`; `RUST_LOG` picks the filter
+pub fn init_logger(path: &Path) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    rotate_log(path)?;
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("cannot open log file {}", path.display()))?;
+
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("redai=debug"))
+        .target(env_logger::Target::Pipe(Box::new(file)))
         .init();
+    Ok(())
+}
+
+/// Env files that feed the configuration, highest precedence first: an
+/// explicit file (`--env` or `REDAI_ENV_FILE`), the nearest `.redai/env`
+/// and the nearest project `.env` in the current directory or above, then
+/// the user-wide `~/.config/redai/.env`. Files that do not exist are skipped
+pub fn env_files(explicit: Option<PathBuf>) -> Vec<PathBuf> {
+    let explicit = explicit.or_else(|| std::env::var_os("REDAI_ENV_FILE").map(PathBuf::from));
+    let project = std::env::current_dir()
+        .map(|cwd| project_env_files(&cwd))
+        .unwrap_or_default();
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+    let user = config_home.map(|dir| dir.join("redai").join(".env"));
+
+    let mut files: Vec<PathBuf> = Vec::new();
+    let candidates = std::iter::once(explicit)
+        .chain(project.into_iter().map(Some))
+        .chain(std::iter::once(user));
+    for path in candidates.flatten() {
+        if path.is_file() && !files.contains(&path) {
+            files.push(path);
+        }
+    }
+    files
+}
+
+/// The nearest `.redai/env`, redai's own settings such as the model kept
+/// apart from the project's variables, then the nearest `.env`, both
+/// searched in `dir` and above
+fn project_env_files(dir: &Path) -> Vec<PathBuf> {
+    [".redai/env", ".env"]
+        .iter()
+        .filter_map(|name| {
+            dir.ancestors()
+                .map(|dir| dir.join(name))
+                .find(|path| path.is_file())
+        })
+        .collect()
+}
+
+/// Where the configuration comes from: env files under the process
+/// environment
+#[derive(Clone, Debug, Default)]
+pub struct ConfigEnv {
+    /// Env files, highest precedence first
+    pub files: Vec<PathBuf>,
+    /// The process environment before the env files were copied into it
+    pub process: HashMap<String, String>,
+}
+
+impl ConfigEnv {
+    /// Copies the variables of `files` into the environment without
+    /// overriding ones that are already set, so earlier files take
+    /// precedence, keeping the environment as it was for reloads
+    pub fn load(files: Vec<PathBuf>) -> Self {
+        let process = std::env::vars().collect();
+        for path in &files {
+            let _ = dotenv::from_path(path);
+        }
+        Self { files, process }
+    }
+}
+
+/// Comma-separated language names as `get_lang` spells them, or file name
+/// globs such as `*.txt`; a leading `!` excludes. The last rule matching a
+/// file decides, files no rule matches are included
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LanguageRules(Vec<(String, bool)>);
+
+impl LanguageRules {
+    pub fn parse(spec: &str) -> Self {
+        let rules = spec
+            .split(',')
+            .map(str::trim)
+            .filter(|rule| !rule.is_empty())
+            .map(|rule| match rule.strip_prefix('!') {
+                Some(pattern) => (pattern.trim().to_string(), false),
+                None => (rule.to_string(), true),
+            })
+            .collect();
+        Self(rules)
+    }
+
+    pub fn includes(&self, filename: &str) -> bool {
+        let language = get_lang(filename);
+        let name = Path::new(filename)
+            .file_name()
+            .map_or(filename.into(), |name| name.to_string_lossy());
+        self.0
+            .iter()
+            .rev()
+            .find(|(pattern, _)| glob_match(pattern, &language) || glob_match(pattern, &name))
+            .is_none_or(|&(_, included)| included)
+    }
+}
+
+/// Matches `text` against a pattern where `*` stands for any run of chars
+/// and `?` for a single one
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // position of the last `*` and the text it was tried against
+    let mut star = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Parses `Name: value` pairs separated by commas, e.g.
+/// `HTTP-Referer: https://example.com, X-Title: redai`
+fn parse_headers(spec: &str) -> Result<Vec<(String, String)>> {
+    use reqwest::header::{HeaderName, HeaderValue};
+
+    let mut headers = Vec::new();
+    for pair in spec.split(',').filter(|p| !p.trim().is_empty()) {
+        let (name, value) = pair
+            .split_once(':')
+            .with_context(|| format!("expected 'Name: value', got '{}'", pair.trim()))?;
+        let (name, value) = (name.trim(), value.trim());
+        HeaderName::try_from(name).with_context(|| format!("bad header name '{name}'"))?;
+        HeaderValue::try_from(value).with_context(|| format!("bad value for header {name}"))?;
+        headers.push((name.to_string(), value.to_string()));
+    }
+    Ok(headers)
+}
+
+/// Keeps one previous log around once the current one gets too big
+fn rotate_log(path: &Path) -> Result<()> {
+    let too_big = std::fs::metadata(path).is_ok_and(|m| m.len() > LOG_MAX_BYTES);
+    if too_big {
+        let mut rotated = path.as_os_str().to_owned();
+        rotated.push(".1");
+        std::fs::rename(path, rotated)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_lookup() {
+        let vars = HashMap::from([
+            ("REDAI_TAB_WIDTH", "8"),
+            ("REDAI_SEARCH_WRAP", "off"),
+            ("OPENROUTER_MODEL", "test/model"),
+        ]);
+        let config = Config::from_lookup(|name| vars.get(name).map(|v| v.to_string())).unwrap();
+        assert_eq!(config.tab_width, 8);
+        assert!(!config.search_wrap);
+        assert_eq!(config.search_export, PathBuf::from(SEARCH_EXPORT_FILE));
+        assert_eq!(config.search_max_line_length, crate::utils::MAX_LINE_LENGTH);
+        assert_eq!(config.search_order, SearchOrder::Relevance);
+        assert!(!config.search_compact);
+        assert_eq!(config.search_defaults, SearchOptions::default());
+        assert_eq!(config.word_boundary, WordBoundary::Punctuation);
+        assert_eq!(config.autocomplete_languages, LanguageRules::default());
+        assert_eq!(
+            config.instruct_key,
+            KeyBinding::new(KeyCode::Char('i'), KeyModifiers::ALT)
+        );
+        assert_eq!(config.model, "test/model");
+        assert!(config.auto_indent);
+        assert!(!config.completion_newline);
+        assert!(config.patch_retry);
+        assert_eq!(config.completion_candidates, 1);
+        assert!(!config.trim_trailing_whitespace);
+        assert!(!config.final_newline);
+        assert!(config.confirm_overwrite);
+        assert!(!config.no_highlight);
+        assert!(!config.no_watch);
+        assert!(!config.no_write);
+        assert_eq!(config.ignore_path_case, IGNORE_PATH_CASE);
+        assert!(!config.show_whitespace);
+        assert!(!config.reindent_paste);
+        assert_eq!(config.snippets_file, PathBuf::from(SNIPPETS_FILE));
+        assert_eq!(config.templates_file, PathBuf::from(TEMPLATES_FILE));
+        assert_eq!(config.bookmarks_file, PathBuf::from(BOOKMARKS_FILE));
+        assert_eq!(
+            config.root_markers,
+            [".git", "Cargo.toml", "package.json", ".redai"]
+        );
+
+        assert_eq!(config.llm_params, LlmParams::default());
+        assert_eq!(config.request_timeout, crate::llm::REQUEST_TIMEOUT);
+        assert_eq!(config.system_prompt, None);
+
+        let params = HashMap::from([
+            ("REDAI_TEMPERATURE", "0"),
+            ("REDAI_REQUEST_TIMEOUT", "2.5"),
+            ("REDAI_MAX_TOKENS", "512"),
+            ("REDAI_STOP", "<|REPLACE|>, ###"),
+        ]);
+        let config = Config::from_lookup(|name| params.get(name).map(|v| v.to_string())).unwrap();
+        assert_eq!(config.llm_params.temperature, Some(0.0));
+        assert_eq!(config.llm_params.max_tokens, Some(512));
+        assert_eq!(config.llm_params.stop, vec!["<|REPLACE|>", "###"]);
+        assert_eq!(config.request_timeout, Duration::from_millis(2500));
+
+        let bad = HashMap::from([("REDAI_MAX_TOKENS", "lots")]);
+        assert!(Config::from_lookup(|name| bad.get(name).map(|v| v.to_string())).is_err());
+
+        let bad = HashMap::from([("REDAI_AUTOCOMPLETE_KEY", "ctrl+nope")]);
+        assert!(Config::from_lookup(|name| bad.get(name).map(|v| v.to_string())).is_err());
+
+        let bad = HashMap::from([("REDAI_HEADERS", "X-Title redai")]);
+        assert!(Config::from_lookup(|name| bad.get(name).map(|v| v.to_string())).is_err());
+
+        let bad = HashMap::from([("REDAI_SEARCH_ORDER", "random")]);
+        assert!(Config::from_lookup(|name| bad.get(name).map(|v| v.to_string())).is_err());
+
+        let bad = HashMap::from([("REDAI_SYSTEM_PROMPT_FILE", "/nonexistent/prompt.txt")]);
+        assert!(Config::from_lookup(|name| bad.get(name).map(|v| v.to_string())).is_err());
+    }
+
+    #[test]
+    fn test_api_key_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("key");
+        std::fs::write(&file, "sk-file\n").unwrap();
+        let file = file.to_string_lossy().to_string();
+        let load = |vars: &[(&str, &str)]| {
+            let vars: HashMap<_, _> = vars.iter().copied().collect();
+            Config::from_lookup(|name| vars.get(name).map(|v| v.to_string()))
+        };
+
+        let key = |vars: &[(&str, &str)]| {
+            let mut config = load(vars).unwrap();
+            let warning = config.resolve_api_key();
+            (config.api_key, warning)
+        };
+
+        let vars = [("OPENROUTER_API_KEY", "sk-env")];
+        assert_eq!(key(&vars), (Some("sk-env".to_string()), None));
+        let vars = [
+            ("OPENROUTER_API_KEY", "sk-env"),
+            ("REDAI_API_KEY_FILE", &file),
+        ];
+        assert_eq!(key(&vars), (Some("sk-file".to_string()), None));
+        let vars = [
+            ("OPENROUTER_API_KEY", "sk-env"),
+            ("REDAI_API_KEY_FILE", &file),
+            ("REDAI_API_KEY_COMMAND", "printf ' sk-command\\n'"),
+        ];
+        assert_eq!(key(&vars), (Some("sk-command".to_string()), None));
+
+        // a failing source leaves AI off with a warning, the config loads
+        let (api_key, warning) = key(&[("REDAI_API_KEY_COMMAND", "echo nope >&2; exit 3")]);
+        assert_eq!(api_key, None);
+        assert!(warning.unwrap().contains("nope"));
+        let (api_key, warning) = key(&[("REDAI_API_KEY_FILE", "/nonexistent/key")]);
+        assert_eq!(api_key, None);
+        assert!(warning.is_some());
+
+        // with AI disabled the command never runs
+        let marker = dir.path().join("ran");
+        let command = format!("touch {}", marker.display());
+        let vars = [
+            ("REDAI_API_KEY_COMMAND", command.as_str()),
+            ("REDAI_DISABLE_AI", "1"),
+        ];
+        assert_eq!(key(&vars), (None, None));
+        assert!(!marker.exists());
+    }
+
+    #[test]
+    fn test_language_rules() {
+        let rules = LanguageRules::parse("!markdown, !*.txt, notes.txt");
+        assert!(rules.includes("src/main.rs"));
+        assert!(!rules.includes("README.md"));
+        assert!(!rules.includes("docs/todo.txt"));
+        assert!(rules.includes("docs/notes.txt"));
+
+        let only_rust = LanguageRules::parse("!*,rust");
+        assert!(only_rust.includes("lib.rs"));
+        assert!(!only_rust.includes("main.py"));
+        assert!(LanguageRules::default().includes("README.md"));
+
+        assert!(glob_match("*.min.?s", "app.min.js"));
+        assert!(!glob_match("*.min.?s", "app.js"));
+    }
+
+    #[test]
+    fn test_parse_headers() {
+        let headers = parse_headers("HTTP-Referer: https://example.com, X-Title: redai,").unwrap();
+        assert_eq!(
+            headers,
+            vec![
+                (
+                    "HTTP-Referer".to_string(),
+                    "https://example.com".to_string()
+                ),
+                ("X-Title".to_string(), "redai".to_string()),
+            ]
+        );
+        assert!(parse_headers("Bad Name: x").is_err());
+        assert!(parse_headers("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_load_env_files_precedence() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("project.env");
+        let user = dir.path().join("user.env");
+        std::fs::write(&project, "OPENROUTER_MODEL=project/model\n").unwrap();
+        std::fs::write(&user, "OPENROUTER_MODEL=user/model\nREDAI_TAB_WIDTH=2\n").unwrap();
+
+        let files = |files: &[&PathBuf]| ConfigEnv {
+            files: files.iter().map(|path| path.to_path_buf()).collect(),
+            process: HashMap::new(),
+        };
+        let config = Config::load(&files(&[&project, &user])).unwrap();
+        assert_eq!(config.model, "project/model");
+        assert_eq!(config.tab_width, 2);
+
+        let missing = dir.path().join("missing.env");
+        let config = Config::load(&files(&[&missing, &user])).unwrap();
+        assert_eq!(config.model, "user/model");
+    }
+
+    #[test]
+    fn test_reload_keeps_process_env_on_top() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env");
+        std::fs::write(&path, "OPENROUTER_MODEL=file/model\nREDAI_TAB_WIDTH=2\n").unwrap();
+        let env = ConfigEnv {
+            files: vec![path.clone()],
+            process: HashMap::from([("OPENROUTER_MODEL".to_string(), "shell/model".to_string())]),
+        };
+        // as on start, a variable set for the process wins over the files
+        let config = Config::load(&env).unwrap();
+        assert_eq!(config.model, "shell/model");
+        assert_eq!(config.tab_width, 2);
+
+        // a variable removed from the file is gone, although the file put it
+        // into the process environment on start
+        std::fs::write(&path, "OPENROUTER_MODEL=file/model\n").unwrap();
+        let config = Config::load(&env).unwrap();
+        assert_ne!(config.tab_width, 2);
+    }
+
+    #[test]
+    fn test_project_env_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("app/src");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::create_dir(dir.path().join(".redai")).unwrap();
+        assert!(project_env_files(&nested).is_empty());
+
+        let redai = dir.path().join(".redai/env");
+        let env = dir.path().join("app/.env");
+        std::fs::write(&redai, "OPENROUTER_MODEL=local/notes\n").unwrap();
+        std::fs::write(&env, "OPENROUTER_MODEL=project/model\nREDAI_TAB_WIDTH=2\n").unwrap();
+        let files = project_env_files(&nested);
+        assert_eq!(files, vec![redai, env]);
+
+        // the project's redai file picks the model, the rest still applies
+        let env = ConfigEnv {
+            files,
+            ..ConfigEnv::default()
+        };
+        let config = Config::load(&env).unwrap();
+        assert_eq!(config.model, "local/notes");
+        assert_eq!(config.tab_width, 2);
+    }
+
+    #[test]
+    fn test_rotate_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("redai.log");
+
+        std::fs::write(&path, "small").unwrap();
+        rotate_log(&path).unwrap();
+        assert!(path.exists());
+
+        std::fs::write(&path, vec![b'x'; LOG_MAX_BYTES as usize + 1]).unwrap();
+        rotate_log(&path).unwrap();
+        assert!(!path.exists());
+        assert!(dir.path().join("redai.log.1").exists());
+    }
 }
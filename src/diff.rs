@@ -1,9 +1,45 @@
 use similar::{Algorithm, ChangeTag, TextDiff};
+use std::time::Duration;
 use unicode_segmentation::UnicodeSegmentation;
 
+/// Editor edit: `start` is a char offset unless produced by
+/// [`compute_text_edits_bytes`], which uses byte offsets
 pub use ratatui_code_editor::code::{Edit, Operation};
 
+/// Edits turning `old` into `new`. `Edit::start` is a char offset, as the
+/// editor buffer uses; the edits are sequential and must be applied in order
 pub fn compute_text_edits(old: &str, new: &str) -> Vec<Edit> {
+    compute_edits_with(old, new, |g| g.chars().count())
+}
+
+/// Byte lengths of the longest common prefix and, in what is left, suffix
+/// of `a` and `b`, on char boundaries
+fn common_affixes(a: &str, b: &str) -> (usize, usize) {
+    let prefix: usize = a
+        .chars()
+        .zip(b.chars())
+        .take_while(|(x, y)| x == y)
+        .map(|(x, _)| x.len_utf8())
+        .sum();
+    let suffix = a[prefix..]
+        .chars()
+        .rev()
+        .zip(b[prefix..].chars().rev())
+        .take_while(|(x, y)| x == y)
+        .map(|(x, _)| x.len_utf8())
+        .sum();
+    (prefix, suffix)
+}
+
+/// Same as [`compute_text_edits`] but `Edit::start` is a byte offset, for
+/// consumers like LSP clients or patch tools that address text in bytes
+#[allow(dead_code)]
+pub fn compute_text_edits_bytes(old: &str, new: &str) -> Vec<Edit> {
+    compute_edits_with(old, new, str::len)
+}
+
+/// Grapheme diff of `old` and `new`, measuring offsets with `len`
+fn compute_edits_with(old: &str, new: &str, len: impl Fn(&str) -> usize) -> Vec<Edit> {
     // Split into graphemes
     let old_gr: Vec<&str> = old.graphemes(true).collect();
     let new_gr: Vec<&str> = new.graphemes(true).collect();
@@ -12,36 +48,55 @@ pub fn compute_text_edits(old: &str, new: &str) -> Vec<Edit> {
     let changes = similar::utils::diff_slices(Algorithm::Myers, &old_gr, &new_gr);
 
     let mut edits = Vec::new();
-    let mut utf16_offset = 0usize; // position in UTF-16 units
+    let mut offset = 0usize;
 
     for (tag, slice) in changes {
         match tag {
             ChangeTag::Equal => {
-                // Calculate UTF-16 length of each grapheme
                 for g in slice {
-                    utf16_offset += g.encode_utf16().count();
+                    offset += len(g);
                 }
             }
             ChangeTag::Delete => {
                 // Combine graphemes into text
                 let text = slice.concat();
                 edits.push(Edit {
-                    start: utf16_offset,
+                    start: offset,
                     text: text.clone(),
                     operation: Operation::Remove,
                 });
                 // Deletion does not move offset forward
             }
             ChangeTag::Insert => {
-                let text = slice.concat();
-                edits.push(Edit {
-                    start: utf16_offset,
-                    text: text.clone(),
-                    operation: Operation::Insert,
-                });
+                let mut text = slice.concat();
+                let mut start = offset;
+                // a replaced grapheme often differs only in part, e.g. `\r\n`
+                // becoming `\n`; keep only that part so converting line
+                // endings edits one char per line
+                if let Some(removed) = edits.last_mut()
+                    && removed.operation == Operation::Remove
+                    && removed.start == offset
+                {
+                    let (prefix, suffix) = common_affixes(&removed.text, &text);
+                    let shift = len(&text[..prefix]);
+                    removed.text = removed.text[prefix..removed.text.len() - suffix].to_string();
+                    removed.start += shift;
+                    text = text[prefix..text.len() - suffix].to_string();
+                    start += shift;
+                    if removed.text.is_empty() {
+                        edits.pop();
+                    }
+                }
+                if !text.is_empty() {
+                    edits.push(Edit {
+                        start,
+                        text,
+                        operation: Operation::Insert,
+                    });
+                }
                 // Insertion moves offset forward
                 for g in slice {
-                    utf16_offset += g.encode_utf16().count();
+                    offset += len(g);
                 }
             }
         }
@@ -184,6 +239,67 @@ pub fn diff_without_unchanged(old: &str, new: &str) -> String {
     result
 }
 
+/// A run of changed lines: rows `start..end` of the new text, empty for a
+/// pure deletion, replacing `removed` lines of the old text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineChange {
+    pub start: usize,
+    pub end: usize,
+    pub removed: usize,
+}
+
+impl LineChange {
+    /// Lines touched by this change on either side
+    pub fn len(&self) -> usize {
+        (self.end - self.start).max(self.removed)
+    }
+}
+
+/// How long [`changed_lines`] searches for the smallest diff; past it the
+/// remaining lines are reported as one coarser change
+const CHANGED_LINES_TIMEOUT: Duration = Duration::from_millis(20);
+
+/// Line-level changes between `old` and `new`, adjacent deletions and
+/// insertions merged into one change
+pub fn changed_lines(old: &str, new: &str) -> Vec<LineChange> {
+    let diff = TextDiff::configure()
+        .algorithm(similar::Algorithm::Myers)
+        .timeout(CHANGED_LINES_TIMEOUT)
+        .diff_lines(old, new);
+
+    let mut changes: Vec<LineChange> = Vec::new();
+    let mut row = 0;
+    let mut in_change = false;
+
+    for change in diff.iter_all_changes() {
+        match change.tag() {
+            ChangeTag::Equal => {
+                row += 1;
+                in_change = false;
+            }
+            tag => {
+                if !in_change {
+                    changes.push(LineChange {
+                        start: row,
+                        end: row,
+                        removed: 0,
+                    });
+                    in_change = true;
+                }
+                let last = changes.last_mut().unwrap();
+                if tag == ChangeTag::Insert {
+                    row += 1;
+                    last.end = row;
+                } else {
+                    last.removed += 1;
+                }
+            }
+        }
+    }
+
+    changes
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -258,6 +374,41 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_compute_edits_bytes() {
+        let before = "// значение 🦀\nlet x = 1;";
+        let after = "// значение 🦀\nlet y = 1;";
+
+        let remove = |start| Edit {
+            start,
+            text: "x".to_string(),
+            operation: Operation::Remove,
+        };
+        assert_eq!(compute_text_edits(before, after)[0], remove(18));
+        assert_eq!(compute_text_edits_bytes(before, after)[0], remove(29));
+        assert_eq!(&before[29..30], "x");
+    }
+
+    #[test]
+    fn test_compute_edits_line_endings() {
+        // one char per converted line, not the whole `\r\n` grapheme
+        let edits = compute_text_edits("a\r\nb\nc\r\n", "a\nb\nc\n");
+        let remove = |start| Edit {
+            start,
+            text: "\r".to_string(),
+            operation: Operation::Remove,
+        };
+        assert_eq!(edits, vec![remove(1), remove(5)]);
+
+        let edits = compute_text_edits("a\nb\r\n", "a\r\nb\r\n");
+        let insert = Edit {
+            start: 1,
+            text: "\r".to_string(),
+            operation: Operation::Insert,
+        };
+        assert_eq!(edits, vec![insert]);
+    }
+
     #[test]
     fn test_compute_edits_complex() {
         let before = "main rust here";
@@ -265,7 +416,7 @@ mod tests {
 
         let edits = compute_text_edits(before, after);
 
-        // `start` offsets are in UTF-16 code units (ratatui_code_editor contract).
+        // `start` offsets are in chars, as ratatui_code_editor expects
         assert_eq!(
             edits,
             vec![
@@ -388,4 +539,48 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_changed_lines() {
+        let old = "a\nb\nc\nd\ne\n";
+        let new = "a\nB\nc\ne\nf\ng\n";
+
+        let changes = changed_lines(old, new);
+        assert_eq!(
+            changes,
+            vec![
+                LineChange {
+                    start: 1,
+                    end: 2,
+                    removed: 1
+                },
+                LineChange {
+                    start: 3,
+                    end: 3,
+                    removed: 1
+                },
+                LineChange {
+                    start: 4,
+                    end: 6,
+                    removed: 0
+                },
+            ]
+        );
+        assert_eq!(changes.iter().map(LineChange::len).sum::<usize>(), 4);
+        assert!(changed_lines(old, old).is_empty());
+    }
+
+    #[test]
+    fn test_changed_lines_rewrite_is_bounded() {
+        // untimed, Myers takes seconds once every line differs
+        let old: String = (0..40_000)
+            .map(|i| format!("let value = compute(value, {i});\n"))
+            .collect();
+        let new = old.replace("value", "amount");
+        let start = std::time::Instant::now();
+        let changes = changed_lines(&old, &new);
+        assert!(start.elapsed() < Duration::from_secs(3));
+        assert_eq!(changes.iter().map(|c| c.removed).sum::<usize>(), 40_000);
+        assert_eq!(changes.iter().map(LineChange::len).sum::<usize>(), 40_000);
+    }
 }
@@ -0,0 +1,307 @@
+use anyhow::Result;
+use crossterm::event::{Event, EventStream, KeyCode, KeyEvent, KeyModifiers, MouseEventKind};
+use ratatui::layout::{Constraint, Direction, Layout};
+use ratatui::style::{Color, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::Paragraph;
+use ratatui::{DefaultTerminal, Frame};
+use similar::{ChangeTag, TextDiff};
+use std::str::FromStr;
+use tokio_stream::StreamExt;
+
+use crate::editor::{COLOR_DELETE, COLOR_INSERT};
+
+/// Unchanged lines shown around each hunk
+const CONTEXT_LINES: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RowKind {
+    Header,
+    Equal,
+    Insert,
+    Delete,
+}
+
+/// One rendered row with its line numbers in the old and new text
+#[derive(Debug, Clone)]
+struct Row {
+    kind: RowKind,
+    old: Option<usize>,
+    new: Option<usize>,
+    text: String,
+}
+
+/// Read-only inline diff of two texts for `redai --diff` and save
+/// conflicts, navigable by hunk
+pub struct DiffView {
+    title: String,
+    rows: Vec<Row>,
+    /// Row of each hunk header
+    hunks: Vec<usize>,
+    scroll: usize,
+    /// Rows that fit on screen, updated on render
+    height: usize,
+    quit: bool,
+}
+
+impl DiffView {
+    pub fn new(old_name: &str, old: &str, new_name: &str, new: &str) -> Self {
+        let diff = TextDiff::from_lines(old, new);
+        let mut rows = Vec::new();
+        let mut hunks = Vec::new();
+
+        for group in diff.grouped_ops(CONTEXT_LINES) {
+            let (Some(first), Some(last)) = (group.first(), group.last()) else {
+                continue;
+            };
+            let old_range = first.old_range().start..last.old_range().end;
+            let new_range = first.new_range().start..last.new_range().end;
+            hunks.push(rows.len());
+            rows.push(Row {
+                kind: RowKind::Header,
+                old: None,
+                new: None,
+                text: format!(
+                    "@@ -{},{} +{},{} @@",
+                    old_range.start + 1,
+                    old_range.len(),
+                    new_range.start + 1,
+                    new_range.len()
+                ),
+            });
+
+            for op in &group {
+                for change in diff.iter_changes(op) {
+                    let kind = match change.tag() {
+                        ChangeTag::Equal => RowKind::Equal,
+                        ChangeTag::Insert => RowKind::Insert,
+                        ChangeTag::Delete => RowKind::Delete,
+                    };
+                    let text = change.value().trim_end_matches(['\n', '\r']);
+                    rows.push(Row {
+                        kind,
+                        old: change.old_index().map(|i| i + 1),
+                        new: change.new_index().map(|i| i + 1),
+                        text: text.replace('\t', "    "),
+                    });
+                }
+            }
+        }
+
+        Self {
+            title: format!("{old_name} → {new_name}"),
+            rows,
+            hunks,
+            scroll: 0,
+            height: 1,
+            quit: false,
+        }
+    }
+
+    pub async fn run(mut self, mut terminal: DefaultTerminal) -> Result<()> {
+        let mut events = EventStream::new();
+        terminal.draw(|frame| self.render(frame))?;
+
+        while !self.quit {
+            match events.next().await {
+                Some(Ok(event)) => self.handle_event(&event),
+                Some(Err(err)) => return Err(err.into()),
+                None => break,
+            }
+            terminal.draw(|frame| self.render(frame))?;
+        }
+        Ok(())
+    }
+
+    pub fn handle_event(&mut self, event: &Event) {
+        match event {
+            Event::Key(key) => self.handle_key(key),
+            Event::Mouse(mouse) => match mouse.kind {
+                MouseEventKind::ScrollUp => self.scroll_by(-3),
+                MouseEventKind::ScrollDown => self.scroll_by(3),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    /// Whether `q` or `Esc` closed the view
+    pub fn closed(&self) -> bool {
+        self.quit
+    }
+
+    fn handle_key(&mut self, key: &KeyEvent) {
+        let page = self.height.max(1) as isize;
+        let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => self.quit = true,
+            KeyCode::Up | KeyCode::Char('k') => self.scroll_by(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.scroll_by(1),
+            KeyCode::PageUp => self.scroll_by(-page),
+            KeyCode::PageDown | KeyCode::Char(' ') => self.scroll_by(page),
+            KeyCode::Home | KeyCode::Char('g') => self.scroll = 0,
+            KeyCode::End | KeyCode::Char('G') => self.scroll_by(isize::MAX),
+            KeyCode::Char('n') => self.jump_to_hunk(false),
+            KeyCode::Char('N') | KeyCode::Char('p') => self.jump_to_hunk(true),
+            KeyCode::F(7) => self.jump_to_hunk(shift),
+            _ => {}
+        }
+    }
+
+    fn max_scroll(&self) -> usize {
+        self.rows.len().saturating_sub(self.height)
+    }
+
+    fn scroll_by(&mut self, delta: isize) {
+        self.scroll = self
+            .scroll
+            .saturating_add_signed(delta)
+            .min(self.max_scroll());
+    }
+
+    /// Scrolls the next (or previous) hunk header to the top, stopping at
+    /// the first and last hunk
+    fn jump_to_hunk(&mut self, backwards: bool) {
+        let target = if backwards {
+            self.hunks.iter().rev().find(|&&row| row < self.scroll)
+        } else {
+            self.hunks.iter().find(|&&row| row > self.scroll)
+        };
+        if let Some(&row) = target {
+            self.scroll = row.min(self.max_scroll());
+        }
+    }
+
+    /// 1-based index of the last hunk that starts at or above the top row
+    fn current_hunk(&self) -> usize {
+        self.hunks.iter().filter(|&&row| row <= self.scroll).count()
+    }
+
+    pub fn render(&mut self, frame: &mut Frame) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(1)])
+            .split(frame.area());
+        self.height = chunks[0].height as usize;
+        self.scroll = self.scroll.min(self.max_scroll());
+
+        let insert = Color::from_str(COLOR_INSERT).unwrap_or(Color::Green);
+        let delete = Color::from_str(COLOR_DELETE).unwrap_or(Color::Red);
+        let digits = self
+            .rows
+            .iter()
+            .filter_map(|row| row.old.max(row.new))
+            .max()
+            .unwrap_or(1)
+            .to_string()
+            .len();
+        let number = |n: Option<usize>| match n {
+            Some(n) => format!("{n:>digits$}"),
+            None => " ".repeat(digits),
+        };
+
+        let lines: Vec<Line> = self
+            .rows
+            .iter()
+            .skip(self.scroll)
+            .take(self.height)
+            .map(|row| {
+                let (sign, style) = match row.kind {
+                    RowKind::Header => {
+                        return Line::styled(row.text.clone(), Style::default().fg(Color::Cyan));
+                    }
+                    RowKind::Equal => (' ', Style::default()),
+                    RowKind::Insert => ('+', Style::default().fg(insert)),
+                    RowKind::Delete => ('-', Style::default().fg(delete)),
+                };
+                let gutter = format!("{} {} ", number(row.old), number(row.new));
+                Line::from(vec![
+                    Span::styled(gutter, Style::default().fg(Color::DarkGray)),
+                    Span::styled(format!("{sign} {}", row.text), style),
+                ])
+            })
+            .collect();
+        frame.render_widget(Paragraph::new(lines), chunks[0]);
+
+        let status = if self.hunks.is_empty() {
+            format!(" {}  no differences  q: quit", self.title)
+        } else {
+            format!(
+                " {}  hunk {}/{}  n/N: next/previous hunk  q: quit",
+                self.title,
+                self.current_hunk().max(1),
+                self.hunks.len()
+            )
+        };
+        let status = Paragraph::new(status).style(Style::default().fg(Color::DarkGray));
+        frame.render_widget(status, chunks[1]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode) -> Event {
+        Event::Key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    #[test]
+    fn test_hunks() {
+        let old: String = (1..=20).map(|i| format!("line {i}\n")).collect();
+        let new = old
+            .replace("line 2\n", "line two\n")
+            .replace("line 18\n", "");
+
+        let view = DiffView::new("a.txt", &old, "b.txt", &new);
+        assert_eq!(view.hunks.len(), 2);
+        assert_eq!(view.rows[0].text, "@@ -1,5 +1,5 @@");
+
+        let changed: Vec<(RowKind, Option<usize>, Option<usize>)> = view
+            .rows
+            .iter()
+            .filter(|row| matches!(row.kind, RowKind::Insert | RowKind::Delete))
+            .map(|row| (row.kind, row.old, row.new))
+            .collect();
+        assert_eq!(
+            changed,
+            vec![
+                (RowKind::Delete, Some(2), None),
+                (RowKind::Insert, None, Some(2)),
+                (RowKind::Delete, Some(18), None),
+            ]
+        );
+
+        let same = DiffView::new("a.txt", &old, "b.txt", &old);
+        assert!(same.hunks.is_empty() && same.rows.is_empty());
+    }
+
+    #[test]
+    fn test_hunk_navigation() {
+        let old: String = (1..=40).map(|i| format!("line {i}\n")).collect();
+        let new = old
+            .replace("line 5\n", "")
+            .replace("line 20\n", "")
+            .replace("line 35\n", "");
+        let mut view = DiffView::new("a", &old, "b", &new);
+        view.height = 5;
+        let hunks = view.hunks.clone();
+        assert_eq!(hunks.len(), 3);
+
+        view.handle_event(&key(KeyCode::Char('n')));
+        assert_eq!(view.scroll, hunks[1]);
+        assert_eq!(view.current_hunk(), 2);
+        view.handle_event(&key(KeyCode::Char('n')));
+        assert_eq!(view.scroll, hunks[2]);
+        view.handle_event(&key(KeyCode::Char('N')));
+        assert_eq!(view.scroll, hunks[1]);
+
+        view.handle_event(&key(KeyCode::End));
+        assert_eq!(view.scroll, view.max_scroll());
+        view.handle_event(&key(KeyCode::Home));
+        assert_eq!(view.scroll, 0);
+        view.handle_event(&key(KeyCode::Char('q')));
+        assert!(view.quit);
+    }
+}
@@ -1,27 +1,163 @@
 use anyhow::Result;
-use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers, MouseEventKind};
 use ratatui::Frame;
 use ratatui::layout::{Position, Rect};
 use ratatui::style::{Color, Style};
 use ratatui::widgets::{Paragraph, Wrap};
-use ratatui_code_editor::code::{Edit, EditBatch, EditState};
+use ratatui_code_editor::code::{Edit, EditBatch, EditState, Operation};
 use ratatui_code_editor::editor::Editor as CodeEditor;
 use ratatui_code_editor::selection::Selection;
 use ratatui_code_editor::utils::get_lang;
-use std::collections::HashMap;
-use std::path::PathBuf;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 use tokio::sync::Mutex;
 use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
 
-use crate::coder::Coder;
+use crate::coder::{Candidate, Coder, ContextStrategy, PinnedRegion};
+use crate::config::LanguageRules;
 use crate::diff::*;
-use crate::llm::LlmClient;
+use crate::encoding::{self, Encoding};
+use crate::error::CoderResult;
+use crate::gotofile::path_token;
+use crate::history::HistoryEntry;
+use crate::keymap::KeyBinding;
+use crate::llm::{ChatBackend, LlmClient};
+use crate::scrollbar;
 use crate::search::SearchMode;
+use crate::tabs::{expand_tabs, show_whitespace, visible_cursor, widget_column};
+use crate::tracker::RecentEdit;
+use crate::utils::{detect_indent, line_endings, reindent, same_path};
+use crate::words::WordBoundary;
 
-const COLOR_INSERT: &str = "#02a365";
-const COLOR_DELETE: &str = "#f6c99f";
+pub const COLOR_INSERT: &str = "#02a365";
+pub const COLOR_DELETE: &str = "#f6c99f";
+
+/// How long the change jumped to stays highlighted
+const FLASH_DURATION: Duration = Duration::from_millis(800);
+
+/// Columns `Alt+Shift+Left`/`Alt+Shift+Right` pan the view by
+const PAN_COLUMNS: isize = 10;
+
+/// Frames of the status bar spinner while an AI request runs, one per tick
+const SPINNER: [char; 8] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧'];
+const SPINNER_FRAME: Duration = Duration::from_millis(250);
+
+/// Inactive buffers that keep their highlight cache; older ones drop it and
+/// recompute highlights on demand when shown again
+const HIGHLIGHTED_INACTIVE_BUFFERS: usize = 3;
+
+/// Languages the editor can highlight, and `text` for none
+pub const LANGUAGES: &[&str] = &[
+    "c",
+    "c_sharp",
+    "cpp",
+    "css",
+    "go",
+    "html",
+    "java",
+    "javascript",
+    "json",
+    "markdown",
+    "python",
+    "rust",
+    "shell",
+    "text",
+    "toml",
+    "typescript",
+    "yaml",
+];
+
+/// Text columns the editor needs beside the line numbers to scroll sideways
+const MIN_TEXT_WIDTH: u16 = 10;
+
+const LARGE_FILE_BYTES: usize = 2 * 1024 * 1024;
+const LARGE_FILE_LINES: usize = 50_000;
+
+/// Size and line count of a file's content
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FileStats {
+    pub bytes: usize,
+    pub lines: usize,
+}
+
+impl FileStats {
+    pub fn of(content: &str) -> Self {
+        Self {
+            bytes: content.len(),
+            lines: content.lines().count(),
+        }
+    }
+
+    /// Large files are opened without syntax highlighting to keep the UI responsive
+    pub fn is_large(&self) -> bool {
+        self.bytes > LARGE_FILE_BYTES || self.lines > LARGE_FILE_LINES
+    }
+}
+
+impl std::fmt::Display for FileStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mb = self.bytes as f64 / (1024.0 * 1024.0);
+        write!(f, "{:.1} MB, {} lines", mb, self.lines)
+    }
+}
+
+/// Modification time and size of a file, which change with its content
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct DiskState {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+impl DiskState {
+    /// None while the file does not exist
+    fn of(filename: &str) -> Option<Self> {
+        let metadata = std::fs::metadata(filename).ok()?;
+        Some(Self {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+        })
+    }
+}
+
+/// An [`Edit`] as external tools write it, e.g.
+/// `{"start": 12, "text": "x", "operation": "insert"}` with `start` a
+/// char offset into the content left by the edits before it
+#[derive(Deserialize)]
+struct JsonEdit {
+    start: usize,
+    text: String,
+    operation: JsonOperation,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum JsonOperation {
+    Insert,
+    Remove,
+}
+
+/// Where a pane not being edited is in the buffer
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct PaneView {
+    /// Line and char column of the cursor, which stay put while text is
+    /// edited elsewhere in the other pane
+    point: (usize, usize),
+    offsets: (usize, usize),
+}
+
+/// The current file shown a second time, with its own cursor and scroll
+/// over the same buffer, so edits in one pane show in the other
+struct SplitView {
+    other: PaneView,
+    /// Area of the pane not being edited; `EditorPanel::area` is the other
+    area: Rect,
+    /// Whether the pane being edited is the upper one
+    upper_focused: bool,
+}
 
 #[derive(Clone, Debug)]
 pub struct Fallback {
@@ -37,13 +173,43 @@ pub enum EditorAction {
     Quit,
     ActivateSearch(SearchMode),
     Save,
+    /// A marked AI edit was accepted and stays in the buffer
+    AcceptedAiEdit,
 }
 
+/// How [`EditorPanel::insert_file_path`] spells the current file
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PathForm {
+    /// Relative to the workspace root, as shown in the tree and search
+    Relative,
+    Absolute,
+    /// File name without extension, e.g. for module names
+    Stem,
+}
+
+/// What a request asks for besides completing at the cursor or rewriting
+/// the selection
+enum RequestKind {
+    Instruct(String),
+    Format,
+    /// A commit message for these changes, or the session's when empty
+    CommitMessage(String),
+}
+
+/// A finished autocomplete request, tagged with its version
+pub type AutocompleteResult = (u64, CoderResult<Vec<Candidate>>);
+
 pub struct Autocomplete {
     coder: Arc<Mutex<Coder>>,
     handle: Option<JoinHandle<()>>,
-    tx: mpsc::Sender<Result<Vec<Edit>>>,
-    rx: mpsc::Receiver<Result<Vec<Edit>>>,
+    tx: mpsc::Sender<AutocompleteResult>,
+    rx: mpsc::Receiver<AutocompleteResult>,
+    /// Bumped for every request, so results of earlier ones can be told apart
+    version: u64,
+    /// File, cursor and content hash the latest request was made for
+    requested_for: Option<(String, usize, u64)>,
+    /// When the running request was made, for the spinner
+    started: Option<Instant>,
 }
 
 pub struct EditorPanel {
@@ -51,9 +217,76 @@ pub struct EditorPanel {
     pub area: Rect,
     pub filename: String,
     pub opened: HashMap<String, CodeEditor>,
+    /// Keys of `opened`, most recently active first
+    recent: Vec<String>,
+    /// Inactive buffers whose highlight cache was dropped since last shown
+    evicted: HashSet<String>,
     pub fallback: Option<Fallback>,
     pub autocomplete: Autocomplete,
     pub self_update: bool,
+    pub autocomplete_key: KeyBinding,
+    /// Key that asks for an instruction to change the selection with
+    pub instruct_key: KeyBinding,
+    /// Files the autocomplete key works in
+    pub autocomplete_languages: LanguageRules,
+    pub tab_width: usize,
+    /// Marks spaces with `·` and tabs with `→`, trailing ones highlighted
+    pub show_whitespace: bool,
+    /// Whether multi-line pastes take the indentation of the cursor's line
+    pub reindent_paste: bool,
+    /// Whether Enter carries the current indentation over to the new line
+    pub auto_indent: bool,
+    /// Extra context from other files sent with completions
+    pub context: ContextStrategy,
+    /// Multi-line completions that end a line leave the cursor on a new line
+    pub completion_newline: bool,
+    /// Model answering AI requests, shown in the status bar; none with AI off
+    pub model: Option<String>,
+    /// Asks the model once more when its patch cannot be parsed
+    pub patch_retry: bool,
+    /// Completions asked for per request, cycled through while one is marked
+    pub completion_candidates: usize,
+    /// Candidates of the last AI result and the one applied
+    candidates: Vec<Candidate>,
+    candidate: usize,
+    /// Where the coder dumps the last LLM exchange, when enabled
+    pub llm_debug_file: Option<PathBuf>,
+    /// Replaces the built-in system prompt of completions
+    pub system_prompt: Option<String>,
+    /// Strips trailing whitespace from every line on save
+    pub trim_trailing_whitespace: bool,
+    /// Ends the file with exactly one newline on save
+    pub final_newline: bool,
+    /// AI edits deleting more lines than this wait for confirmation, 0 never
+    pub confirm_delete_lines: usize,
+    /// AI edits held until Tab or Enter confirms them
+    pending_edits: Option<Vec<Edit>>,
+    /// Whether files are opened with syntax highlighting
+    highlight: bool,
+    /// Encodings open files were read with, written back on save
+    encodings: HashMap<String, Encoding>,
+    /// State of open files on disk when last read or written, to notice
+    /// changes made behind the buffer's back before saving over them
+    disk_states: HashMap<String, Option<DiskState>>,
+    /// Saving over a file changed on disk since it was read waits for a
+    /// confirmation instead
+    pub confirm_overwrite: bool,
+    /// Files are never written, AI edits can only be looked at
+    pub no_write: bool,
+    /// File names differing only in case name the same buffer
+    pub ignore_path_case: bool,
+    /// Languages picked by hand for files whose name misleads `get_lang`
+    languages: HashMap<String, String>,
+    /// Content of the current file when it was opened, and its stats
+    baseline: Option<(String, FileStats)>,
+    /// Line changes of the current file since it was opened
+    changes: Vec<LineChange>,
+    /// Rows highlighted after jumping to a change, until the deadline
+    flash: Option<(usize, usize, Instant)>,
+    /// Second pane on the current file, while the editor is split
+    split: Option<SplitView>,
+    /// Where `Ctrl+Left`/`Ctrl+Right` stop
+    pub word_boundary: WordBoundary,
 }
 
 impl EditorPanel {
@@ -62,11 +295,17 @@ impl EditorPanel {
         content: &str,
         filename: &str,
         llm_client: Option<LlmClient>,
+        highlight: bool,
     ) -> Result<Self> {
         let theme = ratatui_code_editor::theme::vesper();
+        let language = if !highlight || FileStats::of(content).is_large() {
+            "text"
+        } else {
+            language
+        };
         let editor = CodeEditor::new(language, content, theme)?;
 
-        let mut coder = Coder::new(llm_client);
+        let mut coder = Coder::new(llm_client.map(|llm| Box::new(llm) as Box<dyn ChatBackend>));
         coder.update(&PathBuf::from(filename), content);
         let (tx, rx) = mpsc::channel(1);
 
@@ -75,38 +314,472 @@ impl EditorPanel {
             area: Rect::default(),
             filename: filename.to_string(),
             opened: HashMap::new(),
+            recent: Vec::new(),
+            evicted: HashSet::new(),
             fallback: None,
             autocomplete: Autocomplete {
                 coder: Arc::new(Mutex::new(coder)),
                 handle: None,
                 tx,
                 rx,
+                version: 0,
+                requested_for: None,
+                started: None,
             },
             self_update: false,
+            autocomplete_key: KeyBinding::new(KeyCode::Char(' '), KeyModifiers::CONTROL),
+            instruct_key: KeyBinding::new(KeyCode::Char('i'), KeyModifiers::ALT),
+            autocomplete_languages: LanguageRules::default(),
+            tab_width: 4,
+            show_whitespace: false,
+            reindent_paste: false,
+            auto_indent: true,
+            context: ContextStrategy::default(),
+            completion_newline: false,
+            model: None,
+            patch_retry: true,
+            completion_candidates: 1,
+            candidates: Vec::new(),
+            candidate: 0,
+            llm_debug_file: None,
+            system_prompt: None,
+            trim_trailing_whitespace: false,
+            final_newline: false,
+            confirm_delete_lines: 0,
+            pending_edits: None,
+            highlight,
+            encodings: HashMap::new(),
+            disk_states: HashMap::new(),
+            confirm_overwrite: true,
+            no_write: false,
+            languages: HashMap::new(),
+            ignore_path_case: false,
+            baseline: Some((content.to_string(), FileStats::of(content))),
+            changes: Vec::new(),
+            flash: None,
+            split: None,
+            word_boundary: WordBoundary::default(),
         })
     }
 
-    pub fn render(&self, frame: &mut Frame) {
+    pub fn render(&mut self, frame: &mut Frame) {
         if self.filename.is_empty() {
             let welcome = Paragraph::new(" Welcome to redai!")
                 .style(Style::default().fg(Color::Reset))
                 .wrap(Wrap { trim: false });
             frame.render_widget(welcome, self.area);
         } else {
-            frame.render_widget(&self.editor, self.area);
-            if let Some((x, y)) = self.editor.get_visible_cursor(&self.area) {
+            self.render_other_pane(frame);
+            self.render_text(frame, self.area);
+            self.render_flash(frame);
+            if let Some((x, y)) = visible_cursor(&self.editor, self.area, self.tab_width) {
                 frame.set_cursor_position(Position::new(x, y));
             }
         }
     }
 
+    fn render_text(&self, frame: &mut Frame, area: Rect) {
+        frame.render_widget(&self.editor, area);
+        expand_tabs(&self.editor, frame.buffer_mut(), area, self.tab_width);
+        if self.show_whitespace {
+            let buf = frame.buffer_mut();
+            show_whitespace(&self.editor, buf, area, self.tab_width, Color::Red);
+        }
+    }
+
+    /// Draws the pane not being edited of a split and the rule between the
+    /// panes, moving the editor to that pane's place meanwhile
+    fn render_other_pane(&mut self, frame: &mut Frame) {
+        let Some(split) = &self.split else {
+            return;
+        };
+        let (other, area) = (split.other, split.area);
+        let rule_y = self.area.bottom().min(area.bottom());
+        let rule = Rect::new(area.x, rule_y, area.width, 1);
+        let rule_text = "─".repeat(area.width as usize);
+        let rule_widget = Paragraph::new(rule_text).style(Style::default().fg(Color::DarkGray));
+        frame.render_widget(rule_widget, rule.intersection(frame.area()));
+
+        let cursor = self.editor.get_cursor();
+        let selection = self.editor.get_selection();
+        let offsets = (self.editor.get_offset_y(), self.editor.get_offset_x());
+        self.show_pane(other);
+        self.render_text(frame, area);
+        self.editor.set_cursor(cursor);
+        self.editor.set_selection(selection);
+        self.editor.set_offset_y(offsets.0);
+        self.editor.set_offset_x(offsets.1);
+    }
+
+    /// Draws where the view is in the file in `area`, the column right of
+    /// the editor, beside each pane of a split
+    pub fn render_scrollbar(&self, frame: &mut Frame, area: Rect) {
+        if self.filename.is_empty() {
+            return;
+        }
+        let lines = self.editor.code_ref().len_lines();
+        let mut panes = vec![(self.area, self.editor.get_offset_y())];
+        if let Some(split) = &self.split {
+            panes.push((split.area, split.other.offsets.0));
+        }
+        for (pane, offset) in panes {
+            let bar = Rect {
+                y: pane.y,
+                height: pane.height,
+                ..area
+            };
+            scrollbar::render(frame.buffer_mut(), bar, lines, offset, pane.height as usize);
+        }
+    }
+
+    /// Lays the panes out in `area`, the whole editor area: the pane being
+    /// edited gets [`Self::area`]
+    pub fn set_area(&mut self, area: Rect) {
+        let Some(split) = &mut self.split else {
+            self.area = area;
+            return;
+        };
+        // one row between the panes for the rule
+        let upper_height = area.height.saturating_sub(1) / 2;
+        let upper = Rect {
+            height: upper_height,
+            ..area
+        };
+        let lower = Rect {
+            y: area.y + upper_height + 1,
+            height: area.height.saturating_sub(upper_height + 1),
+            ..area
+        };
+        (self.area, split.area) = if split.upper_focused {
+            (upper, lower)
+        } else {
+            (lower, upper)
+        };
+    }
+
+    pub fn is_split(&self) -> bool {
+        self.split.is_some()
+    }
+
+    /// Shows the current file in a second pane below, both starting where
+    /// the view is now, or closes the pane not being edited; returns
+    /// whether the editor is split now
+    pub fn toggle_split(&mut self) -> bool {
+        if let Some(split) = self.split.take() {
+            self.set_area(self.area.union(split.area));
+            return false;
+        }
+        if self.filename.is_empty() {
+            return false;
+        }
+        self.split = Some(SplitView {
+            other: self.pane_view(),
+            area: Rect::default(),
+            upper_focused: true,
+        });
+        self.set_area(self.area);
+        true
+    }
+
+    /// Makes the other pane of a split the one being edited
+    pub fn switch_pane(&mut self) {
+        let Some(mut split) = self.split.take() else {
+            return;
+        };
+        let current = self.pane_view();
+        self.show_pane(split.other);
+        split.other = current;
+        std::mem::swap(&mut self.area, &mut split.area);
+        split.upper_focused = !split.upper_focused;
+        self.split = Some(split);
+    }
+
+    fn pane_view(&self) -> PaneView {
+        PaneView {
+            point: self.editor.code_ref().point(self.editor.get_cursor()),
+            offsets: (self.editor.get_offset_y(), self.editor.get_offset_x()),
+        }
+    }
+
+    /// Puts the cursor and scroll of `view` on the editor, its selection
+    /// dropped, keeping them within the text as it is now
+    fn show_pane(&mut self, view: PaneView) {
+        let code = self.editor.code_ref();
+        let last = code.len_lines().saturating_sub(1);
+        let row = view.point.0.min(last);
+        let cursor = code.line_to_char(row) + view.point.1.min(code.line_len(row));
+        self.editor.set_cursor(cursor);
+        self.editor.set_selection(None);
+        self.editor.set_offset_y(view.offsets.0.min(last));
+        self.editor.set_offset_x(view.offsets.1);
+    }
+
+    fn render_flash(&self, frame: &mut Frame) {
+        let Some((start, end, deadline)) = self.flash else {
+            return;
+        };
+        if Instant::now() >= deadline {
+            return;
+        }
+        let offset_y = self.editor.get_offset_y();
+        let buf = frame.buffer_mut();
+        for row in start.max(offset_y)..end {
+            let y = self.area.top() + (row - offset_y) as u16;
+            if y >= self.area.bottom() {
+                break;
+            }
+            for x in self.area.left()..self.area.right() {
+                buf[(x, y)].set_bg(Color::Rgb(0x3a, 0x3a, 0x2a));
+            }
+        }
+    }
+
+    /// The editor area, grown to the least the editor can scroll the cursor
+    /// into view in; a smaller area makes the editor's offset math underflow
+    pub fn focus_area(&self) -> Rect {
+        let digits = self.editor.code_ref().len_lines().max(1).to_string().len();
+        let gutter = digits.max(5) as u16 + 2;
+        Rect {
+            width: self.area.width.max(gutter + MIN_TEXT_WIDTH),
+            height: self.area.height.max(1),
+            ..self.area
+        }
+    }
+
+    /// When the change highlight should disappear
+    pub fn flash_deadline(&self) -> Option<Instant> {
+        self.flash.map(|(_, _, deadline)| deadline)
+    }
+
+    /// Recomputes the changes since open; skipped for large files
+    pub fn refresh_changes(&mut self) {
+        self.changes = match &self.baseline {
+            Some((baseline, stats)) if !stats.is_large() => {
+                changed_lines(baseline, &self.editor.get_content())
+            }
+            _ => Vec::new(),
+        };
+    }
+
+    /// Moves the cursor to the next (or previous) change since open,
+    /// wrapping around; returns its 1-based index and the change count
+    pub fn jump_to_change(&mut self, backwards: bool) -> Option<(usize, usize)> {
+        if self.changes.is_empty() {
+            return None;
+        }
+        let code = self.editor.code_ref();
+        let row = code.point(self.editor.get_cursor()).0;
+        let index = if backwards {
+            self.changes
+                .iter()
+                .rposition(|c| c.start < row)
+                .unwrap_or(self.changes.len() - 1)
+        } else {
+            self.changes.iter().position(|c| c.start > row).unwrap_or(0)
+        };
+
+        let change = &self.changes[index];
+        let start = change.start.min(code.len_lines().saturating_sub(1));
+        let end = change.end.max(start + 1);
+        self.editor.set_cursor(code.line_to_char(start));
+        self.editor.focus(&self.focus_area());
+        self.flash = Some((start, end, Instant::now() + FLASH_DURATION));
+        Some((index + 1, self.changes.len()))
+    }
+
+    /// Inserts the current file's path at the cursor as one undoable edit,
+    /// replacing the selection; returns the inserted text
+    pub fn insert_file_path(&mut self, form: PathForm, roots: &[PathBuf]) -> Option<String> {
+        if self.filename.is_empty() {
+            return None;
+        }
+        let path =
+            std::path::absolute(&self.filename).unwrap_or_else(|_| self.filename.clone().into());
+        let text = match form {
+            PathForm::Relative => crate::utils::display_path(&path, roots),
+            PathForm::Absolute => path.to_string_lossy().to_string(),
+            PathForm::Stem => path.file_stem()?.to_string_lossy().to_string(),
+        };
+
+        if self.editor.has_marks() {
+            self.editor.remove_marks();
+            self.editor.apply(ratatui_code_editor::actions::Undo {});
+        }
+        self.editor
+            .apply(ratatui_code_editor::actions::InsertText { text: text.clone() });
+        self.editor.focus(&self.focus_area());
+        self.refresh_changes();
+        Some(text)
+    }
+
+    /// 1-based first and last line of the selection, or the cursor line
+    pub fn selected_lines(&mut self) -> (usize, usize) {
+        let cursor = self.editor.get_cursor();
+        let (start, end) = match self.editor.get_selection() {
+            Some(selection) if !selection.is_empty() => selection.sorted(),
+            _ => (cursor, cursor),
+        };
+        let code = self.editor.code_ref();
+        let start_row = code.point(start).0;
+        let (mut end_row, end_col) = code.point(end);
+        // a selection ending at column 0 does not include that line
+        if end_col == 0 && end_row > start_row {
+            end_row -= 1;
+        }
+        (start_row + 1, end_row + 1)
+    }
+
+    /// Identifier the cursor is on or right after
+    pub fn word_under_cursor(&self) -> Option<String> {
+        let code = self.editor.code_ref();
+        let cursor = self.editor.get_cursor();
+        let (start, end) = match code.word_boundaries(cursor) {
+            (start, end) if start < end => (start, end),
+            _ => code.word_boundaries(cursor.checked_sub(1)?),
+        };
+        (start < end).then(|| code.slice(start, end))
+    }
+
+    /// The file path written around the cursor and the line it names, if
+    /// any, e.g. in an import or in `src/app.rs:120`
+    pub fn path_under_cursor(&self) -> Option<(String, Option<usize>)> {
+        let code = self.editor.code_ref();
+        let (row, col) = code.point(self.editor.get_cursor());
+        path_token(&code.line(row).to_string(), col)
+    }
+
+    /// Moves the cursor to the start of 0-based `line`, or the last line
+    pub fn goto_line(&mut self, line: usize) {
+        let code = self.editor.code_ref();
+        let line = line.min(code.len_lines().saturating_sub(1));
+        self.editor.set_cursor(code.line_to_char(line));
+        self.editor.focus(&self.focus_area());
+    }
+
+    /// Cursor position shown on the right side of the status bar
+    pub fn status_info(&self) -> String {
+        if self.filename.is_empty() {
+            return String::new();
+        }
+        let (row, col) = self.editor.code_ref().point(self.editor.get_cursor());
+        let mut info = String::new();
+        if self.autocomplete_busy()
+            && let Some(started) = self.autocomplete.started
+        {
+            info.push_str(&busy_label(started.elapsed()));
+        }
+        if self.no_write {
+            info.push_str("no-write  ");
+        }
+        if let Some(model) = &self.model {
+            // the provider prefix of e.g. `mistralai/codestral-2508` is noise
+            let name = model.rsplit('/').next().unwrap_or(model);
+            info.push_str(&format!("{name}  "));
+        }
+        let encoding = self.encoding();
+        if encoding != Encoding::Utf8 {
+            info.push_str(&format!("{encoding}  "));
+        }
+        let changed: usize = self.changes.iter().map(LineChange::len).sum();
+        if changed > 0 {
+            info.push_str(&format!("±{changed}  "));
+        }
+        info.push_str(&format!("{}  ", self.language()));
+        info.push_str(&format!("Ln {}, Col {} ", row + 1, col + 1));
+        info
+    }
+
+    /// Encoding of the current file
+    pub fn encoding(&self) -> Encoding {
+        self.encodings
+            .get(&self.filename)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// `filename` as spelled by the open buffer of the same file, so that
+    /// e.g. `Src/Main.rs` does not open `src/main.rs` a second time when
+    /// `ignore_path_case` is set
+    fn buffer_name(&self, filename: &str) -> String {
+        std::iter::once(&self.filename)
+            .chain(self.opened.keys())
+            .find(|name| !name.is_empty() && same_path(name, filename, self.ignore_path_case))
+            .cloned()
+            .unwrap_or_else(|| filename.to_string())
+    }
+
+    /// Language of the current file, as picked by hand or guessed from its
+    /// name; `text` with highlighting off
+    pub fn language(&self) -> String {
+        if !self.highlight {
+            return "text".to_string();
+        }
+        match self.languages.get(&self.filename) {
+            Some(lang) => lang.clone(),
+            None => match get_lang(&self.filename).as_str() {
+                "unknown" => "shell".to_string(),
+                lang => lang.to_string(),
+            },
+        }
+    }
+
+    /// Highlights the current file as `lang` for the rest of the session,
+    /// whatever its name suggests
+    pub fn set_language(&mut self, lang: &str) -> Result<()> {
+        let lang = lang.trim().to_lowercase();
+        if !LANGUAGES.contains(&lang.as_str()) {
+            anyhow::bail!("unknown language {lang}, one of: {}", LANGUAGES.join(", "));
+        }
+        if self.filename.is_empty() {
+            anyhow::bail!("no file open");
+        }
+        self.pending_edits = None;
+        self.editor.remove_marks();
+        let filename = self.filename.clone();
+        rebuild_editor(&mut self.editor, &filename, self.highlight, Some(&lang))?;
+        self.languages.insert(filename, lang);
+        Ok(())
+    }
+
+    /// Records the encoding `filename` was read with, for saving it back,
+    /// and the state of the file on disk as read
+    pub fn set_encoding(&mut self, filename: &str, encoding: Encoding) {
+        self.encodings.insert(filename.to_string(), encoding);
+        self.disk_states
+            .insert(filename.to_string(), DiskState::of(filename));
+    }
+
+    /// Whether the current file was changed on disk since it was last read
+    /// or saved; a file deleted since then is not, saving brings it back
+    pub fn changed_on_disk(&self) -> bool {
+        let Some(now) = DiskState::of(&self.filename) else {
+            return false;
+        };
+        self.disk_states
+            .get(&self.filename)
+            .is_some_and(|known| *known != Some(now))
+    }
+
     pub fn handle_event(&mut self, event: &Event) -> EditorAction {
-        match event {
-            Event::Paste(paste) => {
-                self.editor.apply(ratatui_code_editor::actions::InsertText {
-                    text: paste.to_string(),
-                });
+        // held edits wait for an answer: Tab or Enter applies them, any
+        // other key, a click or a paste discards them and does nothing else;
+        // mouse moves, scrolling and focus changes leave them waiting
+        let answers = match event {
+            Event::Key(_) | Event::Paste(_) => true,
+            Event::Mouse(mouse) => matches!(mouse.kind, MouseEventKind::Down(_)),
+            _ => false,
+        };
+        if answers && let Some(edits) = self.pending_edits.take() {
+            if let Event::Key(key) = event
+                && matches!(key.code, KeyCode::Tab | KeyCode::Enter)
+            {
+                let _ = self.apply_marked_edits(edits);
             }
+            return EditorAction::None;
+        }
+
+        match event {
+            Event::Paste(paste) => self.paste(paste),
             Event::Key(key) => {
                 if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('f') {
                     return EditorAction::ActivateSearch(SearchMode::Search);
@@ -114,8 +787,28 @@ impl EditorPanel {
                 if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('g') {
                     return EditorAction::ActivateSearch(SearchMode::GlobalSearch);
                 }
+                if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('p') {
+                    return EditorAction::ActivateSearch(SearchMode::Symbols);
+                }
+                if let Some(delta) = self.scroll_delta(key) {
+                    self.scroll_viewport(delta);
+                    return EditorAction::None;
+                }
+                if key.modifiers == KeyModifiers::ALT | KeyModifiers::SHIFT
+                    && matches!(key.code, KeyCode::Left | KeyCode::Right)
+                {
+                    let delta = if key.code == KeyCode::Left {
+                        -PAN_COLUMNS
+                    } else {
+                        PAN_COLUMNS
+                    };
+                    self.pan_viewport(delta);
+                    return EditorAction::None;
+                }
 
-                self.autocomplete.handle.take().map(|h| h.abort());
+                if let Some(handle) = self.autocomplete.handle.take() {
+                    handle.abort();
+                }
                 let has_marks = self.editor.has_marks();
 
                 if key.code == KeyCode::Esc && has_marks {
@@ -123,8 +816,10 @@ impl EditorPanel {
                     self.editor.apply(ratatui_code_editor::actions::Undo {});
                 } else if is_quit_pressed(*key) {
                     return EditorAction::Quit;
-                } else if is_autocomplete_pressed(*key) {
-                    self.spawn_autocomplete();
+                } else if self.autocomplete_key.matches(key) {
+                    if self.autocomplete_enabled() {
+                        self.spawn_autocomplete();
+                    }
                 } else if is_save_pressed(*key) {
                     return EditorAction::Save;
                 } else {
@@ -132,211 +827,1246 @@ impl EditorPanel {
                     if has_marks {
                         if accepted {
                             self.editor.remove_marks();
+                            self.refresh_changes();
+                            // Alt+Enter goes on with a completion at the new
+                            // cursor; each further step takes another press
+                            let more =
+                                key.code == KeyCode::Enter && key.modifiers == KeyModifiers::ALT;
+                            if more && self.autocomplete_enabled() {
+                                self.spawn_autocomplete();
+                            }
+                            return EditorAction::AcceptedAiEdit;
                         } else {
                             self.editor.remove_marks();
                             self.editor.apply(ratatui_code_editor::actions::Undo {});
-                            let _ = self.editor.input(*key, &self.area);
+                            let _ = self.editor.input(*key, &self.focus_area());
                         }
-                    } else {
-                        let _ = self.editor.input(*key, &self.area);
+                    } else if matches!(key.code, KeyCode::Tab | KeyCode::BackTab)
+                        && self.is_multiline_selection()
+                    {
+                        self.indent_selection(key.code == KeyCode::BackTab);
+                    } else if key.code == KeyCode::Enter && key.modifiers.is_empty() {
+                        self.insert_newline();
+                    } else if !self.move_word(key) {
+                        let _ = self.editor.input(*key, &self.focus_area());
                     }
                 }
             }
             Event::Mouse(mouse) => {
-                let _ = self.editor.mouse(*mouse, &self.area);
+                // a click or scroll in the other pane of a split edits there
+                if let Some(split) = &self.split
+                    && split.area.contains(Position::new(mouse.column, mouse.row))
+                {
+                    self.switch_pane();
+                }
+                let mut mouse = *mouse;
+                mouse.column = widget_column(
+                    &self.editor,
+                    mouse.column,
+                    mouse.row,
+                    self.area,
+                    self.tab_width,
+                );
+                let _ = self.editor.mouse(mouse, &self.area);
             }
             _ => {}
         }
+        if matches!(event, Event::Key(_) | Event::Paste(_)) {
+            self.refresh_changes();
+        }
         EditorAction::None
     }
 
-    pub fn spawn_autocomplete(&mut self) {
-        let tx = self.autocomplete.tx.clone();
-        let content = self.editor.get_content();
-        let filename = self.filename.clone();
+    /// Moves the cursor a word with `Ctrl+Left`/`Ctrl+Right`, extending the
+    /// selection with `Shift`; a line end goes over to the next line
+    fn move_word(&mut self, key: &KeyEvent) -> bool {
+        let forward = match key.code {
+            KeyCode::Left => false,
+            KeyCode::Right => true,
+            _ => return false,
+        };
+        let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+        if key.modifiers - KeyModifiers::SHIFT != KeyModifiers::CONTROL {
+            return false;
+        }
+
+        let code = self.editor.code_ref();
         let cursor = self.editor.get_cursor();
-        let coder = self.autocomplete.coder.clone();
+        let (row, col) = code.point(cursor);
+        let line_start = code.line_to_char(row);
+        let chars: Vec<char> = code.line(row).chars().take(code.line_len(row)).collect();
+        let target = if forward && col >= chars.len() {
+            (row + 1 < code.len_lines()).then(|| code.line_to_char(row + 1))
+        } else if !forward && col == 0 {
+            (row > 0).then(|| line_start - 1)
+        } else if forward {
+            Some(line_start + self.word_boundary.next_word_end(&chars, col))
+        } else {
+            Some(line_start + self.word_boundary.prev_word_start(&chars, col))
+        };
 
-        let handle = tokio::spawn(async move {
-            let coder = coder.lock().await;
-            let result = coder.autocomplete(&content, &filename, cursor).await;
-            let _ = tx.send(result).await;
-        });
-        self.autocomplete.handle = Some(handle);
+        let target = target.unwrap_or(cursor);
+        if shift {
+            self.editor.extend_selection(target);
+        } else {
+            self.editor.clear_selection();
+        }
+        self.editor.set_cursor(target);
+        self.editor.focus(&self.focus_area());
+        true
     }
 
-    pub async fn recv_autocomplete(&mut self) -> Option<Result<Vec<Edit>>> {
-        self.autocomplete.rx.recv().await
+    /// Viewport scroll for `Ctrl+Up`/`Ctrl+Down` (one line) and
+    /// `Alt+PgUp`/`Alt+PgDn` (one page)
+    fn scroll_delta(&self, key: &KeyEvent) -> Option<isize> {
+        let page = (self.area.height as isize).max(1);
+        match (key.code, key.modifiers) {
+            (KeyCode::Up, KeyModifiers::CONTROL) => Some(-1),
+            (KeyCode::Down, KeyModifiers::CONTROL) => Some(1),
+            (KeyCode::PageUp, KeyModifiers::ALT) => Some(-page),
+            (KeyCode::PageDown, KeyModifiers::ALT) => Some(page),
+            _ => None,
+        }
     }
 
-    pub async fn handle_autocomplete(&mut self, result: Result<Vec<Edit>>) -> Result<()> {
-        match result {
-            Ok(edits) => self.apply_edits(edits)?,
-            Err(err) => eprintln!("autocomplete error: {err:#}"),
-        }
-        Ok(())
+    /// Scrolls the view by `delta` lines without moving the cursor, which
+    /// may end up off-screen until the next edit or cursor move
+    pub fn scroll_viewport(&mut self, delta: isize) {
+        let last_line = self.editor.code_ref().len_lines().saturating_sub(1);
+        let offset = self.editor.get_offset_y().saturating_add_signed(delta);
+        self.editor.set_offset_y(offset.min(last_line));
     }
 
-    pub async fn open_file(&mut self, filename: &str) -> Result<()> {
-        if self.filename == filename || std::path::Path::new(filename).is_dir() {
-            return Ok(());
-        }
+    /// Pans the view `delta` columns sideways without moving the cursor, no
+    /// further right than the longest line on screen needs to be seen whole
+    pub fn pan_viewport(&mut self, delta: isize) {
+        let area = self.focus_area();
+        let code = self.editor.code_ref();
+        let gutter = code.len_lines().max(1).to_string().len().max(5) + 2;
+        let width = (area.width as usize).saturating_sub(gutter);
+        let top = self.editor.get_offset_y();
+        let bottom = (top + area.height as usize).min(code.len_lines());
+        let longest = (top..bottom).map(|line| code.line_len(line)).max();
+        let limit = longest.unwrap_or(0).saturating_sub(width);
 
-        let mut new_editor = match self.opened.remove(filename) {
-            Some(ed) => ed,
-            None => {
-                let theme = ratatui_code_editor::theme::vesper();
-                let mut lang = get_lang(filename);
-                if lang == "unknown" {
-                    lang = "shell".to_string();
-                }
-                let content = std::fs::read_to_string(filename)?;
-                CodeEditor::new(&lang, &content, theme)?
-            }
+        // an offset already past the limit, e.g. to show the cursor, stays
+        let offset = self.editor.get_offset_x();
+        let panned = offset.saturating_add_signed(delta);
+        self.editor.set_offset_x(panned.min(limit.max(offset)));
+    }
+
+    fn is_multiline_selection(&mut self) -> bool {
+        let Some(selection) = self.editor.get_selection() else {
+            return false;
         };
+        let code = self.editor.code_ref();
+        let (start, end) = selection.sorted();
+        code.point(start).0 != code.point(end).0
+    }
 
-        if !self.filename.is_empty() {
-            std::mem::swap(&mut self.editor, &mut new_editor);
-            self.opened.insert(self.filename.clone(), new_editor);
-        } else {
-            self.editor = new_editor;
-        }
+    /// Inserts a newline that keeps the current line's indentation, one
+    /// level deeper after a block opener; a single undo step
+    fn insert_newline(&mut self) {
+        let start = match self.editor.get_selection() {
+            Some(selection) if !selection.is_empty() => selection.sorted().0,
+            _ => self.editor.get_cursor(),
+        };
+        let code = self.editor.code_ref();
+        let (row, col) = code.point(start);
+        let before: String = code.line(row).chars().take(col).collect();
 
-        let content = self.editor.get_content();
-        {
-            let mut coder = self.autocomplete.coder.lock().await;
-            coder.update(&PathBuf::from(filename), &content);
+        let mut text = "\n".to_string();
+        if self.auto_indent {
+            text.extend(before.chars().take_while(|c| *c == ' ' || *c == '\t'));
+            if opens_block(&self.language(), &before) {
+                let content = self.editor.get_content();
+                let code = self.editor.code_ref();
+                text.push_str(&detect_indent(&content).unwrap_or_else(|| code.indent()));
+            }
         }
 
-        self.filename = filename.to_string();
-        Ok(())
+        self.editor
+            .apply(ratatui_code_editor::actions::InsertText { text });
+        self.editor.focus(&self.focus_area());
     }
 
-    pub async fn save(&mut self) -> Result<()> {
-        let content = self.editor.get_content();
-        save_to_file(&content, &self.filename)?;
-        let mut coder = self.autocomplete.coder.lock().await;
-        coder.update(&PathBuf::from(&self.filename), &content);
-        self.self_update = true;
-        Ok(())
+    /// Inserts pasted text as a single undo step; with `reindent_paste`,
+    /// the lines after the first are shifted to the cursor line's indentation
+    fn paste(&mut self, paste: &str) {
+        let mut text = paste.to_string();
+        if self.reindent_paste && paste.contains('\n') {
+            let start = match self.editor.get_selection() {
+                Some(selection) if !selection.is_empty() => selection.sorted().0,
+                _ => self.editor.get_cursor(),
+            };
+            let code = self.editor.code_ref();
+            let (row, col) = code.point(start);
+            let before: String = code.line(row).chars().take(col).collect();
+            let indent: String = code
+                .line(row)
+                .chars()
+                .take_while(|c| *c == ' ' || *c == '\t')
+                .collect();
+            let at_indent = before.chars().all(|c| c == ' ' || c == '\t');
+            text = reindent(paste, &indent, at_indent);
+        }
+        self.editor
+            .apply(ratatui_code_editor::actions::InsertText { text });
     }
 
-    pub async fn handle_file_change(&mut self, event: &notify::Event) -> Result<()> {
-        use crate::utils::abs_file;
+    /// Adds or removes one indent level on every selected line as a single
+    /// undoable batch, keeping the selection on the same text
+    fn indent_selection(&mut self, dedent: bool) {
+        let Some(selection) = self.editor.get_selection() else {
+            return;
+        };
+        let cursor = self.editor.get_cursor();
+        let content = self.editor.get_content();
+        let code = self.editor.code_ref();
+        let indent = detect_indent(&content).unwrap_or_else(|| code.indent());
 
-        match event.kind {
-            notify::EventKind::Modify(notify::event::ModifyKind::Data(_)) => {
-                if self.self_update {
-                    self.self_update = false;
-                    return Ok(());
-                }
-                let self_abs = abs_file(&self.filename);
-                let self_path = std::path::Path::new(&self_abs);
-                if event.paths.iter().any(|p| p == &self_path) {
-                    let old_content = self.editor.get_content();
-                    let new_content = std::fs::read_to_string(&self_path)?;
-                    self.self_update = false;
-
-                    if old_content != new_content {
-                        let edits = compute_text_edits(&old_content, &new_content);
-                        if edits.is_empty() {
-                            self.editor.set_content(&new_content);
-                            clamp_editor_state(&mut self.editor);
-                        } else {
-                            self.apply_external_edits(edits)?;
-                        }
-                        let mut coder = self.autocomplete.coder.lock().await;
-                        coder.update(&PathBuf::from(&self.filename), &new_content);
-                    }
+        let (start, end) = selection.sorted();
+        let start_row = code.point(start).0;
+        let (mut end_row, end_col) = code.point(end);
+        // a selection ending at column 0 does not include that line
+        if end_col == 0 && end_row > start_row {
+            end_row -= 1;
+        }
+
+        // (line start, inserted or removed chars) for every touched line
+        let mut changes: Vec<(usize, String)> = Vec::new();
+        for row in start_row..=end_row {
+            let line_start = code.line_to_char(row);
+            let line = code.line(row).to_string();
+            let text = if !dedent {
+                if line.trim().is_empty() {
+                    continue;
                 }
+                indent.clone()
+            } else if line.starts_with('\t') {
+                "\t".to_string()
+            } else {
+                let width = line.chars().take_while(|c| *c == ' ').count();
+                " ".repeat(width.min(indent.chars().count().max(1)))
+            };
+            if !text.is_empty() {
+                changes.push((line_start, text));
             }
-            _ => {}
         }
-        Ok(())
-    }
-
-    fn apply_edits(&mut self, edits: Vec<Edit>) -> Result<()> {
-        if edits.is_empty() {
-            return Ok(());
+        if changes.is_empty() {
+            return;
         }
 
-        let changed_ranges = compute_changed_ranges_normalized(&edits);
+        // positions at a line start stay there so the new indent is selected
+        let map = |pos: usize| -> usize {
+            let mut new_pos = pos;
+            for (line_start, text) in changes.iter().filter(|(s, _)| pos > *s) {
+                let len = text.chars().count();
+                if dedent {
+                    new_pos -= (pos - line_start).min(len);
+                } else {
+                    new_pos += len;
+                }
+            }
+            new_pos
+        };
+        let anchor = if cursor == selection.start {
+            selection.end
+        } else {
+            selection.start
+        };
+        let new_cursor = map(cursor);
+        let new_selection = Selection::from_anchor_and_cursor(map(anchor), new_cursor);
 
-        let last_change = changed_ranges.last().unwrap();
-        let editbatch = EditBatch {
+        let operation = if dedent {
+            Operation::Remove
+        } else {
+            Operation::Insert
+        };
+        let edits = changes
+            .into_iter()
+            .rev()
+            .map(|(start, text)| Edit {
+                start,
+                text,
+                operation: operation.clone(),
+            })
+            .collect();
+
+        self.editor.apply_batch(&EditBatch {
             edits,
             state_before: Some(EditState {
-                offset: self.editor.get_cursor(),
-                selection: self.editor.get_selection(),
+                offset: cursor,
+                selection: Some(selection),
             }),
             state_after: Some(EditState {
-                offset: last_change.end,
-                selection: self.editor.get_selection(),
+                offset: new_cursor,
+                selection: Some(new_selection),
             }),
-        };
+        });
+        self.editor.set_cursor(new_cursor);
+        self.editor.set_selection(Some(new_selection));
+    }
 
-        self.editor.apply_batch(&editbatch);
-        self.editor.set_cursor(last_change.end);
+    /// Replaces the client used for autocomplete; a request in flight holds
+    /// the coder, so it is cancelled first
+    pub async fn set_llm_client(&mut self, llm_client: Option<LlmClient>) {
+        if let Some(handle) = self.autocomplete.handle.take() {
+            handle.abort();
+            let _ = handle.await;
+        }
+        let mut coder = self.autocomplete.coder.lock().await;
+        coder.set_llm(llm_client.map(|llm| Box::new(llm) as Box<dyn ChatBackend>));
+    }
 
-        let marks = changed_ranges
-            .iter()
-            .map(|r| match r.kind {
-                ChangedRangeKind::Insert => (r.start, r.end, COLOR_INSERT),
-                ChangedRangeKind::Delete => (r.start, r.end, COLOR_DELETE),
-            })
-            .collect::<Vec<_>>();
-        self.editor.set_marks(marks);
+    /// Whether autocomplete is enabled for the current file's language
+    pub fn autocomplete_enabled(&self) -> bool {
+        self.autocomplete_languages.includes(&self.filename)
+    }
+
+    /// Starts an autocomplete request, cancelling the one in flight
+    pub fn spawn_autocomplete(&mut self) {
+        self.spawn_request(None);
+    }
+
+    /// Starts a request to change the selection as `instruction` says,
+    /// cancelling the one in flight
+    pub fn spawn_instruct(&mut self, instruction: String) {
+        self.spawn_request(Some(RequestKind::Instruct(instruction)));
+    }
+
+    /// Starts a request to reformat the selection without changing what it
+    /// does, cancelling the one in flight
+    pub fn spawn_format(&mut self) {
+        self.spawn_request(Some(RequestKind::Format));
+    }
+
+    /// Starts a request for a commit message inserted at the cursor,
+    /// describing the staged changes of the file's repository, or with
+    /// nothing staged the edits made to open files this session
+    pub fn spawn_commit_message(&mut self) {
+        let staged = crate::git::staged_diff(Path::new(&self.filename)).unwrap_or_default();
+        self.spawn_request(Some(RequestKind::CommitMessage(staged)));
+    }
+
+    /// Whether some text is selected
+    pub fn has_selection(&mut self) -> bool {
+        self.editor
+            .get_selection()
+            .is_some_and(|selection| !selection.is_empty())
+    }
+
+    fn spawn_request(&mut self, request: Option<RequestKind>) {
+        if let Some(handle) = self.autocomplete.handle.take() {
+            handle.abort();
+        }
+        self.pending_edits = None;
+        let version = self.next_autocomplete_version();
+        let tx = self.autocomplete.tx.clone();
+        let content = self.editor.get_content();
+        let filename = self.filename.clone();
+        let cursor = self.editor.get_cursor();
+        // a selection is rewritten as a whole instead of completed at the cursor
+        let selection = self
+            .editor
+            .get_selection()
+            .filter(|selection| !selection.is_empty())
+            .map(|selection| selection.sorted());
+        let context = self.context;
+        let completion_newline = self.completion_newline;
+        let patch_retry = self.patch_retry;
+        let candidates = self.completion_candidates;
+        let debug_file = self.llm_debug_file.clone();
+        let system_prompt = self.system_prompt.clone();
+        let coder = self.autocomplete.coder.clone();
+
+        let handle = tokio::spawn(async move {
+            let mut coder = coder.lock().await;
+            coder.context = context;
+            coder.completion_newline = completion_newline;
+            coder.patch_retry = patch_retry;
+            coder.candidates = candidates;
+            coder.debug_file = debug_file;
+            coder.system_prompt = system_prompt;
+            let result = match (selection, request) {
+                (_, Some(RequestKind::CommitMessage(staged))) => {
+                    let changes = if staged.is_empty() {
+                        coder.session_changes(Some((Path::new(&filename), &content)))
+                    } else {
+                        staged
+                    };
+                    coder
+                        .commit_message(&changes, cursor)
+                        .await
+                        .map(Candidate::only)
+                }
+                (Some((start, end)), Some(RequestKind::Instruct(instruction))) => coder
+                    .instruct(&content, &filename, start, end, &instruction)
+                    .await
+                    .map(Candidate::only),
+                (Some((start, end)), Some(RequestKind::Format)) => coder
+                    .format_selection(&content, &filename, start, end)
+                    .await
+                    .map(Candidate::only),
+                (Some((start, end)), None) => coder
+                    .rewrite(&content, &filename, start, end)
+                    .await
+                    .map(Candidate::only),
+                (None, _) => coder.autocomplete(&content, &filename, cursor).await,
+            };
+            let _ = tx.send((version, result)).await;
+        });
+        self.autocomplete.handle = Some(handle);
+        self.autocomplete.started = Some(Instant::now());
+    }
+
+    /// Version for a new request made for the current buffer state
+    fn next_autocomplete_version(&mut self) -> u64 {
+        self.autocomplete.version += 1;
+        self.autocomplete.requested_for = Some(self.autocomplete_state());
+        self.autocomplete.version
+    }
+
+    fn autocomplete_state(&self) -> (String, usize, u64) {
+        let mut hasher = std::hash::DefaultHasher::new();
+        std::hash::Hash::hash(&self.editor.get_content(), &mut hasher);
+        let hash = std::hash::Hasher::finish(&hasher);
+        (self.filename.clone(), self.editor.get_cursor(), hash)
+    }
+
+    /// Whether a result of request `version` still applies: it is the latest
+    /// request and file, cursor and content are as they were when it was made
+    pub fn is_current_autocomplete(&self, version: u64) -> bool {
+        version == self.autocomplete.version
+            && self.autocomplete.requested_for.as_ref() == Some(&self.autocomplete_state())
+    }
+
+    /// Whether an autocomplete request is in flight
+    pub fn autocomplete_busy(&self) -> bool {
+        let handle = self.autocomplete.handle.as_ref();
+        handle.is_some_and(|handle| !handle.is_finished())
+    }
+
+    /// Stops an in-flight autocomplete before quitting: the task is aborted
+    /// and awaited briefly, queued results are dropped unapplied, and an
+    /// unaccepted suggestion is reverted so the buffer holds only user edits
+    pub async fn shutdown(&mut self) {
+        if let Some(handle) = self.autocomplete.handle.take() {
+            handle.abort();
+            let _ = tokio::time::timeout(std::time::Duration::from_millis(200), handle).await;
+        }
+        while self.autocomplete.rx.try_recv().is_ok() {}
+
+        if self.editor.has_marks() {
+            self.editor.remove_marks();
+            self.editor.apply(ratatui_code_editor::actions::Undo {});
+        }
+    }
+
+    pub async fn recv_autocomplete(&mut self) -> Option<AutocompleteResult> {
+        self.autocomplete.rx.recv().await
+    }
+
+    /// Applies the best of the AI candidates, keeping the others to cycle
+    /// through, returning the number of deleted lines when its edits are
+    /// held for confirmation instead
+    pub async fn handle_autocomplete(
+        &mut self,
+        candidates: Vec<Candidate>,
+    ) -> Result<Option<usize>> {
+        self.candidates = candidates;
+        self.candidate = 0;
+        let edits = match self.candidates.first() {
+            Some(best) => best.edits.clone(),
+            None => Vec::new(),
+        };
+        self.apply_edits(edits)
+    }
+
+    /// Swaps the marked AI suggestion for the next candidate of the same
+    /// request, wrapping around; false when there is none to swap
+    pub fn next_candidate(&mut self) -> Result<bool> {
+        if self.candidates.len() < 2 || !self.editor.has_marks() {
+            return Ok(false);
+        }
+        self.editor.remove_marks();
+        self.editor.apply(ratatui_code_editor::actions::Undo {});
+        self.candidate = (self.candidate + 1) % self.candidates.len();
+        let edits = self.candidates[self.candidate].edits.clone();
+        self.apply_marked_edits(edits)?;
+        Ok(true)
+    }
 
+    /// Which of several candidates is shown, e.g. `candidate 1 of 3, 87%`,
+    /// the share being how likely the model found it when known
+    pub fn candidate_label(&self) -> Option<String> {
+        if self.candidates.len() < 2 {
+            return None;
+        }
+        let label = format!(
+            "candidate {} of {}",
+            self.candidate + 1,
+            self.candidates.len()
+        );
+        Some(match self.candidates[self.candidate].confidence() {
+            Some(confidence) => format!("{label}, {:.0}%", confidence * 100.0),
+            None => label,
+        })
+    }
+
+    /// Opens `filename`, returning its stats when it was freshly loaded
+    /// and large enough to be opened without highlighting
+    pub async fn open_file(&mut self, filename: &str) -> Result<Option<FileStats>> {
+        let filename = &self.buffer_name(filename);
+        if self.filename == *filename || std::path::Path::new(filename).is_dir() {
+            return Ok(None);
+        }
+
+        let mut large_file = None;
+        let mut new_editor = match self.opened.remove(filename) {
+            Some(ed) => ed,
+            None => {
+                let theme = ratatui_code_editor::theme::vesper();
+                let (content, encoding) = encoding::read_file(filename)?;
+                self.set_encoding(filename, encoding);
+                let stats = FileStats::of(&content);
+                if stats.is_large() {
+                    large_file = Some(stats);
+                }
+                let chosen = self.languages.get(filename).map(String::as_str);
+                let lang = editor_language(filename, stats, self.highlight, chosen);
+                CodeEditor::new(&lang, &content, theme)?
+            }
+        };
+
+        self.pending_edits = None;
+        // the split is on the current file only
+        if let Some(split) = self.split.take() {
+            self.area = self.area.union(split.area);
+        }
+        self.recent.retain(|name| name != filename);
+        self.evicted.remove(filename);
+        if !self.filename.is_empty() {
+            std::mem::swap(&mut self.editor, &mut new_editor);
+            self.opened.insert(self.filename.clone(), new_editor);
+            self.recent.insert(0, self.filename.clone());
+            self.evict_highlights();
+        } else {
+            self.editor = new_editor;
+        }
+
+        let content = self.editor.get_content();
+        {
+            let mut coder = self.autocomplete.coder.lock().await;
+            coder.update(&PathBuf::from(filename), &content);
+            self.baseline = coder
+                .opened_content(&PathBuf::from(filename))
+                .map(|content| {
+                    let stats = FileStats::of(&content);
+                    (content, stats)
+                });
+        }
+
+        self.filename = filename.to_string();
+        self.flash = None;
+        self.refresh_changes();
+        Ok(large_file)
+    }
+
+    pub fn highlight(&self) -> bool {
+        self.highlight
+    }
+
+    /// Turns syntax highlighting on or off for every open buffer; buffers
+    /// are rebuilt keeping cursor, selection and scroll, but not undo history
+    pub fn set_highlight(&mut self, highlight: bool) -> Result<()> {
+        if self.highlight == highlight {
+            return Ok(());
+        }
+        self.highlight = highlight;
+        self.pending_edits = None;
+        self.editor.remove_marks();
+
+        if !self.filename.is_empty() {
+            let filename = self.filename.clone();
+            let chosen = self.languages.get(&filename).map(String::as_str);
+            rebuild_editor(&mut self.editor, &filename, highlight, chosen)?;
+        }
+        for (filename, editor) in self.opened.iter_mut() {
+            let chosen = self.languages.get(filename).map(String::as_str);
+            rebuild_editor(editor, filename, highlight, chosen)?;
+        }
         Ok(())
     }
 
-    pub fn apply_external_edits(&mut self, edits: Vec<Edit>) -> Result<()> {
+    /// Drops the highlight cache of inactive buffers past the most recent
+    /// few to bound memory with many open files
+    fn evict_highlights(&mut self) {
+        for name in self.recent.iter().skip(HIGHLIGHTED_INACTIVE_BUFFERS) {
+            if let Some(editor) = self.opened.get(name)
+                && self.evicted.insert(name.clone())
+            {
+                editor.reset_highlight_cache();
+            }
+        }
+    }
+
+    /// Whether the current buffer has text its file on disk lacks, through
+    /// unsaved edits or changes to the file not merged in
+    pub fn differs_from_disk(&self) -> bool {
+        match encoding::read_file(&self.filename) {
+            Ok((content, _)) => content != self.editor.get_content(),
+            Err(_) => true,
+        }
+    }
+
+    /// Reads the current file again into a fresh editor, with the cursor at
+    /// the top; undo history, marks and pending AI edits are dropped
+    pub async fn reload(&mut self) -> Result<()> {
+        let filename = self.filename.clone();
+        let (content, encoding) = encoding::read_file(&filename)?;
+        self.set_encoding(&filename, encoding);
+        let chosen = self.languages.get(&filename).map(String::as_str);
+        let lang = editor_language(&filename, FileStats::of(&content), self.highlight, chosen);
+        let theme = ratatui_code_editor::theme::vesper();
+        self.editor = CodeEditor::new(&lang, &content, theme)?;
+        self.pending_edits = None;
+        self.flash = None;
+        self.self_update = false;
+        self.autocomplete
+            .coder
+            .lock()
+            .await
+            .update(&PathBuf::from(&filename), &content);
+        self.refresh_changes();
+        Ok(())
+    }
+
+    /// Saves the current file unless it changed on disk since it was read
+    /// and `confirm_overwrite` is set; false when it was not saved for that
+    pub async fn save(&mut self) -> Result<bool> {
+        if self.confirm_overwrite && self.changed_on_disk() {
+            return Ok(false);
+        }
+        self.overwrite().await?;
+        Ok(true)
+    }
+
+    /// Writes the current buffer to its file, whatever the file holds now
+    pub async fn overwrite(&mut self) -> Result<()> {
+        if self.no_write {
+            anyhow::bail!("writes are off");
+        }
+        self.clean_whitespace();
+        let content = self.editor.get_content();
+        // a new file may be in a directory that does not exist yet either
+        if let Some(dir) = Path::new(&self.filename).parent()
+            && !dir.as_os_str().is_empty()
+            && !dir.exists()
+        {
+            std::fs::create_dir_all(dir)?;
+        }
+        encoding::write_file(&self.filename, &content, self.encoding())?;
+        self.disk_states
+            .insert(self.filename.clone(), DiskState::of(&self.filename));
+        let mut coder = self.autocomplete.coder.lock().await;
+        coder.update(&PathBuf::from(&self.filename), &content);
+        self.self_update = true;
+        Ok(())
+    }
+
+    /// Applies the whitespace cleanup enabled for saving as one undoable
+    /// batch, keeping the cursor on its line and column where possible
+    fn clean_whitespace(&mut self) {
+        if !self.trim_trailing_whitespace && !self.final_newline {
+            return;
+        }
+        let content = self.editor.get_content();
+        let cleaned =
+            cleaned_whitespace(&content, self.trim_trailing_whitespace, self.final_newline);
+        if cleaned == content {
+            return;
+        }
+
+        self.replace_keeping_point(&content, &cleaned);
+    }
+
+    /// Line ending counts `(lf, crlf)` of the current file when it mixes
+    /// `\n` and `\r\n`
+    pub fn mixed_line_endings(&self) -> Option<(usize, usize)> {
+        let (lf, crlf) = line_endings(&self.editor.get_content());
+        (lf > 0 && crlf > 0).then_some((lf, crlf))
+    }
+
+    /// Converts every line ending to the more common style as one undoable
+    /// edit, returning that style, `"LF"` or `"CRLF"`
+    pub fn normalize_line_endings(&mut self) -> Option<&'static str> {
+        let (lf, crlf) = self.mixed_line_endings()?;
+        let content = self.editor.get_content();
+        let unix = content.replace("\r\n", "\n");
+        let (style, normalized) = if crlf > lf {
+            ("CRLF", unix.replace('\n', "\r\n"))
+        } else {
+            ("LF", unix)
+        };
+        self.replace_keeping_point(&content, &normalized);
+        Some(style)
+    }
+
+    /// Turns `content`, the current text, into `new` as one undoable batch,
+    /// keeping the cursor on its line and column where possible
+    pub fn replace_keeping_point(&mut self, content: &str, new: &str) {
+        let cursor = self.editor.get_cursor();
+        let (row, col) = self.editor.code_ref().point(cursor);
+        let edits = compute_text_edits(content, new);
+        let editbatch = EditBatch {
+            edits,
+            state_before: Some(EditState {
+                offset: cursor,
+                selection: self.editor.get_selection(),
+            }),
+            state_after: None,
+        };
+        self.editor.apply_batch(&editbatch);
+        self.editor.remove_marks();
+
+        let code = self.editor.code_ref();
+        let row = row.min(code.len_lines().saturating_sub(1));
+        let cursor = code.line_to_char(row) + col.min(code.line_len(row));
+        self.editor.set_cursor(cursor);
+        clamp_editor_state(&mut self.editor);
+        self.refresh_changes();
+    }
+
+    pub async fn handle_file_change(&mut self, event: &notify::Event) -> Result<()> {
+        use crate::utils::abs_file;
+
+        if let notify::EventKind::Modify(notify::event::ModifyKind::Data(_)) = event.kind {
+            if self.self_update {
+                self.self_update = false;
+                return Ok(());
+            }
+            let changed = |name: &str| {
+                let abs = abs_file(name);
+                event.paths.iter().any(|p| p == std::path::Path::new(&abs))
+            };
+            if changed(&self.filename) {
+                let old_content = self.editor.get_content();
+                let (new_content, encoding) = encoding::read_file(abs_file(&self.filename))?;
+                let filename = self.filename.clone();
+                self.set_encoding(&filename, encoding);
+                self.self_update = false;
+
+                if old_content != new_content {
+                    let edits = external_edits_blocking(old_content, new_content.clone()).await?;
+                    self.apply_external_edits(edits)?;
+                    let mut coder = self.autocomplete.coder.lock().await;
+                    coder.update(&PathBuf::from(&self.filename), &new_content);
+                }
+            }
+
+            // inactive buffers follow their files too
+            let names: Vec<String> = self.opened.keys().filter(|n| changed(n)).cloned().collect();
+            for name in names {
+                let (new_content, encoding) = encoding::read_file(abs_file(&name))?;
+                self.set_encoding(&name, encoding);
+                let Some(editor) = self.opened.get_mut(&name) else {
+                    continue;
+                };
+                let old_content = editor.get_content();
+                if old_content != new_content {
+                    let edits = external_edits_blocking(old_content, new_content).await?;
+                    apply_keeping_view(editor, edits);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Loads `filename` as an inactive buffer, e.g. for extra files given on
+    /// the command line; it becomes the next one [`Self::next_buffer`] picks
+    pub fn preload(&mut self, filename: &str) -> Result<()> {
+        let filename = &self.buffer_name(filename);
+        if self.filename == *filename || self.opened.contains_key(filename) {
+            return Ok(());
+        }
+        let (content, encoding) = encoding::read_file(filename)?;
+        self.set_encoding(filename, encoding);
+        let chosen = self.languages.get(filename).map(String::as_str);
+        let lang = editor_language(filename, FileStats::of(&content), self.highlight, chosen);
+        let editor = CodeEditor::new(&lang, &content, ratatui_code_editor::theme::vesper())?;
+        self.opened.insert(filename.to_string(), editor);
+        self.recent.insert(0, filename.to_string());
+        Ok(())
+    }
+
+    /// The open buffer not shown for the longest time, so that switching to
+    /// it repeatedly cycles through all of them
+    pub fn next_buffer(&self) -> Option<String> {
+        self.recent.last().cloned()
+    }
+
+    /// Pins the selected lines as context sent with every AI request until
+    /// cleared, returning their 1-based line range
+    pub async fn pin_selection(&mut self) -> Option<(usize, usize)> {
+        let (start, end) = self
+            .editor
+            .get_selection()
+            .filter(|selection| !selection.is_empty())?
+            .sorted();
+        let code = self.editor.code_ref();
+        let lines = (code.point(start).0 + 1, code.point(end).0 + 1);
+        let region = PinnedRegion {
+            path: PathBuf::from(&self.filename),
+            lines,
+            text: code.slice(start, end),
+        };
+        self.autocomplete.coder.lock().await.pin(region);
+        Some(lines)
+    }
+
+    /// Unpins all pinned context, returning how many regions were pinned
+    pub async fn clear_pinned(&mut self) -> usize {
+        self.autocomplete.coder.lock().await.clear_pinned()
+    }
+
+    /// Fills an empty buffer with a template body, leaving the cursor at its
+    /// `$0`; returns false when the buffer has content
+    pub fn insert_template(&mut self, body: &str) -> bool {
+        if !self.editor.get_content().is_empty() {
+            return false;
+        }
+        self.editor.set_selection(None);
+        self.editor.set_cursor(0);
+        self.wrap_selection(&format!("{body}\n"));
+        true
+    }
+
+    /// Replaces the selection, or inserts at the cursor, with `body`
+    /// expanded around the selected text as one undoable edit, leaving the
+    /// cursor at the snippet's `$0`. A selection starting in the
+    /// indentation is taken from its line start and keeps that indentation
+    pub fn wrap_selection(&mut self, body: &str) {
+        let cursor = self.editor.get_cursor();
+        let selection = self.editor.get_selection();
+        let (mut start, end) = match selection {
+            Some(selection) if !selection.is_empty() => selection.sorted(),
+            _ => (cursor, cursor),
+        };
+        let code = self.editor.code_ref();
+        let line_start = code.line_to_char(code.point(start).0);
+        let line = code.line(code.point(start).0).to_string();
+        let indent: String = line
+            .chars()
+            .take_while(|c| *c == ' ' || *c == '\t')
+            .collect();
+        let at_indent = code.slice(line_start, start).trim().is_empty();
+        if at_indent {
+            start = line_start;
+        }
+
+        let removed = code.slice(start, end);
+        // whole selected lines stay whole lines after wrapping
+        let (selected, newline) = match removed.strip_suffix('\n') {
+            Some(selected) if !selected.is_empty() => (selected, "\n"),
+            _ => (removed.as_str(), ""),
+        };
+        let selected: Vec<&str> = selected
+            .split('\n')
+            .map(|line| line.strip_prefix(indent.as_str()).unwrap_or(line))
+            .collect();
+        let (mut text, mut offset) = crate::snippets::expand(body, &selected.join("\n"), &indent);
+        if at_indent {
+            text.insert_str(0, &indent);
+            offset += indent.chars().count();
+        }
+        text.push_str(newline);
+
+        let mut edits = Vec::new();
+        if !removed.is_empty() {
+            edits.push(Edit {
+                start,
+                text: removed,
+                operation: Operation::Remove,
+            });
+        }
+        edits.push(Edit {
+            start,
+            text,
+            operation: Operation::Insert,
+        });
+        if self.editor.has_marks() {
+            self.editor.remove_marks();
+        }
+        self.editor.apply_batch(&EditBatch {
+            edits,
+            state_before: Some(EditState {
+                offset: cursor,
+                selection,
+            }),
+            state_after: Some(EditState {
+                offset: start + offset,
+                selection: None,
+            }),
+        });
+        self.editor.set_cursor(start + offset);
+        self.editor.set_selection(None);
+        self.editor.focus(&self.focus_area());
+        self.refresh_changes();
+    }
+
+    /// Lists the undone steps, newest first, the current state and the
+    /// steps that can be undone, newest first; walks the history to read it
+    /// and leaves the buffer as it was
+    pub fn undo_history(&mut self) -> Vec<HistoryEntry> {
+        let code = self.editor.code_mut();
+        // after each undo the content is what the batch was applied to
+        let mut undone = Vec::new();
+        while let Some(batch) = code.undo() {
+            let line = batch_line(code, &batch);
+            undone.push((batch, line));
+        }
+        for _ in 0..undone.len() {
+            code.redo();
+        }
+        let mut redo_steps = 0;
+        while code.redo().is_some() {
+            redo_steps += 1;
+        }
+        let mut redone = Vec::new();
+        for _ in 0..redo_steps {
+            if let Some(batch) = code.undo() {
+                let line = batch_line(code, &batch);
+                redone.push((batch, line));
+            }
+        }
+
+        // undoing the redo steps yields the newest first
+        let mut entries: Vec<HistoryEntry> = redone
+            .iter()
+            .enumerate()
+            .map(|(i, (batch, line))| HistoryEntry::step((redo_steps - i) as isize, batch, *line))
+            .collect();
+        entries.push(HistoryEntry::current());
+        entries.extend(
+            undone
+                .iter()
+                .enumerate()
+                .map(|(i, (batch, line))| HistoryEntry::step(-(i as isize) - 1, batch, *line)),
+        );
+        entries
+    }
+
+    /// Undoes (negative) or redoes (positive) that many steps at once
+    pub fn travel_history(&mut self, steps: isize) {
+        for _ in 0..steps.unsigned_abs() {
+            if steps < 0 {
+                self.editor.apply(ratatui_code_editor::actions::Undo {});
+            } else {
+                self.editor.apply(ratatui_code_editor::actions::Redo {});
+            }
+        }
+        self.editor.focus(&self.focus_area());
+        self.refresh_changes();
+    }
+
+    /// Files edited in this session and how many lines their recent edits
+    /// added and removed, counting unsaved edits of the current file
+    pub async fn recent_edits(&self) -> Vec<RecentEdit> {
+        let content = self.editor.get_content();
+        let current = (!self.filename.is_empty())
+            .then(|| (std::path::Path::new(&self.filename), content.as_str()));
+        self.autocomplete.coder.lock().await.recent_edits(current)
+    }
+
+    /// Files of the current and the inactive buffers
+    pub fn open_files(&self) -> impl Iterator<Item = &String> {
+        std::iter::once(&self.filename)
+            .filter(|name| !name.is_empty())
+            .chain(self.opened.keys())
+    }
+
+    /// Applies AI edits with change marks, unless they delete more than
+    /// `confirm_delete_lines` lines: then they are held until confirmed and
+    /// the deleted line count is returned
+    fn apply_edits(&mut self, edits: Vec<Edit>) -> Result<Option<usize>> {
+        if edits.is_empty() {
+            return Ok(None);
+        }
+        let deleted = deleted_lines(&edits);
+        if self.confirm_delete_lines > 0 && deleted > self.confirm_delete_lines {
+            self.pending_edits = Some(edits);
+            return Ok(Some(deleted));
+        }
+        self.apply_marked_edits(edits)?;
+        Ok(None)
+    }
+
+    /// Applies a JSON array of edits from an external tool to the current
+    /// buffer, marked and undoable as one batch like AI edits; returns the
+    /// number of edits, none applied when any of them is out of range
+    pub fn apply_edits_json(&mut self, json: &str) -> Result<usize> {
+        let parsed: Vec<JsonEdit> = serde_json::from_str(json)?;
+        let edits: Vec<Edit> = parsed
+            .into_iter()
+            .map(|edit| Edit {
+                start: edit.start,
+                text: edit.text,
+                operation: match edit.operation {
+                    JsonOperation::Insert => Operation::Insert,
+                    JsonOperation::Remove => Operation::Remove,
+                },
+            })
+            .collect();
+        check_edits(&edits, self.editor.code_ref().len_chars()).map_err(anyhow::Error::msg)?;
+        let count = edits.len();
+        self.pending_edits = None;
+        self.candidates.clear();
+        self.apply_marked_edits(edits)?;
+        Ok(count)
+    }
+
+    fn apply_marked_edits(&mut self, edits: Vec<Edit>) -> Result<()> {
         if edits.is_empty() {
             return Ok(());
         }
+        // the content may have changed since the edits were computed
+        if let Err(err) = check_edits(&edits, self.editor.code_ref().len_chars()) {
+            log::warn!("skipping AI edits: {err}");
+            anyhow::bail!("edits no longer fit the file ({err})");
+        }
 
-        let cursor_before = self.editor.get_cursor();
-        let selection_before = self.editor.get_selection();
+        let changed_ranges = compute_changed_ranges_normalized(&edits);
 
+        let last_change = changed_ranges.last().unwrap();
         let editbatch = EditBatch {
             edits,
             state_before: Some(EditState {
-                offset: cursor_before,
-                selection: selection_before,
+                offset: self.editor.get_cursor(),
+                selection: self.editor.get_selection(),
             }),
             state_after: Some(EditState {
-                offset: cursor_before,
-                selection: selection_before,
+                offset: last_change.end,
+                selection: self.editor.get_selection(),
             }),
         };
 
         self.editor.apply_batch(&editbatch);
+        self.editor.set_cursor(last_change.end);
+        self.editor.set_selection(None);
+        self.refresh_changes();
+
+        let marks = changed_ranges
+            .iter()
+            .map(|r| match r.kind {
+                ChangedRangeKind::Insert => (r.start, r.end, COLOR_INSERT),
+                ChangedRangeKind::Delete => (r.start, r.end, COLOR_DELETE),
+            })
+            .collect::<Vec<_>>();
+        self.editor.set_marks(marks);
+
+        Ok(())
+    }
+
+    pub fn apply_external_edits(&mut self, edits: Vec<Edit>) -> Result<()> {
+        if edits.is_empty() {
+            return Ok(());
+        }
+        // held AI edits refer to the old content
+        self.pending_edits = None;
+
+        apply_keeping_view(&mut self.editor, edits);
         self.editor.remove_marks();
-        clamp_editor_state(&mut self.editor);
+        self.refresh_changes();
 
         Ok(())
     }
 }
 
-fn is_save_pressed(key: KeyEvent) -> bool {
-    key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('s')
+/// Edits applying an external change; even when the diff comes back empty
+/// the change goes through the edit path, keeping undo history and letting
+/// the buffer reparse incrementally, instead of `set_content`
+fn external_edits(old: &str, new: &str) -> Vec<Edit> {
+    let edits = compute_text_edits(old, new);
+    if !edits.is_empty() || old == new {
+        return edits;
+    }
+    vec![
+        Edit {
+            start: 0,
+            text: old.to_string(),
+            operation: Operation::Remove,
+        },
+        Edit {
+            start: 0,
+            text: new.to_string(),
+            operation: Operation::Insert,
+        },
+    ]
 }
 
-fn is_autocomplete_pressed(key: KeyEvent) -> bool {
-    key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char(' ')
+/// [`external_edits`] on a blocking task: diffing a multi-MB file takes a
+/// while, keep it off the async workers
+async fn external_edits_blocking(old: String, new: String) -> Result<Vec<Edit>> {
+    Ok(tokio::task::spawn_blocking(move || external_edits(&old, &new)).await?)
 }
 
-fn is_quit_pressed(key: KeyEvent) -> bool {
-    key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('q')
+/// Applies `edits` as one undoable batch, leaving cursor and selection
+/// where they were as far as the new content allows
+fn apply_keeping_view(editor: &mut CodeEditor, edits: Vec<Edit>) {
+    if let Err(err) = check_edits(&edits, editor.code_ref().len_chars()) {
+        log::warn!("skipping external edits: {err}");
+        return;
+    }
+    let state = EditState {
+        offset: editor.get_cursor(),
+        selection: editor.get_selection(),
+    };
+    let editbatch = EditBatch {
+        edits,
+        state_before: Some(state),
+        state_after: Some(state),
+    };
+    editor.apply_batch(&editbatch);
+    clamp_editor_state(editor);
+}
+
+/// Checks that `edits`, applied in order to content of `len` chars, stay
+/// within it, so that a batch computed against stale content is refused
+/// instead of panicking in the middle of being applied
+fn check_edits(edits: &[Edit], mut len: usize) -> std::result::Result<(), String> {
+    for (i, edit) in edits.iter().enumerate() {
+        let chars = edit.text.chars().count();
+        match edit.operation {
+            Operation::Insert if edit.start <= len => len += chars,
+            Operation::Remove if edit.start + chars <= len => len -= chars,
+            _ => {
+                let end = edit.start + chars;
+                return Err(format!(
+                    "edit {} at {}..{end} is out of range for {len} chars",
+                    i + 1,
+                    edit.start
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 0-based line a batch starts on, in the content it applies to
+fn batch_line(code: &ratatui_code_editor::code::Code, batch: &EditBatch) -> usize {
+    let start = batch.edits.iter().map(|e| e.start).min().unwrap_or(0);
+    code.point(start.min(code.len())).0
 }
 
-fn save_to_file(content: &str, path: &str) -> Result<()> {
-    use std::io::Write;
-    let mut file = std::fs::File::create(path)?;
-    file.write_all(content.as_bytes())?;
+/// Lines removed by `edits`, counting the line breaks deleted
+fn deleted_lines(edits: &[Edit]) -> usize {
+    edits
+        .iter()
+        .filter(|edit| edit.operation == Operation::Remove)
+        .map(|edit| edit.text.matches('\n').count())
+        .sum()
+}
+
+/// Whether a line ending in `line` opens a block in `lang`
+fn opens_block(lang: &str, line: &str) -> bool {
+    let openers: &[char] = match lang {
+        "python" => &[':', '{', '(', '['],
+        "yaml" => &[':'],
+        _ => &['{', '(', '['],
+    };
+    line.trim_end()
+        .chars()
+        .last()
+        .is_some_and(|c| openers.contains(&c))
+}
+
+/// Language to open a file with: plain text without highlighting or for
+/// large files, else the one picked by hand or the one its name suggests
+fn editor_language(
+    filename: &str,
+    stats: FileStats,
+    highlight: bool,
+    chosen: Option<&str>,
+) -> String {
+    if !highlight || stats.is_large() {
+        return "text".to_string();
+    }
+    if let Some(chosen) = chosen {
+        return chosen.to_string();
+    }
+    let lang = get_lang(filename);
+    if lang == "unknown" {
+        "shell".to_string()
+    } else {
+        lang
+    }
+}
+
+/// Recreates `editor` for the highlight setting, keeping its content and view
+fn rebuild_editor(
+    editor: &mut CodeEditor,
+    filename: &str,
+    highlight: bool,
+    chosen: Option<&str>,
+) -> Result<()> {
+    let content = editor.get_content();
+    let lang = editor_language(filename, FileStats::of(&content), highlight, chosen);
+    let theme = ratatui_code_editor::theme::vesper();
+    let mut rebuilt = CodeEditor::new(&lang, &content, theme)?;
+    rebuilt.set_cursor(editor.get_cursor());
+    rebuilt.set_selection(editor.get_selection());
+    rebuilt.set_offset_y(editor.get_offset_y());
+    rebuilt.set_offset_x(editor.get_offset_x());
+    *editor = rebuilt;
     Ok(())
 }
 
+/// `content` with trailing spaces and tabs stripped from every line and/or
+/// ending in exactly one newline, keeping the file's line endings
+fn cleaned_whitespace(content: &str, trim_lines: bool, final_newline: bool) -> String {
+    let mut cleaned = String::with_capacity(content.len());
+    if trim_lines {
+        for line in content.split_inclusive('\n') {
+            let body = line.trim_end_matches(['\n', '\r']);
+            cleaned.push_str(body.trim_end_matches([' ', '\t']));
+            cleaned.push_str(&line[body.len()..]);
+        }
+    } else {
+        cleaned.push_str(content);
+    }
+
+    if final_newline && !cleaned.is_empty() {
+        let newline = if content.contains("\r\n") {
+            "\r\n"
+        } else {
+            "\n"
+        };
+        cleaned.truncate(cleaned.trim_end_matches(['\n', '\r']).len());
+        cleaned.push_str(newline);
+    }
+    cleaned
+}
+
+/// Status bar note of a running AI request: a spinner turning once per
+/// frame, and the seconds waited once there are any
+fn busy_label(elapsed: Duration) -> String {
+    let frame = (elapsed.as_millis() / SPINNER_FRAME.as_millis()) as usize;
+    let spinner = SPINNER[frame % SPINNER.len()];
+    match elapsed.as_secs() {
+        0 => format!("AI {spinner}  "),
+        secs => format!("AI {spinner} {secs}s  "),
+    }
+}
+
+fn is_save_pressed(key: KeyEvent) -> bool {
+    key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('s')
+}
+
+fn is_quit_pressed(key: KeyEvent) -> bool {
+    key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('q')
+}
+
 fn clamp_editor_state(editor: &mut CodeEditor) {
     let len = editor.code_ref().len_chars();
     let cursor = editor.get_cursor().min(len);
@@ -349,3 +2079,1001 @@ fn clamp_editor_state(editor: &mut CodeEditor) {
     });
     editor.set_selection(selection);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::{MouseButton, MouseEvent};
+
+    fn press(panel: &mut EditorPanel, code: KeyCode) {
+        panel.handle_event(&Event::Key(KeyEvent::new(code, KeyModifiers::NONE)));
+    }
+
+    /// Writes `(name, content)` files under `dir`, returning their paths
+    fn write_files(dir: &Path, files: &[(&str, &str)]) -> Vec<String> {
+        files
+            .iter()
+            .map(|(name, content)| {
+                let path = dir.join(name);
+                std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+                std::fs::write(&path, content).unwrap();
+                path.to_string_lossy().to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_busy_label() {
+        assert_eq!(busy_label(Duration::ZERO), "AI ⠋  ");
+        assert_eq!(busy_label(Duration::from_millis(600)), "AI ⠹  ");
+        // the spinner goes round while the seconds count up
+        assert_eq!(busy_label(Duration::from_millis(2100)), "AI ⠋ 2s  ");
+        assert_eq!(busy_label(Duration::from_secs(61)), "AI ⠼ 61s  ");
+    }
+
+    #[test]
+    fn test_accepted_completion_cursor() {
+        let content = "fn a() {\n}\n";
+        let mut panel = EditorPanel::new("rust", content, "main.rs", None, true).unwrap();
+        panel.area = Rect::new(0, 0, 80, 24);
+        let edits = vec![Edit {
+            start: 9,
+            text: "    x();\n".to_string(),
+            operation: Operation::Insert,
+        }];
+
+        panel.apply_edits(edits).unwrap();
+        // the cursor lands right after the completion, on the line below it
+        assert_eq!(panel.editor.get_cursor(), 18);
+        let tab = Event::Key(KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE));
+        let action = panel.handle_event(&tab);
+        assert!(matches!(action, EditorAction::AcceptedAiEdit));
+        assert!(!panel.editor.has_marks());
+        assert_eq!(panel.editor.get_content(), "fn a() {\n    x();\n}\n");
+        assert_eq!(panel.editor.get_cursor(), 18);
+    }
+
+    #[tokio::test]
+    async fn test_accept_and_continue() {
+        let mut panel = EditorPanel::new("rust", "fn a() {\n}\n", "main.rs", None, true).unwrap();
+        panel.area = Rect::new(0, 0, 80, 24);
+        let completion = || {
+            vec![Edit {
+                start: 9,
+                text: "    x();\n".to_string(),
+                operation: Operation::Insert,
+            }]
+        };
+        panel.apply_edits(completion()).unwrap();
+        let version = panel.next_autocomplete_version();
+
+        // Tab only accepts, Alt+Enter accepts and asks for the next step
+        let alt_enter = Event::Key(KeyEvent::new(KeyCode::Enter, KeyModifiers::ALT));
+        let action = panel.handle_event(&alt_enter);
+        assert!(matches!(action, EditorAction::AcceptedAiEdit));
+        assert!(!panel.editor.has_marks());
+        assert_eq!(panel.editor.get_content(), "fn a() {\n    x();\n}\n");
+        assert!(panel.autocomplete_busy());
+        assert!(!panel.is_current_autocomplete(version));
+
+        // without marks it is no accept and requests nothing
+        panel.autocomplete.handle.take().unwrap().abort();
+        panel.handle_event(&alt_enter);
+        assert!(panel.autocomplete.handle.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_mid_autocomplete() {
+        let content = "fn main() {\n    let x = 1;\n}\n";
+        let mut panel = EditorPanel::new("rust", content, "main.rs", None, true).unwrap();
+
+        // a suggestion already applied but not accepted
+        panel
+            .apply_edits(vec![Edit {
+                start: 27,
+                text: "\n    let y = 2;".to_string(),
+                operation: Operation::Insert,
+            }])
+            .unwrap();
+        assert!(panel.editor.has_marks());
+
+        // a slow request still in flight, and a finished one waiting in the queue
+        let tx = panel.autocomplete.tx.clone();
+        panel.autocomplete.handle = Some(tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+            let _ = tx.send((1, Ok(Vec::new()))).await;
+        }));
+        panel
+            .autocomplete
+            .tx
+            .send((0, Ok(Vec::new())))
+            .await
+            .unwrap();
+
+        panel.shutdown().await;
+
+        assert!(panel.autocomplete.handle.is_none());
+        assert!(panel.autocomplete.rx.try_recv().is_err());
+        assert!(!panel.editor.has_marks());
+        assert_eq!(panel.editor.get_content(), content);
+    }
+
+    #[test]
+    fn test_out_of_range_edits_are_skipped() {
+        let content = "fn main() {}\n";
+        let mut panel = EditorPanel::new("rust", content, "main.rs", None, true).unwrap();
+        let insert = |start: usize, text: &str| Edit {
+            start,
+            text: text.to_string(),
+            operation: Operation::Insert,
+        };
+        let remove = |start: usize, text: &str| Edit {
+            start,
+            text: text.to_string(),
+            operation: Operation::Remove,
+        };
+
+        assert!(panel.apply_edits(vec![insert(40, "x")]).is_err());
+        // valid only if the earlier edit of the batch had grown the content
+        let batch = vec![remove(0, "fn main() {}\n"), remove(0, "x")];
+        assert!(panel.apply_edits(batch).is_err());
+        panel
+            .apply_external_edits(vec![remove(10, "{}\n!")])
+            .unwrap();
+        assert_eq!(panel.editor.get_content(), content);
+        assert!(!panel.editor.has_marks());
+
+        let batch = vec![insert(13, "// end\n"), remove(0, "fn")];
+        assert_eq!(check_edits(&batch, 13), Ok(()));
+        panel.apply_edits(batch).unwrap();
+        assert_eq!(panel.editor.get_content(), " main() {}\n// end\n");
+    }
+
+    #[test]
+    fn test_split_same_file() {
+        let content: String = (1..=40).map(|i| format!("line {i}\n")).collect();
+        let mut panel = EditorPanel::new("text", &content, "notes.txt", None, true).unwrap();
+        panel.set_area(Rect::new(0, 0, 80, 21));
+        assert!(panel.toggle_split());
+        assert_eq!(panel.area, Rect::new(0, 0, 80, 10));
+
+        // the lower pane goes to the end while the upper one stays on top
+        panel.switch_pane();
+        assert_eq!(panel.area, Rect::new(0, 11, 80, 10));
+        let end = panel.editor.code_ref().line_to_char(39);
+        panel.editor.set_cursor(end);
+        panel.editor.set_offset_y(30);
+        panel.switch_pane();
+        assert_eq!(panel.editor.get_cursor(), 0);
+        assert_eq!(panel.editor.get_offset_y(), 0);
+
+        // an edit in one pane shows in the other, which keeps its line
+        panel
+            .editor
+            .apply(ratatui_code_editor::actions::InsertText {
+                text: "top\n".to_string(),
+            });
+        panel.switch_pane();
+        assert!(panel.editor.get_content().starts_with("top\nline 1\n"));
+        let (row, _) = panel.editor.code_ref().point(panel.editor.get_cursor());
+        assert_eq!((row, panel.editor.get_offset_y()), (39, 30));
+
+        // a click in the other pane edits there
+        let click = Event::Mouse(crossterm::event::MouseEvent {
+            kind: crossterm::event::MouseEventKind::Down(crossterm::event::MouseButton::Left),
+            column: 20,
+            row: 2,
+            modifiers: KeyModifiers::NONE,
+        });
+        panel.handle_event(&click);
+        assert_eq!(panel.area.y, 0);
+
+        assert!(!panel.toggle_split());
+        assert_eq!(panel.area, Rect::new(0, 0, 80, 21));
+    }
+
+    #[tokio::test]
+    async fn test_next_candidate() {
+        let content = "let x = ;\n";
+        let mut panel = EditorPanel::new("rust", content, "main.rs", None, true).unwrap();
+        let candidate = |value: &str, logprob| Candidate {
+            edits: vec![Edit {
+                start: 8,
+                text: value.to_string(),
+                operation: Operation::Insert,
+            }],
+            logprob,
+        };
+        let candidates = vec![candidate("1", Some(-0.1)), candidate("2", None)];
+        panel.handle_autocomplete(candidates).await.unwrap();
+        assert_eq!(panel.editor.get_content(), "let x = 1;\n");
+        assert_eq!(panel.candidate_label().unwrap(), "candidate 1 of 2, 90%");
+
+        assert!(panel.next_candidate().unwrap());
+        assert_eq!(panel.editor.get_content(), "let x = 2;\n");
+        assert_eq!(panel.candidate_label().unwrap(), "candidate 2 of 2");
+        assert!(panel.next_candidate().unwrap());
+        assert_eq!(panel.editor.get_content(), "let x = 1;\n");
+
+        // once accepted, the candidates are done with
+        panel.editor.remove_marks();
+        assert!(!panel.next_candidate().unwrap());
+        panel.editor.apply(ratatui_code_editor::actions::Undo {});
+        assert_eq!(panel.editor.get_content(), content);
+    }
+
+    #[test]
+    fn test_apply_edits_json() {
+        let content = "fn main() {}\n";
+        let mut panel = EditorPanel::new("rust", content, "main.rs", None, true).unwrap();
+        let json = r#"[
+            {"start": 3, "text": "main", "operation": "remove"},
+            {"start": 3, "text": "run", "operation": "insert"}
+        ]"#;
+        assert_eq!(panel.apply_edits_json(json).unwrap(), 2);
+        assert_eq!(panel.editor.get_content(), "fn run() {}\n");
+        assert!(panel.editor.has_marks());
+
+        // malformed or out of range edits leave the buffer alone
+        assert!(panel.apply_edits_json(r#"[{"start": 0}]"#).is_err());
+        let far = r#"[{"start": 40, "text": "x", "operation": "insert"}]"#;
+        assert!(panel.apply_edits_json(far).is_err());
+        assert_eq!(panel.apply_edits_json("[]").unwrap(), 0);
+
+        panel.editor.apply(ratatui_code_editor::actions::Undo {});
+        assert_eq!(panel.editor.get_content(), content);
+    }
+
+    #[test]
+    fn test_stale_autocomplete() {
+        let content = "fn main() {\n    let x = 1;\n}\n";
+        let mut panel = EditorPanel::new("rust", content, "main.rs", None, true).unwrap();
+        panel.editor.set_cursor(26);
+
+        // a quick second trigger makes the first result stale
+        let first = panel.next_autocomplete_version();
+        let second = panel.next_autocomplete_version();
+        assert!(!panel.is_current_autocomplete(first));
+        assert!(panel.is_current_autocomplete(second));
+
+        // so does moving the cursor or typing before the result arrives
+        panel.editor.set_cursor(10);
+        assert!(!panel.is_current_autocomplete(second));
+        panel.editor.set_cursor(26);
+        assert!(panel.is_current_autocomplete(second));
+        panel
+            .editor
+            .apply(ratatui_code_editor::actions::InsertText {
+                text: "x".to_string(),
+            });
+        panel.editor.set_cursor(26);
+        assert!(!panel.is_current_autocomplete(second));
+
+        let third = panel.next_autocomplete_version();
+        assert!(panel.is_current_autocomplete(third));
+    }
+
+    #[test]
+    fn test_large_file_stats() {
+        let small = FileStats::of("fn main() {}\n");
+        assert!(!small.is_large());
+
+        let many_lines = "x\n".repeat(LARGE_FILE_LINES + 1);
+        let stats = FileStats::of(&many_lines);
+        assert!(stats.is_large());
+        assert_eq!(stats.to_string(), "0.1 MB, 50001 lines");
+    }
+
+    #[test]
+    fn test_tab_indents_selected_lines() {
+        let content = "fn main() {\n    let a = 1;\nlet b = 2;\n}\n";
+        let mut panel = EditorPanel::new("rust", content, "main.rs", None, true).unwrap();
+        let start = panel.editor.code_ref().line_to_char(1);
+        let end = panel.editor.code_ref().line_to_char(3);
+        panel.editor.set_cursor(end);
+        panel.editor.set_selection(Some(Selection::new(start, end)));
+
+        press(&mut panel, KeyCode::Tab);
+        assert_eq!(
+            panel.editor.get_content(),
+            "fn main() {\n        let a = 1;\n    let b = 2;\n}\n"
+        );
+        let selection = panel.editor.get_selection().unwrap();
+        assert_eq!(selection.start, start);
+        assert_eq!(selection.end, end + 8);
+
+        press(&mut panel, KeyCode::BackTab);
+        press(&mut panel, KeyCode::BackTab);
+        assert_eq!(
+            panel.editor.get_content(),
+            "fn main() {\nlet a = 1;\nlet b = 2;\n}\n"
+        );
+
+        panel.editor.apply(ratatui_code_editor::actions::Undo {});
+        panel.editor.apply(ratatui_code_editor::actions::Undo {});
+        panel.editor.apply(ratatui_code_editor::actions::Undo {});
+        assert_eq!(panel.editor.get_content(), content);
+    }
+
+    #[test]
+    fn test_jump_between_changes() {
+        let content = "a\nb\nc\nd\ne\n";
+        let mut panel = EditorPanel::new("text", content, "notes.txt", None, true).unwrap();
+        panel.area = Rect::new(0, 0, 80, 24);
+        assert_eq!(panel.jump_to_change(false), None);
+
+        let code = panel.editor.code_ref();
+        let (b, d) = (code.line_to_char(1), code.line_to_char(3));
+        panel.editor.set_cursor(d);
+        press(&mut panel, KeyCode::Char('x'));
+        panel.editor.set_cursor(b);
+        press(&mut panel, KeyCode::Char('y'));
+        assert!(panel.status_info().starts_with("±2"));
+
+        panel.editor.set_cursor(0);
+        assert_eq!(panel.jump_to_change(false), Some((1, 2)));
+        assert_eq!(panel.editor.get_cursor(), b);
+        assert_eq!(panel.jump_to_change(false), Some((2, 2)));
+        assert_eq!(panel.jump_to_change(false), Some((1, 2)));
+        assert_eq!(panel.jump_to_change(true), Some((2, 2)));
+        assert!(panel.flash_deadline().is_some());
+    }
+
+    #[test]
+    fn test_scroll_keeps_cursor() {
+        let content = "line\n".repeat(50);
+        let mut panel = EditorPanel::new("text", &content, "notes.txt", None, true).unwrap();
+        panel.area = Rect::new(0, 0, 80, 10);
+        panel.editor.set_cursor(3);
+
+        let scroll = |panel: &mut EditorPanel, code, modifiers| {
+            panel.handle_event(&Event::Key(KeyEvent::new(code, modifiers)));
+        };
+        scroll(&mut panel, KeyCode::Down, KeyModifiers::CONTROL);
+        assert_eq!(panel.editor.get_offset_y(), 1);
+        scroll(&mut panel, KeyCode::PageDown, KeyModifiers::ALT);
+        assert_eq!(panel.editor.get_offset_y(), 11);
+        for _ in 0..10 {
+            scroll(&mut panel, KeyCode::PageDown, KeyModifiers::ALT);
+        }
+        assert_eq!(panel.editor.get_offset_y(), 50);
+        for _ in 0..10 {
+            scroll(&mut panel, KeyCode::PageUp, KeyModifiers::ALT);
+        }
+        assert_eq!(panel.editor.get_offset_y(), 0);
+        assert_eq!(panel.editor.get_cursor(), 3);
+        assert_eq!(panel.editor.get_content(), content);
+    }
+
+    #[test]
+    fn test_pan_keeps_cursor() {
+        let content = format!("{}\n{}", "x".repeat(100), "short\n".repeat(50));
+        let mut panel = EditorPanel::new("text", &content, "data.txt", None, true).unwrap();
+        panel.area = Rect::new(0, 0, 80, 10);
+        panel.editor.set_cursor(3);
+
+        let pan = |panel: &mut EditorPanel, code| {
+            let modifiers = KeyModifiers::ALT | KeyModifiers::SHIFT;
+            panel.handle_event(&Event::Key(KeyEvent::new(code, modifiers)));
+        };
+        // 73 columns of text show the 100 char line from column 27 on
+        let mut offsets = Vec::new();
+        for _ in 0..4 {
+            pan(&mut panel, KeyCode::Right);
+            offsets.push(panel.editor.get_offset_x());
+        }
+        assert_eq!(offsets, vec![10, 20, 27, 27]);
+
+        // with only short lines on screen it goes no further, but stays
+        panel.scroll_viewport(1);
+        pan(&mut panel, KeyCode::Right);
+        assert_eq!(panel.editor.get_offset_x(), 27);
+        for _ in 0..4 {
+            pan(&mut panel, KeyCode::Left);
+        }
+        assert_eq!(panel.editor.get_offset_x(), 0);
+        assert_eq!(panel.editor.get_cursor(), 3);
+        assert!(panel.editor.get_selection().is_none());
+    }
+
+    #[test]
+    fn test_insert_file_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("src").join("main.rs");
+        let filename = file.to_string_lossy().to_string();
+        let mut panel = EditorPanel::new("rust", "", &filename, None, true).unwrap();
+        panel.area = Rect::new(0, 0, 80, 24);
+        let roots = vec![dir.path().to_path_buf()];
+
+        let inserted = panel.insert_file_path(PathForm::Relative, &roots);
+        assert_eq!(inserted.as_deref(), Some("src/main.rs"));
+        panel.insert_file_path(PathForm::Stem, &roots);
+        assert_eq!(panel.editor.get_content(), "src/main.rsmain");
+
+        panel
+            .editor
+            .apply(ratatui_code_editor::actions::SelectAll {});
+        panel.insert_file_path(PathForm::Absolute, &roots);
+        assert_eq!(panel.editor.get_content(), filename);
+
+        panel.editor.apply(ratatui_code_editor::actions::Undo {});
+        assert_eq!(panel.editor.get_content(), "src/main.rsmain");
+    }
+
+    #[test]
+    fn test_cleaned_whitespace() {
+        let content = "fn main() {  \r\n\tx; \t\r\n}\r\n\r\n";
+        assert_eq!(cleaned_whitespace(content, false, false), content);
+        assert_eq!(
+            cleaned_whitespace(content, true, false),
+            "fn main() {\r\n\tx;\r\n}\r\n\r\n"
+        );
+        assert_eq!(
+            cleaned_whitespace(content, true, true),
+            "fn main() {\r\n\tx;\r\n}\r\n"
+        );
+        assert_eq!(cleaned_whitespace("a  ", false, true), "a  \n");
+        assert_eq!(cleaned_whitespace("", true, true), "");
+    }
+
+    #[tokio::test]
+    async fn test_save_trims_whitespace() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("main.rs");
+        let filename = path.to_string_lossy().to_string();
+        let content = "let a = 1;   \nlet b = 2;\t";
+        let mut panel = EditorPanel::new("rust", content, &filename, None, true).unwrap();
+        panel.trim_trailing_whitespace = true;
+        panel.final_newline = true;
+        panel.editor.set_cursor(12);
+
+        panel.save().await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "let a = 1;\nlet b = 2;\n"
+        );
+        assert_eq!(panel.editor.get_cursor(), 10);
+
+        panel.editor.apply(ratatui_code_editor::actions::Undo {});
+        assert_eq!(panel.editor.get_content(), content);
+    }
+
+    #[tokio::test]
+    async fn test_recent_buffers() {
+        let dir = tempfile::tempdir().unwrap();
+        let names = write_files(
+            dir.path(),
+            &["a.rs", "b.rs", "c.rs", "d.rs", "e.rs"].map(|name| (name, "fn main() {}\n")),
+        );
+        let mut panel = EditorPanel::new("rust", "", &names[0], None, true).unwrap();
+
+        panel.open_file(&names[1]).await.unwrap();
+        panel.open_file(&names[2]).await.unwrap();
+        assert_eq!(panel.recent, vec![names[1].clone(), names[0].clone()]);
+
+        panel.open_file(&names[0]).await.unwrap();
+        assert_eq!(panel.recent, vec![names[2].clone(), names[1].clone()]);
+        assert_eq!(panel.opened.len(), panel.recent.len());
+        assert!(panel.evicted.is_empty());
+
+        // past the most recent few, inactive buffers drop their highlights
+        panel.open_file(&names[3]).await.unwrap();
+        panel.open_file(&names[4]).await.unwrap();
+        assert_eq!(panel.evicted, HashSet::from([names[1].clone()]));
+
+        // and get them back once shown again
+        use ratatui::{Terminal, backend::TestBackend};
+        panel.open_file(&names[1]).await.unwrap();
+        assert_eq!(panel.evicted, HashSet::from([names[2].clone()]));
+        panel.area = Rect::new(0, 0, 40, 3);
+        let mut terminal = Terminal::new(TestBackend::new(40, 3)).unwrap();
+        let frame = terminal.draw(|frame| panel.render(frame)).unwrap();
+        // `fn` and `main` are styled apart
+        assert_ne!(frame.buffer[(7, 0)].fg, frame.buffer[(10, 0)].fg);
+    }
+
+    #[tokio::test]
+    async fn test_open_unreadable_file_keeps_buffer() {
+        let dir = tempfile::tempdir().unwrap();
+        let binary = dir.path().join("data.bin");
+        std::fs::write(&binary, [0xff, 0xfe, 0x00]).unwrap();
+        let missing = dir.path().join("gone.rs");
+
+        let mut panel = EditorPanel::new("rust", "fn main() {}", "main.rs", None, true).unwrap();
+        panel.editor.set_cursor(3);
+        for path in [binary, missing] {
+            let result = panel.open_file(&path.to_string_lossy()).await;
+            assert!(result.is_err());
+            assert_eq!(panel.filename, "main.rs");
+            assert_eq!(panel.editor.get_content(), "fn main() {}");
+            assert_eq!(panel.editor.get_cursor(), 3);
+            assert!(panel.opened.is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_set_language() {
+        let dir = tempfile::tempdir().unwrap();
+        let names = write_files(
+            dir.path(),
+            &[
+                ("lib.inc", "fn main() {}\n"),
+                ("other.rs", "fn main() {}\n"),
+            ],
+        );
+        let mut panel = EditorPanel::new("text", "", "", None, true).unwrap();
+        panel.area = Rect::new(0, 0, 80, 24);
+        panel.open_file(&names[0]).await.unwrap();
+        assert_eq!(panel.language(), "shell");
+
+        assert!(panel.set_language("cobol").is_err());
+        panel.set_language(" Rust ").unwrap();
+        assert_eq!(panel.language(), "rust");
+        assert!(panel.status_info().starts_with("rust  Ln 1"));
+        panel.model = Some("mistralai/codestral-2508".to_string());
+        assert!(
+            panel
+                .status_info()
+                .starts_with("codestral-2508  rust  Ln 1")
+        );
+
+        // remembered for the file while the session lasts
+        panel.open_file(&names[1]).await.unwrap();
+        panel.opened.clear();
+        panel.open_file(&names[0]).await.unwrap();
+        assert_eq!(panel.language(), "rust");
+        assert_eq!(panel.editor.get_content(), "fn main() {}\n");
+    }
+
+    #[tokio::test]
+    async fn test_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.rs");
+        std::fs::write(&path, "fn a() {}\n").unwrap();
+        let mut panel = EditorPanel::new("text", "", "", None, true).unwrap();
+        panel.area = Rect::new(0, 0, 80, 24);
+        panel.open_file(&path.to_string_lossy()).await.unwrap();
+        assert!(!panel.differs_from_disk());
+
+        panel.editor.set_cursor(5);
+        panel
+            .editor
+            .apply(ratatui_code_editor::actions::InsertText {
+                text: "x".to_string(),
+            });
+        assert!(panel.differs_from_disk());
+        std::fs::write(&path, "fn b() {}\n").unwrap();
+
+        panel.reload().await.unwrap();
+        assert_eq!(panel.editor.get_content(), "fn b() {}\n");
+        assert_eq!(panel.editor.get_cursor(), 0);
+        assert!(!panel.differs_from_disk());
+        // the old buffer's history is gone
+        panel.editor.apply(ratatui_code_editor::actions::Undo {});
+        assert_eq!(panel.editor.get_content(), "fn b() {}\n");
+    }
+
+    #[tokio::test]
+    async fn test_save_keeps_encoding() {
+        let dir = tempfile::tempdir().unwrap();
+        let latin1 = dir.path().join("latin1.txt");
+        std::fs::write(&latin1, b"caf\xe9\n").unwrap();
+        let latin1 = latin1.to_string_lossy().to_string();
+
+        let mut panel = EditorPanel::new("rust", "fn main() {}", "main.rs", None, true).unwrap();
+        panel.area = Rect::new(0, 0, 80, 24);
+        panel.open_file(&latin1).await.unwrap();
+        assert_eq!(panel.editor.get_content(), "café\n");
+        assert_eq!(panel.status_info(), "Latin-1  shell  Ln 1, Col 1 ");
+
+        panel.editor.set_cursor(0);
+        panel
+            .editor
+            .apply(ratatui_code_editor::actions::InsertText {
+                text: "é ".to_string(),
+            });
+        panel.save().await.unwrap();
+        assert_eq!(std::fs::read(&latin1).unwrap(), b"\xe9 caf\xe9\n");
+    }
+
+    #[tokio::test]
+    async fn test_save_detects_external_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        std::fs::write(&path, "one\n").unwrap();
+        let filename = path.to_string_lossy().to_string();
+
+        let mut panel = EditorPanel::new("text", "", "", None, true).unwrap();
+        panel.open_file(&filename).await.unwrap();
+        panel.editor.set_content("mine\n");
+        assert!(!panel.changed_on_disk());
+
+        // the file changes behind the buffer's back
+        std::fs::write(&path, "theirs, longer\n").unwrap();
+        assert!(panel.changed_on_disk());
+        assert!(!panel.save().await.unwrap());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "theirs, longer\n");
+
+        panel.overwrite().await.unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "mine\n");
+        assert!(!panel.changed_on_disk());
+        assert!(panel.save().await.unwrap());
+
+        // without the check a save writes over it right away
+        std::fs::write(&path, "theirs again\n").unwrap();
+        panel.confirm_overwrite = false;
+        assert!(panel.save().await.unwrap());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "mine\n");
+
+        // with writes off nothing reaches the disk
+        panel.no_write = true;
+        panel.editor.set_content("proposed\n");
+        assert!(panel.save().await.is_err());
+        assert!(panel.overwrite().await.is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "mine\n");
+        assert!(panel.status_info().starts_with("no-write  "));
+    }
+
+    #[test]
+    fn test_normalize_line_endings() {
+        let content = "a\r\nb\nc\r\nd\r\n";
+        let mut panel = EditorPanel::new("text", content, "mixed.txt", None, true).unwrap();
+        assert_eq!(panel.mixed_line_endings(), Some((1, 3)));
+
+        panel
+            .editor
+            .set_cursor(panel.editor.code_ref().line_to_char(2) + 1);
+        assert_eq!(panel.normalize_line_endings(), Some("CRLF"));
+        assert_eq!(panel.editor.get_content(), "a\r\nb\r\nc\r\nd\r\n");
+        assert_eq!(
+            panel.editor.code_ref().point(panel.editor.get_cursor()),
+            (2, 1)
+        );
+        assert_eq!(panel.mixed_line_endings(), None);
+        assert_eq!(panel.normalize_line_endings(), None);
+
+        panel.editor.apply(ratatui_code_editor::actions::Undo {});
+        assert_eq!(panel.editor.get_content(), content);
+    }
+
+    #[test]
+    fn test_insert_template() {
+        let mut panel = EditorPanel::new("text", "", "main.rs", None, true).unwrap();
+        panel.area = Rect::new(0, 0, 80, 24);
+        assert!(panel.insert_template("fn main() {\n    $0\n}"));
+        assert_eq!(panel.editor.get_content(), "fn main() {\n    \n}\n");
+        assert_eq!(panel.editor.get_cursor(), 16);
+        assert!(!panel.insert_template("//! $0"));
+    }
+
+    #[test]
+    fn test_undo_history() {
+        let mut panel = EditorPanel::new("text", "x\n", "main.rs", None, true).unwrap();
+        panel.area = Rect::new(0, 0, 80, 24);
+        for text in ["one\n", "two\n", "three\n"] {
+            panel.editor.set_cursor(panel.editor.code_ref().len());
+            panel.wrap_selection(text);
+        }
+        panel.editor.apply(ratatui_code_editor::actions::Undo {});
+
+        let history = panel.undo_history();
+        let steps: Vec<isize> = history.iter().map(|e| e.steps).collect();
+        assert_eq!(steps, vec![1, 0, -1, -2]);
+        assert!(history[0].label.starts_with("line 4 "));
+        assert!(history[0].label.ends_with("three"));
+        assert!(history[3].label.ends_with("one"));
+        // reading the history leaves the buffer alone
+        assert_eq!(panel.editor.get_content(), "x\none\ntwo\n");
+
+        panel.travel_history(-2);
+        assert_eq!(panel.editor.get_content(), "x\n");
+        panel.travel_history(3);
+        assert_eq!(panel.editor.get_content(), "x\none\ntwo\nthree\n");
+        assert_eq!(panel.undo_history().len(), 4);
+    }
+
+    #[test]
+    fn test_tiny_area() {
+        use ratatui::{Terminal, backend::TestBackend};
+
+        let content = format!("fn main() {{\n\t{}\n}}\n", "x".repeat(40));
+        for (width, height) in [(0, 0), (1, 1), (5, 0), (3, 2), (12, 3)] {
+            let mut panel = EditorPanel::new("rust", &content, "main.rs", None, true).unwrap();
+            panel.show_whitespace = true;
+            panel.area = Rect::new(0, 0, width, height);
+            panel.editor.set_cursor(50);
+            panel.handle_event(&Event::Key(KeyEvent::new(KeyCode::End, KeyModifiers::NONE)));
+            panel.wrap_selection("y");
+            let backend = TestBackend::new(width.max(1), height.max(1));
+            let mut terminal = Terminal::new(backend).unwrap();
+            terminal.draw(|frame| panel.render(frame)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_wrap_selection() {
+        let content = "fn main() {\n    foo();\n    bar();\n}\n";
+        let mut panel = EditorPanel::new("text", content, "main.rs", None, true).unwrap();
+        panel.area = Rect::new(0, 0, 80, 24);
+        let code = panel.editor.code_ref();
+        let (start, end) = (code.line_to_char(1) + 4, code.line_to_char(3));
+        panel.editor.set_selection(Some(Selection::new(start, end)));
+
+        panel.wrap_selection("if $0 {\n    $SELECTION\n}");
+        assert_eq!(
+            panel.editor.get_content(),
+            "fn main() {\n    if  {\n        foo();\n        bar();\n    }\n}\n"
+        );
+        assert_eq!(
+            panel.editor.code_ref().point(panel.editor.get_cursor()),
+            (1, 7)
+        );
+
+        panel.editor.apply(ratatui_code_editor::actions::Undo {});
+        assert_eq!(panel.editor.get_content(), content);
+
+        let start = panel.editor.code_ref().line_to_char(1) + 4;
+        panel
+            .editor
+            .set_selection(Some(Selection::new(start, start + 3)));
+        panel.wrap_selection("Some($SELECTION)");
+        assert!(panel.editor.get_content().contains("    Some(foo)();\n"));
+        assert_eq!(panel.editor.get_cursor(), start + 9);
+    }
+
+    #[tokio::test]
+    async fn test_set_highlight() {
+        let dir = tempfile::tempdir().unwrap();
+        let other = dir.path().join("lib.rs");
+        std::fs::write(&other, "pub fn lib() {}\n").unwrap();
+
+        let mut panel = EditorPanel::new("rust", "fn main() {}", "main.rs", None, false).unwrap();
+        assert!(!panel.editor.code_ref().is_highlight());
+        panel.open_file(&other.to_string_lossy()).await.unwrap();
+        assert!(!panel.editor.code_ref().is_highlight());
+
+        panel.editor.set_cursor(4);
+        panel.set_highlight(true).unwrap();
+        assert!(panel.editor.code_ref().is_highlight());
+        assert_eq!(panel.editor.get_cursor(), 4);
+        assert_eq!(panel.editor.get_content(), "pub fn lib() {}\n");
+        assert!(panel.opened["main.rs"].code_ref().is_highlight());
+    }
+
+    #[tokio::test]
+    async fn test_external_change_is_a_minimal_edit() {
+        let old: String = (0..200).map(|i| format!("line {i}\n")).collect();
+        let new = old.replace("line 150\n", "line 150 changed\n");
+
+        let edits = external_edits(&old, &new);
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].text, " changed");
+        assert!(matches!(edits[0].operation, Operation::Insert));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("watched.txt");
+        std::fs::write(&path, &old).unwrap();
+        let filename = path.to_string_lossy().to_string();
+        let mut panel = EditorPanel::new("text", &old, &filename, None, true).unwrap();
+        panel.editor.set_cursor(12);
+
+        std::fs::write(&path, &new).unwrap();
+        let event = notify::Event::new(notify::EventKind::Modify(notify::event::ModifyKind::Data(
+            notify::event::DataChange::Content,
+        )))
+        .add_path(path.clone());
+        panel.handle_file_change(&event).await.unwrap();
+
+        assert_eq!(panel.editor.get_content(), new);
+        assert_eq!(panel.editor.get_cursor(), 12);
+        // the change is undoable like any edit
+        panel.editor.apply(ratatui_code_editor::actions::Undo {});
+        assert_eq!(panel.editor.get_content(), old);
+    }
+
+    #[tokio::test]
+    async fn test_ignore_path_case() {
+        let dir = tempfile::tempdir().unwrap();
+        let names = write_files(
+            dir.path(),
+            &[
+                ("src/main.rs", "// src/main.rs\n"),
+                ("src/lib.rs", "// src/lib.rs\n"),
+            ],
+        );
+        let upper = |name: &str| name.replace("src/", "Src/").replace(".rs", ".RS");
+
+        let mut panel =
+            EditorPanel::new("rust", "// src/main.rs\n", &names[0], None, true).unwrap();
+        panel.area = Rect::new(0, 0, 80, 24);
+        panel.preload(&names[1]).unwrap();
+        // on a case-sensitive filesystem the other spelling is another file
+        assert!(panel.preload(&upper(&names[0])).is_err());
+        assert!(panel.open_file(&upper(&names[1])).await.is_err());
+
+        // the same buffers, whatever the case of the name
+        panel.ignore_path_case = true;
+        panel.preload(&upper(&names[0])).unwrap();
+        panel.open_file(&upper(&names[1])).await.unwrap();
+        assert_eq!(panel.filename, names[1]);
+        panel.open_file(&upper(&names[0])).await.unwrap();
+        assert_eq!(panel.filename, names[0]);
+        let open: Vec<&String> = panel.open_files().collect();
+        assert_eq!(open, vec![&names[0], &names[1]]);
+    }
+
+    #[tokio::test]
+    async fn test_preload_and_next_buffer() {
+        let dir = tempfile::tempdir().unwrap();
+        let names = write_files(
+            dir.path(),
+            &[
+                ("a.rs", "// a.rs\n"),
+                ("b.rs", "// b.rs\n"),
+                ("c.rs", "// c.rs\n"),
+            ],
+        );
+
+        let mut panel = EditorPanel::new("rust", "// a.rs\n", &names[0], None, true).unwrap();
+        panel.preload(&names[1]).unwrap();
+        panel.preload(&names[2]).unwrap();
+        assert!(
+            panel
+                .preload(&dir.path().join("gone.rs").to_string_lossy())
+                .is_err()
+        );
+        assert_eq!(panel.open_files().count(), 3);
+
+        let mut visited = Vec::new();
+        for _ in 0..3 {
+            let next = panel.next_buffer().unwrap();
+            panel.open_file(&next).await.unwrap();
+            visited.push(panel.filename.clone());
+        }
+        assert_eq!(
+            visited,
+            vec![names[1].clone(), names[2].clone(), names[0].clone()]
+        );
+
+        // an inactive buffer follows its file
+        std::fs::write(&names[1], "// b.rs changed\n").unwrap();
+        let event = notify::Event::new(notify::EventKind::Modify(notify::event::ModifyKind::Data(
+            notify::event::DataChange::Content,
+        )))
+        .add_path(PathBuf::from(&names[1]));
+        panel.handle_file_change(&event).await.unwrap();
+        assert_eq!(panel.opened[&names[1]].get_content(), "// b.rs changed\n");
+    }
+
+    #[test]
+    fn test_large_deletion_waits_for_confirmation() {
+        let content = "fn a() {\n    1\n}\nfn b() {\n    2\n}\n";
+        let mut panel = EditorPanel::new("rust", content, "main.rs", None, true).unwrap();
+        panel.area = Rect::new(0, 0, 80, 24);
+        panel.confirm_delete_lines = 2;
+        let delete_a = || {
+            vec![Edit {
+                start: 0,
+                text: "fn a() {\n    1\n}\n".to_string(),
+                operation: Operation::Remove,
+            }]
+        };
+
+        // discarded by any other key
+        assert_eq!(panel.apply_edits(delete_a()).unwrap(), Some(3));
+        assert_eq!(panel.editor.get_content(), content);
+        press(&mut panel, KeyCode::Char('x'));
+        assert_eq!(panel.editor.get_content(), content);
+        assert!(panel.pending_edits.is_none());
+
+        // still waiting after mouse moves, scrolling and focus changes
+        assert_eq!(panel.apply_edits(delete_a()).unwrap(), Some(3));
+        let mouse = |kind| {
+            Event::Mouse(MouseEvent {
+                kind,
+                column: 10,
+                row: 1,
+                modifiers: KeyModifiers::NONE,
+            })
+        };
+        panel.handle_event(&mouse(MouseEventKind::Moved));
+        panel.handle_event(&mouse(MouseEventKind::ScrollDown));
+        panel.handle_event(&Event::FocusLost);
+        assert!(panel.pending_edits.is_some());
+
+        // a paste or a click discards them without pasting or moving
+        panel.handle_event(&Event::Paste("pasted".to_string()));
+        assert!(panel.pending_edits.is_none());
+        assert_eq!(panel.editor.get_content(), content);
+        assert_eq!(panel.apply_edits(delete_a()).unwrap(), Some(3));
+        panel.handle_event(&mouse(MouseEventKind::Down(MouseButton::Left)));
+        assert!(panel.pending_edits.is_none());
+        assert_eq!(panel.editor.get_cursor(), 0);
+
+        // applied with marks on Tab
+        assert_eq!(panel.apply_edits(delete_a()).unwrap(), Some(3));
+        press(&mut panel, KeyCode::Tab);
+        assert_eq!(panel.editor.get_content(), "fn b() {\n    2\n}\n");
+        assert!(panel.editor.has_marks());
+
+        // small edits apply right away
+        panel.editor.remove_marks();
+        let small = vec![Edit {
+            start: 0,
+            text: "pub ".to_string(),
+            operation: Operation::Insert,
+        }];
+        assert_eq!(panel.apply_edits(small).unwrap(), None);
+        assert!(panel.editor.get_content().starts_with("pub fn b"));
+    }
+
+    #[test]
+    fn test_selected_lines() {
+        let mut panel = EditorPanel::new("text", "a\nb\nc\nd\n", "notes.txt", None, true).unwrap();
+        panel.editor.set_cursor(2);
+        assert_eq!(panel.selected_lines(), (2, 2));
+
+        panel.editor.set_selection(Some(Selection::new(2, 6)));
+        assert_eq!(panel.selected_lines(), (2, 3));
+        panel.editor.set_selection(Some(Selection::new(2, 5)));
+        assert_eq!(panel.selected_lines(), (2, 3));
+    }
+
+    #[test]
+    fn test_enter_keeps_indentation() {
+        let content = "def main():\n    if x:\n        pass\n";
+        let mut panel = EditorPanel::new("python", content, "main.py", None, true).unwrap();
+        panel.area = Rect::new(0, 0, 80, 24);
+        let end_of_if = panel.editor.code_ref().line_to_char(1) + 9;
+        panel.editor.set_cursor(end_of_if);
+
+        press(&mut panel, KeyCode::Enter);
+        assert_eq!(
+            panel.editor.get_content(),
+            "def main():\n    if x:\n        \n        pass\n"
+        );
+
+        panel.editor.apply(ratatui_code_editor::actions::Undo {});
+        assert_eq!(panel.editor.get_content(), content);
+
+        let end_of_pass = panel.editor.code_ref().line_to_char(2) + 12;
+        panel.editor.set_cursor(end_of_pass);
+        press(&mut panel, KeyCode::Enter);
+        assert_eq!(
+            panel.editor.get_content(),
+            "def main():\n    if x:\n        pass\n        \n"
+        );
+
+        panel.auto_indent = false;
+        press(&mut panel, KeyCode::Enter);
+        assert!(panel.editor.get_content().ends_with("        \n\n"));
+    }
+
+    #[test]
+    fn test_paste_reindents() {
+        let content = "fn main() {\n    \n}\n";
+        let mut panel = EditorPanel::new("rust", content, "main.rs", None, true).unwrap();
+        panel.area = Rect::new(0, 0, 80, 24);
+        panel.reindent_paste = true;
+        let inside = panel.editor.code_ref().line_to_char(1) + 4;
+        panel.editor.set_cursor(inside);
+        let pasted = "if x {\n\ty();\n}".to_string();
+
+        panel.handle_event(&Event::Paste(pasted.clone()));
+        assert_eq!(
+            panel.editor.get_content(),
+            "fn main() {\n    if x {\n    \ty();\n    }\n}\n"
+        );
+        panel.editor.apply(ratatui_code_editor::actions::Undo {});
+        assert_eq!(panel.editor.get_content(), content);
+
+        // off, the text goes in as pasted
+        panel.reindent_paste = false;
+        panel.editor.set_cursor(inside);
+        panel.handle_event(&Event::Paste(pasted));
+        assert_eq!(
+            panel.editor.get_content(),
+            "fn main() {\n    if x {\n\ty();\n}\n}\n"
+        );
+    }
+}
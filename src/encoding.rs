@@ -0,0 +1,152 @@
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+const UTF16LE_BOM: &[u8] = &[0xFF, 0xFE];
+const UTF16BE_BOM: &[u8] = &[0xFE, 0xFF];
+
+/// Encoding a file was read with, so it can be written back the same way
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+    /// Fallback for text that is not valid UTF-8, every byte is one char
+    Latin1,
+}
+
+impl Encoding {
+    /// Decodes `bytes` by their BOM, as UTF-8 when valid, else as Latin-1;
+    /// content with NUL bytes and no UTF-16 BOM is rejected as binary
+    pub fn decode(bytes: &[u8]) -> Result<(String, Encoding)> {
+        if let Some(rest) = bytes.strip_prefix(UTF8_BOM) {
+            let text = String::from_utf8(rest.to_vec()).map_err(invalid)?;
+            return Ok((text, Encoding::Utf8Bom));
+        }
+        if let Some(rest) = bytes.strip_prefix(UTF16LE_BOM) {
+            return Ok((decode_utf16(rest, u16::from_le_bytes)?, Encoding::Utf16Le));
+        }
+        if let Some(rest) = bytes.strip_prefix(UTF16BE_BOM) {
+            return Ok((decode_utf16(rest, u16::from_be_bytes)?, Encoding::Utf16Be));
+        }
+        if let Ok(text) = std::str::from_utf8(bytes) {
+            return Ok((text.to_string(), Encoding::Utf8));
+        }
+        if bytes.contains(&0) {
+            return Err(Error::new(ErrorKind::InvalidData, "binary file"));
+        }
+        let text = bytes.iter().map(|&b| b as char).collect();
+        Ok((text, Encoding::Latin1))
+    }
+
+    /// Encodes `text` back, with the BOM it was read with
+    pub fn encode(self, text: &str) -> Result<Vec<u8>> {
+        let bytes = match self {
+            Encoding::Utf8 => text.as_bytes().to_vec(),
+            Encoding::Utf8Bom => [UTF8_BOM, text.as_bytes()].concat(),
+            Encoding::Utf16Le => {
+                let units = text.encode_utf16().flat_map(u16::to_le_bytes);
+                UTF16LE_BOM.iter().copied().chain(units).collect()
+            }
+            Encoding::Utf16Be => {
+                let units = text.encode_utf16().flat_map(u16::to_be_bytes);
+                UTF16BE_BOM.iter().copied().chain(units).collect()
+            }
+            Encoding::Latin1 => text
+                .chars()
+                .map(|c| {
+                    u8::try_from(c).map_err(|_| {
+                        let msg = format!("{c:?} cannot be saved as {self}");
+                        Error::new(ErrorKind::InvalidData, msg)
+                    })
+                })
+                .collect::<Result<_>>()?,
+        };
+        Ok(bytes)
+    }
+}
+
+impl std::fmt::Display for Encoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let label = match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf8Bom => "UTF-8 BOM",
+            Encoding::Utf16Le => "UTF-16 LE",
+            Encoding::Utf16Be => "UTF-16 BE",
+            Encoding::Latin1 => "Latin-1",
+        };
+        f.write_str(label)
+    }
+}
+
+/// Reads and decodes a text file, see [`Encoding::decode`]
+pub fn read_file(path: impl AsRef<Path>) -> Result<(String, Encoding)> {
+    Encoding::decode(&std::fs::read(path)?)
+}
+
+/// Like [`read_file`], but a path that does not exist yet is an empty
+/// UTF-8 file, created by the first save
+pub fn read_or_new(path: impl AsRef<Path>) -> Result<(String, Encoding)> {
+    match read_file(path) {
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(Default::default()),
+        result => result,
+    }
+}
+
+/// Encodes and writes a text file, see [`Encoding::encode`]
+pub fn write_file(path: impl AsRef<Path>, text: &str, encoding: Encoding) -> Result<()> {
+    std::fs::write(path, encoding.encode(text)?)
+}
+
+fn decode_utf16(bytes: &[u8], unit: fn([u8; 2]) -> u16) -> Result<String> {
+    if !bytes.len().is_multiple_of(2) {
+        return Err(Error::new(ErrorKind::InvalidData, "truncated UTF-16"));
+    }
+    let units: Vec<u16> = bytes.chunks_exact(2).map(|c| unit([c[0], c[1]])).collect();
+    String::from_utf16(&units).map_err(invalid)
+}
+
+fn invalid(err: impl std::error::Error + Send + Sync + 'static) -> Error {
+    Error::new(ErrorKind::InvalidData, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_and_encode() {
+        let cases: &[(&[u8], Encoding)] = &[
+            (b"caf\xc3\xa9\n", Encoding::Utf8),
+            (b"\xef\xbb\xbfcaf\xc3\xa9\n", Encoding::Utf8Bom),
+            (b"\xff\xfec\0a\0f\0\xe9\0\n\0", Encoding::Utf16Le),
+            (b"\xfe\xff\0c\0a\0f\0\xe9\0\n", Encoding::Utf16Be),
+            (b"caf\xe9\n", Encoding::Latin1),
+        ];
+        for &(bytes, expected) in cases {
+            let (text, encoding) = Encoding::decode(bytes).unwrap();
+            assert_eq!((text.as_str(), encoding), ("café\n", expected));
+            assert_eq!(encoding.encode(&text).unwrap(), bytes);
+        }
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert!(Encoding::decode(b"\x7fELF\0\x01\xff").is_err());
+        assert!(Encoding::decode(b"\xff\xfe\0").is_err());
+        assert!(Encoding::Latin1.encode("→").is_err());
+    }
+
+    #[test]
+    fn test_read_or_new() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("new.rs");
+        assert_eq!(read_or_new(&path).unwrap(), (String::new(), Encoding::Utf8));
+        std::fs::write(&path, "fn main() {}\n").unwrap();
+        assert_eq!(read_or_new(&path).unwrap().0, "fn main() {}\n");
+        // other errors, like reading a directory, still fail
+        assert!(read_or_new(dir.path()).is_err());
+    }
+}
@@ -0,0 +1,114 @@
+use async_openai::error::OpenAIError;
+use std::fmt;
+
+pub type CoderResult<T> = std::result::Result<T, CoderError>;
+
+/// Errors produced by the LLM and coder layer
+#[derive(Debug)]
+pub enum CoderError {
+    /// The provider did not answer in time
+    NetworkTimeout,
+    /// The request never reached the provider or the connection broke
+    Network(String),
+    /// The provider rejected the request (auth, quota, unknown model)
+    Api(String),
+    /// The provider answered, but not with a usable chat completion
+    BadResponse(String),
+    /// The model answered, but not in the SEARCH/DIVIDE/REPLACE format
+    PatchParse(String),
+    Io(std::io::Error),
+}
+
+impl CoderError {
+    /// Short hint telling the user what to do about the error
+    pub fn advice(&self) -> &'static str {
+        match self {
+            CoderError::NetworkTimeout => "provider is slow, try again",
+            CoderError::Network(_) => "check network and OPENROUTER_BASE_URL",
+            CoderError::Api(_) => "check API key and model",
+            CoderError::BadResponse(_) => "provider returned an unexpected response",
+            CoderError::PatchParse(_) => "model returned malformed patch, try again",
+            CoderError::Io(_) => "check file permissions",
+        }
+    }
+
+    /// Whether repeating the same request may succeed
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            CoderError::NetworkTimeout | CoderError::Network(_) | CoderError::PatchParse(_)
+        )
+    }
+}
+
+impl fmt::Display for CoderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CoderError::NetworkTimeout => write!(f, "request timed out"),
+            CoderError::Network(msg) => write!(f, "network error: {msg}"),
+            CoderError::Api(msg) => write!(f, "api error: {msg}"),
+            CoderError::BadResponse(msg) => write!(f, "bad response: {msg}"),
+            CoderError::PatchParse(msg) => write!(f, "invalid patch: {msg}"),
+            CoderError::Io(err) => write!(f, "io error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for CoderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CoderError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for CoderError {
+    fn from(err: std::io::Error) -> Self {
+        CoderError::Io(err)
+    }
+}
+
+impl From<OpenAIError> for CoderError {
+    fn from(err: OpenAIError) -> Self {
+        match err {
+            OpenAIError::Reqwest(e) if e.is_timeout() => CoderError::NetworkTimeout,
+            OpenAIError::Reqwest(e) => CoderError::Network(e.to_string()),
+            OpenAIError::ApiError(e) => CoderError::Api(e.to_string()),
+            OpenAIError::JSONDeserialize(e, _) => CoderError::BadResponse(e.to_string()),
+            OpenAIError::FileSaveError(msg) | OpenAIError::FileReadError(msg) => {
+                CoderError::Io(std::io::Error::other(msg))
+            }
+            other => CoderError::BadResponse(other.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_advice_distinguishes_errors() {
+        let parse = CoderError::PatchParse("missing <|DIVIDE|>".to_string());
+        let api = CoderError::Api("401".to_string());
+
+        assert_ne!(parse.advice(), api.advice());
+        assert!(parse.is_retryable());
+        assert!(!api.is_retryable());
+    }
+
+    #[test]
+    fn test_api_error_conversion() {
+        let err = OpenAIError::ApiError(async_openai::error::ApiError {
+            message: "No auth credentials found".to_string(),
+            r#type: None,
+            param: None,
+            code: Some("401".to_string()),
+        });
+
+        let err = CoderError::from(err);
+        assert!(matches!(err, CoderError::Api(_)));
+        assert!(err.to_string().contains("No auth credentials found"));
+    }
+}
@@ -0,0 +1,46 @@
+use anyhow::{Context, Result, bail};
+use std::path::Path;
+use std::process::Command;
+
+/// Trimmed stdout of a git command run in `dir`
+pub fn run(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .context("cannot run git")?;
+    if !output.status.success() {
+        bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Changes staged in the repository holding `file`, as `git diff --cached`
+/// shows them; empty with nothing staged
+pub fn staged_diff(file: &Path) -> Result<String> {
+    let file = std::path::absolute(file)?;
+    let dir = file.parent().context("file has no parent directory")?;
+    run(dir, &["diff", "--cached", "--no-color"])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_staged_diff() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("notes.txt");
+        run(dir.path(), &["init", "--quiet"]).unwrap();
+        std::fs::write(&file, "staged line\n").unwrap();
+        assert_eq!(staged_diff(&file).unwrap(), "");
+
+        run(dir.path(), &["add", "notes.txt"]).unwrap();
+        let diff = staged_diff(&file).unwrap();
+        assert!(diff.contains("+staged line"), "{diff}");
+
+        let outside = tempfile::tempdir().unwrap();
+        assert!(staged_diff(&outside.path().join("notes.txt")).is_err());
+    }
+}
@@ -0,0 +1,126 @@
+use std::path::{Path, PathBuf};
+
+/// Extensions tried for a path written without one, as imports often are
+const EXTENSIONS: &[&str] = &[
+    "rs", "py", "js", "ts", "tsx", "jsx", "go", "c", "h", "cpp", "hpp", "java", "rb", "lua",
+];
+/// Files standing for a directory in an import, e.g. `./components`
+const INDEX_FILES: &[&str] = &["mod.rs", "index.js", "index.ts", "__init__.py"];
+
+/// The path written around char `column` of `line`, with a trailing
+/// `:line` or `:line:col` split off as a 0-based line, e.g. from
+/// `"src/app.rs:120"`; None when there is no path-like text there
+pub fn path_token(line: &str, column: usize) -> Option<(String, Option<usize>)> {
+    let chars: Vec<char> = line.chars().collect();
+    let is_path = |c: char| c.is_alphanumeric() || "_-./\\~@+:".contains(c);
+    let mut start = column.min(chars.len());
+    // the cursor may sit right after the path, e.g. before a closing quote
+    if !chars.get(start).copied().is_some_and(is_path) {
+        start = start.checked_sub(1)?;
+        if !is_path(chars[start]) {
+            return None;
+        }
+    }
+    let mut end = start;
+    while start > 0 && is_path(chars[start - 1]) {
+        start -= 1;
+    }
+    while end < chars.len() && is_path(chars[end]) {
+        end += 1;
+    }
+
+    let token: String = chars[start..end].iter().collect();
+    // sentence punctuation and a `::item` of a module path are not the file
+    let token = token.split("::").next().unwrap_or_default();
+    let token = token.trim_end_matches(['.', ',', ':']);
+    let mut parts = token.splitn(3, ':');
+    let path = parts.next().unwrap_or_default();
+    let line = parts
+        .next()
+        .and_then(|n| n.parse::<usize>().ok())
+        .map(|n| n.saturating_sub(1));
+    (!path.is_empty() && path.chars().any(char::is_alphanumeric)).then(|| (path.to_string(), line))
+}
+
+/// The file `token` names, looked up relative to the directory of
+/// `current_file`, then to each root and the working directory; a name
+/// without an extension is also tried with common ones and as a
+/// directory with an index file
+pub fn resolve_path(token: &str, current_file: &Path, roots: &[PathBuf]) -> Option<PathBuf> {
+    let token = match token.strip_prefix("~/") {
+        Some(rest) => PathBuf::from(std::env::var_os("HOME")?).join(rest),
+        None => PathBuf::from(token),
+    };
+    let mut bases = Vec::new();
+    if token.is_absolute() {
+        bases.push(PathBuf::new());
+    } else {
+        if let Some(dir) = current_file.parent() {
+            bases.push(dir.to_path_buf());
+        }
+        bases.extend(roots.iter().cloned());
+        bases.push(std::env::current_dir().ok()?);
+    }
+
+    let mut names = vec![token.clone()];
+    if token.extension().is_none() {
+        for extension in EXTENSIONS {
+            names.push(token.with_extension(extension));
+        }
+        names.extend(INDEX_FILES.iter().map(|index| token.join(index)));
+    }
+    bases
+        .iter()
+        .flat_map(|base| names.iter().map(move |name| base.join(name)))
+        .find(|candidate| candidate.is_file())
+        .map(|found| std::fs::canonicalize(&found).unwrap_or(found))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_token() {
+        let line = r#"import { x } from "./lib/utils";"#;
+        let inside = line.find("lib").unwrap();
+        assert_eq!(path_token(line, inside), Some(("./lib/utils".into(), None)));
+        // right after the path, on the closing quote
+        let after = line.find("\";").unwrap();
+        assert_eq!(path_token(line, after), Some(("./lib/utils".into(), None)));
+
+        let line = "error at src/app.rs:120:5, see README.md.";
+        assert_eq!(path_token(line, 12), Some(("src/app.rs".into(), Some(119))));
+        assert_eq!(path_token(line, 35), Some(("README.md".into(), None)));
+        assert_eq!(
+            path_token("use crate::tags::find;", 6),
+            Some(("crate".into(), None))
+        );
+        assert_eq!(path_token("a  == b", 3), None);
+    }
+
+    #[test]
+    fn test_resolve_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = std::fs::canonicalize(dir.path()).unwrap();
+        std::fs::create_dir_all(root.join("src/components")).unwrap();
+        std::fs::write(root.join("src/main.rs"), "").unwrap();
+        std::fs::write(root.join("src/utils.js"), "").unwrap();
+        std::fs::write(root.join("src/components/index.js"), "").unwrap();
+        std::fs::write(root.join("README.md"), "").unwrap();
+        let current = root.join("src/main.rs");
+        let roots = [root.clone()];
+
+        // next to the current file, with an extension or an index file added
+        let found = resolve_path("./utils", &current, &roots);
+        assert_eq!(found, Some(root.join("src/utils.js")));
+        let found = resolve_path("components", &current, &roots);
+        assert_eq!(found, Some(root.join("src/components/index.js")));
+        // relative to the project root
+        let found = resolve_path("README.md", &current, &roots);
+        assert_eq!(found, Some(root.join("README.md")));
+        let found = resolve_path("../README.md", &current, &roots);
+        assert_eq!(found, Some(root.join("README.md")));
+        assert_eq!(resolve_path("missing.txt", &current, &roots), None);
+    }
+}
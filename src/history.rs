@@ -0,0 +1,248 @@
+use crossterm::event::{Event, KeyCode, KeyEventKind, MouseEventKind};
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+};
+use ratatui_code_editor::code::{EditBatch, Operation};
+
+/// Characters of edited text shown per entry
+const PREVIEW_CHARS: usize = 24;
+
+/// One step of the undo history, or the current state
+#[derive(Clone, Debug, PartialEq)]
+pub struct HistoryEntry {
+    /// Undo (negative) or redo (positive) steps that reach this entry
+    pub steps: isize,
+    pub label: String,
+}
+
+impl HistoryEntry {
+    /// An undo step: choosing it undoes the step and every later one; a redo
+    /// step: choosing it redoes every step up to and including it
+    pub fn step(steps: isize, batch: &EditBatch, line: usize) -> Self {
+        let (mut inserted, mut removed) = (0, 0);
+        for edit in &batch.edits {
+            let chars = edit.text.chars().count();
+            match edit.operation {
+                Operation::Insert => inserted += chars,
+                Operation::Remove => removed += chars,
+            }
+        }
+        Self {
+            steps,
+            label: format!(
+                "line {:<5} +{inserted} -{removed}  {}",
+                line + 1,
+                preview(batch)
+            ),
+        }
+    }
+
+    pub fn current() -> Self {
+        Self {
+            steps: 0,
+            label: "current state".to_string(),
+        }
+    }
+}
+
+/// First non-blank line of the inserted text, or of the removed text for
+/// pure deletions
+fn preview(batch: &EditBatch) -> String {
+    let edit = batch
+        .edits
+        .iter()
+        .find(|e| matches!(e.operation, Operation::Insert) && !e.text.trim().is_empty())
+        .or_else(|| batch.edits.iter().find(|e| !e.text.trim().is_empty()));
+    let Some(edit) = edit else {
+        return String::new();
+    };
+    let line = edit.text.lines().map(str::trim).find(|l| !l.is_empty());
+    let line = line.unwrap_or_default();
+    match line.char_indices().nth(PREVIEW_CHARS) {
+        Some((end, _)) => format!("{}…", &line[..end]),
+        None => line.to_string(),
+    }
+}
+
+pub enum HistoryAction {
+    None,
+    Close,
+    Travel(isize),
+}
+
+/// Popup listing the undo history; choosing an entry undoes or redoes to it
+pub struct HistoryMenu {
+    entries: Vec<HistoryEntry>,
+    selected: usize,
+    scroll: usize,
+    area: Rect,
+}
+
+impl HistoryMenu {
+    pub fn new(entries: Vec<HistoryEntry>) -> Self {
+        let selected = entries.iter().position(|e| e.steps == 0).unwrap_or(0);
+        Self {
+            entries,
+            selected,
+            scroll: 0,
+            area: Rect::default(),
+        }
+    }
+
+    pub fn handle_event(&mut self, event: &Event) -> HistoryAction {
+        let last = self.entries.len().saturating_sub(1);
+        match event {
+            Event::Key(key) if !matches!(key.kind, KeyEventKind::Press) => HistoryAction::None,
+            Event::Key(key) => {
+                match key.code {
+                    KeyCode::Esc => return HistoryAction::Close,
+                    KeyCode::Enter => return self.travel(self.selected),
+                    KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+                    KeyCode::Down => self.selected = (self.selected + 1).min(last),
+                    KeyCode::PageUp => self.selected = self.selected.saturating_sub(self.page()),
+                    KeyCode::PageDown => self.selected = (self.selected + self.page()).min(last),
+                    KeyCode::Home => self.selected = 0,
+                    KeyCode::End => self.selected = last,
+                    _ => {}
+                }
+                HistoryAction::None
+            }
+            Event::Mouse(mouse) if matches!(mouse.kind, MouseEventKind::Down(_)) => {
+                let inside = mouse.column > self.area.x
+                    && mouse.column < self.area.right().saturating_sub(1)
+                    && mouse.row > self.area.y
+                    && mouse.row < self.area.bottom().saturating_sub(1);
+                if !inside {
+                    return HistoryAction::Close;
+                }
+                self.travel(self.scroll + (mouse.row - self.area.y - 1) as usize)
+            }
+            Event::Mouse(mouse) => {
+                match mouse.kind {
+                    MouseEventKind::ScrollUp => self.selected = self.selected.saturating_sub(1),
+                    MouseEventKind::ScrollDown => self.selected = (self.selected + 1).min(last),
+                    _ => {}
+                }
+                HistoryAction::None
+            }
+            _ => HistoryAction::None,
+        }
+    }
+
+    fn page(&self) -> usize {
+        (self.area.height.saturating_sub(2) as usize).max(1)
+    }
+
+    fn travel(&self, index: usize) -> HistoryAction {
+        match self.entries.get(index) {
+            Some(entry) => HistoryAction::Travel(entry.steps),
+            None => HistoryAction::None,
+        }
+    }
+
+    /// Draws the menu at the top of `area`, the editor
+    pub fn render(&mut self, frame: &mut Frame, area: Rect) {
+        let widest = self.entries.iter().map(|e| e.label.chars().count());
+        let width = (widest.max().unwrap_or(0) as u16 + 4)
+            .max(24)
+            .min(area.width);
+        let height = (self.entries.len() as u16 + 2).min(area.height.saturating_sub(2).max(3));
+        self.area = Rect::new(
+            area.x + area.width.saturating_sub(width) / 2,
+            area.y + 1.min(area.height.saturating_sub(height)),
+            width,
+            height.min(area.height),
+        );
+
+        frame.render_widget(Clear, self.area);
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(" Undo history ")
+            .border_style(Style::default().fg(Color::DarkGray));
+        let inner = block.inner(self.area);
+        frame.render_widget(block, self.area);
+
+        let rows = inner.height as usize;
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if rows > 0 && self.selected >= self.scroll + rows {
+            self.scroll = self.selected + 1 - rows;
+        }
+        let visible = self.entries.iter().enumerate().skip(self.scroll).take(rows);
+        for (row, (i, entry)) in visible.enumerate() {
+            let style = if i == self.selected {
+                Style::default()
+                    .bg(Color::DarkGray)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            // steps that were undone and can be redone are dimmed
+            let (marker, color) = match entry.steps {
+                0 => ("●", Color::Yellow),
+                steps if steps > 0 => (" ", Color::DarkGray),
+                _ => (" ", Color::Reset),
+            };
+            let line = Line::from(vec![
+                Span::styled(format!("{marker} "), Style::default().fg(Color::Yellow)),
+                Span::styled(entry.label.clone(), Style::default().fg(color)),
+            ]);
+            let item_area = Rect::new(inner.x, inner.y + row as u16, inner.width, 1);
+            frame.render_widget(Paragraph::new(line).style(style), item_area);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui_code_editor::code::Edit;
+
+    #[test]
+    fn test_history_entry_label() {
+        let batch = EditBatch {
+            edits: vec![
+                Edit {
+                    start: 4,
+                    text: "old".to_string(),
+                    operation: Operation::Remove,
+                },
+                Edit {
+                    start: 4,
+                    text: "\n    let value = compute_the_answer_to_everything();\n".to_string(),
+                    operation: Operation::Insert,
+                },
+            ],
+            state_before: None,
+            state_after: None,
+        };
+        let entry = HistoryEntry::step(-1, &batch, 2);
+        assert_eq!(entry.label, "line 3     +53 -3  let value = compute_the_…");
+
+        let mut menu = HistoryMenu::new(vec![
+            HistoryEntry::step(1, &batch, 0),
+            HistoryEntry::current(),
+            entry,
+        ]);
+        assert_eq!(menu.selected, 1);
+        let key = |code| Event::Key(crossterm::event::KeyEvent::from(code));
+        assert!(matches!(
+            menu.handle_event(&key(KeyCode::Enter)),
+            HistoryAction::Travel(0)
+        ));
+        menu.handle_event(&key(KeyCode::Down));
+        assert!(matches!(
+            menu.handle_event(&key(KeyCode::Enter)),
+            HistoryAction::Travel(-1)
+        ));
+        menu.handle_event(&key(KeyCode::Home));
+        assert!(matches!(
+            menu.handle_event(&key(KeyCode::Enter)),
+            HistoryAction::Travel(1)
+        ));
+    }
+}
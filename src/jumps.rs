@@ -0,0 +1,114 @@
+/// Jumps kept before the oldest ones are dropped
+const MAX_JUMPS: usize = 100;
+
+/// A cursor position left by a jump
+#[derive(Clone, Debug, PartialEq)]
+pub struct Jump {
+    pub filename: String,
+    pub offset: usize,
+}
+
+/// Positions left by search jumps, go to definition, tree opens and change
+/// jumps, walked with `Ctrl+O` / `Ctrl+I` like Vim's jump list; ordinary
+/// cursor movement is not recorded
+#[derive(Default)]
+pub struct JumpList {
+    entries: Vec<Jump>,
+    /// Position while walking the list; `entries.len()` when not walking
+    index: usize,
+}
+
+impl JumpList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the position a jump starts from, dropping the jumps that were
+    /// walked back over
+    pub fn push(&mut self, jump: Jump) {
+        self.entries.truncate(self.index + 1);
+        if self.entries.last() != Some(&jump) {
+            self.entries.push(jump);
+        }
+        if self.entries.len() > MAX_JUMPS {
+            self.entries.remove(0);
+        }
+        self.index = self.entries.len();
+    }
+
+    /// Previous position; `current` is recorded first so that `forward`
+    /// can return to it
+    pub fn back(&mut self, current: Jump) -> Option<Jump> {
+        if self.index == 0 {
+            return None;
+        }
+        if self.index == self.entries.len() {
+            if self.entries.last() != Some(&current) {
+                self.entries.push(current);
+            }
+            self.index = self.entries.len() - 1;
+        }
+        if self.index == 0 {
+            return None;
+        }
+        self.index -= 1;
+        Some(self.entries[self.index].clone())
+    }
+
+    /// Next position after walking back
+    pub fn forward(&mut self) -> Option<Jump> {
+        if self.index + 1 >= self.entries.len() {
+            return None;
+        }
+        self.index += 1;
+        Some(self.entries[self.index].clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(filename: &str, offset: usize) -> Jump {
+        Jump {
+            filename: filename.to_string(),
+            offset,
+        }
+    }
+
+    #[test]
+    fn test_back_and_forward() {
+        let mut jumps = JumpList::new();
+        assert_eq!(jumps.back(at("a.rs", 0)), None);
+        assert_eq!(jumps.forward(), None);
+
+        jumps.push(at("a.rs", 10));
+        jumps.push(at("b.rs", 20));
+        assert_eq!(jumps.back(at("c.rs", 30)), Some(at("b.rs", 20)));
+        assert_eq!(jumps.back(at("b.rs", 20)), Some(at("a.rs", 10)));
+        assert_eq!(jumps.back(at("a.rs", 10)), None);
+        assert_eq!(jumps.forward(), Some(at("b.rs", 20)));
+        assert_eq!(jumps.forward(), Some(at("c.rs", 30)));
+        assert_eq!(jumps.forward(), None);
+
+        // a new jump after walking back drops the newer entries
+        jumps.back(at("c.rs", 30));
+        jumps.back(at("b.rs", 20));
+        jumps.push(at("a.rs", 15));
+        assert_eq!(jumps.forward(), None);
+        assert_eq!(jumps.back(at("d.rs", 0)), Some(at("a.rs", 15)));
+        assert_eq!(jumps.back(at("a.rs", 15)), Some(at("a.rs", 10)));
+        assert_eq!(jumps.back(at("a.rs", 10)), None);
+    }
+
+    #[test]
+    fn test_bounded() {
+        let mut jumps = JumpList::new();
+        for offset in 0..MAX_JUMPS + 10 {
+            jumps.push(at("a.rs", offset));
+            jumps.push(at("a.rs", offset));
+        }
+        assert_eq!(jumps.entries.len(), MAX_JUMPS);
+        assert_eq!(jumps.entries[0], at("a.rs", 10));
+    }
+}
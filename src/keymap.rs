@@ -0,0 +1,95 @@
+use anyhow::{Result, bail};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// A single key combination such as `Ctrl+Space` or `Alt+\`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KeyBinding {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    pub const fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        Self { code, modifiers }
+    }
+
+    /// Parses bindings like `ctrl+space`, `alt+\` or `ctrl+j`
+    pub fn parse(spec: &str) -> Result<Self> {
+        let spec = spec.trim();
+        let (mods, key) = match spec.rsplit_once('+') {
+            // `ctrl++` binds the plus key itself
+            Some((mods, "")) => (mods.strip_suffix('+').unwrap_or(mods), "+"),
+            Some((mods, key)) => (mods, key),
+            None => ("", spec),
+        };
+
+        let mut modifiers = KeyModifiers::NONE;
+        for m in mods.split('+').filter(|m| !m.is_empty()) {
+            modifiers |= match m.trim().to_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" | "meta" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                other => bail!("unknown modifier '{other}' in key binding '{spec}'"),
+            };
+        }
+
+        let lower = key.trim().to_lowercase();
+        let code = match lower.as_str() {
+            "space" => KeyCode::Char(' '),
+            "enter" | "return" => KeyCode::Enter,
+            "tab" => KeyCode::Tab,
+            "esc" | "escape" => KeyCode::Esc,
+            "backspace" => KeyCode::Backspace,
+            "plus" => KeyCode::Char('+'),
+            f if f.starts_with('f') && f.len() > 1 => match f[1..].parse::<u8>() {
+                Ok(n) if (1..=12).contains(&n) => KeyCode::F(n),
+                _ => bail!("unknown key '{key}' in key binding '{spec}'"),
+            },
+            _ => {
+                let mut chars = lower.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => bail!("unknown key '{key}' in key binding '{spec}'"),
+                }
+            }
+        };
+
+        Ok(Self { code, modifiers })
+    }
+
+    pub fn matches(&self, key: &KeyEvent) -> bool {
+        key.code == self.code && key.modifiers.contains(self.modifiers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_bindings() {
+        let ctrl_space = KeyBinding::parse("ctrl+space").unwrap();
+        assert_eq!(ctrl_space.code, KeyCode::Char(' '));
+        assert_eq!(ctrl_space.modifiers, KeyModifiers::CONTROL);
+
+        let alt_backslash = KeyBinding::parse("Alt+\\").unwrap();
+        assert_eq!(alt_backslash.code, KeyCode::Char('\\'));
+        assert_eq!(alt_backslash.modifiers, KeyModifiers::ALT);
+
+        let ctrl_plus = KeyBinding::parse("ctrl++").unwrap();
+        assert_eq!(ctrl_plus.code, KeyCode::Char('+'));
+
+        assert!(KeyBinding::parse("hyper+j").is_err());
+        assert!(KeyBinding::parse("ctrl+jk").is_err());
+    }
+
+    #[test]
+    fn test_matches_key_event() {
+        let binding = KeyBinding::parse("ctrl+j").unwrap();
+        let pressed = KeyEvent::new(KeyCode::Char('j'), KeyModifiers::CONTROL);
+        let plain = KeyEvent::new(KeyCode::Char('j'), KeyModifiers::NONE);
+
+        assert!(binding.matches(&pressed));
+        assert!(!binding.matches(&plain));
+    }
+}
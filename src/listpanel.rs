@@ -0,0 +1,136 @@
+use crossterm::event::MouseEventKind;
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::Paragraph,
+};
+
+/// Selection and scrolling of a titled list with one row per item, shared
+/// by the outline, recent edits and bookmarks panels
+#[derive(Default)]
+pub struct ListPanel {
+    pub selected: Option<usize>,
+    pub scroll_offset: usize,
+}
+
+impl ListPanel {
+    /// Rows for items in `area`, below the title
+    fn visible_height(area: Rect) -> usize {
+        area.height.saturating_sub(1) as usize
+    }
+
+    /// Selects `index` of `len` items and scrolls to it; false when there
+    /// is no such item
+    pub fn select(&mut self, index: usize, len: usize, area: Rect) -> bool {
+        if index >= len {
+            return false;
+        }
+        self.selected = Some(index);
+        self.scroll_into_view(index, area);
+        true
+    }
+
+    /// Selects the item above the selected one
+    pub fn select_prev(&mut self, len: usize, area: Rect) -> bool {
+        self.select(self.selected.unwrap_or(0).saturating_sub(1), len, area)
+    }
+
+    /// Selects the item below the selected one, or the first
+    pub fn select_next(&mut self, len: usize, area: Rect) -> bool {
+        self.select(self.selected.map_or(0, |s| s + 1), len, area)
+    }
+
+    pub fn scroll_into_view(&mut self, index: usize, area: Rect) {
+        let visible_height = Self::visible_height(area);
+        if index < self.scroll_offset {
+            self.scroll_offset = index;
+        } else if index >= self.scroll_offset + visible_height {
+            self.scroll_offset = index.saturating_sub(visible_height.saturating_sub(1));
+        }
+    }
+
+    /// Index of the item shown at screen `row`, in range or not
+    pub fn row_at(&self, row: u16, area: Rect) -> usize {
+        self.scroll_offset + row.saturating_sub(area.y + 1) as usize
+    }
+
+    /// Scrolls for the mouse wheel, keeping the last item in view
+    pub fn scroll(&mut self, kind: MouseEventKind, len: usize, area: Rect) {
+        match kind {
+            MouseEventKind::ScrollDown if self.scroll_offset + Self::visible_height(area) < len => {
+                self.scroll_offset += 1;
+            }
+            MouseEventKind::ScrollUp => {
+                self.scroll_offset = self.scroll_offset.saturating_sub(1);
+            }
+            _ => {}
+        }
+    }
+
+    /// Draws `title`, then the visible rows of `len` items or `empty` when
+    /// there are none
+    pub fn render_rows<'a>(
+        &self,
+        frame: &mut Frame,
+        area: Rect,
+        (title, empty): (&str, &str),
+        len: usize,
+        row: impl Fn(usize) -> Line<'a>,
+    ) {
+        let title = Paragraph::new(title).style(Style::default().fg(Color::White));
+        frame.render_widget(title, Rect { height: 1, ..area });
+
+        if len == 0 {
+            let empty = Paragraph::new(empty).style(Style::default().fg(Color::Gray));
+            frame.render_widget(empty, Rect::new(area.x, area.y + 1, area.width, 1));
+            return;
+        }
+
+        let visible = (self.scroll_offset..len).take(Self::visible_height(area));
+        for (y, i) in visible.enumerate() {
+            let style = if self.selected == Some(i) {
+                Style::default()
+                    .bg(Color::DarkGray)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            let item_area = Rect::new(area.x, area.y + 1 + y as u16, area.width, 1);
+            frame.render_widget(Paragraph::new(row(i)).style(style), item_area);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_and_scroll() {
+        // a title and three rows
+        let area = Rect::new(0, 5, 20, 4);
+        let mut list = ListPanel::default();
+        assert!(!list.select_prev(0, area));
+        assert_eq!(list.selected, None);
+
+        for _ in 0..4 {
+            list.select_next(10, area);
+        }
+        assert_eq!((list.selected, list.scroll_offset), (Some(3), 1));
+        assert_eq!(list.row_at(6, area), 1);
+        assert_eq!(list.row_at(8, area), 3);
+        assert!(!list.select(10, 10, area));
+        assert_eq!(list.selected, Some(3));
+
+        list.select(0, 10, area);
+        assert_eq!(list.scroll_offset, 0);
+        for _ in 0..20 {
+            list.scroll(MouseEventKind::ScrollDown, 10, area);
+        }
+        assert_eq!(list.scroll_offset, 7);
+        list.scroll(MouseEventKind::ScrollUp, 10, area);
+        assert_eq!(list.scroll_offset, 6);
+    }
+}
@@ -1,9 +1,120 @@
+use crate::config::Config;
+use crate::error::{CoderError, CoderResult};
 use async_openai::{Client, config::OpenAIConfig};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 use serde_json::{Value, json};
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+/// How long a request may take unless `REDAI_REQUEST_TIMEOUT` says otherwise
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+pub type ChatFuture<'a> = Pin<Box<dyn Future<Output = CoderResult<String>> + Send + 'a>>;
+pub type ChoicesFuture<'a> = Pin<Box<dyn Future<Output = CoderResult<Vec<Choice>>> + Send + 'a>>;
+
+/// One of the replies to a request for several, with what the provider
+/// says about it
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Choice {
+    pub content: String,
+    /// Mean log probability of the reply's tokens, when the provider
+    /// returns logprobs
+    pub logprob: Option<f64>,
+    /// Why generation stopped, e.g. `stop`, or `length` for a reply cut off
+    pub finish_reason: Option<String>,
+}
+
+impl Choice {
+    /// The choices of a chat completion response, in the order returned
+    pub fn from_response(response: &Value) -> CoderResult<Vec<Self>> {
+        let choices = response["choices"]
+            .as_array()
+            .map_or(&[][..], Vec::as_slice);
+        if choices.is_empty() {
+            return Err(CoderError::BadResponse(
+                "missing message content".to_string(),
+            ));
+        }
+        choices
+            .iter()
+            .map(|choice| {
+                let content = choice["message"]["content"].as_str().ok_or_else(|| {
+                    CoderError::BadResponse("missing message content".to_string())
+                })?;
+                let logprobs: Vec<f64> = choice["logprobs"]["content"]
+                    .as_array()
+                    .map(|tokens| {
+                        tokens
+                            .iter()
+                            .filter_map(|t| t["logprob"].as_f64())
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                Ok(Self {
+                    content: content.to_string(),
+                    logprob: (!logprobs.is_empty())
+                        .then(|| logprobs.iter().sum::<f64>() / logprobs.len() as f64),
+                    finish_reason: choice["finish_reason"].as_str().map(String::from),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Anything that can answer a chat completion request; `Coder` only talks
+/// to the model through this, so tests can swap in `MockBackend`
+pub trait ChatBackend: Send + Sync {
+    fn chat(&self, messages: Vec<Value>) -> ChatFuture<'_>;
+
+    /// Up to `n` replies to `messages`; backends that cannot offer several
+    /// answer with the one [`Self::chat`] gives
+    fn chat_choices(&self, messages: Vec<Value>, n: usize) -> ChoicesFuture<'_> {
+        let _ = n;
+        let reply = self.chat(messages);
+        Box::pin(async move {
+            Ok(vec![Choice {
+                content: reply.await?,
+                ..Choice::default()
+            }])
+        })
+    }
+}
+
+/// Optional sampling controls; unset fields are left out of the request
+/// so providers rejecting unknown params still work
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LlmParams {
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub max_tokens: Option<u32>,
+    pub stop: Vec<String>,
+}
+
+impl LlmParams {
+    /// Adds the set fields to a chat request body
+    pub fn merge_into(&self, request: &mut Value) {
+        if let Some(temperature) = self.temperature {
+            request["temperature"] = json!(temperature);
+        }
+        if let Some(top_p) = self.top_p {
+            request["top_p"] = json!(top_p);
+        }
+        if let Some(max_tokens) = self.max_tokens {
+            request["max_tokens"] = json!(max_tokens);
+        }
+        if !self.stop.is_empty() {
+            request["stop"] = json!(self.stop);
+        }
+    }
+}
 
 pub struct LlmClient {
     client: Client<OpenAIConfig>,
     model: String,
+    params: LlmParams,
+    timeout: Duration,
+    headers: HeaderMap,
 }
 
 impl LlmClient {
@@ -17,18 +128,156 @@ impl LlmClient {
         Self {
             client,
             model: model.into(),
+            params: LlmParams::default(),
+            timeout: REQUEST_TIMEOUT,
+            headers: HeaderMap::new(),
+        }
+        .rebuild_http_client()
+    }
+
+    pub fn with_params(mut self, params: LlmParams) -> Self {
+        self.params = params;
+        self
+    }
+
+    /// Fails a request with `CoderError::NetworkTimeout` when the provider
+    /// has not answered within `timeout`
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self.rebuild_http_client()
+    }
+
+    /// Sends `headers` with every request, e.g. `HTTP-Referer` and `X-Title`
+    /// for OpenRouter attribution or an org id for a gateway
+    pub fn with_headers(mut self, headers: &[(String, String)]) -> Self {
+        if headers.is_empty() {
+            return self;
+        }
+        for (name, value) in headers {
+            match (HeaderName::try_from(name), HeaderValue::try_from(value)) {
+                (Ok(name), Ok(value)) => {
+                    self.headers.insert(name, value);
+                }
+                _ => log::warn!("skipping invalid header {name}"),
+            }
+        }
+        self.rebuild_http_client()
+    }
+
+    /// Rebuilds the http client with the current timeout and headers
+    fn rebuild_http_client(mut self) -> Self {
+        let http_client = reqwest::Client::builder()
+            .timeout(self.timeout)
+            .default_headers(self.headers.clone())
+            .build();
+        match http_client {
+            Ok(http_client) => self.client = self.client.with_http_client(http_client),
+            Err(err) => log::error!("cannot build http client: {err}"),
+        }
+        self
+    }
+
+    /// Client for the configured model, or none without an API key or
+    /// with AI disabled
+    pub fn from_config(config: &Config) -> Option<Self> {
+        if config.disable_ai {
+            return None;
         }
+        config
+            .api_key
+            .as_deref()
+            .filter(|key| !key.trim().is_empty())
+            .map(|key| {
+                Self::new(key, &config.base_url, &config.model)
+                    .with_params(config.llm_params.clone())
+                    .with_timeout(config.request_timeout)
+                    .with_headers(&config.headers)
+            })
+    }
+
+    pub async fn chat(&self, messages: Vec<Value>) -> CoderResult<String> {
+        let mut choices = self.chat_choices(messages, 1).await?;
+        Ok(choices.swap_remove(0).content)
     }
 
-    pub async fn chat(&self, messages: Vec<Value>) -> anyhow::Result<String> {
-        let request = json!({ "model": self.model, "messages": messages });
+    /// Asks for `n` replies with their logprobs when `n` is more than one;
+    /// a provider ignoring either still gets its replies through
+    pub async fn chat_choices(&self, messages: Vec<Value>, n: usize) -> CoderResult<Vec<Choice>> {
+        let mut request = json!({ "model": self.model, "messages": messages });
+        self.params.merge_into(&mut request);
+        if n > 1 {
+            request["n"] = json!(n);
+            request["logprobs"] = json!(true);
+        }
         let response: Value = self.client.chat().create_byot(request).await?;
-        let content = response["choices"][0]["message"]["content"]
-            .as_str()
-            .unwrap_or("")
-            .to_string();
+        Choice::from_response(&response)
+    }
+}
+
+impl ChatBackend for LlmClient {
+    fn chat(&self, messages: Vec<Value>) -> ChatFuture<'_> {
+        Box::pin(LlmClient::chat(self, messages))
+    }
+
+    fn chat_choices(&self, messages: Vec<Value>, n: usize) -> ChoicesFuture<'_> {
+        Box::pin(LlmClient::chat_choices(self, messages, n))
+    }
+}
+
+/// Backend replying with canned responses in turn, repeating the last one,
+/// and recording every request
+#[cfg(test)]
+pub struct MockBackend {
+    replies: Vec<String>,
+    pub requests: std::sync::Arc<std::sync::Mutex<Vec<Vec<Value>>>>,
+}
+
+#[cfg(test)]
+impl MockBackend {
+    pub fn new(reply: &str) -> Self {
+        Self::with_replies(&[reply])
+    }
+
+    pub fn with_replies(replies: &[&str]) -> Self {
+        Self {
+            replies: replies.iter().map(|reply| reply.to_string()).collect(),
+            requests: Default::default(),
+        }
+    }
+}
 
-        Ok(content)
+#[cfg(test)]
+impl ChatBackend for MockBackend {
+    fn chat(&self, messages: Vec<Value>) -> ChatFuture<'_> {
+        let mut requests = self.requests.lock().unwrap();
+        requests.push(messages);
+        let turn = (requests.len() - 1).min(self.replies.len() - 1);
+        let reply = self.replies[turn].clone();
+        Box::pin(async move { Ok(reply) })
+    }
+
+    /// Several choices are the canned replies, one request's worth
+    fn chat_choices(&self, messages: Vec<Value>, n: usize) -> ChoicesFuture<'_> {
+        if n <= 1 {
+            let reply = self.chat(messages);
+            return Box::pin(async move {
+                Ok(vec![Choice {
+                    content: reply.await?,
+                    ..Choice::default()
+                }])
+            });
+        }
+        self.requests.lock().unwrap().push(messages);
+        let choices = self
+            .replies
+            .iter()
+            .take(n)
+            .map(|reply| Choice {
+                content: reply.clone(),
+                ..Choice::default()
+            })
+            .collect();
+        Box::pin(async move { Ok(choices) })
     }
 }
 
@@ -39,6 +288,75 @@ mod tests {
     use dotenv::dotenv;
     use indoc::indoc;
 
+    #[test]
+    fn test_choices_from_response() {
+        let response = json!({ "choices": [
+            {
+                "message": { "content": "first" },
+                "finish_reason": "stop",
+                "logprobs": { "content": [{ "logprob": -0.5 }, { "logprob": -1.5 }] },
+            },
+            { "message": { "content": "second" }, "finish_reason": "length" },
+        ]});
+        let choices = Choice::from_response(&response).unwrap();
+        assert_eq!(
+            choices,
+            vec![
+                Choice {
+                    content: "first".to_string(),
+                    logprob: Some(-1.0),
+                    finish_reason: Some("stop".to_string()),
+                },
+                Choice {
+                    content: "second".to_string(),
+                    logprob: None,
+                    finish_reason: Some("length".to_string()),
+                },
+            ]
+        );
+        assert!(Choice::from_response(&json!({ "choices": [] })).is_err());
+        assert!(Choice::from_response(&json!({ "choices": [{ "message": {} }] })).is_err());
+    }
+
+    #[test]
+    fn test_params_merge_only_set_fields() {
+        let mut request = json!({ "model": "m", "messages": [] });
+        LlmParams::default().merge_into(&mut request);
+        assert_eq!(request, json!({ "model": "m", "messages": [] }));
+
+        let params = LlmParams {
+            temperature: Some(0.0),
+            max_tokens: Some(256),
+            stop: vec!["<|REPLACE|>".to_string()],
+            ..Default::default()
+        };
+        params.merge_into(&mut request);
+        assert_eq!(request["temperature"], json!(0.0));
+        assert_eq!(request["max_tokens"], json!(256));
+        assert_eq!(request["stop"], json!(["<|REPLACE|>"]));
+        assert!(request.get("top_p").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_unanswered_request_times_out() {
+        // accepts the connection, never answers
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}/v1", listener.local_addr().unwrap());
+        let _server = tokio::spawn(async move {
+            let mut connections = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                connections.push(stream);
+            }
+        });
+
+        let client = LlmClient::new("key", &base_url, "model")
+            .with_timeout(Duration::from_millis(200))
+            .with_headers(&[("X-Title".to_string(), "redai".to_string())]);
+        let err = client.chat(vec![]).await.unwrap_err();
+        assert!(matches!(err, CoderError::NetworkTimeout), "{err}");
+        assert!(err.is_retryable());
+    }
+
     #[tokio::test]
     #[ignore]
     async fn test_openrouter_chat() -> anyhow::Result<()> {
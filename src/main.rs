@@ -2,79 +2,135 @@ use crossterm::{
     event::{DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture},
     execute,
 };
-use dotenv::dotenv;
 use ratatui_code_editor::utils::get_lang;
 use std::env;
 use std::fs;
 use std::io::stdout;
 
 mod app;
+mod bookmarks;
+mod cli;
 mod coder;
 mod config;
 mod diff;
+mod diffview;
 mod editor;
+mod encoding;
+mod error;
+mod git;
+mod gotofile;
+mod history;
+mod jumps;
+mod keymap;
+mod listpanel;
 mod llm;
+mod outline;
+mod permalink;
 mod prompts;
+mod recent;
+mod scrollbar;
 mod search;
+mod searcher;
+mod snippets;
+mod status;
+mod symbols;
+mod tabs;
+mod tags;
 mod tracker;
 mod tree;
 mod utils;
 mod watcher;
+mod words;
 
 use app::App;
-use config::Config;
+use cli::Args;
+use config::{Config, ConfigEnv};
+use diffview::DiffView;
 use llm::LlmClient;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    dotenv().ok();
-
     set_panic_hook();
 
-    let config = Config::from_env()?;
-    let Config {
-        api_key,
-        base_url,
-        model,
-    } = config;
+    let mut args = Args::parse(env::args().skip(1))?;
+    let env = ConfigEnv::load(config::env_files(args.env_file.clone()));
 
-    let args: Vec<String> = env::args().collect();
+    let mut config = Config::from_env()?;
+    if let Some(log_file) = &config.log_file {
+        config::init_logger(log_file)?;
+    }
 
-    let filename = if args.len() > 1 {
-        args[1].clone()
-    } else {
-        "".to_string()
-    };
+    if let Some((old, new)) = &args.diff {
+        return run_diff(old, new).await;
+    }
+    args.detect_project_root(&config.root_markers);
+    args.switches.apply(&mut config);
+    if let Some(warning) = config.resolve_api_key() {
+        args.warnings.push(warning);
+    }
+    let filename = args.files.first().cloned().unwrap_or_default();
 
-    let (mut language, content) = if filename.is_empty() {
-        (String::new(), String::new())
+    let (mut language, (content, encoding)) = if filename.is_empty() {
+        (String::new(), Default::default())
     } else {
-        (get_lang(&filename), fs::read_to_string(&filename)?)
+        let file = encoding::read_or_new(&filename)
+            .map_err(|err| anyhow::anyhow!("cannot open {filename}: {err}"))?;
+        (get_lang(&filename), file)
     };
 
     if language == "unknown" {
         language = "shell".to_string();
     }
 
+    // read before the UI takes over, stdin included
+    let edits = args.apply_edits.as_deref().map(read_input).transpose()?;
+
     let terminal = ratatui::init();
     execute!(stdout(), EnableMouseCapture, EnableBracketedPaste)?;
 
-    let llm_client = api_key
-        .as_deref()
-        .filter(|key| !key.trim().is_empty())
-        .map(|key| LlmClient::new(key, &base_url, &model));
+    let llm_client = LlmClient::from_config(&config);
 
-    let app = App::new(&language, &content, &filename, llm_client)?;
+    let mut app = App::new(
+        &language, &content, encoding, &filename, llm_client, &config, &args, env,
+    )?;
+    if let Some(edits) = edits {
+        app.apply_edits_json(&edits);
+    }
 
     let result = app.run(terminal).await;
 
     restore();
 
-    if let Err(e) = result {
-        return Err(e);
-    }
+    result
+}
+
+/// Read-only review of the changes between `old` and `new`
+async fn run_diff(old: &str, new: &str) -> anyhow::Result<()> {
+    let old_text = read_input(old)?;
+    let new_text = read_input(new)?;
+    let name = |path: &str| if path == "-" { "stdin" } else { path }.to_string();
+    let view = DiffView::new(&name(old), &old_text, &name(new), &new_text);
+
+    let terminal = ratatui::init();
+    execute!(stdout(), EnableMouseCapture)?;
+    let result = view.run(terminal).await;
+    restore();
+    result
+}
 
-    Ok(())
+/// Contents of `path`, or of stdin for `-`
+fn read_input(path: &str) -> anyhow::Result<String> {
+    use anyhow::Context;
+    use std::io::Read;
+
+    if path == "-" {
+        let mut text = String::new();
+        std::io::stdin()
+            .read_to_string(&mut text)
+            .context("cannot read stdin")?;
+        return Ok(text);
+    }
+    fs::read_to_string(path).with_context(|| format!("cannot read {path}"))
 }
 
 fn restore() {
@@ -88,6 +144,9 @@ fn set_panic_hook() {
 
     std::panic::set_hook(Box::new(move |info| {
         restore();
+        let backtrace = std::backtrace::Backtrace::force_capture();
+        log::error!("{info}\n{backtrace}");
+        log::logger().flush();
         default_hook(info);
         std::process::exit(1);
     }));
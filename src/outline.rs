@@ -0,0 +1,172 @@
+use crossterm::event::{Event, KeyCode, KeyEventKind, MouseEventKind};
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Style},
+    text::{Line, Span},
+};
+use regex::Regex;
+use std::sync::LazyLock;
+
+use crate::listpanel::ListPanel;
+
+/// Declarations recognized across the languages redai opens most often
+static SYMBOL_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^(\s*)(?:(?:pub(?:\([^)]*\))?|export|default|async|public|private|protected|static|unsafe|abstract)\s+)*(fn|struct|enum|trait|impl|mod|class|def|func|function|interface|type)\b(?:<[^>]*>)?\s+([A-Za-z_][\w:]*(?:\s+for\s+[A-Za-z_][\w:]*)?)",
+    )
+    .unwrap()
+});
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutlineItem {
+    pub line: usize,
+    pub depth: usize,
+    pub kind: String,
+    pub name: String,
+}
+
+pub enum OutlineAction {
+    None,
+    Close,
+    JumpTo(usize),
+    JumpToAndExit(usize),
+}
+
+pub struct OutlinePanel {
+    pub items: Vec<OutlineItem>,
+    pub list: ListPanel,
+}
+
+impl OutlinePanel {
+    pub fn new() -> Self {
+        Self {
+            items: Vec::new(),
+            list: ListPanel::default(),
+        }
+    }
+
+    /// Rebuilds the outline from the content of the current file
+    pub fn refresh(&mut self, content: &str) {
+        self.items = outline(content);
+        self.list.selected = (!self.items.is_empty()).then_some(0);
+        self.list.scroll_offset = 0;
+    }
+
+    pub fn handle_event(&mut self, event: &Event, area: Rect) -> OutlineAction {
+        match event {
+            Event::Key(key) if !matches!(key.kind, KeyEventKind::Press) => OutlineAction::None,
+            Event::Key(key) => match key.code {
+                KeyCode::Esc => OutlineAction::Close,
+                KeyCode::Up => {
+                    let moved = self.list.select_prev(self.items.len(), area);
+                    self.jump(moved)
+                }
+                KeyCode::Down => {
+                    let moved = self.list.select_next(self.items.len(), area);
+                    self.jump(moved)
+                }
+                KeyCode::Enter => match self.list.selected.and_then(|i| self.items.get(i)) {
+                    Some(item) => OutlineAction::JumpToAndExit(item.line),
+                    None => OutlineAction::None,
+                },
+                _ => OutlineAction::None,
+            },
+            Event::Mouse(mouse) => match mouse.kind {
+                MouseEventKind::Down(_) => {
+                    let index = self.list.row_at(mouse.row, area);
+                    let moved = self.list.select(index, self.items.len(), area);
+                    self.jump(moved)
+                }
+                kind => {
+                    self.list.scroll(kind, self.items.len(), area);
+                    OutlineAction::None
+                }
+            },
+            _ => OutlineAction::None,
+        }
+    }
+
+    /// Jumps to the newly selected symbol, keeping the outline open
+    fn jump(&self, moved: bool) -> OutlineAction {
+        match self.list.selected.and_then(|i| self.items.get(i)) {
+            Some(item) if moved => OutlineAction::JumpTo(item.line),
+            _ => OutlineAction::None,
+        }
+    }
+
+    pub fn render(&self, frame: &mut Frame, area: Rect) {
+        let titles = ("Outline", "No symbols");
+        self.list
+            .render_rows(frame, area, titles, self.items.len(), |i| {
+                let item = &self.items[i];
+                Line::from(vec![
+                    Span::raw("  ".repeat(item.depth)),
+                    Span::styled(format!("{} ", item.kind), Style::default().fg(Color::Blue)),
+                    Span::raw(item.name.clone()),
+                ])
+            });
+    }
+}
+
+/// Extracts declarations line by line, nesting them by indentation
+pub fn outline(content: &str) -> Vec<OutlineItem> {
+    let mut items = Vec::new();
+    let mut indents: Vec<usize> = Vec::new();
+
+    for (line, text) in content.lines().enumerate() {
+        let Some(caps) = SYMBOL_RE.captures(text) else {
+            continue;
+        };
+        let indent = caps[1].replace('\t', "    ").len();
+        while indents.last().is_some_and(|&i| i >= indent) {
+            indents.pop();
+        }
+        items.push(OutlineItem {
+            line,
+            depth: indents.len(),
+            kind: caps[2].to_string(),
+            name: caps[3].to_string(),
+        });
+        indents.push(indent);
+    }
+
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outline_rust() {
+        let content = "use std::fmt;\n\npub struct Foo;\n\nimpl Display for Foo {\n    pub fn fmt(&self) {}\n    fn helper() {}\n}\n";
+        let items = outline(content);
+
+        let names: Vec<_> = items
+            .iter()
+            .map(|i| (i.kind.as_str(), i.name.as_str(), i.depth))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("struct", "Foo", 0),
+                ("impl", "Display for Foo", 0),
+                ("fn", "fmt", 1),
+                ("fn", "helper", 1),
+            ]
+        );
+        assert_eq!(items[2].line, 5);
+    }
+
+    #[test]
+    fn test_outline_python() {
+        let content = "class A:\n    def run(self):\n        pass\n\nasync def main():\n    pass\n";
+        let items = outline(content);
+
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[1].name, "run");
+        assert_eq!(items[1].depth, 1);
+        assert_eq!(items[2].depth, 0);
+    }
+}
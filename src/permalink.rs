@@ -0,0 +1,97 @@
+use crate::git;
+use anyhow::{Context, Result, bail};
+use std::path::Path;
+
+/// Web link to lines `start..=end` (1-based) of `file` at the current HEAD
+/// commit, built from the `origin` remote (or the first one)
+pub fn permalink(file: &Path, start: usize, end: usize) -> Result<String> {
+    let file = std::fs::canonicalize(file).context("file is not saved")?;
+    let dir = file.parent().context("file has no parent directory")?;
+
+    let toplevel =
+        git::run(dir, &["rev-parse", "--show-toplevel"]).context("not in a git repository")?;
+    let toplevel = std::fs::canonicalize(toplevel)?;
+    let commit = git::run(dir, &["rev-parse", "HEAD"]).context("no commits yet")?;
+    let remote = match git::run(dir, &["remote", "get-url", "origin"]) {
+        Ok(url) => url,
+        Err(_) => {
+            let remotes = git::run(dir, &["remote"])?;
+            let Some(name) = remotes.lines().next() else {
+                bail!("no git remote");
+            };
+            git::run(dir, &["remote", "get-url", name])?
+        }
+    };
+    let base = web_url(&remote).with_context(|| format!("unsupported remote {remote}"))?;
+
+    let path = file.strip_prefix(&toplevel)?;
+    let path: Vec<String> = path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect();
+    Ok(format!(
+        "{base}/blob/{commit}/{}{}",
+        path.join("/"),
+        line_anchor(start, end)
+    ))
+}
+
+/// HTTPS web URL of a remote: `git@github.com:owner/repo.git`,
+/// `ssh://git@host:22/owner/repo` and `https://user@host/owner/repo.git`
+/// all become `https://host/owner/repo`
+fn web_url(remote: &str) -> Option<String> {
+    let remote = remote.trim().trim_end_matches('/');
+    let remote = remote.strip_suffix(".git").unwrap_or(remote);
+
+    let (host, path) = if let Some(rest) = remote
+        .strip_prefix("https://")
+        .or_else(|| remote.strip_prefix("http://"))
+        .or_else(|| remote.strip_prefix("ssh://"))
+        .or_else(|| remote.strip_prefix("git://"))
+    {
+        rest.split_once('/')?
+    } else {
+        // scp-like syntax: user@host:path
+        remote.split_once(':')?
+    };
+    // drop the user and, for ssh URLs, the port
+    let host = host.rsplit('@').next()?;
+    let host = if remote.starts_with("ssh://") {
+        host.split(':').next()?
+    } else {
+        host
+    };
+    if host.is_empty() || path.is_empty() {
+        return None;
+    }
+    Some(format!("https://{host}/{}", path.trim_start_matches('/')))
+}
+
+fn line_anchor(start: usize, end: usize) -> String {
+    if start == end {
+        format!("#L{start}")
+    } else {
+        format!("#L{start}-L{end}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_web_url() {
+        let expected = Some("https://github.com/vipmax/redai".to_string());
+        assert_eq!(web_url("git@github.com:vipmax/redai.git"), expected);
+        assert_eq!(web_url("https://github.com/vipmax/redai.git"), expected);
+        assert_eq!(web_url("https://token@github.com/vipmax/redai"), expected);
+        assert_eq!(
+            web_url("ssh://git@github.com:22/vipmax/redai.git"),
+            expected
+        );
+        assert_eq!(web_url("/srv/git/redai.git"), None);
+
+        assert_eq!(line_anchor(3, 3), "#L3");
+        assert_eq!(line_anchor(3, 7), "#L3-L7");
+    }
+}
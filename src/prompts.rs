@@ -7,6 +7,7 @@ Small content is a part of user's file working on.
 Edits MUST AFFECT only small context. 
 Recent user edits shows what the user has been working on recently - use this to understand users coding patterns, current focus, and maintain consistency with their recent changes. import functions, try to use recent functions from edits.
 Use diagnostics context to get extra information from lsp server, if any errors in diagnostic, understand it and try to fix.
+Other open files, when present, lists declarations from files the user worked on recently - use them to call existing functions and types correctly.
 Your response must be in the form of a change using the following tokens:
 
 <|SEARCH|> — indicates the text to find
@@ -52,7 +53,80 @@ Edits MUST AFFECT only small context. Do not include `small context` prefix in a
 check it multiple times!
 "#;
 
+pub const REWRITE_PROMPT: &str = r#"
+You are a code editor assistant.
+The user selected a block of code and wants it rewritten: cleaned up, simplified, fixed or completed, whatever the code most obviously needs.
+You will have multiple contexts: big, selected code, recent user edits.
+In big context the selection is wrapped in <|selection|> and <|/selection|>. Use it only to understand the surroundings.
+Recent user edits shows what the user has been working on recently - keep consistent with them.
+Rewrite ONLY the selected code. Keep its indentation and keep its meaning unless it is clearly wrong.
+
+Your response must be the complete new text of the selection in this form:
+<|DIVIDE|>{{replacement}}<|REPLACE|>
+
+Your response must begin with <|DIVIDE|>. THIS IS VERY IMPORTANT.
+Your response must end with <|REPLACE|>. THIS IS VERY IMPORTANT. do not add anything else after <|REPLACE|>.
+Do NOT wrap the code in markdown fences.
+
+ACCEPTED OUTPUT:
+<|DIVIDE|>let total: i32 = items.iter().sum();<|REPLACE|>
+"#;
+
+pub const REWRITE_REMINDER: &str = r#"
+Rewrite only the selected code. Respond with <|DIVIDE|>{{replacement}}<|REPLACE|> and nothing else.
+"#;
+
+pub const INSTRUCT_PROMPT: &str = r#"
+You are a code editor assistant.
+The user selected a block of code and gives an instruction for changing it.
+You will have multiple contexts: big, selected code, recent user edits.
+In big context the selection is wrapped in <|selection|> and <|/selection|>. Use it only to understand the surroundings.
+Change ONLY the selected code, as the instruction says. Keep its indentation.
+
+Answer with the complete new text of the selection in one markdown code block.
+"#;
+
+pub const INSTRUCT_REMINDER: &str = r#"
+Answer with the changed selected code in one markdown code block.
+"#;
+
+pub const FORMAT_PROMPT: &str = r#"
+You are a code formatter.
+The user selected a block of code to tidy up.
+You will have multiple contexts: big, selected code, recent user edits.
+In big context the selection is wrapped in <|selection|> and <|/selection|>. Use it only to understand the surroundings.
+Reformat ONLY the selected code: indentation, line breaks, spacing and alignment, in the usual style
+of its language and of the surrounding code. Keep the indentation of its first line.
+Do NOT change what the code does: no renames, no added or removed statements, no fixes.
+
+Answer with the complete formatted selection in one markdown code block.
+"#;
+
+pub const FORMAT_REMINDER: &str = r#"
+Answer with the formatted selected code in one markdown code block, its meaning unchanged.
+"#;
+
+pub const COMMIT_PROMPT: &str = r#"
+You write git commit messages.
+The user gives the changes to commit as removed (-) and added (+) lines per file.
+Write one commit message for all of them: a summary line of at most 72 characters in the
+imperative mood ("Add", "Fix", not "Added"), then, only when the reason is not obvious from
+the summary, a blank line and a short body wrapped at 72 characters.
+Describe what changed and why, not file by file. Do not invent changes that are not shown.
+
+Answer with the commit message in one markdown code block.
+"#;
+
+pub const PATCH_RETRY: &str = r#"
+Respond again with the same change strictly in the format:
+<|SEARCH|>{{search}}<|DIVIDE|>{{replace}}<|REPLACE|>
+Keep <|cursor|> in {{search}} exactly where it is in the user's code.
+Nothing before <|SEARCH|>, nothing after <|REPLACE|>, no explanations, no markdown.
+"#;
+
 pub const STOKEN: &str = "<|SEARCH|>";
 pub const DTOKEN: &str = "<|DIVIDE|>";
 pub const RTOKEN: &str = "<|REPLACE|>";
 pub const CTOKEN: &str = "<|cursor|>";
+pub const SELECTION_START: &str = "<|selection|>";
+pub const SELECTION_END: &str = "<|/selection|>";
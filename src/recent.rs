@@ -0,0 +1,111 @@
+use crossterm::event::{Event, KeyCode, KeyEventKind, MouseEventKind};
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Style},
+    text::{Line, Span},
+};
+use std::path::PathBuf;
+
+use crate::listpanel::ListPanel;
+use crate::tracker::RecentEdit;
+use crate::utils::display_path;
+
+pub enum RecentAction {
+    None,
+    Close,
+    Open(String),
+}
+
+/// Files edited in this session, to get back to them after a detour
+pub struct RecentPanel {
+    pub items: Vec<RecentEdit>,
+    pub list: ListPanel,
+    /// Paths of `items` as shown, relative to the roots
+    labels: Vec<String>,
+}
+
+impl RecentPanel {
+    pub fn new() -> Self {
+        Self {
+            items: Vec::new(),
+            list: ListPanel::default(),
+            labels: Vec::new(),
+        }
+    }
+
+    /// Replaces the listed files, keeping the selected file selected
+    pub fn refresh(&mut self, items: Vec<RecentEdit>, roots: &[PathBuf]) {
+        let selected = self.list.selected.and_then(|i| self.items.get(i));
+        let selected = selected.and_then(|old| items.iter().position(|e| e.path == old.path));
+        self.list.selected = selected.or((!items.is_empty()).then_some(0));
+        self.labels = items
+            .iter()
+            .map(|item| {
+                let path = std::fs::canonicalize(&item.path).unwrap_or_else(|_| item.path.clone());
+                display_path(&path, roots)
+            })
+            .collect();
+        self.items = items;
+        self.list.scroll_offset = self.list.scroll_offset.min(self.list.selected.unwrap_or(0));
+    }
+
+    pub fn handle_event(&mut self, event: &Event, area: Rect) -> RecentAction {
+        match event {
+            Event::Key(key) if !matches!(key.kind, KeyEventKind::Press) => RecentAction::None,
+            Event::Key(key) => match key.code {
+                KeyCode::Esc => RecentAction::Close,
+                KeyCode::Up => {
+                    self.list.select_prev(self.items.len(), area);
+                    RecentAction::None
+                }
+                KeyCode::Down => {
+                    self.list.select_next(self.items.len(), area);
+                    RecentAction::None
+                }
+                KeyCode::Enter => self.open(),
+                _ => RecentAction::None,
+            },
+            Event::Mouse(mouse) => match mouse.kind {
+                MouseEventKind::Down(_) => {
+                    let index = self.list.row_at(mouse.row, area);
+                    self.list.select(index, self.items.len(), area);
+                    self.open()
+                }
+                kind => {
+                    self.list.scroll(kind, self.items.len(), area);
+                    RecentAction::None
+                }
+            },
+            _ => RecentAction::None,
+        }
+    }
+
+    fn open(&self) -> RecentAction {
+        match self.list.selected.and_then(|i| self.items.get(i)) {
+            Some(item) => RecentAction::Open(item.path.to_string_lossy().to_string()),
+            None => RecentAction::None,
+        }
+    }
+
+    pub fn render(&self, frame: &mut Frame, area: Rect) {
+        let titles = ("Recent edits", "No edits yet");
+        self.list
+            .render_rows(frame, area, titles, self.items.len(), |i| {
+                let item = &self.items[i];
+                Line::from(vec![
+                    Span::styled(
+                        format!("+{}", item.added),
+                        Style::default().fg(Color::Green),
+                    ),
+                    Span::raw(" "),
+                    Span::styled(
+                        format!("-{}", item.removed),
+                        Style::default().fg(Color::Red),
+                    ),
+                    Span::raw(" "),
+                    Span::raw(self.labels[i].clone()),
+                ])
+            });
+    }
+}
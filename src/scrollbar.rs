@@ -0,0 +1,70 @@
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::style::{Color, Style};
+use ratatui::widgets::{Scrollbar, ScrollbarOrientation, ScrollbarState, StatefulWidget};
+
+/// Splits the rightmost column off `area` for a scrollbar, leaving the
+/// rest for the content; too narrow an area keeps its whole width
+pub fn split(area: Rect) -> (Rect, Rect) {
+    if area.width < 2 {
+        return (area, Rect { width: 0, ..area });
+    }
+    let content = Rect {
+        width: area.width - 1,
+        ..area
+    };
+    let bar = Rect {
+        x: content.right(),
+        width: 1,
+        ..area
+    };
+    (content, bar)
+}
+
+/// Draws a vertical scrollbar in `area` for `total` rows of which
+/// `viewport` are shown from `offset`; nothing when all of them fit
+pub fn render(buf: &mut Buffer, area: Rect, total: usize, offset: usize, viewport: usize) {
+    if area.is_empty() || total <= viewport {
+        return;
+    }
+    // the thumb reaches the bottom once the last row is in view
+    let positions = total.saturating_sub(viewport) + 1;
+    let mut state = ScrollbarState::new(positions)
+        .position(offset.min(positions - 1))
+        .viewport_content_length(viewport);
+    Scrollbar::new(ScrollbarOrientation::VerticalRight)
+        .begin_symbol(None)
+        .end_symbol(None)
+        .track_symbol(Some("│"))
+        .track_style(Style::default().fg(Color::DarkGray))
+        .thumb_style(Style::default().fg(Color::Gray))
+        .render(area, buf, &mut state);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scrollbar() {
+        let (content, bar) = split(Rect::new(2, 0, 10, 4));
+        assert_eq!(
+            (content, bar),
+            (Rect::new(2, 0, 9, 4), Rect::new(11, 0, 1, 4))
+        );
+        assert_eq!(split(Rect::new(0, 0, 1, 4)).0.width, 1);
+
+        let thumb = |total, offset| {
+            let mut buf = Buffer::empty(bar);
+            render(&mut buf, bar, total, offset, 4);
+            (0..4)
+                .map(|y| buf[(11, y)].symbol().to_string())
+                .collect::<Vec<_>>()
+                .join("")
+        };
+        assert_eq!(thumb(4, 0), "    ");
+        assert_eq!(thumb(16, 0), "█│││");
+        assert_eq!(thumb(16, 12), "│││█");
+        assert_eq!(thumb(8, 2), "│██│");
+    }
+}
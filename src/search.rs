@@ -9,23 +9,19 @@ use ratatui::{
     text::{Line, Span},
     widgets::{Block, Borders, Paragraph},
 };
-use rayon::prelude::*;
-use regex::RegexBuilder;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use std::time::Instant;
 use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
 
+use crate::scrollbar;
+use crate::searcher::{
+    MatchSpan, SearchKind, SearchOrder, SearchResult, SearchStep, Searcher, sort_results,
+};
+use crate::symbols::SymbolIndex;
 use crate::utils::*;
-
-#[derive(Clone, Debug)]
-pub struct SearchResult {
-    pub line: usize,
-    pub column: usize,
-    pub match_start: usize,
-    pub match_end: usize,
-    pub line_content: String,
-    pub file_path: Option<String>,
-}
+use crate::words::WordBoundary;
 
 #[derive(Clone, Debug)]
 pub enum SearchUpdate {
@@ -38,6 +34,11 @@ pub enum SearchUpdate {
         files_processed: usize,
         duration: std::time::Duration,
     },
+    /// Symbol index refreshed, holding definitions of this many files
+    SymbolsIndexed {
+        files: usize,
+        duration: std::time::Duration,
+    },
 }
 
 pub enum SearchAction {
@@ -47,12 +48,26 @@ pub enum SearchAction {
     Clear,
     JumpTo(SearchResult),
     JumpToAndExit(SearchResult),
+    /// Write all results to the export file
+    Export,
+    /// Ask for the text to replace every local match with
+    Replace,
 }
 
 #[derive(PartialEq)]
 pub enum SearchMode {
     Search,
     GlobalSearch,
+    /// Definitions across the workspace, filtered by fuzzy name
+    Symbols,
+}
+
+/// Matching options a new search starts with
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SearchOptions {
+    pub case_sensitive: bool,
+    pub regex_mode: bool,
+    pub whole_word: bool,
 }
 
 pub struct SearchPanel {
@@ -60,7 +75,17 @@ pub struct SearchPanel {
     pub query: String,
     pub case_sensitive: bool,
     pub regex_mode: bool,
+    pub whole_word: bool,
+    /// Replacing adjusts the replacement to the casing of each match
+    pub preserve_case: bool,
+    /// Options taken by every new search; the `Ctrl` toggles change them
+    /// for the current search only
+    pub defaults: SearchOptions,
     pub results: Vec<SearchResult>,
+    /// Matches of a local search, which fill no `results`: a result is only
+    /// made from its match in `searched` when shown
+    spans: Vec<MatchSpan>,
+    searched: String,
     pub scroll_offset: usize,
     pub selected: Option<usize>,
     pub mode: SearchMode,
@@ -68,6 +93,24 @@ pub struct SearchPanel {
     pub files_processed: Option<usize>,
     pub search_in_progress: bool,
     pub search_progress: Option<(usize, usize)>,
+    /// Whether Up/Down wrap around at the ends of the results list
+    pub wrap_around: bool,
+    /// When navigation last hit the end of results with wrapping off
+    end_reached: Option<Instant>,
+    /// Workspace roots searched by global search
+    pub roots: Vec<PathBuf>,
+    /// File `Ctrl+S` writes the results to
+    pub export_path: PathBuf,
+    /// Files with a line this long are skipped as minified, 0 never
+    pub max_line_length: usize,
+    /// Order of global search results
+    pub order: SearchOrder,
+    /// What the `Word` option takes for a whole word
+    pub word_boundary: WordBoundary,
+    /// Hides the options and timing rows to leave more room for results
+    pub compact: bool,
+    /// Definitions of workspace files, kept between symbol searches
+    symbols: Arc<Mutex<SymbolIndex>>,
     rx: mpsc::UnboundedReceiver<SearchUpdate>,
     tx: mpsc::UnboundedSender<SearchUpdate>,
     handle: Option<JoinHandle<()>>,
@@ -81,7 +124,12 @@ impl SearchPanel {
             query: String::new(),
             case_sensitive: false,
             regex_mode: false,
+            whole_word: false,
+            preserve_case: false,
+            defaults: SearchOptions::default(),
             results: Vec::new(),
+            spans: Vec::new(),
+            searched: String::new(),
             scroll_offset: 0,
             selected: None,
             mode: SearchMode::Search,
@@ -89,12 +137,52 @@ impl SearchPanel {
             files_processed: None,
             search_in_progress: false,
             search_progress: None,
+            wrap_around: true,
+            end_reached: None,
+            roots: Vec::new(),
+            export_path: PathBuf::new(),
+            max_line_length: crate::utils::MAX_LINE_LENGTH,
+            order: SearchOrder::default(),
+            word_boundary: WordBoundary::default(),
+            compact: false,
+            symbols: Arc::default(),
             rx,
             tx,
             handle: None,
         }
     }
 
+    /// Matching engine configured with the panel's query and options
+    pub fn searcher(&self) -> Searcher {
+        Searcher {
+            case_sensitive: self.case_sensitive,
+            regex_mode: self.regex_mode,
+            whole_word: self.whole_word,
+            word_boundary: self.word_boundary,
+            max_line_length: self.max_line_length,
+            ..Searcher::new(&self.query)
+        }
+    }
+
+    /// Number of results, local matches included
+    pub fn result_count(&self) -> usize {
+        self.results.len().max(self.spans.len())
+    }
+
+    /// Result at `index`, made on demand for local matches
+    pub fn result(&self, index: usize) -> Option<SearchResult> {
+        match self.spans.get(index) {
+            Some(span) => Some(span.result(&self.searched, None)),
+            None => self.results.get(index).cloned(),
+        }
+    }
+
+    fn drop_results(&mut self) {
+        self.results.clear();
+        self.spans.clear();
+        self.searched.clear();
+    }
+
     pub async fn recv(&mut self) -> Option<SearchUpdate> {
         self.rx.recv().await
     }
@@ -107,6 +195,9 @@ impl SearchPanel {
             }
             SearchUpdate::Results(new_results) => {
                 self.results.extend(new_results);
+                if self.mode != SearchMode::Symbols {
+                    self.sort_results();
+                }
                 if !self.results.is_empty() && self.selected.is_none() {
                     self.selected = Some(0);
                 }
@@ -123,6 +214,25 @@ impl SearchPanel {
                 }
                 self.handle = None;
             }
+            SearchUpdate::SymbolsIndexed { files, duration } => {
+                self.handle = None;
+                if self.mode == SearchMode::Symbols {
+                    self.search_time = Some(duration);
+                    self.files_processed = Some(files);
+                    self.find_symbols();
+                }
+            }
+        }
+    }
+
+    /// Sorts the results by [`Self::order`] as they stream in, keeping the
+    /// selected result selected
+    fn sort_results(&mut self) {
+        let key = |r: &SearchResult| (r.file_path.clone(), r.line, r.column, r.kind);
+        let selected = self.selected.and_then(|i| self.results.get(i)).map(key);
+        sort_results(&mut self.results, self.order);
+        if let Some(selected) = selected {
+            self.selected = self.results.iter().position(|r| key(r) == selected);
         }
     }
 
@@ -135,33 +245,141 @@ impl SearchPanel {
         }
     }
 
-    /// Start a global search, managing the handle internally
-    pub fn start_global_search(&mut self, root_path: std::path::PathBuf) {
+    /// Start a global search over all workspace roots, managing the handle internally
+    pub fn start_global_search(&mut self) {
         self.cancel();
-        self.results.clear();
+        self.drop_results();
         self.selected = None;
         self.scroll_offset = 0;
         self.search_in_progress = true;
         self.search_progress = None;
 
         self.handle = Some(Self::spawn_global_search(
-            root_path,
-            self.query.clone(),
-            self.case_sensitive,
-            self.regex_mode,
+            self.roots.clone(),
+            self.searcher(),
             self.tx.clone(),
         ));
     }
 
+    /// Refreshes the symbol index in the background, scanning only files
+    /// changed since the last refresh; results follow once it is done
+    pub fn refresh_symbols(&mut self) {
+        self.cancel();
+        self.search_in_progress = true;
+        let index = self.symbols.clone();
+        let roots = self.roots.clone();
+        let max_line_length = self.max_line_length;
+        let tx = self.tx.clone();
+        self.handle = Some(tokio::task::spawn_blocking(move || {
+            let start = Instant::now();
+            let files = index.lock().unwrap().refresh(&roots, max_line_length);
+            let _ = tx.send(SearchUpdate::SymbolsIndexed {
+                files,
+                duration: start.elapsed(),
+            });
+        }));
+    }
+
+    /// Lists the indexed definitions matching the query; while the index
+    /// is refreshing they are listed once the refresh is done
+    pub fn find_symbols(&mut self) {
+        let Ok(index) = self.symbols.try_lock() else {
+            return;
+        };
+        let results = index.find(&self.query);
+        drop(index);
+        self.drop_results();
+        self.results = results;
+        self.search_in_progress = false;
+        self.selected = (!self.results.is_empty()).then_some(0);
+        self.scroll_offset = 0;
+    }
+
+    /// Takes the definitions of a saved file into the symbol index; a
+    /// refresh in progress picks the file up on the next one instead
+    pub fn update_symbols(&self, path: &Path, content: &str) {
+        if let Ok(mut index) = self.symbols.try_lock() {
+            index.update_file(path, content);
+        }
+    }
+
+    /// Shows the panel again; results of a hidden search in the same mode
+    /// are kept so the previous position is restored instantly. Symbols are
+    /// listed again once the index has caught up with changed files
     pub fn activate(&mut self, mode: SearchMode) {
+        if self.mode != mode {
+            self.clear_results();
+        }
+        // a new search starts from the defaults, a hidden one keeps its own
+        if !self.active && self.query.is_empty() {
+            self.case_sensitive = self.defaults.case_sensitive;
+            self.regex_mode = self.defaults.regex_mode;
+            self.whole_word = self.defaults.whole_word;
+        }
         self.active = true;
         self.mode = mode;
+        if self.mode == SearchMode::Symbols {
+            self.refresh_symbols();
+        }
+    }
+
+    /// Switches between search modes, keeping the query and its options; a
+    /// non-empty query is re-run in the new mode right away
+    pub fn switch_mode(&mut self, mode: SearchMode) -> SearchAction {
+        if self.mode != mode {
+            self.clear_results();
+        }
+        self.mode = mode;
+        if self.mode == SearchMode::Symbols {
+            self.refresh_symbols();
+            SearchAction::None
+        } else if self.query.is_empty() {
+            SearchAction::None
+        } else {
+            SearchAction::UpdateSearch
+        }
+    }
+
+    /// Drops results and stops a running global search
+    fn clear_results(&mut self) {
+        self.cancel();
+        self.drop_results();
+        self.selected = None;
+        self.scroll_offset = 0;
+        self.search_time = None;
+        self.files_processed = None;
+    }
+
+    /// Shows results produced outside the panel, e.g. definition candidates
+    pub fn show_results(&mut self, mode: SearchMode, query: &str, results: Vec<SearchResult>) {
+        self.cancel();
+        self.activate(mode);
+        self.query = query.to_string();
+        self.selected = (!results.is_empty()).then_some(0);
+        self.drop_results();
+        self.results = results;
+        self.scroll_offset = 0;
+        self.search_time = None;
+        self.files_processed = None;
+    }
+
+    /// Hides the panel but keeps query, results, selection and scroll
+    pub fn hide(&mut self) {
+        self.active = false;
+        self.end_reached = None;
     }
 
+    /// Whether the panel was hidden with a search still around
+    pub fn is_hidden(&self) -> bool {
+        !self.active && !self.query.is_empty()
+    }
+
+    /// Hides the panel and drops the search entirely
     pub fn deactivate(&mut self) {
+        self.cancel();
         self.active = false;
         self.query.clear();
-        self.results.clear();
+        self.drop_results();
         self.selected = None;
         self.scroll_offset = 0;
         self.search_time = None;
@@ -185,14 +403,11 @@ impl SearchPanel {
                     let mode = match key.code {
                         KeyCode::Char('f') => Some(SearchMode::Search),
                         KeyCode::Char('g') => Some(SearchMode::GlobalSearch),
+                        KeyCode::Char('p') => Some(SearchMode::Symbols),
                         _ => None,
                     };
                     if let Some(new_mode) = mode {
-                        self.mode = new_mode;
-                        if !self.query.is_empty() {
-                            return SearchAction::UpdateSearch;
-                        }
-                        return SearchAction::None;
+                        return self.switch_mode(new_mode);
                     }
                 }
                 self.handle_input(*key, area)
@@ -220,14 +435,14 @@ impl SearchPanel {
 
         match key.code {
             KeyCode::Esc => {
-                self.deactivate();
+                self.hide();
                 SearchAction::Close
             }
             KeyCode::Enter => {
-                if let Some(selected) = self.selected {
-                    if let Some(result) = self.results.get(selected) {
-                        return SearchAction::JumpToAndExit(result.clone());
-                    }
+                if let Some(selected) = self.selected
+                    && let Some(result) = self.result(selected)
+                {
+                    return SearchAction::JumpToAndExit(result);
                 }
                 if self.mode == SearchMode::GlobalSearch {
                     SearchAction::UpdateSearch
@@ -252,8 +467,42 @@ impl SearchPanel {
                         SearchAction::UpdateSearch
                     }
                 }
+                'w' => {
+                    self.whole_word = !self.whole_word;
+                    if self.mode == SearchMode::GlobalSearch {
+                        SearchAction::None
+                    } else {
+                        SearchAction::UpdateSearch
+                    }
+                }
+                's' => SearchAction::Export,
+                'k' => {
+                    self.compact = !self.compact;
+                    SearchAction::None
+                }
                 _ => SearchAction::None,
             },
+            // case sensitivity for this search and every later one
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::ALT) => {
+                self.defaults.case_sensitive = !self.case_sensitive;
+                self.case_sensitive = self.defaults.case_sensitive;
+                if self.mode == SearchMode::GlobalSearch {
+                    SearchAction::None
+                } else {
+                    SearchAction::UpdateSearch
+                }
+            }
+            KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::ALT) => {
+                if self.mode == SearchMode::Search && !self.spans.is_empty() {
+                    SearchAction::Replace
+                } else {
+                    SearchAction::None
+                }
+            }
+            KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::ALT) => {
+                self.preserve_case = !self.preserve_case;
+                SearchAction::None
+            }
             KeyCode::Char(c) => {
                 self.query.push(c);
                 if self.mode == SearchMode::GlobalSearch {
@@ -271,68 +520,70 @@ impl SearchPanel {
                     SearchAction::UpdateSearch
                 }
             }
-            KeyCode::Up => {
-                let selected = self.selected.unwrap_or(0);
-                if selected > 0 {
-                    self.selected = Some(selected - 1);
-                    // Adjust scroll_offset to keep selected item visible
-                    let visible_height = area.height.saturating_sub(7) as usize;
-                    if let Some(sel) = self.selected {
-                        if sel < self.scroll_offset {
-                            self.scroll_offset = sel;
-                        } else if sel >= self.scroll_offset + visible_height {
-                            self.scroll_offset =
-                                sel.saturating_sub(visible_height.saturating_sub(1));
-                        }
-                    }
-                    if let Some(result) = self.results.get(selected - 1) {
-                        return SearchAction::JumpTo(result.clone());
-                    }
-                }
-                SearchAction::None
-            }
-            KeyCode::Down => {
-                let selected = self.selected.unwrap_or(0);
-                if selected < self.results.len().saturating_sub(1) {
-                    self.selected = Some(selected + 1);
-                    // Adjust scroll_offset to keep selected item visible
-                    let visible_height = area.height.saturating_sub(7) as usize;
-                    if let Some(sel) = self.selected {
-                        if sel < self.scroll_offset {
-                            self.scroll_offset = sel;
-                        } else if sel >= self.scroll_offset + visible_height {
-                            self.scroll_offset =
-                                sel.saturating_sub(visible_height.saturating_sub(1));
-                        }
-                    }
-                    if let Some(result) = self.results.get(selected + 1) {
-                        return SearchAction::JumpTo(result.clone());
-                    }
-                }
-                SearchAction::None
-            }
+            KeyCode::Up => self.select_prev(area),
+            KeyCode::Down => self.select_next(area),
             _ => SearchAction::None,
         }
     }
 
+    /// Moves the selection up, wrapping to the last result if enabled
+    pub fn select_prev(&mut self, area: Rect) -> SearchAction {
+        if self.result_count() == 0 {
+            return SearchAction::None;
+        }
+        let selected = self.selected.unwrap_or(0);
+        let prev = if selected > 0 {
+            selected - 1
+        } else if self.wrap_around {
+            self.result_count() - 1
+        } else {
+            self.end_reached = Some(Instant::now());
+            return SearchAction::None;
+        };
+        self.select(prev, area)
+    }
+
+    /// Moves the selection down, wrapping to the first result if enabled
+    pub fn select_next(&mut self, area: Rect) -> SearchAction {
+        if self.result_count() == 0 {
+            return SearchAction::None;
+        }
+        let selected = self.selected.unwrap_or(0);
+        let next = if selected < self.result_count() - 1 {
+            selected + 1
+        } else if self.wrap_around {
+            0
+        } else {
+            self.end_reached = Some(Instant::now());
+            return SearchAction::None;
+        };
+        self.select(next, area)
+    }
+
+    fn select(&mut self, index: usize, area: Rect) -> SearchAction {
+        self.selected = Some(index);
+        self.end_reached = None;
+
+        // Adjust scroll_offset to keep selected item visible
+        let visible_height = self.layout(area)[4].height as usize;
+        if index < self.scroll_offset {
+            self.scroll_offset = index;
+        } else if index >= self.scroll_offset + visible_height {
+            self.scroll_offset = index.saturating_sub(visible_height.saturating_sub(1));
+        }
+
+        match self.result(index) {
+            Some(result) => SearchAction::JumpTo(result),
+            None => SearchAction::None,
+        }
+    }
+
     pub fn handle_mouse_click(&mut self, mouse: &MouseEvent, area: Rect) -> SearchAction {
         if !self.active {
             return SearchAction::None;
         }
 
-        // Calculate the layout chunks to find the results list area
-        let chunks = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([
-                Constraint::Length(3), // Search input box
-                Constraint::Length(2), // Options
-                Constraint::Length(1), // Results count
-                Constraint::Length(1), // Timing and file count info
-                Constraint::Min(1),    // Results list
-            ])
-            .split(area);
-
-        let results_list_area = chunks[4];
+        let results_list_area = self.layout(area)[4];
 
         // Check if click is within the results list area
         if mouse.column >= results_list_area.x
@@ -346,12 +597,12 @@ impl SearchPanel {
             let clicked_index = self.scroll_offset + relative_row as usize;
 
             // Clamp to valid range
-            let clicked_index = clicked_index.min(self.results.len().saturating_sub(1));
+            let clicked_index = clicked_index.min(self.result_count().saturating_sub(1));
 
-            if clicked_index < self.results.len() {
+            if clicked_index < self.result_count() {
                 self.selected = Some(clicked_index);
-                if let Some(result) = self.results.get(clicked_index) {
-                    return SearchAction::JumpTo(result.clone());
+                if let Some(result) = self.result(clicked_index) {
+                    return SearchAction::JumpTo(result);
                 }
             }
         }
@@ -360,10 +611,8 @@ impl SearchPanel {
     }
 
     pub fn scroll_down(&mut self, area: Rect) {
-        // Calculate visible height for results area
-        // Layout: input(3) + options(2) + count(1) + timing(1) + results(rest)
-        let visible_height = area.height.saturating_sub(7) as usize;
-        let max_offset = self.results.len().saturating_sub(visible_height);
+        let visible_height = self.layout(area)[4].height as usize;
+        let max_offset = self.result_count().saturating_sub(visible_height);
         if self.scroll_offset < max_offset {
             self.scroll_offset += 1;
         }
@@ -376,7 +625,7 @@ impl SearchPanel {
     }
 
     pub fn search(&mut self, content: &str) {
-        self.results.clear();
+        self.drop_results();
         self.selected = None;
         self.scroll_offset = 0;
         self.files_processed = None;
@@ -386,42 +635,43 @@ impl SearchPanel {
             return;
         }
 
+        // counting the matches is one pass, results are made as they show
         let start = Instant::now();
-
-        let search_query = if self.case_sensitive {
-            self.query.clone()
-        } else {
-            self.query.to_lowercase()
-        };
-
-        let content_for_search_owned;
-        let content_for_search = if self.case_sensitive {
-            content
-        } else {
-            content_for_search_owned = content.to_lowercase();
-            &content_for_search_owned
-        };
-
-        let results = Self::search_matches(
-            &content,
-            content_for_search,
-            &search_query,
-            None,
-            self.case_sensitive,
-            self.regex_mode,
-        );
-
-        self.results.extend(results);
+        self.spans = self.searcher().find_spans(content);
+        if !self.spans.is_empty() {
+            self.searched = content.to_string();
+            self.selected = Some(0);
+        }
         self.search_time = Some(start.elapsed());
+    }
 
-        if !self.results.is_empty() {
-            self.selected = Some(0);
+    /// `content` with every match of the local search replaced by
+    /// `replacement`, and how many were; with `preserve_case` in literal mode
+    /// the replacement takes each match's casing, see [`preserve_case`]
+    pub fn replace_all(&self, content: &str, replacement: &str) -> (String, usize) {
+        let mut replaced = String::with_capacity(content.len());
+        let mut chars = content.chars();
+        let mut offset = 0;
+        for span in &self.spans {
+            replaced.extend(chars.by_ref().take(span.match_start - offset));
+            let matched: String = chars
+                .by_ref()
+                .take(span.match_end - span.match_start)
+                .collect();
+            if self.preserve_case && !self.regex_mode {
+                replaced.push_str(&preserve_case(&matched, replacement));
+            } else {
+                replaced.push_str(replacement);
+            }
+            offset = span.match_end;
         }
+        replaced.extend(chars);
+        (replaced, self.spans.len())
     }
 
     #[cfg(test)]
     pub fn global_search(&mut self, root_path: &std::path::Path) {
-        self.results.clear();
+        self.drop_results();
         self.selected = None;
         self.scroll_offset = 0;
 
@@ -432,41 +682,16 @@ impl SearchPanel {
         }
 
         let start = Instant::now();
-
-        let search_query = if self.case_sensitive {
-            self.query.clone()
-        } else {
-            self.query.to_lowercase()
-        };
-
-        // First, collect a list of all files
-        let mut files = Vec::new();
-        Self::collect_files(root_path, &mut files);
-
-        // Sort files by directory level (depth), then alphabetically
-        files.sort_by(|a, b| {
-            let depth_a = a.components().count();
-            let depth_b = b.components().count();
-            match depth_a.cmp(&depth_b) {
-                std::cmp::Ordering::Equal => a.cmp(b),
-                other => other,
+        let mut results = Vec::new();
+        let roots = [root_path.to_path_buf()];
+        let files_count = self.searcher().search_roots(&roots, |step| {
+            if let SearchStep::Results(found) = step {
+                results.extend(found);
             }
         });
 
-        let files_count = files.len();
-
-        // Parallel search across all files using rayon
-        let case_sensitive = self.case_sensitive;
-        let regex_mode = self.regex_mode;
-
-        let all_results: Vec<SearchResult> = files
-            .par_iter()
-            .flat_map(|file_path| {
-                Self::search_in_file(file_path, &search_query, case_sensitive, regex_mode)
-            })
-            .collect();
-
-        self.results.extend(all_results);
+        self.results.extend(results);
+        self.sort_results();
         self.search_time = Some(start.elapsed());
         self.files_processed = Some(files_count);
 
@@ -475,216 +700,117 @@ impl SearchPanel {
         }
     }
 
+    /// All results as `path:line:col:content` lines like `grep -n`, with
+    /// 1-based positions and a header naming the query and timing; local
+    /// results belong to `current_file`
+    pub fn quickfix(&self, current_file: &str) -> String {
+        let mut options = Vec::new();
+        if self.case_sensitive {
+            options.push("case");
+        }
+        if self.regex_mode {
+            options.push("regex");
+        }
+        if self.whole_word {
+            options.push("word");
+        }
+        let options = if options.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", options.join(", "))
+        };
+        let mut out = format!(
+            "# redai search {:?}{}: {} results",
+            self.query,
+            options,
+            self.result_count()
+        );
+        if let Some(duration) = self.search_time {
+            out.push_str(&format!(" in {} ms", duration.as_millis()));
+        }
+        if let Some(files) = self.files_processed {
+            out.push_str(&format!(", {files} files"));
+        }
+        out.push('\n');
+
+        let cwd = std::env::current_dir().unwrap_or_default();
+        for result in (0..self.result_count()).filter_map(|i| self.result(i)) {
+            let path = Path::new(result.file_path.as_deref().unwrap_or(current_file));
+            let path = path.strip_prefix(&cwd).unwrap_or(path);
+            // a file name match points at the start of the file
+            let (line, column, content) = match result.kind {
+                SearchKind::FileName => {
+                    (0, 0, path.file_name().unwrap_or_default().to_string_lossy())
+                }
+                SearchKind::Content => (
+                    result.line,
+                    result.column,
+                    result.line_content.trim_end_matches(['\n', '\r']).into(),
+                ),
+            };
+            out.push_str(&format!(
+                "{}:{}:{}:{}\n",
+                path.display(),
+                line + 1,
+                column + 1,
+                content
+            ));
+        }
+        out
+    }
+
+    /// Writes [`Self::quickfix`] to the export file, returning the number
+    /// of results written
+    pub fn export(&self, current_file: &str) -> std::io::Result<usize> {
+        if let Some(dir) = self.export_path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.export_path, self.quickfix(current_file))?;
+        Ok(self.result_count())
+    }
+
     /// Spawns global search in a separate task and sends updates through a channel
     /// Returns JoinHandle for task cancellation capability
     pub fn spawn_global_search(
-        root_path: std::path::PathBuf,
-        query: String,
-        case_sensitive: bool,
-        regex_mode: bool,
+        roots: Vec<PathBuf>,
+        searcher: Searcher,
         tx: mpsc::UnboundedSender<SearchUpdate>,
     ) -> tokio::task::JoinHandle<()> {
         tokio::task::spawn_blocking(move || {
             let start = Instant::now();
-
-            if query.is_empty() {
-                let _ = tx.send(SearchUpdate::Finished {
-                    files_processed: 0,
-                    duration: start.elapsed(),
-                });
-                return;
-            }
-
-            let search_query = if case_sensitive {
-                query.clone()
-            } else {
-                query.clone().to_lowercase()
-            };
-
-            // Collect list of all files
-            let mut files = Vec::new();
-            Self::collect_files(&root_path, &mut files);
-
-            // Sort files by directory level (depth), then alphabetically
-            files.sort_by(|a, b| {
-                let depth_a = a.components().count();
-                let depth_b = b.components().count();
-                match depth_a.cmp(&depth_b) {
-                    std::cmp::Ordering::Equal => a.cmp(b),
-                    other => other,
-                }
-            });
-
-            let files_count = files.len();
-
-            // Send initial progress
-            let _ = tx.send(SearchUpdate::Progress {
-                processed: 0,
-                total: files_count,
+            let files_processed = searcher.search_roots(&roots, |step| {
+                let update = match step {
+                    SearchStep::Progress { processed, total } => {
+                        SearchUpdate::Progress { processed, total }
+                    }
+                    SearchStep::Results(results) => SearchUpdate::Results(results),
+                };
+                let _ = tx.send(update);
             });
-
-            // Process files in batches to send intermediate results
-            let batch_size = 50.max(files_count / 100); // Batches of 50 files or 1% of total
-            let mut all_results = Vec::new();
-
-            for (batch_idx, batch) in files.chunks(batch_size).enumerate() {
-                // Parallel batch processing
-                let batch_results: Vec<SearchResult> = batch
-                    .par_iter()
-                    .flat_map(|file_path| {
-                        Self::search_in_file(file_path, &search_query, case_sensitive, regex_mode)
-                    })
-                    .collect();
-
-                // Send new batch results before adding to the main list
-                if !batch_results.is_empty() {
-                    let _ = tx.send(SearchUpdate::Results(batch_results.clone()));
-                }
-
-                all_results.extend(batch_results);
-
-                // Send progress
-                let processed = ((batch_idx + 1) * batch_size).min(files_count);
-                let _ = tx.send(SearchUpdate::Progress {
-                    processed,
-                    total: files_count,
-                });
-            }
-
-            // Send final results
-            let duration = start.elapsed();
             let _ = tx.send(SearchUpdate::Finished {
-                files_processed: files_count,
-                duration,
+                files_processed,
+                duration: start.elapsed(),
             });
         })
     }
 
-    fn collect_files(root_path: &std::path::Path, files: &mut Vec<std::path::PathBuf>) {
-        if is_search_ignored_dir(root_path) {
-            return;
-        }
-
-        if let Ok(entries) = std::fs::read_dir(root_path) {
-            for entry in entries.flatten() {
-                let path = entry.path();
-                if is_ignored_path(&path) {
-                    continue;
-                }
-
-                if path.is_dir() {
-                    Self::collect_files(&path, files);
-                } else if path.is_file() {
-                    files.push(path);
-                }
-            }
-        }
-    }
-
-    fn search_in_file(
-        file_path: &std::path::Path,
-        search_query: &str,
-        case_sensitive: bool,
-        regex_mode: bool,
-    ) -> Vec<SearchResult> {
-        // Skip files that are too large or binary
-        if should_skip_file_for_search(file_path) {
-            return Vec::new();
-        }
-
-        if let Ok(content) = std::fs::read_to_string(file_path) {
-            let file_path_str = file_path.to_string_lossy().to_string();
-
-            let content_for_search_owned;
-            let content_for_search = if case_sensitive {
-                content.as_str()
-            } else {
-                content_for_search_owned = content.to_lowercase();
-                &content_for_search_owned
-            };
-
-            Self::search_matches(
-                &content,
-                content_for_search,
-                search_query,
-                Some(file_path_str),
-                case_sensitive,
-                regex_mode,
-            )
-        } else {
-            Vec::new()
-        }
-    }
-
-    fn search_matches(
-        content: &str,
-        content_for_search: &str,
-        search_query: &str,
-        file_path: Option<String>,
-        case_sensitive: bool,
-        regex_mode: bool,
-    ) -> Vec<SearchResult> {
-        if regex_mode {
-            // Regex path: iterate matches on original content (Unicode-aware)
-            let mut results = Vec::new();
-            let regex = RegexBuilder::new(search_query)
-                .case_insensitive(!case_sensitive)
-                .multi_line(true)
-                .unicode(true)
-                .build();
-
-            if let Ok(re) = regex {
-                for m in re.find_iter(content) {
-                    let match_start_byte = m.start();
-                    let match_end_byte = m.end();
-
-                    let match_start_char = content[..match_start_byte].chars().count();
-                    let match_end_char = content[..match_end_byte].chars().count();
-
-                    let point = byte_to_point(match_start_byte, &content);
-                    let line = get_line(point.0, content).to_string();
-
-                    results.push(SearchResult {
-                        line: point.0,
-                        column: point.1,
-                        match_start: match_start_char,
-                        match_end: match_end_char,
-                        line_content: line,
-                        file_path: file_path.clone(),
-                    });
-                }
-            }
-
-            results
-        } else {
-            // Literal find path (with optional case-insensitivity via transformed view)
-            let mut results = Vec::new();
-            let mut start_byte = 0;
-
-            while let Some(pos) = content_for_search[start_byte..].find(&search_query) {
-                let match_start_byte = start_byte + pos;
-                let match_end_byte = match_start_byte + search_query.len();
-
-                let match_start_char = content[..match_start_byte].chars().count();
-                let match_end_char = match_start_char + search_query.chars().count();
-
-                let point = byte_to_point(match_start_byte, &content);
-                let line = get_line(point.0, content).to_string();
-
-                results.push(SearchResult {
-                    line: point.0,
-                    column: point.1,
-                    match_start: match_start_char,
-                    match_end: match_end_char,
-                    line_content: line,
-                    file_path: file_path.clone(),
-                });
-
-                start_byte = match_end_byte;
-            }
-
-            results
-        }
+    /// Rows of the panel in `area`: search input, options, results count,
+    /// timing and the results list, then the scrollbar column beside the
+    /// list; compact mode leaves the options and timing rows empty
+    fn layout(&self, area: Rect) -> [Rect; 6] {
+        let (options, timing) = if self.compact { (0, 0) } else { (2, 1) };
+        let [input, options, count, timing, results] = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3),       // Search input box
+                Constraint::Length(options), // Options
+                Constraint::Length(1),       // Results count
+                Constraint::Length(timing),  // Timing and file count info
+                Constraint::Min(1),          // Results list
+            ])
+            .areas(area);
+        let (results, bar) = scrollbar::split(results);
+        [input, options, count, timing, results, bar]
     }
 
     pub fn render(&mut self, frame: &mut Frame, area: Rect) {
@@ -692,22 +818,14 @@ impl SearchPanel {
             return;
         }
 
-        let chunks = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([
-                Constraint::Length(3), // Search input box
-                Constraint::Length(2), // Options
-                Constraint::Length(1), // Results count
-                Constraint::Length(1), // Timing and file count info
-                Constraint::Min(1),    // Results list
-            ])
-            .split(area);
+        let chunks = self.layout(area);
 
         // Search input box
         let search_block = Block::default()
             .title(match self.mode {
                 SearchMode::Search => "Search",
                 SearchMode::GlobalSearch => "Global Search",
+                SearchMode::Symbols => "Workspace Symbols",
             })
             .borders(Borders::NONE)
             .border_style(Style::default().fg(Color::Cyan));
@@ -718,7 +836,7 @@ impl SearchPanel {
         frame.render_widget(search_input, chunks[0]);
 
         // Options
-        let options = vec![
+        let mut options = vec![
             Span::raw("Ctrl+C: "),
             Span::styled(
                 if self.case_sensitive { "Case" } else { "case" },
@@ -737,27 +855,85 @@ impl SearchPanel {
                     Color::Gray
                 }),
             ),
+            Span::raw(" | Ctrl+W: "),
+            Span::styled(
+                if self.whole_word { "Word" } else { "word" },
+                Style::default().fg(if self.whole_word {
+                    Color::Green
+                } else {
+                    Color::Gray
+                }),
+            ),
         ];
+        if self.mode == SearchMode::Search {
+            options.push(Span::raw(" | Alt+P: "));
+            options.push(Span::styled(
+                if self.preserve_case {
+                    "Keep case"
+                } else {
+                    "keep case"
+                },
+                Style::default().fg(if self.preserve_case {
+                    Color::Green
+                } else {
+                    Color::Gray
+                }),
+            ));
+        }
         let options_line = Line::from(options);
         let options_para = Paragraph::new(vec![
             options_line,
-            Line::from(vec![Span::raw(match self.mode {
-                SearchMode::Search => "↑↓: Navigate | Enter: Jump",
-                SearchMode::GlobalSearch => "↑↓: Navigate | Enter: Search",
-            })]),
+            Line::from(vec![Span::raw(format!(
+                "↑↓: Navigate | Enter: {} | Ctrl+S: Export | Ctrl+K: Compact{}",
+                match self.mode {
+                    SearchMode::GlobalSearch => "Search",
+                    SearchMode::Search | SearchMode::Symbols => "Jump",
+                },
+                if self.mode == SearchMode::Search {
+                    " | Alt+R: Replace"
+                } else {
+                    ""
+                }
+            ))]),
         ])
         .style(Style::default().fg(Color::Gray));
         frame.render_widget(options_para, chunks[1]);
 
         // Results count
-        let results_text = if self.results.is_empty() {
+        let results_text = if self.result_count() == 0 {
             "No results".to_string()
         } else {
             let selected = self.selected.map(|i| i + 1).unwrap_or(0);
-            format!("{}/{} matches", selected, self.results.len())
+            let names = self
+                .results
+                .iter()
+                .filter(|r| r.kind == SearchKind::FileName)
+                .count();
+            if names > 0 {
+                format!(
+                    "{}/{} matches ({} files by name)",
+                    selected,
+                    self.result_count(),
+                    names
+                )
+            } else {
+                format!("{}/{} matches", selected, self.result_count())
+            }
         };
-        let results_para = Paragraph::new(results_text).style(Style::default().fg(Color::Yellow));
-        frame.render_widget(results_para, chunks[2]);
+        let mut results_line = vec![Span::styled(
+            results_text,
+            Style::default().fg(Color::Yellow),
+        )];
+        let end_reached = self
+            .end_reached
+            .is_some_and(|at| at.elapsed() < std::time::Duration::from_secs(1));
+        if end_reached {
+            results_line.push(Span::styled(
+                " | end of results",
+                Style::default().fg(Color::Red),
+            ));
+        }
+        frame.render_widget(Paragraph::new(Line::from(results_line)), chunks[2]);
 
         // Timing and file count info with progress
         let timing_text = if self.search_in_progress {
@@ -812,8 +988,10 @@ impl SearchPanel {
 
         // Calculate visible range: trim from top (scroll_offset) and bottom
         let start_idx = self.scroll_offset;
-        let end_idx = (self.scroll_offset + visible_height).min(self.results.len());
-        let visible_results = &self.results[start_idx..end_idx];
+        let end_idx = (self.scroll_offset + visible_height).min(self.result_count());
+        let visible_results: Vec<SearchResult> = (start_idx..end_idx)
+            .filter_map(|i| self.result(i))
+            .collect();
 
         // Adjust selected to be relative to visible slice
         let selected_relative = self.selected.and_then(|sel| {
@@ -845,70 +1023,44 @@ impl SearchPanel {
             };
 
             let max_width = results_area.width as usize;
-            let line = if let Some(file_path) = &result.file_path {
-                // global search
-
-                let relative_path = relative_to_current_dir(std::path::Path::new(file_path))
-                    .map(|p| p.to_string_lossy().to_string())
-                    .unwrap_or_default();
-
-                let position = format!(
-                    "{}:{}:{}",
-                    relative_path,
-                    result.line + 1,
-                    result.column + 1
-                );
-                let mut content = result.line_content.trim().to_string();
-
-                // Calculate available width for content (position + space)
-                let position_len = position.len();
-                let available_width = max_width.saturating_sub(position_len + 1);
-
-                // Truncate content if needed (safe UTF-8 truncation)
-                let content_char_count = content.chars().count();
-                if content_char_count > available_width {
-                    if available_width > 3 {
-                        let truncate_to = available_width.saturating_sub(3);
-                        content = content.chars().take(truncate_to).collect::<String>();
-                        content.push_str("...");
-                    } else {
-                        content = "...".to_string();
-                    }
-                }
-
-                Line::from(vec![
-                    Span::styled(position, Style::default().fg(Color::Blue)),
+            if result.kind == SearchKind::FileName {
+                let label = "file";
+                let available_width = max_width.saturating_sub(label.len() + 1);
+                let mut spans = vec![
+                    Span::styled(label, Style::default().fg(Color::Magenta)),
                     Span::raw(" "),
-                    Span::raw(content),
-                ])
+                ];
+                spans.extend(match_spans(result, available_width));
+                let item_area = Rect {
+                    y,
+                    height: 1,
+                    ..results_area
+                };
+                frame.render_widget(Paragraph::new(Line::from(spans)).style(style), item_area);
+                continue;
+            }
+            let position = if let Some(file_path) = &result.file_path {
+                // global search, the path gets at most half of the row
+                let suffix = format!(":{}:{}", result.line + 1, result.column + 1);
+                let relative_path = display_path(Path::new(file_path), &self.roots);
+                let path_width = (max_width / 2).saturating_sub(suffix.len());
+
+                format!("{}{}", truncate_left(&relative_path, path_width), suffix)
             } else {
                 // local search
-                let position = format!("{}:{}", result.line + 1, result.column + 1);
-                let mut content = result.line_content.trim().to_string();
-
-                // Calculate available width for content (position + space)
-                let position_len = position.len();
-                let available_width = max_width.saturating_sub(position_len + 1);
-
-                // Truncate content if needed (safe UTF-8 truncation)
-                let content_char_count = content.chars().count();
-                if content_char_count > available_width {
-                    if available_width > 3 {
-                        let truncate_to = available_width.saturating_sub(3);
-                        content = content.chars().take(truncate_to).collect::<String>();
-                        content.push_str("...");
-                    } else {
-                        content = "...".to_string();
-                    }
-                }
-
-                Line::from(vec![
-                    Span::styled(position, Style::default().fg(Color::Blue)),
-                    Span::raw(" "),
-                    Span::raw(content),
-                ])
+                format!("{}:{}", result.line + 1, result.column + 1)
             };
 
+            // Calculate available width for content (position + space)
+            let available_width = max_width.saturating_sub(position.len() + 1);
+
+            let mut spans = vec![
+                Span::styled(position, Style::default().fg(Color::Blue)),
+                Span::raw(" "),
+            ];
+            spans.extend(match_spans(result, available_width));
+            let line = Line::from(spans);
+
             let item_area = Rect {
                 x: results_area.x,
                 y,
@@ -919,7 +1071,116 @@ impl SearchPanel {
             let item_para = Paragraph::new(line).style(style);
             frame.render_widget(item_para, item_area);
         }
+
+        let (count, offset) = (self.result_count(), self.scroll_offset);
+        scrollbar::render(frame.buffer_mut(), chunks[5], count, offset, visible_height);
+    }
+}
+
+/// Casing of `replacement` adjusted to the matched text for a
+/// case-preserving literal replace:
+/// - `color` (all lower) gives `shade`
+/// - `COLOR` (all caps, two or more letters) gives `SHADE`
+/// - `Color` (first upper, rest lower) gives `Shade`
+/// - anything else, e.g. `colorMap`, keeps `replacement` as typed
+fn preserve_case(matched: &str, replacement: &str) -> String {
+    let letters: Vec<char> = matched.chars().filter(|c| c.is_alphabetic()).collect();
+    let upper = letters.iter().filter(|c| c.is_uppercase()).count();
+    let lower = letters.iter().filter(|c| c.is_lowercase()).count();
+    let first_upper = letters.first().is_some_and(|c| c.is_uppercase());
+
+    if upper == 0 && lower > 0 {
+        replacement.to_lowercase()
+    } else if lower == 0 && upper > 1 {
+        replacement.to_uppercase()
+    } else if first_upper && upper == 1 {
+        let mut chars = replacement.chars();
+        match chars.next() {
+            Some(first) => first
+                .to_uppercase()
+                .chain(chars.flat_map(char::to_lowercase))
+                .collect(),
+            None => String::new(),
+        }
+    } else {
+        replacement.to_string()
+    }
+}
+
+/// Keeps the end of `text` within `max_width` chars, marking a cut with an ellipsis
+fn truncate_left(text: &str, max_width: usize) -> String {
+    let len = text.chars().count();
+    if len <= max_width {
+        return text.to_string();
+    }
+    let keep = max_width.saturating_sub(1);
+    let tail: String = text.chars().skip(len - keep).collect();
+    format!("…{}", tail)
+}
+
+/// Splits the trimmed result line into spans with the match emphasized,
+/// truncating with ellipses so the match stays visible
+fn match_spans(result: &SearchResult, available_width: usize) -> Vec<Span<'static>> {
+    let chars: Vec<char> = result.line_content.chars().collect();
+    let lead = chars.iter().take_while(|c| c.is_whitespace()).count();
+    let trail = chars[lead..]
+        .iter()
+        .rev()
+        .take_while(|c| c.is_whitespace())
+        .count();
+    let text = &chars[lead..chars.len() - trail];
+
+    // match offsets relative to the trimmed line, clamped to this line
+    let match_len = result.match_end.saturating_sub(result.match_start);
+    let hl_start = result.column.saturating_sub(lead).min(text.len());
+    let hl_end = (result.column + match_len)
+        .saturating_sub(lead)
+        .clamp(hl_start, text.len());
+
+    let (mut from, mut to) = (0, text.len());
+    let (mut prefix, mut suffix) = (false, false);
+    if text.len() > available_width {
+        if available_width <= 3 {
+            return vec![Span::raw("...")];
+        }
+        if hl_end <= available_width - 3 {
+            to = available_width - 3;
+            suffix = true;
+        } else {
+            // Shift the window right so the match is not cut off
+            prefix = true;
+            let room = available_width.saturating_sub(6).max(1);
+            from = hl_start
+                .saturating_sub(4)
+                .max(hl_end.saturating_sub(room))
+                .min(hl_start);
+            if text.len() - from <= available_width - 3 {
+                from = text.len() - (available_width - 3);
+            } else {
+                to = from + room;
+                suffix = true;
+            }
+        }
+    }
+
+    let slice = |a: usize, b: usize| -> String {
+        text[a.clamp(from, to)..b.clamp(from, to)].iter().collect()
+    };
+    let highlight = Style::default()
+        .fg(Color::Yellow)
+        .add_modifier(Modifier::BOLD);
+
+    let mut spans = Vec::new();
+    if prefix {
+        spans.push(Span::raw("..."));
+    }
+    spans.push(Span::raw(slice(from, hl_start)));
+    spans.push(Span::styled(slice(hl_start, hl_end), highlight));
+    spans.push(Span::raw(slice(hl_end, to)));
+    if suffix {
+        spans.push(Span::raw("..."));
     }
+    spans
 }
 
 #[cfg(test)]
@@ -932,10 +1193,108 @@ mod tests {
         search_panel.query = "let".to_string();
         search_panel.search("let mut foo = 2;\nfoo *= 50;");
 
-        assert_eq!(search_panel.results.len(), 1);
-        assert_eq!(search_panel.results[0].line, 0);
-        assert_eq!(search_panel.results[0].column, 0);
-        assert_eq!(search_panel.results[0].match_start, 0);
+        assert_eq!(search_panel.result_count(), 1);
+        let result = search_panel.result(0).unwrap();
+        assert_eq!(result.line, 0);
+        assert_eq!(result.column, 0);
+        assert_eq!(result.match_start, 0);
+    }
+
+    #[test]
+    fn test_local_results_made_on_demand() {
+        let mut search_panel = SearchPanel::new();
+        search_panel.query = "foo".to_string();
+        search_panel.search(&"let foo = 1;\n".repeat(100_000));
+
+        assert_eq!(search_panel.result_count(), 100_000);
+        assert!(search_panel.results.is_empty());
+        let last = search_panel.result(99_999).unwrap();
+        assert_eq!((last.line, last.column), (99_999, 4));
+        assert_eq!(last.match_start, 99_999 * 13 + 4);
+        assert_eq!(last.line_content, "let foo = 1;");
+        assert!(search_panel.result(100_000).is_none());
+    }
+
+    #[test]
+    fn test_navigation_wraparound() {
+        let mut search_panel = SearchPanel::new();
+        search_panel.query = "foo".to_string();
+        search_panel.search(&"foo\n".repeat(20));
+        let area = Rect::new(0, 0, 40, 12);
+
+        search_panel.select(19, area);
+        assert!(search_panel.scroll_offset > 0);
+
+        search_panel.select_next(area);
+        assert_eq!(search_panel.selected, Some(0));
+        assert_eq!(search_panel.scroll_offset, 0);
+
+        search_panel.select_prev(area);
+        assert_eq!(search_panel.selected, Some(19));
+        assert!(search_panel.scroll_offset + 5 > 19);
+    }
+
+    #[test]
+    fn test_navigation_stops_without_wraparound() {
+        let mut search_panel = SearchPanel::new();
+        search_panel.wrap_around = false;
+        search_panel.query = "foo".to_string();
+        search_panel.search("foo\nfoo\n");
+        let area = Rect::new(0, 0, 40, 12);
+
+        search_panel.select(1, area);
+        let action = search_panel.select_next(area);
+        assert!(matches!(action, SearchAction::None));
+        assert_eq!(search_panel.selected, Some(1));
+        assert!(search_panel.end_reached.is_some());
+
+        search_panel.select_prev(area);
+        assert!(search_panel.end_reached.is_none());
+    }
+
+    #[test]
+    fn test_hide_keeps_results() {
+        let mut search_panel = SearchPanel::new();
+        search_panel.activate(SearchMode::Search);
+        search_panel.query = "foo".to_string();
+        search_panel.search(&"foo\n".repeat(30));
+        search_panel.select(25, Rect::new(0, 0, 40, 12));
+        let scroll_offset = search_panel.scroll_offset;
+
+        search_panel.hide();
+        assert!(search_panel.is_hidden());
+
+        search_panel.activate(SearchMode::Search);
+        assert_eq!(search_panel.result_count(), 30);
+        assert_eq!(search_panel.selected, Some(25));
+        assert_eq!(search_panel.scroll_offset, scroll_offset);
+
+        search_panel.hide();
+        search_panel.deactivate();
+        assert!(!search_panel.is_hidden());
+        assert_eq!(search_panel.result_count(), 0);
+    }
+
+    #[test]
+    fn test_match_spans() {
+        let text = |spans: &[Span]| -> Vec<String> {
+            spans.iter().map(|s| s.content.to_string()).collect()
+        };
+
+        let mut search_panel = SearchPanel::new();
+        search_panel.query = "needle".to_string();
+        search_panel.search("    let needle = 1;  ");
+        let result = &search_panel.result(0).unwrap();
+
+        let spans = match_spans(result, 80);
+        assert_eq!(text(&spans), vec!["let ", "needle", " = 1;"]);
+
+        let spans = match_spans(result, 14);
+        assert_eq!(text(&spans), vec!["let ", "needle", " ", "..."]);
+
+        // the match sits past the cut, so the window shifts right
+        let spans = match_spans(result, 12);
+        assert_eq!(text(&spans), vec!["...", "", "needle", "", "..."]);
     }
 
     #[test]
@@ -945,7 +1304,7 @@ mod tests {
         let content = std::fs::read_to_string("README.md").unwrap();
         search_panel.search(&content);
 
-        assert_eq!(search_panel.results.len(), 5);
+        assert_eq!(search_panel.result_count(), 5);
     }
 
     #[test]
@@ -984,6 +1343,31 @@ mod tests {
         dir.close().unwrap();
     }
 
+    #[test]
+    fn test_global_search_file_names_first() {
+        use tempfile::tempdir;
+
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "uses config\n").unwrap();
+        std::fs::write(dir.path().join("config.rs"), "fn main() {}\n").unwrap();
+
+        let mut search_panel = SearchPanel::new();
+        search_panel.query = "config".to_string();
+        search_panel.mode = SearchMode::GlobalSearch;
+        search_panel.global_search(dir.path());
+
+        let kinds: Vec<_> = search_panel.results.iter().map(|r| r.kind).collect();
+        assert_eq!(kinds, vec![SearchKind::FileName, SearchKind::Content]);
+        assert_eq!(search_panel.results[0].line_content, "config.rs");
+        assert!(
+            search_panel.results[1]
+                .file_path
+                .as_deref()
+                .unwrap()
+                .ends_with("a.txt")
+        );
+    }
+
     #[test]
     fn test_global_search_specific_directory_with_timing() {
         use std::path::PathBuf;
@@ -1018,4 +1402,224 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_quickfix() {
+        let mut search_panel = SearchPanel::new();
+        search_panel.query = "foo".to_string();
+        search_panel.whole_word = true;
+        search_panel.search("let foo = 1;\n  foo += 2;\r\n");
+        search_panel.search_time = Some(std::time::Duration::from_millis(12));
+
+        // file name matches only come from global search
+        let mut results: Vec<_> = (0..2).filter_map(|i| search_panel.result(i)).collect();
+        let mut name = results[0].clone();
+        name.kind = SearchKind::FileName;
+        name.file_path = Some("/work/src/foo.rs".to_string());
+        results.push(name);
+        search_panel.drop_results();
+        search_panel.results = results;
+
+        assert_eq!(
+            search_panel.quickfix("/work/src/main.rs"),
+            "# redai search \"foo\" (word): 3 results in 12 ms\n\
+             /work/src/main.rs:1:5:let foo = 1;\n\
+             /work/src/main.rs:2:3:  foo += 2;\n\
+             /work/src/foo.rs:1:1:foo.rs\n"
+        );
+    }
+
+    #[test]
+    fn test_truncate_left() {
+        assert_eq!(truncate_left("src/mod.rs", 20), "src/mod.rs");
+        assert_eq!(truncate_left("src/search/mod.rs", 10), "…ch/mod.rs");
+        assert_eq!(truncate_left("src/mod.rs", 0), "…");
+    }
+
+    #[test]
+    fn test_replace_all() {
+        let area = Rect::new(0, 0, 40, 12);
+        let alt = |c| Event::Key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::ALT));
+        let content = "color Color COLOR colorMap\n";
+        let mut search_panel = SearchPanel::new();
+        search_panel.activate(SearchMode::Search);
+        search_panel.query = "color".to_string();
+        search_panel.search(content);
+
+        let action = search_panel.handle_event(&alt('r'), area);
+        assert!(matches!(action, SearchAction::Replace));
+        let (replaced, count) = search_panel.replace_all(content, "shade");
+        assert_eq!(count, 4);
+        assert_eq!(replaced, "shade shade shade shadeMap\n");
+
+        search_panel.handle_event(&alt('p'), area);
+        assert!(search_panel.preserve_case);
+        let (replaced, _) = search_panel.replace_all(content, "shade");
+        assert_eq!(replaced, "shade Shade SHADE shadeMap\n");
+
+        // regex matches take the replacement as typed
+        search_panel.regex_mode = true;
+        search_panel.query = "c.lor".to_string();
+        search_panel.search("Café Color\n");
+        let (replaced, count) = search_panel.replace_all("Café Color\n", "shade");
+        assert_eq!((replaced.as_str(), count), ("Café shade\n", 1));
+    }
+
+    #[test]
+    fn test_preserve_case() {
+        assert_eq!(preserve_case("color", "Shade"), "shade");
+        assert_eq!(preserve_case("Color", "shade"), "Shade");
+        assert_eq!(preserve_case("COLOR", "shade"), "SHADE");
+        assert_eq!(preserve_case("C", "shade"), "Shade");
+        assert_eq!(preserve_case("colorMap", "shadeMap"), "shadeMap");
+        assert_eq!(preserve_case("CoLoR", "shade"), "shade");
+        assert_eq!(preserve_case("123", "Shade"), "Shade");
+    }
+
+    fn ctrl(c: char) -> Event {
+        Event::Key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL))
+    }
+
+    #[test]
+    fn test_default_options() {
+        let area = Rect::new(0, 0, 40, 12);
+        let mut search_panel = SearchPanel::new();
+        search_panel.defaults.case_sensitive = true;
+        search_panel.activate(SearchMode::Search);
+        assert!(search_panel.case_sensitive && !search_panel.regex_mode);
+
+        // a one-off toggle lasts while the search is only hidden
+        search_panel.handle_event(&ctrl('c'), area);
+        search_panel.query = "foo".to_string();
+        search_panel.hide();
+        search_panel.activate(SearchMode::Search);
+        assert!(!search_panel.case_sensitive);
+        search_panel.deactivate();
+        search_panel.activate(SearchMode::Search);
+        assert!(search_panel.case_sensitive);
+
+        // Alt+c changes the default as well
+        let alt_c = Event::Key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::ALT));
+        search_panel.handle_event(&alt_c, area);
+        assert!(search_panel.query.is_empty());
+        search_panel.deactivate();
+        search_panel.activate(SearchMode::GlobalSearch);
+        assert!(!search_panel.case_sensitive && !search_panel.defaults.case_sensitive);
+    }
+
+    #[test]
+    fn test_switch_mode_keeps_query_and_options() {
+        let area = Rect::new(0, 0, 40, 12);
+        let mut search_panel = SearchPanel::new();
+        search_panel.activate(SearchMode::Search);
+        search_panel.query = "fo+".to_string();
+        search_panel.case_sensitive = true;
+        search_panel.regex_mode = true;
+        search_panel.search("foo\nFoo\n");
+        assert_eq!(search_panel.result_count(), 1);
+
+        let action = search_panel.handle_event(&ctrl('g'), area);
+        assert!(matches!(action, SearchAction::UpdateSearch));
+        assert!(search_panel.mode == SearchMode::GlobalSearch);
+        assert_eq!(search_panel.result_count(), 0);
+        assert_eq!(search_panel.query, "fo+");
+        assert!(search_panel.case_sensitive && search_panel.regex_mode);
+
+        let action = search_panel.handle_event(&ctrl('f'), area);
+        assert!(matches!(action, SearchAction::UpdateSearch));
+        assert!(search_panel.mode == SearchMode::Search);
+        search_panel.search("foo\nFoo\n");
+        assert_eq!(search_panel.result_count(), 1);
+    }
+
+    #[test]
+    fn test_compact_layout() {
+        let area = Rect::new(0, 0, 40, 12);
+        let mut search_panel = SearchPanel::new();
+        search_panel.activate(SearchMode::Search);
+        search_panel.query = "x".to_string();
+        search_panel.search(&"x\n".repeat(20));
+        assert_eq!(search_panel.layout(area)[4].height, 5);
+
+        // the options and timing rows give their room to the results
+        search_panel.handle_event(&ctrl('k'), area);
+        assert!(search_panel.compact);
+        let results = search_panel.layout(area)[4];
+        assert_eq!((results.y, results.height), (4, 8));
+        search_panel.select(7, area);
+        assert_eq!(search_panel.scroll_offset, 0);
+        search_panel.select(8, area);
+        assert_eq!(search_panel.scroll_offset, 1);
+
+        let click = MouseEvent {
+            kind: MouseEventKind::Down(crossterm::event::MouseButton::Left),
+            column: 2,
+            row: 4,
+            modifiers: KeyModifiers::NONE,
+        };
+        let action = search_panel.handle_mouse_click(&click, area);
+        assert!(matches!(action, SearchAction::JumpTo(result) if result.line == 1));
+        // the scrollbar column right of the list is not a result
+        let bar = search_panel.layout(area)[5];
+        assert_eq!((bar.x, bar.width), (39, 1));
+        let click = MouseEvent {
+            column: 39,
+            ..click
+        };
+        let action = search_panel.handle_mouse_click(&click, area);
+        assert!(matches!(action, SearchAction::None));
+    }
+
+    #[test]
+    fn test_switch_mode_with_empty_query() {
+        let area = Rect::new(0, 0, 40, 12);
+        let mut search_panel = SearchPanel::new();
+        search_panel.activate(SearchMode::Search);
+
+        let action = search_panel.handle_event(&ctrl('g'), area);
+        assert!(matches!(action, SearchAction::None));
+        assert!(search_panel.mode == SearchMode::GlobalSearch);
+
+        let action = search_panel.handle_event(&ctrl('f'), area);
+        assert!(matches!(action, SearchAction::None));
+        assert!(search_panel.mode == SearchMode::Search);
+        assert!(search_panel.query.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_symbol_search() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("lib.rs");
+        std::fs::write(&file, "fn open_file() {}\nfn close_file() {}\n").unwrap();
+        let area = Rect::new(0, 0, 40, 12);
+        let mut search_panel = SearchPanel::new();
+        search_panel.roots = vec![dir.path().to_path_buf()];
+        search_panel.activate(SearchMode::Search);
+
+        // an empty query lists every symbol once the index is built
+        let action = search_panel.handle_event(&ctrl('p'), area);
+        assert!(matches!(action, SearchAction::None));
+        assert!(search_panel.search_in_progress);
+        let update = search_panel.recv().await.unwrap();
+        search_panel.apply_update(update);
+        assert!(!search_panel.search_in_progress);
+        assert_eq!(search_panel.files_processed, Some(1));
+        assert_eq!(search_panel.results.len(), 2);
+
+        let key = |c| Event::Key(KeyEvent::from(KeyCode::Char(c)));
+        search_panel.handle_event(&key('c'), area);
+        assert!(matches!(
+            search_panel.handle_event(&key('f'), area),
+            SearchAction::UpdateSearch
+        ));
+        search_panel.find_symbols();
+        assert_eq!(search_panel.results.len(), 1);
+        assert_eq!(search_panel.results[0].match_start, 21);
+        assert_eq!(search_panel.selected, Some(0));
+
+        // a save is picked up without another refresh
+        search_panel.update_symbols(&file, "fn cf() {}\n");
+        search_panel.find_symbols();
+        assert_eq!(search_panel.results[0].match_start, 3);
+    }
 }
@@ -0,0 +1,620 @@
+use rayon::prelude::*;
+use regex::{Regex, RegexBuilder};
+use std::path::{Path, PathBuf};
+
+use crate::utils::*;
+use crate::words::WordBoundary;
+
+/// What a search result matched
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SearchKind {
+    /// The file name; `line_content` holds the displayed path and the
+    /// match offsets point into it
+    FileName,
+    /// A line of the file; match offsets point into its content
+    #[default]
+    Content,
+}
+
+#[derive(Clone, Debug)]
+pub struct SearchResult {
+    pub line: usize,
+    pub column: usize,
+    pub match_start: usize,
+    pub match_end: usize,
+    pub line_content: String,
+    pub file_path: Option<String>,
+    pub kind: SearchKind,
+}
+
+/// Where a match is, without the text of its line; cheap to find in bulk,
+/// [`MatchSpan::result`] makes the full result when it is shown
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MatchSpan {
+    pub line: usize,
+    pub column: usize,
+    pub match_start: usize,
+    pub match_end: usize,
+    /// Byte offset of the start of the line
+    line_start: usize,
+}
+
+impl MatchSpan {
+    /// The result for this match in `content`, the text it was found in
+    pub fn result(&self, content: &str, file_path: Option<String>) -> SearchResult {
+        let line = content[self.line_start..]
+            .split('\n')
+            .next()
+            .unwrap_or_default();
+        SearchResult {
+            line: self.line,
+            column: self.column,
+            match_start: self.match_start,
+            match_end: self.match_end,
+            line_content: line.to_string(),
+            file_path,
+            kind: SearchKind::Content,
+        }
+    }
+}
+
+/// How global search results are ordered
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SearchOrder {
+    /// File name matches first, then content matches, shallow files first
+    #[default]
+    Relevance,
+    /// By path, then line and column; a file's name match before its lines
+    Path,
+}
+
+impl SearchOrder {
+    pub fn parse(spec: &str) -> Option<Self> {
+        match spec.trim().to_lowercase().as_str() {
+            "relevance" => Some(SearchOrder::Relevance),
+            "path" => Some(SearchOrder::Path),
+            _ => None,
+        }
+    }
+}
+
+/// Sorts `results` by `order`, so that the list does not depend on which
+/// files were searched first
+pub fn sort_results(results: &mut [SearchResult], order: SearchOrder) {
+    let path = |r: &SearchResult| r.file_path.as_deref().map(PathBuf::from);
+    match order {
+        SearchOrder::Relevance => results.sort_by_cached_key(|r| {
+            let path = path(r);
+            let depth = path.as_ref().map_or(0, |p| p.components().count());
+            let content = r.kind == SearchKind::Content;
+            (content, depth, path, r.line, r.column)
+        }),
+        SearchOrder::Path => results.sort_by_cached_key(|r| {
+            let content = r.kind == SearchKind::Content;
+            (path(r), content, r.line, r.column)
+        }),
+    }
+}
+
+/// What [`Searcher::search_roots`] reports while it runs
+#[derive(Debug)]
+pub enum SearchStep {
+    /// Files searched so far out of all files to search
+    Progress { processed: usize, total: usize },
+    /// Matches of the files searched since the last step
+    Results(Vec<SearchResult>),
+}
+
+/// Matching engine behind the search panel, usable without any UI
+#[derive(Clone, Debug, Default)]
+pub struct Searcher {
+    pub query: String,
+    pub case_sensitive: bool,
+    pub regex_mode: bool,
+    pub whole_word: bool,
+    /// What a whole word is with `whole_word`
+    pub word_boundary: WordBoundary,
+    /// Files with a line this long are skipped as minified, 0 never
+    pub max_line_length: usize,
+}
+
+impl Searcher {
+    pub fn new(query: &str) -> Self {
+        Self {
+            query: query.to_string(),
+            max_line_length: MAX_LINE_LENGTH,
+            ..Default::default()
+        }
+    }
+
+    /// The query compiled once, to search many files or names with it
+    pub fn matcher(&self) -> Matcher<'_> {
+        let pattern = if self.query.is_empty() {
+            Pattern::Nothing
+        } else if self.regex_mode || !self.case_sensitive {
+            // lowercasing the content could change its length (`İ` becomes
+            // two chars), so case-insensitive literals go through the regex
+            // engine's case folding and match offsets stay those of `content`
+            let pattern = if self.regex_mode {
+                self.query.clone()
+            } else {
+                regex::escape(&self.query)
+            };
+            RegexBuilder::new(&pattern)
+                .case_insensitive(!self.case_sensitive)
+                .multi_line(true)
+                .unicode(true)
+                .build()
+                .map_or(Pattern::Nothing, Pattern::Regex)
+        } else {
+            Pattern::Literal
+        };
+        Matcher {
+            searcher: self,
+            pattern,
+        }
+    }
+
+    /// Finds all matches in `content`
+    #[cfg(test)]
+    pub fn search_str(&self, content: &str) -> Vec<SearchResult> {
+        self.matcher().search_matches(content, None)
+    }
+
+    /// Searches the names, then the content of every file under `roots`,
+    /// shallow files first, reporting each step to `report` as a batch
+    /// finishes; returns the number of files searched
+    pub fn search_roots(&self, roots: &[PathBuf], mut report: impl FnMut(SearchStep)) -> usize {
+        if self.query.is_empty() {
+            return 0;
+        }
+        let files = Self::collect_files(roots);
+        let total = files.len();
+        report(SearchStep::Progress {
+            processed: 0,
+            total,
+        });
+
+        // file name matches come first, ahead of any content match
+        let matcher = self.matcher();
+        let name_results = matcher.search_names(&files, roots);
+        if !name_results.is_empty() {
+            report(SearchStep::Results(name_results));
+        }
+
+        // batches of 50 files or 1% of them, searched in parallel
+        let batch_size = 50.max(total / 100);
+        let mut processed = 0;
+        for batch in files.chunks(batch_size) {
+            let results: Vec<SearchResult> = batch
+                .par_iter()
+                .flat_map(|file_path| matcher.search_file(file_path))
+                .collect();
+            if !results.is_empty() {
+                report(SearchStep::Results(results));
+            }
+            processed += batch.len();
+            report(SearchStep::Progress { processed, total });
+        }
+        total
+    }
+
+    /// Finds all matches in `content`, see [`Matcher::find_spans`]
+    pub fn find_spans(&self, content: &str) -> Vec<MatchSpan> {
+        self.matcher().find_spans(content)
+    }
+
+    /// Lists searchable files under every root, sorted by depth, then
+    /// alphabetically; ignore rules apply inside each root, not to the roots
+    pub fn collect_files(roots: &[PathBuf]) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        for root in roots {
+            collect_files_into(root, root, &mut files);
+        }
+        files.sort_by(|a, b| {
+            let depth_a = a.components().count();
+            let depth_b = b.components().count();
+            match depth_a.cmp(&depth_b) {
+                std::cmp::Ordering::Equal => a.cmp(b),
+                other => other,
+            }
+        });
+        files.dedup();
+        files
+    }
+}
+
+/// How a [`Matcher`] finds the query
+enum Pattern {
+    /// An empty query or an invalid regex, which match nothing
+    Nothing,
+    /// Case-sensitive literal text, found with `str::find`
+    Literal,
+    Regex(Regex),
+}
+
+/// A [`Searcher`] with its query compiled
+pub struct Matcher<'a> {
+    searcher: &'a Searcher,
+    pattern: Pattern,
+}
+
+impl Matcher<'_> {
+    /// Finds all matches in a single file, skipping binary and oversized files
+    pub fn search_file(&self, file_path: &Path) -> Vec<SearchResult> {
+        if should_skip_file_for_search(file_path, self.searcher.max_line_length) {
+            return Vec::new();
+        }
+
+        match crate::encoding::read_file(file_path) {
+            Ok((content, _)) => {
+                let file_path_str = file_path.to_string_lossy().to_string();
+                self.search_matches(&content, Some(file_path_str))
+            }
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Files whose name matches, one result per file, in the order given;
+    /// results show the path relative to `roots`
+    pub fn search_names(&self, files: &[PathBuf], roots: &[PathBuf]) -> Vec<SearchResult> {
+        files
+            .iter()
+            .filter_map(|file_path| self.search_name(file_path, roots))
+            .collect()
+    }
+
+    fn search_name(&self, file_path: &Path, roots: &[PathBuf]) -> Option<SearchResult> {
+        let name = file_path.file_name()?.to_string_lossy();
+        let found = self.search_matches(&name, None).into_iter().next()?;
+
+        // the name ends the displayed path, shift the match onto it
+        let path = display_path(file_path, roots);
+        let shift = path.chars().count().saturating_sub(name.chars().count());
+        Some(SearchResult {
+            line: 0,
+            column: shift + found.column,
+            match_start: shift + found.match_start,
+            match_end: shift + found.match_end,
+            line_content: path,
+            file_path: Some(file_path.to_string_lossy().to_string()),
+            kind: SearchKind::FileName,
+        })
+    }
+
+    fn search_matches(&self, content: &str, file_path: Option<String>) -> Vec<SearchResult> {
+        self.find_spans(content)
+            .iter()
+            .map(|span| span.result(content, file_path.clone()))
+            .collect()
+    }
+
+    /// Finds all matches in `content` in one pass over it, copying no lines,
+    /// so that even thousands of matches are counted right away
+    pub fn find_spans(&self, content: &str) -> Vec<MatchSpan> {
+        let Searcher {
+            query,
+            whole_word,
+            word_boundary,
+            ..
+        } = self.searcher;
+        let mut spans = Vec::new();
+        // position reached so far: byte, char offset, line and line start
+        let (mut byte, mut chars, mut line, mut line_start) = (0, 0, 0, 0);
+        let mut push = |match_start_byte: usize, match_end_byte: usize| {
+            if *whole_word
+                && !(word_boundary.is_boundary_at(content, match_start_byte)
+                    && word_boundary.is_boundary_at(content, match_end_byte))
+            {
+                return;
+            }
+
+            for (i, c) in content[byte..match_start_byte].char_indices() {
+                chars += 1;
+                if c == '\n' {
+                    line += 1;
+                    line_start = byte + i + 1;
+                }
+            }
+            byte = match_start_byte;
+
+            let column = content[line_start..match_start_byte].chars().count();
+            let length = content[match_start_byte..match_end_byte].chars().count();
+            spans.push(MatchSpan {
+                line,
+                column,
+                match_start: chars,
+                match_end: chars + length,
+                line_start,
+            });
+        };
+
+        match &self.pattern {
+            Pattern::Nothing => {}
+            Pattern::Regex(re) => {
+                for m in re.find_iter(content) {
+                    push(m.start(), m.end());
+                }
+            }
+            Pattern::Literal => {
+                let mut start_byte = 0;
+                while let Some(pos) = content[start_byte..].find(query.as_str()) {
+                    let match_start_byte = start_byte + pos;
+                    let match_end_byte = match_start_byte + query.len();
+                    push(match_start_byte, match_end_byte);
+                    start_byte = match_end_byte;
+                }
+            }
+        }
+
+        spans
+    }
+}
+
+/// Walks `dir` inside `root`; ignore rules only look at the part of the
+/// path below the root, so a root living in e.g. `target/` is still searched
+fn collect_files_into(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) {
+    let relative = dir.strip_prefix(root).unwrap_or(dir);
+    if is_search_ignored_dir(relative) {
+        return;
+    }
+
+    if let Ok(entries) = std::fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if is_ignored_path(path.strip_prefix(root).unwrap_or(&path)) {
+                continue;
+            }
+
+            if path.is_dir() {
+                collect_files_into(root, &path, files);
+            } else if path.is_file() {
+                files.push(path);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_str() {
+        let searcher = Searcher::new("Foo");
+        let results = searcher.search_str("let foo = 1;\nFOO += foo_bar;");
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[1].line, 1);
+        assert_eq!(results[1].column, 0);
+        assert_eq!(results[1].match_start, 13);
+        assert_eq!(results[1].match_end, 16);
+    }
+
+    #[test]
+    fn test_find_spans() {
+        let content = "ä foo\r\n\nxx foo foo";
+        let spans = Searcher::new("foo").find_spans(content);
+        let found: Vec<_> = spans
+            .iter()
+            .map(|s| (s.line, s.column, s.match_start, s.match_end))
+            .collect();
+        assert_eq!(found, vec![(0, 2, 2, 5), (2, 3, 11, 14), (2, 7, 15, 18)]);
+        assert_eq!(spans[0].result(content, None).line_content, "ä foo\r");
+        assert_eq!(spans[2].result(content, None).line_content, "xx foo foo");
+    }
+
+    #[test]
+    fn test_search_str_case_folding() {
+        // `İ` lowercases to two chars, which used to shift every later match
+        let results = Searcher::new("foo").search_str("İİİ FOO\nİ foo");
+        let found: Vec<_> = results
+            .iter()
+            .map(|r| (r.line, r.column, r.match_start, r.match_end))
+            .collect();
+        assert_eq!(found, vec![(0, 4, 4, 7), (1, 2, 10, 13)]);
+        assert_eq!(results[0].line_content, "İİİ FOO");
+
+        // simple case folding: `ß` matches its capital `ẞ` but not `SS`
+        let results = Searcher::new("STRAẞE").search_str("straße strasse Straße");
+        let starts: Vec<_> = results.iter().map(|r| r.match_start).collect();
+        assert_eq!(starts, vec![0, 15]);
+
+        // regex metacharacters in a literal query stay literal
+        assert_eq!(Searcher::new("A.B").search_str("a.b axb").len(), 1);
+        let mut searcher = Searcher::new("A.B");
+        searcher.case_sensitive = true;
+        assert!(searcher.search_str("a.b").is_empty());
+    }
+
+    /// `cargo test --release -- --ignored bench_case_insensitive --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_case_insensitive() {
+        let line = "    let Ünïcode_value = compute(İstanbul, straße, needle);\n";
+        let content = line.repeat(200_000);
+        let mut searcher = Searcher::new("NEEDLE");
+        for case_sensitive in [false, true] {
+            searcher.case_sensitive = case_sensitive;
+            let start = std::time::Instant::now();
+            let results = searcher.search_str(&content);
+            println!(
+                "case_sensitive={case_sensitive}: {} matches in {:?}",
+                results.len(),
+                start.elapsed()
+            );
+        }
+
+        // many small inputs, as file names are, with the query compiled
+        // for each of them or once
+        searcher.case_sensitive = false;
+        let names: Vec<String> = (0..20_000).map(|i| format!("needle_{i}.rs")).collect();
+        let start = std::time::Instant::now();
+        let found = names.iter().filter(|n| !searcher.find_spans(n).is_empty());
+        println!(
+            "compiled per name: {} in {:?}",
+            found.count(),
+            start.elapsed()
+        );
+        let start = std::time::Instant::now();
+        let matcher = searcher.matcher();
+        let found = names.iter().filter(|n| !matcher.find_spans(n).is_empty());
+        println!("compiled once: {} in {:?}", found.count(), start.elapsed());
+    }
+
+    #[test]
+    fn test_search_str_whole_word() {
+        let mut searcher = Searcher::new("foo");
+        searcher.whole_word = true;
+        let results = searcher.search_str("foo foo_bar barfoo (foo)");
+        let columns: Vec<_> = results.iter().map(|r| r.column).collect();
+        assert_eq!(columns, vec![0, 20]);
+
+        searcher.regex_mode = true;
+        searcher.query = "f.o".to_string();
+        assert_eq!(searcher.search_str("foo foo_bar").len(), 1);
+
+        searcher.regex_mode = false;
+        searcher.query = "Bar".to_string();
+        assert!(searcher.search_str("fooBar").is_empty());
+        searcher.word_boundary = WordBoundary::Subword;
+        assert_eq!(searcher.search_str("fooBar foo_Bar Barn").len(), 2);
+    }
+
+    #[test]
+    fn test_search_roots() {
+        use tempfile::tempdir;
+
+        let dir = tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("nested")).unwrap();
+        std::fs::write(dir.path().join("nested/b.txt"), "needle\n").unwrap();
+        std::fs::write(dir.path().join("a.txt"), "hay needle\n").unwrap();
+
+        let mut results = Vec::new();
+        let mut progress = Vec::new();
+        let searched =
+            Searcher::new("needle").search_roots(&[dir.path().to_path_buf()], |step| match step {
+                SearchStep::Progress { processed, total } => progress.push((processed, total)),
+                SearchStep::Results(found) => results.extend(found),
+            });
+        assert_eq!(searched, 2);
+        assert_eq!(progress, [(0, 2), (2, 2)]);
+        assert_eq!(results.len(), 2);
+        assert!(results[0].file_path.as_deref().unwrap().ends_with("a.txt"));
+        assert_eq!(results[0].column, 4);
+
+        let searched = Searcher::new("").search_roots(&[dir.path().to_path_buf()], |_| {
+            panic!("an empty query searches nothing")
+        });
+        assert_eq!(searched, 0);
+    }
+
+    #[test]
+    fn test_search_skips_minified() {
+        let dir = tempfile::tempdir().unwrap();
+        let minified = dir.path().join("app.min.js");
+        std::fs::write(&minified, format!("var needle={};\n", "1,".repeat(20))).unwrap();
+
+        let mut searcher = Searcher::new("needle");
+        assert_eq!(searcher.matcher().search_file(&minified).len(), 1);
+        searcher.max_line_length = 40;
+        assert!(searcher.matcher().search_file(&minified).is_empty());
+        searcher.max_line_length = 0;
+        assert_eq!(searcher.matcher().search_file(&minified).len(), 1);
+    }
+
+    #[test]
+    fn test_sort_results() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("a")).unwrap();
+        std::fs::write(dir.path().join("a/deep.rs"), "x\nx x\n").unwrap();
+        std::fs::write(dir.path().join("b.rs"), "x\n").unwrap();
+        std::fs::write(dir.path().join("x.rs"), "none\n").unwrap();
+
+        let searcher = Searcher::new("x");
+        let roots = [dir.path().to_path_buf()];
+        let files = Searcher::collect_files(&roots);
+        let found = || {
+            let matcher = searcher.matcher();
+            let mut results = matcher.search_names(&files, &roots);
+            // files searched in reverse, as a parallel search might finish them
+            results.extend(files.iter().rev().flat_map(|f| matcher.search_file(f)));
+            results
+        };
+        let summary = |results: &[SearchResult]| {
+            results
+                .iter()
+                .map(|r| {
+                    let path = Path::new(r.file_path.as_deref().unwrap());
+                    let name = path.strip_prefix(dir.path()).unwrap().display();
+                    format!("{name}:{}:{}", r.line, r.column)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut results = found();
+        sort_results(&mut results, SearchOrder::Relevance);
+        assert_eq!(
+            summary(&results),
+            [
+                "x.rs:0:0",
+                "b.rs:0:0",
+                "a/deep.rs:0:0",
+                "a/deep.rs:1:0",
+                "a/deep.rs:1:2"
+            ]
+        );
+
+        let mut results = found();
+        sort_results(&mut results, SearchOrder::Path);
+        assert_eq!(
+            summary(&results),
+            [
+                "a/deep.rs:0:0",
+                "a/deep.rs:1:0",
+                "a/deep.rs:1:2",
+                "b.rs:0:0",
+                "x.rs:0:0"
+            ]
+        );
+        let mut again = found();
+        sort_results(&mut again, SearchOrder::Path);
+        assert_eq!(summary(&again), summary(&results));
+    }
+
+    #[test]
+    fn test_collect_files_multiple_roots() {
+        use tempfile::tempdir;
+
+        let dir = tempdir().unwrap();
+        let a = dir.path().join("a");
+        let b = dir.path().join("target");
+        std::fs::create_dir_all(a.join("node_modules")).unwrap();
+        std::fs::create_dir(&b).unwrap();
+        std::fs::write(a.join("x.txt"), "x").unwrap();
+        std::fs::write(a.join("node_modules/dep.js"), "x").unwrap();
+        std::fs::write(b.join("y.txt"), "y").unwrap();
+
+        let files = Searcher::collect_files(&[a.clone(), b.clone()]);
+        assert_eq!(files, vec![a.join("x.txt"), b.join("y.txt")]);
+    }
+
+    #[test]
+    fn test_search_names() {
+        let root = PathBuf::from("/work");
+        let files = vec![
+            root.join("src/search.rs"),
+            root.join("src/searcher.rs"),
+            root.join("research/notes.md"),
+        ];
+
+        let results = Searcher::new("SEARCH")
+            .matcher()
+            .search_names(&files, &[root]);
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|r| r.kind == SearchKind::FileName));
+        assert_eq!(results[1].line_content, "src/searcher.rs");
+        assert_eq!((results[1].match_start, results[1].match_end), (4, 10));
+        assert_eq!(results[1].column, 4);
+    }
+}
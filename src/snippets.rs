@@ -0,0 +1,419 @@
+use anyhow::{Context, Result, bail};
+use crossterm::event::{Event, KeyCode, KeyEventKind, MouseEventKind};
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+};
+use std::path::Path;
+
+use crate::config::glob_match;
+
+/// Where the selected text goes in a snippet body
+const SELECTION: &str = "$SELECTION";
+/// Where the cursor ends up after the snippet is applied
+const CURSOR: &str = "$0";
+
+/// Available without a snippets file; the file can override them by name
+const BUILTIN: &str = "\
+snippet rust if
+if $0 {
+    $SELECTION
+}
+snippet rust some
+Some($SELECTION)$0
+snippet rust dbg
+dbg!($SELECTION)$0
+snippet python try
+try:
+    $SELECTION
+except $0:
+    raise
+snippet javascript try
+try {
+    $SELECTION
+} catch (err) {
+    $0
+}
+snippet typescript try
+try {
+    $SELECTION
+} catch (err) {
+    $0
+}
+snippet * quote
+\"$SELECTION\"$0
+";
+
+/// A template wrapped around the selection, for one language or all (`*`)
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snippet {
+    pub language: String,
+    pub name: String,
+    pub body: String,
+}
+
+/// Parses snippets: each starts with a `snippet LANGUAGE NAME` line and
+/// its body runs until the next one, without trailing blank lines
+pub fn parse(text: &str) -> Result<Vec<Snippet>> {
+    let blocks = parse_blocks(text, "snippet LANGUAGE NAME")?;
+    let snippets = blocks.into_iter().map(|(mut header, body)| Snippet {
+        name: header.pop().unwrap_or_default(),
+        language: header.pop().unwrap_or_default(),
+        body,
+    });
+    Ok(snippets.collect())
+}
+
+/// Splits `text` into blocks starting with a header line like `usage`,
+/// e.g. `template PATTERN`, returning the header's fields and the body
+fn parse_blocks(text: &str, usage: &str) -> Result<Vec<(Vec<String>, String)>> {
+    let (keyword, fields) = usage.split_once(' ').unwrap_or((usage, ""));
+    let fields = fields.split_whitespace().count();
+    let mut blocks: Vec<(Vec<String>, String)> = Vec::new();
+    let mut body: Vec<&str> = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let header = line
+            .strip_prefix(keyword)
+            .and_then(|rest| rest.strip_prefix(' '));
+        let Some(header) = header else {
+            if blocks.is_empty() && !line.trim().is_empty() {
+                bail!("line {}: expected `{usage}`", i + 1);
+            }
+            body.push(line);
+            continue;
+        };
+        if let Some(last) = blocks.last_mut() {
+            last.1 = join_body(&body);
+        }
+        body.clear();
+        let header: Vec<String> = header.split_whitespace().map(String::from).collect();
+        if header.len() != fields {
+            bail!("line {}: expected `{usage}`", i + 1);
+        }
+        blocks.push((header, String::new()));
+    }
+    if let Some(last) = blocks.last_mut() {
+        last.1 = join_body(&body);
+    }
+    Ok(blocks)
+}
+
+fn join_body(lines: &[&str]) -> String {
+    let end = lines
+        .iter()
+        .rposition(|line| !line.trim().is_empty())
+        .map_or(0, |i| i + 1);
+    lines[..end].join("\n")
+}
+
+/// Built-in snippets and those in `path`, for `language` or any language;
+/// a snippet in the file replaces a built-in one with the same name
+pub fn load(path: &Path, language: &str) -> Result<Vec<Snippet>> {
+    let mut snippets = parse(BUILTIN)?;
+    match std::fs::read_to_string(path) {
+        Ok(text) => {
+            let own = parse(&text).with_context(|| format!("invalid {}", path.display()))?;
+            for snippet in own {
+                snippets.retain(|s| (&s.language, &s.name) != (&snippet.language, &snippet.name));
+                snippets.push(snippet);
+            }
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err).with_context(|| format!("reading {}", path.display())),
+    }
+    snippets.retain(|s| s.language == language || s.language == "*");
+    // the language's own snippets first
+    snippets.sort_by_key(|s| s.language == "*");
+    Ok(snippets)
+}
+
+/// Content for new files, by file name or pattern, e.g. `main.rs` or `*.rs`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template {
+    pub pattern: String,
+    pub body: String,
+}
+
+/// Built-in templates, tried in order after those in the templates file
+const BUILTIN_TEMPLATES: &str = "\
+template main.rs
+fn main() {
+    $0
+}
+template *.rs
+//! $0
+template *.py
+def main():
+    $0
+
+
+if __name__ == \"__main__\":
+    main()
+template *.sh
+#!/usr/bin/env bash
+set -euo pipefail
+
+$0
+template *.html
+<!DOCTYPE html>
+<html lang=\"en\">
+<head>
+    <meta charset=\"utf-8\">
+    <title>$0</title>
+</head>
+<body>
+</body>
+</html>
+";
+
+/// Parses templates: each starts with a `template PATTERN` line and its
+/// body runs until the next one, without trailing blank lines
+pub fn parse_templates(text: &str) -> Result<Vec<Template>> {
+    let blocks = parse_blocks(text, "template PATTERN")?;
+    let templates = blocks.into_iter().map(|(mut header, body)| Template {
+        pattern: header.pop().unwrap_or_default(),
+        body,
+    });
+    Ok(templates.collect())
+}
+
+/// The first template in `path`, then among the built-in ones, whose
+/// pattern matches the name of `filename`
+pub fn find_template(path: &Path, filename: &str) -> Result<Option<Template>> {
+    let mut templates = match std::fs::read_to_string(path) {
+        Ok(text) => {
+            parse_templates(&text).with_context(|| format!("invalid {}", path.display()))?
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(err) => return Err(err).with_context(|| format!("reading {}", path.display())),
+    };
+    templates.extend(parse_templates(BUILTIN_TEMPLATES)?);
+    let name = Path::new(filename)
+        .file_name()
+        .map_or(filename.into(), |name| name.to_string_lossy());
+    Ok(templates
+        .into_iter()
+        .find(|t| glob_match(&t.pattern, &name)))
+}
+
+/// Expands `body` with `selection` in place of `$SELECTION`, returning the
+/// text and the char offset of `$0` in it, or of its end without one.
+/// Lines after the first are indented with `indent`, and selected lines
+/// after the first also with the indentation of the line they land on
+pub fn expand(body: &str, selection: &str, indent: &str) -> (String, usize) {
+    // a NUL stands for the cursor so that a `$0` in the selection stays
+    let body = body.replacen(CURSOR, "\0", 1);
+    let mut text = String::new();
+    for (i, line) in body.split('\n').enumerate() {
+        if i > 0 {
+            text.push('\n');
+            if !line.trim().is_empty() {
+                text.push_str(indent);
+            }
+        }
+        if !line.contains(SELECTION) {
+            text.push_str(line);
+            continue;
+        }
+        let line_indent = &line[..line.len() - line.trim_start().len()];
+        let mut nested = String::new();
+        for (j, selected) in selection.split('\n').enumerate() {
+            if j > 0 {
+                nested.push('\n');
+                if !selected.trim().is_empty() {
+                    nested.push_str(indent);
+                    nested.push_str(line_indent);
+                }
+            }
+            nested.push_str(selected);
+        }
+        text.push_str(&line.replace(SELECTION, &nested));
+    }
+
+    match text.find('\0') {
+        Some(index) => {
+            let cursor = text[..index].chars().count();
+            text.remove(index);
+            (text, cursor)
+        }
+        None => {
+            let cursor = text.chars().count();
+            (text, cursor)
+        }
+    }
+}
+
+pub enum SnippetAction {
+    None,
+    Close,
+    Apply(Snippet),
+}
+
+/// Popup listing the snippets that can wrap the selection
+pub struct SnippetMenu {
+    items: Vec<Snippet>,
+    selected: usize,
+    area: Rect,
+}
+
+impl SnippetMenu {
+    pub fn new(items: Vec<Snippet>) -> Self {
+        Self {
+            items,
+            selected: 0,
+            area: Rect::default(),
+        }
+    }
+
+    pub fn handle_event(&mut self, event: &Event) -> SnippetAction {
+        match event {
+            Event::Key(key) if !matches!(key.kind, KeyEventKind::Press) => SnippetAction::None,
+            Event::Key(key) => match key.code {
+                KeyCode::Esc => SnippetAction::Close,
+                KeyCode::Up => {
+                    self.selected = self.selected.saturating_sub(1);
+                    SnippetAction::None
+                }
+                KeyCode::Down => {
+                    self.selected = (self.selected + 1).min(self.items.len().saturating_sub(1));
+                    SnippetAction::None
+                }
+                KeyCode::Enter => self.apply(self.selected),
+                _ => SnippetAction::None,
+            },
+            Event::Mouse(mouse) if matches!(mouse.kind, MouseEventKind::Down(_)) => {
+                let inside = mouse.column > self.area.x
+                    && mouse.column < self.area.right().saturating_sub(1)
+                    && mouse.row > self.area.y
+                    && mouse.row < self.area.bottom().saturating_sub(1);
+                if !inside {
+                    return SnippetAction::Close;
+                }
+                self.apply((mouse.row - self.area.y - 1) as usize)
+            }
+            _ => SnippetAction::None,
+        }
+    }
+
+    fn apply(&self, index: usize) -> SnippetAction {
+        match self.items.get(index) {
+            Some(snippet) => SnippetAction::Apply(snippet.clone()),
+            None => SnippetAction::None,
+        }
+    }
+
+    /// Draws the menu at the top of `area`, the editor
+    pub fn render(&mut self, frame: &mut Frame, area: Rect) {
+        let label = |s: &Snippet| format!("{} ({})", s.name, s.language);
+        let widest = self.items.iter().map(|s| label(s).chars().count());
+        let width = (widest.max().unwrap_or(0) as u16 + 4)
+            .max(12)
+            .min(area.width);
+        let height = (self.items.len() as u16 + 2).min(area.height);
+        self.area = Rect::new(
+            area.x + area.width.saturating_sub(width) / 2,
+            area.y + 1.min(area.height.saturating_sub(height)),
+            width,
+            height,
+        );
+
+        frame.render_widget(Clear, self.area);
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(" Snippets ")
+            .border_style(Style::default().fg(Color::DarkGray));
+        let inner = block.inner(self.area);
+        frame.render_widget(block, self.area);
+
+        let visible = self.items.iter().enumerate().take(inner.height as usize);
+        for (row, (i, snippet)) in visible.enumerate() {
+            let style = if i == self.selected {
+                Style::default()
+                    .bg(Color::DarkGray)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            let line = Line::from(vec![
+                Span::raw(format!(" {}", snippet.name)),
+                Span::styled(
+                    format!(" ({})", snippet.language),
+                    Style::default().fg(Color::Gray),
+                ),
+            ]);
+            let item_area = Rect::new(inner.x, inner.y + row as u16, inner.width, 1);
+            frame.render_widget(Paragraph::new(line).style(style), item_area);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_load() {
+        let text =
+            "snippet rust unwrap\n$SELECTION.unwrap()$0\n\n\nsnippet * box\n[\n$SELECTION\n]\n";
+        let snippets = parse(text).unwrap();
+        assert_eq!(snippets.len(), 2);
+        assert_eq!(snippets[0].name, "unwrap");
+        assert_eq!(snippets[0].body, "$SELECTION.unwrap()$0");
+        assert_eq!(
+            (snippets[1].language.as_str(), snippets[1].body.as_str()),
+            ("*", "[\n$SELECTION\n]")
+        );
+
+        assert!(parse("oops\nsnippet rust x\n").is_err());
+        assert!(parse("snippet rust\nx\n").is_err());
+        parse(BUILTIN).unwrap();
+        parse_templates(BUILTIN_TEMPLATES).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("snippets");
+        std::fs::write(
+            &path,
+            "snippet rust some\nSome(($SELECTION))\nsnippet go err\nx\n",
+        )
+        .unwrap();
+        let rust = load(&path, "rust").unwrap();
+        let names: Vec<_> = rust.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["if", "dbg", "some", "quote"]);
+        assert_eq!(rust[2].body, "Some(($SELECTION))");
+        assert_eq!(load(&dir.path().join("missing"), "go").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_find_template() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("templates");
+        let find = |filename| find_template(&path, filename).unwrap().map(|t| t.pattern);
+        assert_eq!(find("src/main.rs").as_deref(), Some("main.rs"));
+        assert_eq!(find("src/lib.rs").as_deref(), Some("*.rs"));
+        assert_eq!(find("notes.txt"), None);
+
+        std::fs::write(&path, "template *.txt\nTODO $0\ntemplate lib.rs\n").unwrap();
+        assert_eq!(find("notes.txt").as_deref(), Some("*.txt"));
+        let lib = find_template(&path, "lib.rs").unwrap().unwrap();
+        assert_eq!(lib.body, "");
+        assert!(parse_templates("template\nx\n").is_err());
+    }
+
+    #[test]
+    fn test_expand() {
+        let body = "if $0 {\n    $SELECTION\n}";
+        let (text, cursor) = expand(body, "foo();\n\nbar();", "  ");
+        assert_eq!(text, "if  {\n      foo();\n\n      bar();\n  }");
+        assert_eq!(cursor, 3);
+
+        let (text, cursor) = expand("Some($SELECTION)", "x", "");
+        assert_eq!((text.as_str(), cursor), ("Some(x)", 7));
+
+        // a `$0` in the selected text is kept as it is
+        let (text, cursor) = expand("($SELECTION)$0", "$0", "");
+        assert_eq!((text.as_str(), cursor), ("($0)", 4));
+    }
+}
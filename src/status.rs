@@ -0,0 +1,111 @@
+use ratatui::Frame;
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::style::{Color, Style};
+use ratatui::widgets::Paragraph;
+use std::time::{Duration, Instant};
+
+const INFO_TTL: Duration = Duration::from_secs(4);
+const WARN_TTL: Duration = Duration::from_secs(6);
+const ERROR_TTL: Duration = Duration::from_secs(8);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StatusLevel {
+    Info,
+    Warn,
+    Error,
+}
+
+#[derive(Clone, Debug)]
+pub struct StatusMessage {
+    pub text: String,
+    pub level: StatusLevel,
+    pub created: Instant,
+    /// Dismissed by the next key press, not only when it expires
+    pub toast: bool,
+}
+
+impl StatusMessage {
+    fn is_expired(&self) -> bool {
+        let ttl = match self.level {
+            StatusLevel::Info => INFO_TTL,
+            StatusLevel::Warn => WARN_TTL,
+            StatusLevel::Error => ERROR_TTL,
+        };
+        self.created.elapsed() > ttl
+    }
+}
+
+/// One-line bar at the bottom of the screen with a transient message
+/// on the left and persistent editor info on the right
+pub struct StatusBar {
+    message: Option<StatusMessage>,
+}
+
+impl StatusBar {
+    pub fn new() -> Self {
+        Self { message: None }
+    }
+
+    pub fn info(&mut self, text: impl Into<String>) {
+        self.set(text.into(), StatusLevel::Info);
+    }
+
+    pub fn warn(&mut self, text: impl Into<String>) {
+        self.set(text.into(), StatusLevel::Warn);
+    }
+
+    pub fn error(&mut self, text: impl Into<String>) {
+        self.set(text.into(), StatusLevel::Error);
+    }
+
+    /// Shows an info message that the next key press dismisses
+    pub fn toast(&mut self, text: impl Into<String>) {
+        self.set(text.into(), StatusLevel::Info);
+        if let Some(message) = &mut self.message {
+            message.toast = true;
+        }
+    }
+
+    /// Clears the current message if it is a toast
+    pub fn dismiss_toast(&mut self) {
+        if self.message.as_ref().is_some_and(|m| m.toast) {
+            self.message = None;
+        }
+    }
+
+    /// Returns the current message unless it has expired
+    pub fn message(&self) -> Option<&StatusMessage> {
+        self.message.as_ref().filter(|m| !m.is_expired())
+    }
+
+    fn set(&mut self, text: String, level: StatusLevel) {
+        self.message = Some(StatusMessage {
+            text,
+            level,
+            created: Instant::now(),
+            toast: false,
+        });
+    }
+
+    pub fn render(&self, frame: &mut Frame, area: Rect, info: &str) {
+        let info_width = (info.chars().count() as u16 + 1).min(area.width);
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Min(0), Constraint::Length(info_width)])
+            .split(area);
+
+        if let Some(message) = self.message() {
+            let color = match message.level {
+                StatusLevel::Info => Color::Gray,
+                StatusLevel::Warn => Color::Yellow,
+                StatusLevel::Error => Color::Red,
+            };
+            let text = format!(" {}", message.text);
+            let para = Paragraph::new(text).style(Style::default().fg(color));
+            frame.render_widget(para, chunks[0]);
+        }
+
+        let info_para = Paragraph::new(info).style(Style::default().fg(Color::DarkGray));
+        frame.render_widget(info_para, chunks[1]);
+    }
+}
@@ -0,0 +1,269 @@
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::outline::outline;
+use crate::searcher::{SearchKind, SearchResult, Searcher};
+use crate::utils::should_skip_file_for_search;
+
+/// Matches listed for a query; the best ones of a large workspace suffice
+const MAX_RESULTS: usize = 500;
+
+/// A definition in a workspace file
+#[derive(Clone, Debug, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: String,
+    /// 0-based line and char column of the name
+    pub line: usize,
+    pub column: usize,
+    /// Char offset of the name in the file
+    pub offset: usize,
+    pub line_content: String,
+}
+
+/// Modification time of a file when scanned and the definitions in it
+type FileSymbols = (Option<SystemTime>, Vec<Symbol>);
+
+/// Definitions of every workspace file, kept between queries; a file is
+/// only scanned again once its modification time changes
+#[derive(Default)]
+pub struct SymbolIndex {
+    files: HashMap<PathBuf, FileSymbols>,
+}
+
+impl SymbolIndex {
+    /// Scans the files of `roots` that are new or changed since the last
+    /// refresh, in parallel, and forgets deleted ones; returns the number
+    /// of files in the index
+    pub fn refresh(&mut self, roots: &[PathBuf], max_line_length: usize) -> usize {
+        let files: Vec<PathBuf> = Searcher::collect_files(roots)
+            .into_iter()
+            .map(|path| std::path::absolute(&path).unwrap_or(path))
+            .collect();
+        let scanned: Vec<(PathBuf, FileSymbols)> = files
+            .par_iter()
+            .filter_map(|path| {
+                let modified = modified(path);
+                if let Some((known, _)) = self.files.get(path)
+                    && known.is_some()
+                    && *known == modified
+                {
+                    return None;
+                }
+                let symbols = if should_skip_file_for_search(path, max_line_length) {
+                    Vec::new()
+                } else {
+                    std::fs::read_to_string(path)
+                        .map(|content| symbols(&content))
+                        .unwrap_or_default()
+                };
+                Some((path.clone(), (modified, symbols)))
+            })
+            .collect();
+
+        let present: HashSet<&PathBuf> = files.iter().collect();
+        self.files.retain(|path, _| present.contains(path));
+        self.files.extend(scanned);
+        self.files.len()
+    }
+
+    /// Takes the definitions of a file just saved from its content; files
+    /// not indexed yet are left to the next refresh
+    pub fn update_file(&mut self, path: &Path, content: &str) {
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        if let Some(entry) = self.files.get_mut(&path) {
+            *entry = (modified(&path), symbols(content));
+        }
+    }
+
+    /// Definitions whose name matches `query` as a fuzzy subsequence, best
+    /// first, as results pointing at the name
+    pub fn find(&self, query: &str) -> Vec<SearchResult> {
+        let mut found: Vec<(i64, &Path, &Symbol)> = self
+            .files
+            .iter()
+            .flat_map(|(path, (_, symbols))| symbols.iter().map(move |s| (path, s)))
+            .filter_map(|(path, symbol)| {
+                let score = fuzzy_score(query, &symbol.name)?;
+                Some((score, path.as_path(), symbol))
+            })
+            .collect();
+        found.sort_by(|a, b| {
+            b.0.cmp(&a.0)
+                .then_with(|| a.2.name.cmp(&b.2.name))
+                .then_with(|| a.1.cmp(b.1))
+                .then_with(|| a.2.line.cmp(&b.2.line))
+        });
+        found
+            .into_iter()
+            .take(MAX_RESULTS)
+            .map(|(_, path, symbol)| SearchResult {
+                line: symbol.line,
+                column: symbol.column,
+                match_start: symbol.offset,
+                match_end: symbol.offset + symbol.name.chars().count(),
+                line_content: symbol.line_content.clone(),
+                file_path: Some(path.to_string_lossy().to_string()),
+                kind: SearchKind::Content,
+            })
+            .collect()
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Definitions in `content`, as the outline finds them
+pub fn symbols(content: &str) -> Vec<Symbol> {
+    let items = outline(content);
+    if items.is_empty() {
+        return Vec::new();
+    }
+    let mut line_starts = vec![0];
+    let mut chars = 0;
+    for line in content.split_inclusive('\n') {
+        chars += line.chars().count();
+        line_starts.push(chars);
+    }
+    let lines: Vec<&str> = content.lines().collect();
+
+    items
+        .into_iter()
+        .filter_map(|item| {
+            let text = lines.get(item.line)?;
+            // the name follows the keyword, e.g. `impl Display for Foo`
+            let keyword = text.find(item.kind.as_str()).unwrap_or(0);
+            let byte = keyword + text[keyword..].find(item.name.as_str())?;
+            let column = text[..byte].chars().count();
+            Some(Symbol {
+                offset: line_starts[item.line] + column,
+                name: item.name,
+                kind: item.kind,
+                line: item.line,
+                column,
+                line_content: text.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Scores `name` for `query` typed as a subsequence of it, ignoring case;
+/// an exact or prefix match, consecutive characters and word starts score
+/// higher, longer names lower. None when `query` is not a subsequence
+pub fn fuzzy_score(query: &str, name: &str) -> Option<i64> {
+    let query: Vec<char> = query.chars().flat_map(char::to_lowercase).collect();
+    let name_chars: Vec<char> = name.chars().collect();
+    if query.is_empty() {
+        return Some(-(name_chars.len() as i64));
+    }
+
+    let mut score = 0;
+    let mut next = 0;
+    let mut previous: Option<usize> = None;
+    for q in &query {
+        let found = (next..name_chars.len()).find(|&i| name_chars[i].to_lowercase().eq([*q]))?;
+        let word_start = found == 0
+            || !name_chars[found - 1].is_alphanumeric()
+            || name_chars[found - 1].is_lowercase() && name_chars[found].is_uppercase();
+        if word_start {
+            score += 8;
+        }
+        if previous.is_some_and(|p| p + 1 == found) {
+            score += 5;
+        }
+        previous = Some(found);
+        next = found + 1;
+    }
+
+    let lower = name.to_lowercase();
+    let query: String = query.into_iter().collect();
+    if lower == query {
+        score += 100;
+    } else if lower.starts_with(&query) {
+        score += 50;
+    }
+    Some(score - name_chars.len() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_symbols() {
+        let content = "use x;\n\npub struct Foo;\n\nimpl Display for Foo {\n    fn fmt() {}\n}\n";
+        let found: Vec<_> = symbols(content)
+            .into_iter()
+            .map(|s| (s.name, s.line, s.column, s.offset))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("Foo".to_string(), 2, 11, 19),
+                ("Display for Foo".to_string(), 4, 5, 30),
+                ("fmt".to_string(), 5, 7, 55),
+            ]
+        );
+        assert_eq!(&content[19..22], "Foo");
+        assert_eq!(&content[55..58], "fmt");
+    }
+
+    #[test]
+    fn test_fuzzy_score() {
+        assert_eq!(fuzzy_score("xyz", "open_file"), None);
+        assert!(fuzzy_score("of", "open_file").is_some());
+        // exact beats prefix beats word starts beats scattered letters
+        let rank = |name| fuzzy_score("open", name).unwrap();
+        assert!(rank("open") > rank("open_file"));
+        assert!(rank("open_file") > rank("reopen"));
+        assert!(fuzzy_score("of", "open_file") > fuzzy_score("of", "proof"));
+        assert!(fuzzy_score("ef", "EditorFocus") > fuzzy_score("ef", "refresh"));
+    }
+
+    #[test]
+    fn test_index_refresh_and_find() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.rs");
+        let b = dir.path().join("b.py");
+        std::fs::write(&a, "fn open_file() {}\nstruct Opener;\n").unwrap();
+        std::fs::write(&b, "def reopen():\n    pass\n").unwrap();
+        let roots = [dir.path().to_path_buf()];
+
+        let mut index = SymbolIndex::default();
+        assert_eq!(index.refresh(&roots, 0), 2);
+        let names = |index: &SymbolIndex, query| -> Vec<String> {
+            index
+                .find(query)
+                .iter()
+                .map(|r| {
+                    r.line_content[r.column..]
+                        .split('(')
+                        .next()
+                        .unwrap()
+                        .to_string()
+                })
+                .collect()
+        };
+        // both prefix matches, the closer in length first
+        assert_eq!(
+            names(&index, "open"),
+            vec!["Opener;", "open_file", "reopen"]
+        );
+        let result = &index.find("opener")[0];
+        assert_eq!(
+            (result.line, result.match_start, result.match_end),
+            (1, 25, 31)
+        );
+
+        // a save updates the file right away, a deleted file is dropped
+        index.update_file(&a, "fn close() {}\n");
+        assert_eq!(names(&index, "open"), vec!["reopen"]);
+        std::fs::remove_file(&b).unwrap();
+        index.refresh(&roots, 0);
+        assert!(names(&index, "open").is_empty());
+        assert_eq!(names(&index, "clo"), vec!["close"]);
+    }
+}
@@ -0,0 +1,276 @@
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::style::Color;
+use ratatui_code_editor::code::{Code, RopeGraphemes, grapheme_width_and_chars_len};
+use ratatui_code_editor::editor::Editor as CodeEditor;
+
+// ratatui_code_editor draws every tab as a single cell; these helpers widen
+// them to the next tab stop after the widget has rendered, and translate
+// cursor and mouse positions between the two layouts. Content is untouched.
+
+/// Width of the line number gutter, mirroring the editor widget
+fn gutter_width(code: &Code) -> usize {
+    code.len_lines().max(1).to_string().len().max(5) + 2
+}
+
+fn line_has_tab(code: &Code, line_idx: usize) -> bool {
+    code.line(line_idx).chars().any(|c| c == '\t')
+}
+
+/// Walks the graphemes of `[from, to)` chars of a line, yielding
+/// (widget width, expanded width) and tracking tab stops from `col`
+fn columns(
+    code: &Code,
+    line_idx: usize,
+    from: usize,
+    to: usize,
+    mut col: usize,
+    tab_width: usize,
+) -> Vec<(usize, usize)> {
+    let line_start = code.line_to_char(line_idx);
+    let slice = code.char_slice(line_start + from, line_start + to);
+    let mut widths = Vec::new();
+    for g in RopeGraphemes::new(&slice) {
+        let (width, _) = grapheme_width_and_chars_len(g);
+        let expanded = if g == "\t" {
+            tab_width - col % tab_width
+        } else {
+            width
+        };
+        col += expanded;
+        widths.push((width, expanded));
+    }
+    widths
+}
+
+/// Expanded width of the first `chars` chars of a line
+fn expanded_width(code: &Code, line_idx: usize, chars: usize, tab_width: usize) -> usize {
+    let chars = chars.min(code.line_len(line_idx));
+    columns(code, line_idx, 0, chars, 0, tab_width)
+        .iter()
+        .map(|(_, expanded)| expanded)
+        .sum()
+}
+
+/// Re-lays out the rows the widget drew so each tab spans up to the next tab stop
+pub fn expand_tabs(editor: &CodeEditor, buf: &mut Buffer, area: Rect, tab_width: usize) {
+    if tab_width <= 1 {
+        return;
+    }
+    let code = editor.code_ref();
+    let text_x = area.left() + gutter_width(code) as u16;
+    if text_x >= area.right() {
+        return;
+    }
+    let max_x = (area.right() - text_x) as usize;
+
+    for row in 0..area.height {
+        let line_idx = editor.get_offset_y() + row as usize;
+        if line_idx >= code.len_lines() {
+            break;
+        }
+        if !line_has_tab(code, line_idx) {
+            continue;
+        }
+
+        let line_len = code.line_len(line_idx);
+        let start_col = editor.get_offset_x().min(line_len);
+        let y = area.top() + row;
+        let src: Vec<_> = (0..max_x as u16)
+            .map(|dx| buf[(text_x + dx, y)].clone())
+            .collect();
+
+        let prefix = expanded_width(code, line_idx, start_col, tab_width);
+        let (mut sx, mut dx) = (0, 0);
+        for (width, expanded) in columns(code, line_idx, start_col, line_len, prefix, tab_width) {
+            if sx >= src.len() || dx >= max_x {
+                break;
+            }
+            if width == expanded {
+                for i in 0..width.min(src.len() - sx) {
+                    if dx + i < max_x {
+                        buf[(text_x + (dx + i) as u16, y)] = src[sx + i].clone();
+                    }
+                }
+            } else {
+                for i in 0..expanded {
+                    if dx + i < max_x {
+                        buf[(text_x + (dx + i) as u16, y)] = src[sx].clone();
+                    }
+                }
+            }
+            sx += width;
+            dx += expanded;
+        }
+    }
+}
+
+/// Draws `·` over spaces and `→` over tabs in the rows the widget drew, in
+/// the expanded layout, so run it after [`expand_tabs`]; whitespace ending
+/// a line is drawn in `trailing` to tell it apart
+pub fn show_whitespace(
+    editor: &CodeEditor,
+    buf: &mut Buffer,
+    area: Rect,
+    tab_width: usize,
+    trailing: Color,
+) {
+    let code = editor.code_ref();
+    let text_x = area.left() + gutter_width(code) as u16;
+    if text_x >= area.right() {
+        return;
+    }
+    let max_x = (area.right() - text_x) as usize;
+
+    for row in 0..area.height {
+        let line_idx = editor.get_offset_y() + row as usize;
+        if line_idx >= code.len_lines() {
+            break;
+        }
+        let line = code.line(line_idx);
+        let line_len = code.line_len(line_idx);
+        let content_end = line
+            .chars()
+            .take(line_len)
+            .enumerate()
+            .filter(|(_, c)| !c.is_whitespace())
+            .last()
+            .map_or(0, |(i, _)| i + 1);
+
+        let start_col = editor.get_offset_x().min(line_len);
+        let line_start = code.line_to_char(line_idx);
+        let slice = code.char_slice(line_start + start_col, line_start + line_len);
+        let y = area.top() + row;
+        let mut col = expanded_width(code, line_idx, start_col, tab_width);
+        let (mut dx, mut chars) = (0, start_col);
+        for g in RopeGraphemes::new(&slice) {
+            if dx >= max_x {
+                break;
+            }
+            let (width, len) = grapheme_width_and_chars_len(g);
+            let (marker, expanded) = if g == " " {
+                (Some("·"), width)
+            } else if g == "\t" {
+                (Some("→"), tab_width - col % tab_width)
+            } else {
+                (None, width)
+            };
+            if let Some(marker) = marker {
+                let color = if chars >= content_end {
+                    trailing
+                } else {
+                    Color::DarkGray
+                };
+                buf[(text_x + dx as u16, y)]
+                    .set_symbol(marker)
+                    .set_fg(color);
+            }
+            col += expanded;
+            dx += expanded;
+            chars += len;
+        }
+    }
+}
+
+/// Screen position of the cursor with tabs expanded
+pub fn visible_cursor(editor: &CodeEditor, area: Rect, tab_width: usize) -> Option<(u16, u16)> {
+    let (x, y) = editor.get_visible_cursor(&area)?;
+    let code = editor.code_ref();
+    let (line_idx, col) = code.point(editor.get_cursor());
+    if tab_width <= 1 || !line_has_tab(code, line_idx) {
+        return Some((x, y));
+    }
+
+    let text_x = area.left() + gutter_width(code) as u16;
+    let max_x = area.right().saturating_sub(text_x) as usize;
+    let offset = expanded_width(code, line_idx, editor.get_offset_x(), tab_width);
+    let visual = expanded_width(code, line_idx, col, tab_width).saturating_sub(offset);
+    let x = text_x + visual.min(max_x) as u16;
+    (x < area.right()).then_some((x, y))
+}
+
+/// Maps a clicked screen column in the expanded layout back to the column
+/// the editor widget would see for the same character
+pub fn widget_column(
+    editor: &CodeEditor,
+    column: u16,
+    row: u16,
+    area: Rect,
+    tab_width: usize,
+) -> u16 {
+    let code = editor.code_ref();
+    let text_x = area.left() + gutter_width(code) as u16;
+    let line_idx = editor.get_offset_y() + row.saturating_sub(area.top()) as usize;
+    if tab_width <= 1
+        || column < text_x
+        || line_idx >= code.len_lines()
+        || !line_has_tab(code, line_idx)
+    {
+        return column;
+    }
+
+    let line_len = code.line_len(line_idx);
+    let start_col = editor.get_offset_x().min(line_len);
+    let prefix = expanded_width(code, line_idx, start_col, tab_width);
+    let clicked = (column - text_x) as usize;
+
+    let (mut widget_x, mut expanded_x) = (0, 0);
+    for (width, expanded) in columns(code, line_idx, start_col, line_len, prefix, tab_width) {
+        if expanded_x + expanded > clicked {
+            break;
+        }
+        widget_x += width;
+        expanded_x += expanded;
+    }
+    if expanded_x < clicked && start_col + widget_x >= line_len {
+        // past the end of the line, keep the overshoot
+        widget_x += clicked - expanded_x;
+    }
+    text_x + widget_x as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expanded_width() {
+        let editor = CodeEditor::new("go", "\tx := 1\nab\tc\n", vec![]).unwrap();
+        let code = editor.code_ref();
+
+        assert_eq!(expanded_width(code, 0, 1, 4), 4);
+        assert_eq!(expanded_width(code, 0, 2, 8), 9);
+        assert_eq!(expanded_width(code, 1, 3, 4), 4);
+    }
+
+    #[test]
+    fn test_show_whitespace() {
+        let editor = CodeEditor::new("go", "\tx := 1 \n", vec![]).unwrap();
+        let area = Rect::new(0, 0, 40, 2);
+        let mut buf = Buffer::empty(area);
+        expand_tabs(&editor, &mut buf, area, 4);
+        show_whitespace(&editor, &mut buf, area, 4, Color::Red);
+
+        let text_x = gutter_width(editor.code_ref()) as u16;
+        let cell = |dx: u16| buf[(text_x + dx, 0)].clone();
+        assert_eq!(cell(0).symbol(), "→");
+        assert_eq!(cell(1).symbol(), " ");
+        assert_eq!((cell(5).symbol(), cell(5).fg), ("·", Color::DarkGray));
+        assert_eq!((cell(10).symbol(), cell(10).fg), ("·", Color::Red));
+        assert_eq!(buf[(text_x, 1)].symbol(), " ");
+    }
+
+    #[test]
+    fn test_cursor_and_mouse_roundtrip() {
+        let mut editor = CodeEditor::new("go", "\tx := 1\n", vec![]).unwrap();
+        let area = Rect::new(0, 0, 40, 5);
+        let text_x = gutter_width(editor.code_ref()) as u16;
+
+        editor.set_cursor(1);
+        assert_eq!(visible_cursor(&editor, area, 4), Some((text_x + 4, 0)));
+
+        // clicking on the `x` after an expanded tab lands on char 1
+        assert_eq!(widget_column(&editor, text_x + 4, 0, area, 4), text_x + 1);
+        assert_eq!(widget_column(&editor, text_x + 2, 0, area, 4), text_x);
+    }
+}
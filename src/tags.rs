@@ -0,0 +1,159 @@
+use anyhow::{Result, bail};
+use std::path::{Path, PathBuf};
+
+use crate::searcher::{SearchKind, SearchResult};
+
+/// Tag file names looked up in the project root, in order
+const TAG_FILES: &[&str] = &["tags", ".tags"];
+
+/// Where a tag points inside its file
+#[derive(Clone, Debug, PartialEq)]
+pub enum TagAddress {
+    /// 0-based line number
+    Line(usize),
+    /// Full text of the line, taken from a `/^...$/` search pattern
+    Pattern(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TagEntry {
+    pub name: String,
+    pub file: PathBuf,
+    pub address: TagAddress,
+}
+
+/// Parses a universal-ctags file, resolving file names against `base`
+pub fn parse_tags(content: &str, base: &Path) -> Vec<TagEntry> {
+    content
+        .lines()
+        .filter(|line| !line.starts_with("!_TAG_"))
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, '\t');
+            let name = parts.next()?;
+            let file = parts.next()?;
+            let rest = parts.next()?;
+            // the address ends right before `;"` and the extension fields
+            let address = rest.split(";\"").next().unwrap_or(rest);
+            Some(TagEntry {
+                name: name.to_string(),
+                file: base.join(file),
+                address: parse_address(address)?,
+            })
+        })
+        .collect()
+}
+
+fn parse_address(address: &str) -> Option<TagAddress> {
+    if let Ok(line) = address.trim().parse::<usize>() {
+        return Some(TagAddress::Line(line.saturating_sub(1)));
+    }
+
+    let pattern = address
+        .strip_prefix('/')
+        .and_then(|a| a.strip_suffix('/'))
+        .or_else(|| address.strip_prefix('?').and_then(|a| a.strip_suffix('?')))?;
+    let pattern = pattern.strip_prefix('^').unwrap_or(pattern);
+    let pattern = pattern.strip_suffix('$').unwrap_or(pattern);
+    let pattern = pattern.replace("\\/", "/").replace("\\\\", "\\");
+    Some(TagAddress::Pattern(pattern))
+}
+
+fn find_tags_file(root: &Path) -> Option<PathBuf> {
+    TAG_FILES
+        .iter()
+        .map(|name| root.join(name))
+        .find(|path| path.is_file())
+}
+
+/// Looks up `name` in the project's tags file and resolves every
+/// definition to a result that can be jumped to
+pub fn find_definitions(root: &Path, name: &str) -> Result<Vec<SearchResult>> {
+    let Some(tags_file) = find_tags_file(root) else {
+        bail!(
+            "no tags file in {}, generate one with `ctags -R`",
+            root.display()
+        );
+    };
+    let content = std::fs::read_to_string(&tags_file)?;
+
+    let results = parse_tags(&content, root)
+        .into_iter()
+        .filter(|entry| entry.name == name)
+        .filter_map(|entry| resolve(&entry))
+        .collect();
+    Ok(results)
+}
+
+/// Finds the tag's line in its file and points at the name on that line
+fn resolve(entry: &TagEntry) -> Option<SearchResult> {
+    let content = std::fs::read_to_string(&entry.file).ok()?;
+    let lines: Vec<&str> = content.split('\n').collect();
+
+    let line = match &entry.address {
+        TagAddress::Line(line) => *line,
+        TagAddress::Pattern(pattern) => lines
+            .iter()
+            .position(|l| l.trim_end_matches('\r') == pattern)
+            .or_else(|| lines.iter().position(|l| l.contains(pattern.as_str())))?,
+    };
+    let line_content = lines.get(line)?.to_string();
+
+    let column = line_content
+        .find(&entry.name)
+        .map(|byte| line_content[..byte].chars().count())
+        .unwrap_or(0);
+    let line_start: usize = lines[..line].iter().map(|l| l.chars().count() + 1).sum();
+    let match_start = line_start + column;
+
+    Some(SearchResult {
+        line,
+        column,
+        match_start,
+        match_end: match_start + entry.name.chars().count(),
+        line_content,
+        file_path: Some(entry.file.to_string_lossy().to_string()),
+        kind: SearchKind::Content,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tags() {
+        let content = "!_TAG_FILE_FORMAT\t2\t/extended format/\n\
+            Coder\tsrc/coder.rs\t/^pub struct Coder {$/;\"\ts\n\
+            main\tsrc/main.rs\t42;\"\tf\n";
+        let entries = parse_tags(content, Path::new("/repo"));
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].file, PathBuf::from("/repo/src/coder.rs"));
+        assert_eq!(
+            entries[0].address,
+            TagAddress::Pattern("pub struct Coder {".to_string())
+        );
+        assert_eq!(entries[1].address, TagAddress::Line(41));
+    }
+
+    #[test]
+    fn test_find_definitions() {
+        use tempfile::tempdir;
+
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("lib.rs"), "use x;\n\npub fn parse() {}\n").unwrap();
+        std::fs::write(
+            dir.path().join("tags"),
+            "parse\tlib.rs\t/^pub fn parse() {}$/;\"\tf\n",
+        )
+        .unwrap();
+
+        let results = find_definitions(dir.path(), "parse").unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].line, 2);
+        assert_eq!(results[0].column, 7);
+        assert_eq!(results[0].match_start, 15);
+
+        assert!(find_definitions(dir.path(), "missing").unwrap().is_empty());
+    }
+}
@@ -1,4 +1,5 @@
-use crate::diff::diff_without_unchanged;
+use crate::diff::{changed_lines, diff_without_unchanged};
+use std::path::PathBuf;
 use std::time::{Duration, Instant};
 
 pub struct Snapshot {
@@ -6,7 +7,17 @@ pub struct Snapshot {
     pub content: String,
 }
 
+/// A file edited in this session and the size of its recent edits
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecentEdit {
+    pub path: PathBuf,
+    pub added: usize,
+    pub removed: usize,
+}
+
 pub struct Tracker {
+    /// Content when tracking started, kept when old snapshots expire
+    opened: String,
     snapshots: Vec<Snapshot>,
     max_age: Duration,
     max_versions: usize,
@@ -15,6 +26,7 @@ pub struct Tracker {
 impl Tracker {
     pub fn new(initial: String) -> Self {
         Self {
+            opened: initial.clone(),
             snapshots: vec![Snapshot {
                 timestamp: Instant::now(),
                 content: initial,
@@ -68,6 +80,26 @@ impl Tracker {
         }
     }
 
+    /// Lines added and removed from the oldest snapshot to `content`, the
+    /// edits [`Self::summarize_recent_edits`] describes when it is the latest
+    pub fn edit_counts(&self, content: &str) -> (usize, usize) {
+        let oldest = self.snapshots.first().map_or(&self.opened, |s| &s.content);
+        changed_lines(oldest, content)
+            .iter()
+            .fold((0, 0), |(added, removed), change| {
+                (added + change.end - change.start, removed + change.removed)
+            })
+    }
+
+    /// Latest tracked content
+    pub fn content(&self) -> &str {
+        self.snapshots.last().map_or(&self.opened, |s| &s.content)
+    }
+
+    pub fn opened(&self) -> &str {
+        &self.opened
+    }
+
     pub fn snapshots(&self) -> &Vec<Snapshot> {
         &self.snapshots
     }
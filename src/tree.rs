@@ -1,36 +1,62 @@
 use crate::app::Theme;
+use crate::scrollbar;
 use crate::utils::{find_color, is_ignored_path};
-use crossterm::event::{Event, KeyCode, KeyEventKind, MouseEventKind};
+use crossterm::event::{Event, KeyCode, KeyEventKind, KeyModifiers, MouseEventKind};
 use notify::event::ModifyKind;
 use ratatui::Frame;
 use ratatui::layout::{Position, Rect};
 use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::Span;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use tui_tree_widget::{Tree, TreeItem, TreeState};
 
+/// Entries listed per directory before the rest is collapsed into a marker
+pub const MAX_ENTRIES: usize = 5000;
+
+/// Identifier suffix of the "more entries" marker; paths cannot contain NUL
+const TRUNCATED_SUFFIX: &str = "\0truncated";
+
+/// Levels of folders below the roots that expand-all opens
+const EXPAND_DEPTH: usize = 3;
+/// Folders expand-all opens at most, so a huge tree stays responsive
+const EXPAND_LIMIT: usize = 500;
+
+#[derive(Debug, PartialEq)]
 pub enum TreeAction {
     None,
+    /// Open the file and focus the editor
     OpenFile(String),
+    /// Load the file into a buffer, keeping the current one and the focus
+    OpenFileBackground(String),
     Quit,
 }
 
 pub struct TreePanel {
     pub state: TreeState<String>,
     pub items: Vec<TreeItem<'static, String>>,
+    /// Workspace roots, each shown as a top-level node
+    pub roots: Vec<PathBuf>,
+    /// Entries listed per directory, 0 for no limit
+    pub max_entries: usize,
 }
 
 impl TreePanel {
-    pub fn new(root_path: &Path, theme: &Theme) -> Self {
-        let items = build_initial_tree_items(root_path, theme);
+    pub fn new(roots: &[PathBuf], theme: &Theme) -> Self {
+        let items = build_workspace_tree_items(roots, theme, MAX_ENTRIES);
         let mut state = TreeState::default();
-        if let Some(item) = items.first() {
+        for item in &items {
             state.open(vec![item.identifier().clone()]);
         }
-        Self { state, items }
+        Self {
+            state,
+            items,
+            roots: roots.to_vec(),
+            max_entries: MAX_ENTRIES,
+        }
     }
 
     pub fn render(&mut self, frame: &mut Frame, area: Rect) {
+        let (area, bar) = scrollbar::split(area);
         let widget = Tree::new(&self.items)
             .expect("all item identifiers are unique")
             .highlight_style(
@@ -40,19 +66,22 @@ impl TreePanel {
                     .add_modifier(Modifier::BOLD),
             );
         frame.render_stateful_widget(widget, area, &mut self.state);
+
+        let rows = self.state.flatten(&self.items).len();
+        let offset = self.state.get_offset();
+        scrollbar::render(frame.buffer_mut(), bar, rows, offset, area.height as usize);
     }
 
     pub fn expand(&mut self, path: &str, theme: &Theme) -> anyhow::Result<()> {
-        expand_path_in_tree_items(&mut self.items, path, &std::env::current_dir()?, theme)?;
+        expand_path_in_tree_items(&mut self.items, path, theme, self.max_entries)?;
         Ok(())
     }
 
     pub fn refresh(&mut self, theme: &Theme) -> anyhow::Result<()> {
-        let root_path = std::env::current_dir()?;
         let mut opened_paths = self.state.opened().iter().cloned().collect::<Vec<_>>();
         opened_paths.sort_by_key(|path| path.len());
 
-        self.items = build_initial_tree_items(&root_path, theme);
+        self.items = build_workspace_tree_items(&self.roots, theme, self.max_entries);
 
         for opened_path in opened_paths {
             if let Some(target_path) = opened_path.last() {
@@ -63,21 +92,26 @@ impl TreePanel {
     }
 
     pub fn open_file_path(&mut self, filename: &str, theme: &Theme) {
-        let root_path = match std::env::current_dir() {
-            Ok(p) => p,
-            Err(_) => return,
-        };
-
         let file_path = Path::new(filename);
         let abs_file_path = if file_path.is_absolute() {
             file_path.to_path_buf()
         } else {
-            root_path.join(file_path)
+            match std::env::current_dir() {
+                Ok(cwd) => cwd.join(file_path),
+                Err(_) => return,
+            }
         };
 
-        if !abs_file_path.starts_with(&root_path) {
+        // the innermost root containing the file
+        let Some(root_path) = self
+            .roots
+            .iter()
+            .filter(|root| abs_file_path.starts_with(root))
+            .max_by_key(|root| root.components().count())
+            .cloned()
+        else {
             return;
-        }
+        };
 
         let root_id = root_path.to_string_lossy().into_owned();
         let mut open_path = vec![root_id.clone()];
@@ -92,7 +126,8 @@ impl TreePanel {
                 current_path = current_path.join(component);
                 let dir_id = current_path.to_string_lossy().into_owned();
 
-                let _ = expand_path_in_tree_items(&mut self.items, &dir_id, &root_path, theme);
+                let _ =
+                    expand_path_in_tree_items(&mut self.items, &dir_id, theme, self.max_entries);
 
                 open_path.push(dir_id.clone());
                 select_path.push(dir_id);
@@ -109,6 +144,63 @@ impl TreePanel {
         self.state.select(select_path);
     }
 
+    /// Closes every folder but the roots; a selection inside a closed folder
+    /// moves up to the entry directly under its root
+    pub fn collapse_all(&mut self) {
+        self.state.close_all();
+        for item in &self.items {
+            self.state.open(vec![item.identifier().clone()]);
+        }
+        let mut selected = self.state.selected().to_vec();
+        if selected.len() > 2 {
+            selected.truncate(2);
+            self.state.select(selected);
+        }
+    }
+
+    /// Opens the folders up to `EXPAND_DEPTH` levels below the roots, level
+    /// by level, loading their entries, until `EXPAND_LIMIT` are open
+    pub fn expand_all(&mut self, theme: &Theme) {
+        let mut level: Vec<Vec<String>> = self
+            .items
+            .iter()
+            .map(|item| vec![item.identifier().clone()])
+            .collect();
+        let mut opened = 0;
+        for _ in 0..EXPAND_DEPTH {
+            let mut next = Vec::new();
+            for path in &level {
+                let Some(item) = find_item(&self.items, path) else {
+                    continue;
+                };
+                let folders = item
+                    .children()
+                    .iter()
+                    .map(|child| child.identifier())
+                    .filter(|id| Path::new(id.as_str()).is_dir());
+                for id in folders {
+                    let mut child = path.clone();
+                    child.push(id.clone());
+                    next.push(child);
+                }
+            }
+            for path in &next {
+                if opened == EXPAND_LIMIT {
+                    return;
+                }
+                let id = path.last().expect("paths start at a root");
+                let unloaded =
+                    find_item(&self.items, path).is_some_and(|i| i.children().is_empty());
+                if unloaded {
+                    let _ = self.expand(id, theme);
+                }
+                self.state.open(path.clone());
+                opened += 1;
+            }
+            level = next;
+        }
+    }
+
     /// Returns the selected path if a leaf (file) was activated
     #[allow(dead_code)]
     pub fn selected_path(&self) -> Option<String> {
@@ -153,15 +245,22 @@ impl TreePanel {
 
     pub fn handle_event(&mut self, event: &Event, area: Rect, theme: &Theme) -> TreeAction {
         let mut check_selected = false;
+        let mut background = false;
         let opened_before = self.state.opened().clone();
 
         match event {
             Event::Key(key) if !matches!(key.kind, KeyEventKind::Press) => {}
             Event::Key(key) => match key.code {
                 KeyCode::Char('q') => return TreeAction::Quit,
+                KeyCode::Char('-') => self.collapse_all(),
+                KeyCode::Char('+') | KeyCode::Char('=') => self.expand_all(theme),
                 KeyCode::Enter => {
                     self.toggle_selected();
                     check_selected = true;
+                    // Ctrl+Enter needs keyboard enhancement, Alt+Enter does not
+                    background = key
+                        .modifiers
+                        .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT);
                 }
                 KeyCode::Left => self.key_left(),
                 KeyCode::Right => self.key_right(),
@@ -197,11 +296,16 @@ impl TreePanel {
 
         if check_selected && !selected.is_empty() {
             let name = selected.last().unwrap().to_string();
+            if name.ends_with(TRUNCATED_SUFFIX) {
+                return TreeAction::None;
+            }
             let path = Path::new(&name);
             if path.is_dir() {
                 if self.state.opened().contains(&selected) {
                     let _ = self.expand(&name, theme);
                 }
+            } else if background {
+                return TreeAction::OpenFileBackground(name);
             } else {
                 return TreeAction::OpenFile(name);
             }
@@ -226,7 +330,15 @@ pub fn should_refresh_tree(event: &notify::Event) -> bool {
     )
 }
 
-pub fn build_tree_items(path: &Path, theme: &Theme) -> Vec<TreeItem<'static, String>> {
+/// Children of the directory at `path`, folders first; past `max_entries`
+/// (0 for no limit) the rest is summarized by a marker so huge directories
+/// do not stall the UI. Folders are listed before the cap applies, so they
+/// are never hidden behind files
+pub fn build_tree_items(
+    path: &Path,
+    theme: &Theme,
+    max_entries: usize,
+) -> Vec<TreeItem<'static, String>> {
     let mut folders = Vec::new();
     let mut files = Vec::new();
 
@@ -236,32 +348,73 @@ pub fn build_tree_items(path: &Path, theme: &Theme) -> Vec<TreeItem<'static, Str
             if is_ignored_path(&path) {
                 continue;
             }
-
-            let name = path.file_name().unwrap().to_string_lossy().into_owned();
-            let abs_path = path.to_string_lossy().into_owned();
-
-            if path.is_dir() {
-                let color = find_color(theme, "type").unwrap_or_default();
-                let name = Span::styled(name, Style::default().fg(color));
-                if let Ok(item) = TreeItem::new(abs_path, name, vec![]) {
-                    folders.push(item);
-                }
+            // the type comes with the listing, only symlinks need a stat
+            let is_dir = match entry.file_type() {
+                Ok(file_type) if !file_type.is_symlink() => file_type.is_dir(),
+                _ => path.is_dir(),
+            };
+            if is_dir {
+                folders.push(path);
             } else {
-                let color = find_color(theme, "variable").unwrap_or_default();
-                let name = Span::styled(name, Style::default().fg(color));
-                files.push(TreeItem::new_leaf(abs_path, name));
+                files.push(path);
             }
         }
     }
 
-    let mut items = Vec::with_capacity(folders.len() + files.len());
-    items.extend(folders);
-    items.extend(files);
+    let total = folders.len() + files.len();
+    let shown = if max_entries > 0 {
+        total.min(max_entries)
+    } else {
+        total
+    };
+    let folder_color = find_color(theme, "type").unwrap_or_default();
+    let file_color = find_color(theme, "variable").unwrap_or_default();
+    let item = |path: PathBuf, color: Color, is_dir: bool| {
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        let name = Span::styled(name, Style::default().fg(color));
+        let abs_path = path.to_string_lossy().into_owned();
+        if is_dir {
+            TreeItem::new(abs_path, name, vec![]).ok()
+        } else {
+            Some(TreeItem::new_leaf(abs_path, name))
+        }
+    };
+
+    let mut items: Vec<_> = folders
+        .into_iter()
+        .map(|path| (path, folder_color, true))
+        .chain(files.into_iter().map(|path| (path, file_color, false)))
+        .take(shown)
+        .filter_map(|(path, color, is_dir)| item(path, color, is_dir))
+        .collect();
+    let skipped = total - shown;
+    if skipped > 0 {
+        let id = format!("{}{TRUNCATED_SUFFIX}", path.to_string_lossy());
+        let text = format!("… {skipped} more entries not shown (REDAI_TREE_MAX_ENTRIES)");
+        let text = Span::styled(text, Style::default().fg(Color::DarkGray));
+        items.push(TreeItem::new_leaf(id, text));
+    }
     items
 }
 
-pub fn build_initial_tree_items(root_path: &Path, theme: &Theme) -> Vec<TreeItem<'static, String>> {
-    let child_items = build_tree_items(root_path, theme);
+/// Builds one top-level node per workspace root
+pub fn build_workspace_tree_items(
+    roots: &[PathBuf],
+    theme: &Theme,
+    max_entries: usize,
+) -> Vec<TreeItem<'static, String>> {
+    roots
+        .iter()
+        .flat_map(|root| build_initial_tree_items(root, theme, max_entries))
+        .collect()
+}
+
+pub fn build_initial_tree_items(
+    root_path: &Path,
+    theme: &Theme,
+    max_entries: usize,
+) -> Vec<TreeItem<'static, String>> {
+    let child_items = build_tree_items(root_path, theme, max_entries);
 
     // Create root tree item containing all children
     let root_name = root_path
@@ -272,28 +425,39 @@ pub fn build_initial_tree_items(root_path: &Path, theme: &Theme) -> Vec<TreeItem
 
     let root_identifier = root_path.to_string_lossy().into_owned();
 
-    let items = match TreeItem::new(root_identifier, root_name, child_items.clone()) {
+    match TreeItem::new(root_identifier, root_name, child_items.clone()) {
         Ok(root_item) => vec![root_item],
         Err(_) => child_items,
-    };
-    items
+    }
+}
+
+/// The item at `path`, the identifiers from a root down to it
+fn find_item<'a>(
+    items: &'a [TreeItem<'static, String>],
+    path: &[String],
+) -> Option<&'a TreeItem<'static, String>> {
+    let (first, rest) = path.split_first()?;
+    let item = items.iter().find(|item| item.identifier() == first)?;
+    if rest.is_empty() {
+        Some(item)
+    } else {
+        find_item(item.children(), rest)
+    }
 }
 
 pub fn expand_path_in_tree_items(
     items: &mut [TreeItem<'static, String>],
     target_path: &str,
-    root_path: &Path,
     theme: &Theme,
+    max_entries: usize,
 ) -> std::io::Result<bool> {
-    for i in 0..items.len() {
-        let item = &mut items[i];
-
+    for item in items.iter_mut() {
         let found = item.identifier() == target_path;
         if found {
             // target_path is now an absolute path, use it directly
             let full_path = Path::new(target_path);
             if full_path.is_dir() {
-                let children = build_tree_items(full_path, theme);
+                let children = build_tree_items(full_path, theme, max_entries);
                 for child in children {
                     let _ = item.add_child(child);
                 }
@@ -307,8 +471,8 @@ pub fn expand_path_in_tree_items(
                 let found = expand_path_in_tree_items(
                     std::slice::from_mut(child),
                     target_path,
-                    root_path,
                     theme,
+                    max_entries,
                 )?;
                 if found {
                     return Ok(true);
@@ -318,4 +482,109 @@ pub fn expand_path_in_tree_items(
     }
 
     Ok(false)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_large_directory_is_truncated() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        for i in 0..10 {
+            std::fs::write(dir.path().join(format!("{i}.txt")), "").unwrap();
+        }
+        let theme = ratatui_code_editor::theme::vesper();
+
+        let items = build_tree_items(dir.path(), &theme, 4);
+        assert_eq!(items.len(), 5);
+        let marker = items.last().unwrap().identifier();
+        assert!(marker.ends_with(TRUNCATED_SUFFIX));
+
+        assert_eq!(build_tree_items(dir.path(), &theme, 0).len(), 11);
+        assert_eq!(build_tree_items(dir.path(), &theme, 11).len(), 11);
+    }
+
+    #[test]
+    fn test_truncation_keeps_folders() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..20 {
+            std::fs::write(dir.path().join(format!("{i}.txt")), "").unwrap();
+        }
+        for i in 0..3 {
+            std::fs::create_dir(dir.path().join(format!("dir{i}"))).unwrap();
+        }
+        let theme = ratatui_code_editor::theme::vesper();
+        let is_folder = |item: &TreeItem<String>| Path::new(item.identifier()).is_dir();
+
+        // whatever order the directory lists them in
+        let items = build_tree_items(dir.path(), &theme, 3);
+        assert_eq!(items.len(), 4);
+        assert!(items[..3].iter().all(is_folder));
+        assert!(items[3].identifier().ends_with(TRUNCATED_SUFFIX));
+
+        let items = build_tree_items(dir.path(), &theme, 5);
+        assert!(items[..3].iter().all(is_folder));
+        assert!(!items[3..].iter().any(is_folder));
+    }
+
+    #[test]
+    fn test_enter_opens_in_background_with_modifier() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a.rs");
+        std::fs::write(&file, "").unwrap();
+        let name = file.to_string_lossy().to_string();
+        let theme = ratatui_code_editor::theme::vesper();
+        let mut tree = TreePanel::new(&[dir.path().to_path_buf()], &theme);
+        tree.open_file_path(&name, &theme);
+        assert_eq!(tree.state.selected().last(), Some(&name));
+
+        let area = Rect::new(0, 0, 30, 10);
+        let enter =
+            |modifiers| Event::Key(crossterm::event::KeyEvent::new(KeyCode::Enter, modifiers));
+        let action = tree.handle_event(&enter(KeyModifiers::CONTROL), area, &theme);
+        assert_eq!(action, TreeAction::OpenFileBackground(name.clone()));
+        let action = tree.handle_event(&enter(KeyModifiers::NONE), area, &theme);
+        assert_eq!(action, TreeAction::OpenFile(name));
+    }
+
+    #[test]
+    fn test_collapse_and_expand_all() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().to_path_buf();
+        let deep = root.join("a/b/c/d");
+        std::fs::create_dir_all(&deep).unwrap();
+        std::fs::write(deep.join("x.rs"), "").unwrap();
+        std::fs::write(root.join("a/b/y.rs"), "").unwrap();
+        let theme = ratatui_code_editor::theme::vesper();
+        let mut tree = TreePanel::new(std::slice::from_ref(&root), &theme);
+        let id = |path: &Path| path.to_string_lossy().to_string();
+        let ids = |parts: &[&str]| -> Vec<String> {
+            let mut path = root.clone();
+            let mut ids = vec![id(&root)];
+            for part in parts {
+                path = path.join(part);
+                ids.push(id(&path));
+            }
+            ids
+        };
+
+        let area = Rect::new(0, 0, 30, 10);
+        let key = |c| Event::Key(crossterm::event::KeyEvent::from(KeyCode::Char(c)));
+        tree.handle_event(&key('+'), area, &theme);
+        let opened = tree.state.opened();
+        assert!(opened.contains(&ids(&["a", "b", "c"])));
+        // deeper folders stay closed
+        assert!(!opened.contains(&ids(&["a", "b", "c", "d"])));
+        assert_eq!(opened.len(), 4);
+
+        tree.state.select(ids(&["a", "b", "y.rs"]));
+        tree.handle_event(&key('-'), area, &theme);
+        assert_eq!(
+            tree.state.opened().iter().collect::<Vec<_>>(),
+            vec![&ids(&[])]
+        );
+        assert_eq!(tree.state.selected(), ids(&["a"]));
+    }
+}
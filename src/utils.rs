@@ -102,7 +102,7 @@ pub fn get_ignore_files() -> Vec<&'static str> {
 }
 
 /// Checks if any part of the path matches an ignored directory
-pub fn is_ignored_dir(path: &std::path::PathBuf) -> bool {
+pub fn is_ignored_dir(path: &std::path::Path) -> bool {
     let ignore_dirs = get_ignore_dirs();
     path.iter()
         .any(|p| ignore_dirs.contains(&p.to_string_lossy().as_ref()))
@@ -123,18 +123,24 @@ pub fn is_ignored_file(file_name: &str) -> bool {
     })
 }
 
+/// Whether two file names name the same file, optionally ignoring case as
+/// case-insensitive filesystems do
+pub fn same_path(a: &str, b: &str, ignore_case: bool) -> bool {
+    a == b || ignore_case && a.to_lowercase() == b.to_lowercase()
+}
+
 /// Checks if a path should be ignored (either directory or file)
-pub fn is_ignored_path(path: &std::path::PathBuf) -> bool {
+pub fn is_ignored_path(path: &std::path::Path) -> bool {
     // Check if any directory in the path should be ignored
     if is_ignored_dir(path) {
         return true;
     }
 
     // Check if the file itself should be ignored
-    if let Some(file_name) = path.file_name() {
-        if let Some(file_name_str) = file_name.to_str() {
-            return is_ignored_file(file_name_str);
-        }
+    if let Some(file_name) = path.file_name()
+        && let Some(file_name_str) = file_name.to_str()
+    {
+        return is_ignored_file(file_name_str);
     }
 
     false
@@ -146,58 +152,97 @@ pub fn is_search_ignored_dir(path: &std::path::Path) -> bool {
         .any(|p| SEARCH_IGNORE_DIRS.contains(&p.to_string_lossy().as_ref()))
 }
 
-/// Checks if a file should be skipped during search (too large or binary)
-pub fn should_skip_file_for_search(path: &std::path::Path) -> bool {
+/// Lines at least this long mark a minified or generated file
+pub const MAX_LINE_LENGTH: usize = 20_000;
+
+/// Checks if a file should be skipped during search: too large, binary, or
+/// minified, i.e. with a line of `max_line_length` bytes or more near its
+/// start; 0 turns the line length check off
+pub fn should_skip_file_for_search(path: &std::path::Path, max_line_length: usize) -> bool {
     // Check file size (skip files larger than 10MB)
     const MAX_FILE_SIZE: u64 = 10 * 1024 * 1024; // 10MB
 
-    if let Ok(metadata) = std::fs::metadata(path) {
-        if metadata.len() > MAX_FILE_SIZE {
-            return true;
-        }
+    if let Ok(metadata) = std::fs::metadata(path)
+        && metadata.len() > MAX_FILE_SIZE
+    {
+        return true;
     }
 
-    // Check if file is binary by reading first 512 bytes
-    if let Ok(mut file) = std::fs::File::open(path) {
-        use std::io::Read;
-        let mut buffer = vec![0u8; 512];
-        if let Ok(size) = file.read(&mut buffer) {
-            // Check for null bytes or high percentage of non-printable characters
-            let null_count = buffer[..size].iter().filter(|&&b| b == 0).count();
-            if null_count > 0 {
-                return true; // Binary file
-            }
+    // Read just enough to see one line of the maximum length
+    let Ok(file) = std::fs::File::open(path) else {
+        return false;
+    };
+    use std::io::Read;
+    let mut head = Vec::new();
+    if file
+        .take(max_line_length.max(512) as u64)
+        .read_to_end(&mut head)
+        .is_err()
+    {
+        return false;
+    }
 
-            // Check if it's valid UTF-8
-            if std::str::from_utf8(&buffer[..size]).is_err() {
-                // If not valid UTF-8, check if it's mostly non-printable
-                let non_printable = buffer[..size]
-                    .iter()
-                    .filter(|&&b| !(b >= 32 && b < 127) && b != 9 && b != 10 && b != 13)
-                    .count();
-                if non_printable as f64 / size as f64 > 0.3 {
-                    return true; // Likely binary
-                }
-            }
+    // Check if file is binary by its first 512 bytes
+    let buffer = &head[..head.len().min(512)];
+    let size = buffer.len();
+
+    // UTF-16 text is full of null bytes but has a BOM
+    if buffer.starts_with(&[0xFF, 0xFE]) || buffer.starts_with(&[0xFE, 0xFF]) {
+        return false;
+    }
+
+    // Check for null bytes or high percentage of non-printable characters
+    let null_count = buffer.iter().filter(|&&b| b == 0).count();
+    if null_count > 0 {
+        return true; // Binary file
+    }
+
+    // Check if it's valid UTF-8
+    if std::str::from_utf8(buffer).is_err() {
+        // If not valid UTF-8, check if it's mostly non-printable
+        let non_printable = buffer
+            .iter()
+            .filter(|&&b| !(32..127).contains(&b) && b != 9 && b != 10 && b != 13)
+            .count();
+        if non_printable as f64 / size as f64 > 0.3 {
+            return true; // Likely binary
         }
     }
 
-    false
+    // Minified or generated, e.g. bundled JS and CSS
+    max_line_length > 0
+        && head
+            .split(|&b| b == b'\n')
+            .any(|line| line.len() >= max_line_length)
 }
 
-/// Returns the absolute path of the input
+/// Returns the absolute path of the input, or the input itself when it
+/// cannot be resolved, e.g. after the file or the working directory is gone
 pub fn abs_file(input: &str) -> String {
-    let srcdir = std::path::PathBuf::from(input);
-    let c = std::fs::canonicalize(&srcdir).unwrap();
-    c.to_string_lossy().to_string()
+    match std::fs::canonicalize(input) {
+        Ok(path) => path.to_string_lossy().to_string(),
+        Err(_) => input.to_string(),
+    }
 }
 
-/// Returns relative path for current dor
-pub fn relative_to_current_dir(path: &std::path::Path) -> Option<std::path::PathBuf> {
-    let current_dir = std::env::current_dir().ok()?;
-    path.strip_prefix(&current_dir)
-        .ok()
-        .map(|p| p.to_path_buf())
+/// Returns the path relative to the root containing it; with several roots
+/// the root's name is kept so files from different roots stay apart
+pub fn display_path(path: &std::path::Path, roots: &[std::path::PathBuf]) -> String {
+    let root = roots
+        .iter()
+        .filter(|root| path.starts_with(root))
+        .max_by_key(|root| root.components().count());
+    let Some(root) = root else {
+        return path.to_string_lossy().to_string();
+    };
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    match root.file_name() {
+        Some(name) if roots.len() > 1 => std::path::Path::new(name)
+            .join(relative)
+            .to_string_lossy()
+            .to_string(),
+        _ => relative.to_string_lossy().to_string(),
+    }
 }
 
 /// Converts a byte index to a line and column number
@@ -239,30 +284,92 @@ pub fn offset_to_byte(o: usize, s: &str) -> usize {
     byte_index
 }
 
-pub fn get_line(line_number: usize, text: &str) -> &str {
-    let mut line_start = 0;
-    let mut current_line = 0;
+/// Guesses the indent unit of `text` from its indented lines,
+/// returns None when nothing in the text is indented
+pub fn detect_indent(text: &str) -> Option<String> {
+    let mut tabs = 0;
+    let mut spaces = 0;
+    let mut min_width = usize::MAX;
 
-    for (i, c) in text.char_indices() {
-        if c == '\n' {
-            if current_line == line_number {
-                return &text[line_start..i];
+    for line in text.lines().take(1000) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if line.starts_with('\t') {
+            tabs += 1;
+        } else {
+            let width = line.len() - line.trim_start_matches(' ').len();
+            if width > 1 {
+                spaces += 1;
+                min_width = min_width.min(width);
             }
-            current_line += 1;
-            line_start = i + 1;
         }
     }
 
-    if current_line == line_number {
-        return &text[line_start..];
+    if tabs == 0 && spaces == 0 {
+        None
+    } else if tabs > spaces {
+        Some("\t".to_string())
+    } else {
+        Some(" ".repeat(min_width.min(8)))
     }
+}
 
-    ""
+/// Multi-line `text` with its indentation shifted to `indent`: the
+/// common indentation of its lines is replaced by `indent` on every line
+/// after the first, and the first line, which goes where the cursor is,
+/// loses its own when `at_indent` says the cursor is in leading whitespace.
+/// Blank lines are left empty, except the last one: the rest of the line
+/// pasted into follows it
+pub fn reindent(text: &str, indent: &str, at_indent: bool) -> String {
+    let Some((first, rest)) = text.split_once('\n') else {
+        return text.to_string();
+    };
+    let leading = |line: &str| line.len() - line.trim_start_matches([' ', '\t']).len();
+    let blank = |line: &str| line.trim().is_empty();
+    // a first line copied from its middle has no indentation to go by
+    let first_counts = at_indent && leading(first) > 0;
+    let base = rest
+        .split('\n')
+        .chain(first_counts.then_some(first))
+        .filter(|line| !blank(line))
+        .map(leading)
+        .min()
+        .unwrap_or(0);
+
+    let mut out = String::with_capacity(text.len());
+    out.push_str(if at_indent {
+        first.trim_start_matches([' ', '\t'])
+    } else {
+        first
+    });
+    let last = rest.split('\n').count() - 1;
+    for (i, line) in rest.split('\n').enumerate() {
+        out.push('\n');
+        if blank(line) {
+            if i == last {
+                out.push_str(indent);
+            }
+            // keep a `\r` line ending
+            out.push_str(line.trim_start_matches([' ', '\t']));
+        } else {
+            out.push_str(indent);
+            out.push_str(&line[base.min(leading(line))..]);
+        }
+    }
+    out
+}
+
+/// Counts of `\n` and `\r\n` line endings in `text`
+pub fn line_endings(text: &str) -> (usize, usize) {
+    let newlines = text.matches('\n').count();
+    let crlf = text.matches("\r\n").count();
+    (newlines - crlf, crlf)
 }
 
 pub fn find_color(theme: &Theme, key: &str) -> Option<Color> {
     theme.iter().find(|(k, _)| *k == key).map(|(_, v)| {
-        let (r, g, b) = rgb(*v);
+        let (r, g, b) = rgb(v);
         Color::Rgb(r, g, b)
     })
 }
@@ -277,7 +384,16 @@ pub fn is_focused(mouse: &MouseEvent, area: Rect) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::path::PathBuf;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_same_path() {
+        assert!(same_path("src/main.rs", "src/main.rs", false));
+        assert!(!same_path("Src/Main.rs", "src/main.rs", false));
+        assert!(same_path("Src/Main.rs", "src/main.rs", true));
+        assert!(same_path("ÜBER.md", "über.md", true));
+        assert!(!same_path("src/main.rs", "src/lib.rs", true));
+    }
 
     #[test]
     fn test_byte_to_point_ascii() {
@@ -345,35 +461,60 @@ mod tests {
     #[test]
     fn test_is_search_ignored_dir() {
         let path = PathBuf::from("target/debug");
-        assert!(is_search_ignored_dir(&path.as_path()));
+        assert!(is_search_ignored_dir(path.as_path()));
 
         let path = PathBuf::from("node_modules/package");
-        assert!(is_search_ignored_dir(&path.as_path()));
+        assert!(is_search_ignored_dir(path.as_path()));
 
         let path = PathBuf::from(".git/config");
-        assert!(is_search_ignored_dir(&path.as_path()));
+        assert!(is_search_ignored_dir(path.as_path()));
 
         let path = PathBuf::from("src/main.rs");
-        assert!(!is_search_ignored_dir(&path.as_path()));
+        assert!(!is_search_ignored_dir(path.as_path()));
+    }
+
+    #[test]
+    fn test_detect_indent() {
+        assert_eq!(
+            detect_indent("fn a() {\n  b();\n    c();\n}"),
+            Some("  ".to_string())
+        );
+        assert_eq!(detect_indent("fn a() {\n\tb();\n}"), Some("\t".to_string()));
+        assert_eq!(detect_indent("a\nb\n"), None);
+    }
+
+    #[test]
+    fn test_reindent() {
+        // copied from the middle of a line nested two levels deep
+        let pasted = "if ready {\n            go();\n\n        }";
+        assert_eq!(
+            reindent(pasted, "    ", false),
+            "if ready {\n        go();\n\n    }"
+        );
+        // pasted whole lines at the start of an indented line
+        let pasted = "\tlet a = 1;\n\tif a {\n\t\tb();\n\t}\n";
+        assert_eq!(
+            reindent(pasted, "  ", true),
+            "let a = 1;\n  if a {\n  \tb();\n  }\n  "
+        );
+        assert_eq!(reindent("one line", "    ", true), "one line");
+        assert_eq!(reindent("a\r\n  b\r\n", "\t", false), "a\r\n\tb\r\n\t");
     }
 
     #[test]
-    fn test_get_line() {
-        let text = "\
-first line
-second line
-third line";
-
-        assert_eq!(get_line(0, text), "first line");
-        assert_eq!(get_line(1, text), "second line");
-        assert_eq!(get_line(2, text), "third line");
-        assert_eq!(get_line(3, text), ""); // out of bounds
+    fn test_line_endings() {
+        assert_eq!(line_endings("a\nb\r\nc\r\n\r"), (1, 2));
+        assert_eq!(line_endings("no newline"), (0, 0));
     }
 
     #[test]
-    fn test_get_line_empty_text() {
-        let text = "";
-        assert_eq!(get_line(0, text), "");
-        assert_eq!(get_line(1, text), "");
+    fn test_display_path() {
+        let one = vec![PathBuf::from("/work/app")];
+        let two = vec![PathBuf::from("/work/app"), PathBuf::from("/work/lib")];
+        let file = Path::new("/work/lib/src/main.rs");
+
+        assert_eq!(display_path(Path::new("/work/app/a.rs"), &one), "a.rs");
+        assert_eq!(display_path(file, &two), "lib/src/main.rs");
+        assert_eq!(display_path(file, &one), "/work/lib/src/main.rs");
     }
 }
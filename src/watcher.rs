@@ -1,12 +1,16 @@
 use notify::{RecursiveMode, Watcher, recommended_watcher};
 use std::collections::HashSet;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tokio::sync::mpsc;
-use tokio::sync::mpsc::Receiver;
+use tokio::sync::mpsc::{Receiver, Sender};
 
 pub struct FsWatcher {
-    watcher: notify::RecommendedWatcher,
+    /// None when watching is disabled
+    watcher: Option<notify::RecommendedWatcher>,
     pub watch_rx: Receiver<notify::Result<notify::Event>>,
+    /// Keeps the channel of a disabled watcher open, so that receiving
+    /// waits forever instead of returning None right away
+    _idle_tx: Option<Sender<notify::Result<notify::Event>>>,
     watched_paths: HashSet<PathBuf>,
 }
 
@@ -20,8 +24,20 @@ impl FsWatcher {
         .expect("Failed to create watcher");
 
         Self {
-            watcher,
+            watcher: Some(watcher),
             watch_rx,
+            _idle_tx: None,
+            watched_paths: HashSet::new(),
+        }
+    }
+
+    /// A watcher that watches nothing and never reports a change
+    pub fn disabled() -> Self {
+        let (idle_tx, watch_rx) = mpsc::channel(1);
+        Self {
+            watcher: None,
+            watch_rx,
+            _idle_tx: Some(idle_tx),
             watched_paths: HashSet::new(),
         }
     }
@@ -30,6 +46,9 @@ impl FsWatcher {
     where
         I: IntoIterator<Item = PathBuf>,
     {
+        let Some(watcher) = &mut self.watcher else {
+            return Ok(());
+        };
         let desired = paths.into_iter().collect::<HashSet<_>>();
 
         let to_unwatch = self
@@ -44,14 +63,48 @@ impl FsWatcher {
             .collect::<Vec<_>>();
 
         for path in to_unwatch {
-            self.watcher.unwatch(&path)?;
+            watcher.unwatch(&path)?;
         }
 
         for path in &to_watch {
-            self.watcher.watch(path, RecursiveMode::NonRecursive)?;
+            watcher.watch(path, RecursiveMode::NonRecursive)?;
         }
 
         self.watched_paths = desired;
         Ok(())
     }
+
+    /// Registers a watched path anew, e.g. after the file was replaced by
+    /// a new one that the old registration may not follow
+    pub fn rewatch(&mut self, path: &Path) -> anyhow::Result<()> {
+        let Some(watcher) = &mut self.watcher else {
+            return Ok(());
+        };
+        if self.watched_paths.contains(path) {
+            let _ = watcher.unwatch(path);
+            watcher.watch(path, RecursiveMode::NonRecursive)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_disabled_watcher() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a.txt");
+        std::fs::write(&file, "a").unwrap();
+
+        let mut watcher = FsWatcher::disabled();
+        watcher.sync([file.clone()]).unwrap();
+        std::fs::write(&file, "b").unwrap();
+        let event = tokio::time::timeout(
+            std::time::Duration::from_millis(100),
+            watcher.watch_rx.recv(),
+        );
+        assert!(event.await.is_err(), "a disabled watcher never yields");
+    }
 }
@@ -0,0 +1,198 @@
+/// What counts as a word for `Ctrl+Left`/`Ctrl+Right` and whole-word search
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum WordBoundary {
+    /// Words are runs of anything but whitespace
+    Whitespace,
+    /// Letters, digits and `_` make words, other symbols runs of their own
+    #[default]
+    Punctuation,
+    /// Like `Punctuation`, also stopping at camelCase humps and underscores
+    Subword,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Class {
+    Space,
+    Word,
+    Symbol,
+}
+
+impl WordBoundary {
+    pub fn parse(spec: &str) -> Option<Self> {
+        match spec.trim().to_lowercase().as_str() {
+            "whitespace" => Some(WordBoundary::Whitespace),
+            "punctuation" => Some(WordBoundary::Punctuation),
+            "subword" | "camel" => Some(WordBoundary::Subword),
+            _ => None,
+        }
+    }
+
+    fn class(self, c: char) -> Class {
+        if c.is_whitespace() {
+            Class::Space
+        } else if self == WordBoundary::Whitespace || c.is_alphanumeric() || c == '_' {
+            Class::Word
+        } else {
+            Class::Symbol
+        }
+    }
+
+    /// Whether a word starts or ends between `chars[i - 1]` and `chars[i]`;
+    /// the ends of `chars` are boundaries
+    pub fn is_boundary(self, chars: &[char], i: usize) -> bool {
+        let (Some(&prev), Some(&next)) =
+            (i.checked_sub(1).and_then(|p| chars.get(p)), chars.get(i))
+        else {
+            return true;
+        };
+        if self.class(prev) != self.class(next) {
+            return true;
+        }
+        if self != WordBoundary::Subword || self.class(prev) != Class::Word {
+            return false;
+        }
+        // `_` separates parts, `fooBar` is `foo|Bar`, `HTTPServer` is
+        // `HTTP|Server`
+        let after = chars.get(i + 1).copied();
+        (prev == '_') != (next == '_')
+            || (prev.is_lowercase() || prev.is_numeric()) && next.is_uppercase()
+            || prev.is_uppercase() && next.is_uppercase() && after.is_some_and(char::is_lowercase)
+    }
+
+    /// Whether a word starts or ends at byte `at` of `text`
+    pub fn is_boundary_at(self, text: &str, at: usize) -> bool {
+        let (Some(before), Some(after)) = (text.get(..at), text.get(at..)) else {
+            return true;
+        };
+        let chars: Vec<char> = before
+            .chars()
+            .next_back()
+            .into_iter()
+            .chain(after.chars().take(2))
+            .collect();
+        self.is_boundary(&chars, usize::from(!before.is_empty()))
+    }
+
+    /// Characters word movement passes over without stopping
+    fn skipped(self, c: char) -> bool {
+        c.is_whitespace() || self == WordBoundary::Subword && c == '_'
+    }
+
+    /// Where `Ctrl+Right` from `col` stops: the end of the next word
+    pub fn next_word_end(self, chars: &[char], col: usize) -> usize {
+        let mut i = col.min(chars.len());
+        while i < chars.len() && self.skipped(chars[i]) {
+            i += 1;
+        }
+        if i < chars.len() {
+            i += 1;
+        }
+        while i < chars.len() && !self.is_boundary(chars, i) {
+            i += 1;
+        }
+        i
+    }
+
+    /// Where `Ctrl+Left` from `col` stops: the start of the previous word
+    pub fn prev_word_start(self, chars: &[char], col: usize) -> usize {
+        let mut i = col.min(chars.len());
+        while i > 0 && self.skipped(chars[i - 1]) {
+            i -= 1;
+        }
+        i = i.saturating_sub(1);
+        while i > 0 && !self.is_boundary(chars, i) {
+            i -= 1;
+        }
+        i
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stops(policy: WordBoundary, line: &str) -> (Vec<usize>, Vec<usize>) {
+        let chars: Vec<char> = line.chars().collect();
+        let mut forward = Vec::new();
+        let mut col = 0;
+        while col < chars.len() {
+            col = policy.next_word_end(&chars, col);
+            forward.push(col);
+        }
+        let mut backward = Vec::new();
+        while col > 0 {
+            col = policy.prev_word_start(&chars, col);
+            backward.push(col);
+        }
+        (forward, backward)
+    }
+
+    #[test]
+    fn test_word_stops() {
+        use WordBoundary::*;
+        let line = "let fooBar = a.b_c;";
+        assert_eq!(
+            stops(Whitespace, line),
+            (vec![3, 10, 12, 19], vec![13, 11, 4, 0])
+        );
+        assert_eq!(
+            stops(Punctuation, line),
+            (
+                vec![3, 10, 12, 14, 15, 18, 19],
+                vec![18, 15, 14, 13, 11, 4, 0]
+            )
+        );
+        assert_eq!(
+            stops(Subword, line),
+            (
+                vec![3, 7, 10, 12, 14, 15, 16, 18, 19],
+                vec![18, 17, 15, 14, 13, 11, 7, 4, 0]
+            )
+        );
+    }
+
+    #[test]
+    fn test_subword_identifier_styles() {
+        let forward = |line| stops(WordBoundary::Subword, line).0;
+        assert_eq!(forward("snake_case_name"), vec![5, 10, 15]);
+        assert_eq!(forward("SCREAMING_CASE"), vec![9, 14]);
+        assert_eq!(forward("camelCaseName"), vec![5, 9, 13]);
+        assert_eq!(forward("PascalCase"), vec![6, 10]);
+        assert_eq!(forward("HTTPServer"), vec![4, 10]);
+        assert_eq!(forward("parseHTTP2Request"), vec![5, 10, 17]);
+        assert_eq!(forward("__init__"), vec![6, 8]);
+        assert_eq!(forward("größeÄnderung"), vec![5, 13]);
+        assert_eq!(
+            stops(WordBoundary::Subword, "camelCaseName").1,
+            vec![9, 5, 0]
+        );
+        assert_eq!(
+            stops(WordBoundary::Punctuation, "camelCase_name").0,
+            vec![14]
+        );
+    }
+
+    #[test]
+    fn test_boundary_at() {
+        let text = "getHTTPServer foo_bar";
+        for (policy, at, expected) in [
+            (WordBoundary::Punctuation, 0, true),
+            (WordBoundary::Punctuation, 3, false),
+            (WordBoundary::Subword, 3, true),
+            (WordBoundary::Subword, 7, true),
+            (WordBoundary::Subword, 6, false),
+            (WordBoundary::Subword, 17, true),
+            (WordBoundary::Subword, 18, true),
+            (WordBoundary::Whitespace, 13, true),
+            (WordBoundary::Whitespace, text.len(), true),
+        ] {
+            assert_eq!(
+                policy.is_boundary_at(text, at),
+                expected,
+                "{policy:?} at {at}"
+            );
+        }
+        assert_eq!(WordBoundary::parse(" Camel "), Some(WordBoundary::Subword));
+        assert_eq!(WordBoundary::parse("words"), None);
+    }
+}